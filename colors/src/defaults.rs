@@ -25,6 +25,24 @@ pub fn tabs_active() -> ColorArray {
         .to_arr()
 }
 
+pub fn tabs_foreground() -> ColorArray {
+    ColorBuilder::from_hex(String::from("#0F0D0E"), Format::SRGB0_1)
+        .unwrap()
+        .to_arr()
+}
+
+pub fn tabs_active_foreground() -> ColorArray {
+    ColorBuilder::from_hex(String::from("#0F0D0E"), Format::SRGB0_1)
+        .unwrap()
+        .to_arr()
+}
+
+pub fn split() -> ColorArray {
+    ColorBuilder::from_hex(String::from("#4C4345"), Format::SRGB0_1)
+        .unwrap()
+        .to_arr()
+}
+
 pub fn foreground() -> ColorArray {
     ColorBuilder::from_hex(String::from("#F9F4DA"), Format::SRGB0_1)
         .unwrap()
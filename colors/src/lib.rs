@@ -3,14 +3,14 @@ pub mod defaults;
 pub mod term;
 
 use regex::Regex;
-use serde::{de, Deserialize};
+use serde::{de, Deserialize, Serialize};
 use std::num::ParseIntError;
 
 pub type ColorWGPU = wgpu::Color;
 pub type ColorArray = [f32; 4];
 pub type ColorComposition = (ColorArray, ColorWGPU);
 
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct ColorRgb {
     pub r: u8,
     pub g: u8,
@@ -45,7 +45,7 @@ pub enum Format {
     SRGB0_1,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AnsiColor {
     Named(NamedColor),
     Spec(ColorRgb),
@@ -81,6 +81,12 @@ pub struct Colors {
         rename = "tabs-active"
     )]
     pub tabs_active: ColorArray,
+    #[serde(
+        deserialize_with = "deserialize_to_arr",
+        default = "defaults::tabs_active_foreground",
+        rename = "tabs-active-foreground"
+    )]
+    pub tabs_active_foreground: ColorArray,
     #[serde(default = "defaults::cursor", deserialize_with = "deserialize_to_arr")]
     pub cursor: ColorArray,
 
@@ -92,6 +98,15 @@ pub struct Colors {
     pub magenta: ColorArray,
     #[serde(default = "defaults::tabs", deserialize_with = "deserialize_to_arr")]
     pub tabs: ColorArray,
+    #[serde(
+        deserialize_with = "deserialize_to_arr",
+        default = "defaults::tabs_foreground",
+        rename = "tabs-foreground"
+    )]
+    pub tabs_foreground: ColorArray,
+    /// Borders between the panes of a tab.
+    #[serde(default = "defaults::split", deserialize_with = "deserialize_to_arr")]
+    pub split: ColorArray,
     #[serde(default = "defaults::white", deserialize_with = "deserialize_to_arr")]
     pub white: ColorArray,
     #[serde(
@@ -214,11 +229,14 @@ impl Default for Colors {
             red: defaults::red(),
             yellow: defaults::yellow(),
             tabs_active: defaults::tabs_active(),
+            tabs_active_foreground: defaults::tabs_active_foreground(),
             cursor: defaults::cursor(),
             black: defaults::black(),
             cyan: defaults::cyan(),
             magenta: defaults::magenta(),
             tabs: defaults::tabs(),
+            tabs_foreground: defaults::tabs_foreground(),
+            split: defaults::split(),
             white: defaults::white(),
             dim_black: defaults::dim_black(),
             dim_blue: defaults::dim_blue(),
@@ -254,7 +272,7 @@ pub fn hex_to_color_wgpu(s: &str) -> ColorWGPU {
         .to_wgpu()
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum NamedColor {
     /// Black.
     Black = 0,
@@ -1,3 +1,5 @@
+use crate::ClipboardPermission;
+
 pub fn default_env_vars() -> Vec<String> {
     vec![]
 }
@@ -33,3 +35,83 @@ pub fn default_theme() -> String {
 pub fn default_font_size() -> f32 {
     16.
 }
+
+pub fn default_session_save_interval() -> u64 {
+    60
+}
+
+pub fn default_hide_when_single() -> bool {
+    true
+}
+
+pub fn default_osc52_read() -> ClipboardPermission {
+    ClipboardPermission::Ask
+}
+
+pub fn default_osc52_write() -> ClipboardPermission {
+    ClipboardPermission::Allow
+}
+
+pub fn default_osc52_max_size() -> usize {
+    // 1 MiB
+    1_048_576
+}
+
+pub fn default_middle_click_paste() -> bool {
+    true
+}
+
+pub fn default_clipboard_history_size() -> usize {
+    50
+}
+
+pub fn default_export_scope() -> String {
+    String::from("history")
+}
+
+pub fn default_export_format() -> String {
+    String::from("text")
+}
+
+pub fn default_leader_timeout() -> u64 {
+    // Milliseconds
+    1000
+}
+
+pub fn default_semantic_escape_chars() -> String {
+    String::from(",│`|:\"' ()[]{}<>\t")
+}
+
+pub fn default_smart_selection_rules() -> Vec<String> {
+    vec![
+        // URL
+        String::from(r#"[a-zA-Z][a-zA-Z0-9+.-]*://[^\s<>"'`]*[^\s<>"'`.,;:!?)\]]"#),
+        // Email
+        String::from(r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+"),
+        // UUID
+        String::from(
+            r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+        ),
+        // IPv6
+        String::from(
+            r"(?:[0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}|(?:[0-9a-fA-F]{1,4}:){1,6}(?::[0-9a-fA-F]{1,4}){1,6}|(?:[0-9a-fA-F]{1,4}:){1,7}:|::(?:[0-9a-fA-F]{1,4}:){0,5}[0-9a-fA-F]{1,4}",
+        ),
+        // path:line:column
+        String::from(r"(?:~|\.{1,2})?/?(?:[\w.@+-]+/)*[\w.@+-]+:\d+(?::\d+)?"),
+        // Quoted string
+        String::from(r#""[^"]*"|'[^']*'"#),
+    ]
+}
+
+pub fn default_close_confirmation() -> bool {
+    true
+}
+
+pub fn default_close_confirmation_allowlist() -> Vec<String> {
+    [
+        "bash", "sh", "zsh", "fish", "nu", "dash", "ksh", "tmux", "screen",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect()
+}
@@ -3,6 +3,7 @@ use crate::defaults::*;
 use colors::Colors;
 use log::warn;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::default::Default;
 
 #[derive(Default, Debug, Deserialize, PartialEq, Clone, Copy)]
@@ -35,6 +36,418 @@ impl Default for Developer {
     }
 }
 
+#[derive(Default, Debug, Deserialize, PartialEq, Clone, Copy)]
+pub enum CopyFormat {
+    #[default]
+    #[serde(alias = "plain")]
+    Plain,
+    #[serde(alias = "rich")]
+    Rich,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Clone, Copy)]
+pub enum ClipboardPermission {
+    #[serde(alias = "allow")]
+    Allow,
+    #[serde(alias = "deny")]
+    Deny,
+    #[serde(alias = "ask")]
+    Ask,
+}
+
+#[derive(Default, Debug, Deserialize, PartialEq, Clone, Copy)]
+pub enum CopyOnSelect {
+    #[serde(alias = "none")]
+    None,
+    #[serde(alias = "primary")]
+    Primary,
+    #[serde(alias = "clipboard")]
+    Clipboard,
+    #[default]
+    #[serde(alias = "both")]
+    Both,
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct Clipboard {
+    #[serde(default = "CopyFormat::default", rename = "copy-format")]
+    pub copy_format: CopyFormat,
+    #[serde(default = "bool::default", rename = "trim-trailing-whitespace")]
+    pub trim_trailing_whitespace: bool,
+    #[serde(default = "default_osc52_read", rename = "osc52-read")]
+    pub osc52_read: ClipboardPermission,
+    #[serde(default = "default_osc52_write", rename = "osc52-write")]
+    pub osc52_write: ClipboardPermission,
+    #[serde(default = "default_osc52_max_size", rename = "osc52-max-size")]
+    pub osc52_max_size: usize,
+    #[serde(default = "CopyOnSelect::default", rename = "copy-on-select")]
+    pub copy_on_select: CopyOnSelect,
+    #[serde(default = "default_middle_click_paste", rename = "middle-click-paste")]
+    pub middle_click_paste: bool,
+    #[serde(default = "default_clipboard_history_size", rename = "history-size")]
+    pub history_size: usize,
+    #[serde(default = "bool::default", rename = "persist-history")]
+    pub persist_history: bool,
+    #[serde(default = "Vec::default", rename = "history-exclude")]
+    pub history_exclude: Vec<String>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Clipboard {
+        Clipboard {
+            copy_format: CopyFormat::default(),
+            trim_trailing_whitespace: false,
+            osc52_read: default_osc52_read(),
+            osc52_write: default_osc52_write(),
+            osc52_max_size: default_osc52_max_size(),
+            copy_on_select: CopyOnSelect::default(),
+            middle_click_paste: default_middle_click_paste(),
+            history_size: default_clipboard_history_size(),
+            persist_history: false,
+            history_exclude: Vec::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct Selection {
+    #[serde(
+        default = "default_semantic_escape_chars",
+        rename = "semantic-escape-chars"
+    )]
+    pub semantic_escape_chars: String,
+    #[serde(default = "default_smart_selection_rules", rename = "smart-rules")]
+    pub smart_rules: Vec<String>,
+}
+
+impl Default for Selection {
+    fn default() -> Selection {
+        Selection {
+            semantic_escape_chars: default_semantic_escape_chars(),
+            smart_rules: default_smart_selection_rules(),
+        }
+    }
+}
+
+/// Where a program run by a binding lives.
+#[derive(Default, Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum CommandTarget {
+    /// Detached from Rio, without a terminal.
+    #[default]
+    #[serde(alias = "background")]
+    Background,
+    /// In a new tab, closed when the program exits.
+    #[serde(alias = "tab")]
+    Tab,
+}
+
+/// A program run by a binding, either `"program"` or
+/// `{ program = "program", args = [], target = "background" }`.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Program {
+    Just(String),
+    WithArgs {
+        program: String,
+        #[serde(default = "Vec::default")]
+        args: Vec<String>,
+        #[serde(default = "CommandTarget::default")]
+        target: CommandTarget,
+    },
+}
+
+impl Program {
+    pub fn program(&self) -> &str {
+        match self {
+            Program::Just(program) => program,
+            Program::WithArgs { program, .. } => program,
+        }
+    }
+
+    pub fn args(&self) -> &[String] {
+        match self {
+            Program::Just(_) => &[],
+            Program::WithArgs { args, .. } => args,
+        }
+    }
+
+    pub fn target(&self) -> CommandTarget {
+        match self {
+            Program::Just(_) => CommandTarget::default(),
+            Program::WithArgs { target, .. } => *target,
+        }
+    }
+}
+
+/// What a binding exports, e.g. `{ scope = "screen", format = "html" }`.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+pub struct Export {
+    /// "history", "screen" or "selection".
+    #[serde(default = "default_export_scope")]
+    pub scope: String,
+    /// "text", "ansi", "html" or "svg".
+    #[serde(default = "default_export_format")]
+    pub format: String,
+}
+
+/// A key binding as written in the configuration file, interpreted by Rio
+/// itself since keys and actions depend on the window system.
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+pub struct KeyBinding {
+    /// Key name, e.g. "A", "Return" or "F1".
+    pub key: Option<String>,
+    /// Scancode, used instead of `key`.
+    pub scancode: Option<u32>,
+    /// Modifiers separated by `|`, e.g. "Control|Shift".
+    #[serde(default = "String::default")]
+    pub mods: String,
+    /// Binding modes separated by `|`, prefixed by `~` to exclude a mode.
+    #[serde(default = "String::default")]
+    pub mode: String,
+    /// Action name, e.g. "Paste".
+    pub action: Option<String>,
+    /// Text written to the PTY, used instead of `action`.
+    pub chars: Option<String>,
+    /// Program to run, used instead of `action`.
+    pub command: Option<Program>,
+    /// Name of the macro replayed, used instead of `action`.
+    #[serde(rename = "macro")]
+    pub macro_name: Option<String>,
+    /// What is exported, used instead of `action`.
+    pub export: Option<Export>,
+    /// Key table this binding belongs to, e.g. "leader". Bindings without
+    /// one are always active.
+    pub table: Option<String>,
+    /// Key table entered by this binding, used instead of `action`.
+    #[serde(rename = "key-table")]
+    pub key_table: Option<String>,
+}
+
+/// A mouse binding as written in the configuration file.
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+pub struct MouseBinding {
+    /// "Left", "Middle", "Right", "Back", "Forward" or a button number.
+    pub button: String,
+    /// Modifiers separated by `|`, e.g. "Control|Shift".
+    #[serde(default = "String::default")]
+    pub mods: String,
+    /// Binding modes separated by `|`, prefixed by `~` to exclude a mode.
+    #[serde(default = "String::default")]
+    pub mode: String,
+    /// Action name, e.g. "PasteSelection" or "OpenLink".
+    pub action: Option<String>,
+    /// Text written to the PTY, used instead of `action`.
+    pub chars: Option<String>,
+    /// Program to run, used instead of `action`.
+    pub command: Option<Program>,
+    /// Name of the macro replayed, used instead of `action`.
+    #[serde(rename = "macro")]
+    pub macro_name: Option<String>,
+    /// What is exported, used instead of `action`.
+    pub export: Option<Export>,
+}
+
+/// Key starting a chord, the next key is looked up in the "leader" table.
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+pub struct Leader {
+    pub key: String,
+    #[serde(default = "String::default")]
+    pub mods: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct Bindings {
+    #[serde(default = "Vec::default")]
+    pub keys: Vec<KeyBinding>,
+    #[serde(default = "Vec::default")]
+    pub mouse: Vec<MouseBinding>,
+    pub leader: Option<Leader>,
+    /// Milliseconds to wait for the key following the leader, 0 waits forever.
+    #[serde(default = "default_leader_timeout", rename = "leader-timeout")]
+    pub leader_timeout: u64,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings {
+            keys: Vec::default(),
+            mouse: Vec::default(),
+            leader: None,
+            leader_timeout: default_leader_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct Session {
+    #[serde(default = "bool::default")]
+    pub restore: bool,
+    #[serde(default = "default_session_save_interval", rename = "save-interval")]
+    pub save_interval: u64,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session {
+            restore: false,
+            save_interval: default_session_save_interval(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Deserialize, PartialEq, Clone, Copy)]
+pub enum TabBarPosition {
+    #[default]
+    #[serde(alias = "top")]
+    Top,
+    #[serde(alias = "bottom")]
+    Bottom,
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct TabBar {
+    #[serde(default = "TabBarPosition::default")]
+    pub position: TabBarPosition,
+    /// Hide the tab bar while there is a single tab.
+    #[serde(default = "default_hide_when_single", rename = "hide-when-single")]
+    pub hide_when_single: bool,
+}
+
+impl Default for TabBar {
+    fn default() -> TabBar {
+        TabBar {
+            position: TabBarPosition::default(),
+            hide_when_single: default_hide_when_single(),
+        }
+    }
+}
+
+/// Where new tabs are placed.
+#[derive(Default, Debug, Deserialize, PartialEq, Clone, Copy)]
+pub enum NewTabPosition {
+    #[default]
+    #[serde(alias = "end")]
+    End,
+    /// Right after the current tab.
+    #[serde(alias = "next")]
+    Next,
+}
+
+/// Asks before closing tabs or windows, or quitting, while programs run in
+/// the foreground of their shells.
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct CloseConfirmation {
+    #[serde(default = "default_close_confirmation")]
+    pub enabled: bool,
+    /// Programs closed without asking.
+    #[serde(default = "default_close_confirmation_allowlist")]
+    pub allowlist: Vec<String>,
+}
+
+impl Default for CloseConfirmation {
+    fn default() -> CloseConfirmation {
+        CloseConfirmation {
+            enabled: default_close_confirmation(),
+            allowlist: default_close_confirmation_allowlist(),
+        }
+    }
+}
+
+impl CloseConfirmation {
+    /// Whether closing while `process` runs needs a confirmation.
+    pub fn asks_for(&self, process: &str) -> bool {
+        self.enabled && !self.allowlist.iter().any(|name| name == process)
+    }
+}
+
+/// What happens to a tab once its program exits.
+#[derive(Default, Debug, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum ExitBehavior {
+    /// Close the tab.
+    #[serde(alias = "close")]
+    Close,
+    /// Close the tab when the program exited successfully, otherwise keep
+    /// it open with the exit status.
+    #[default]
+    #[serde(alias = "close-on-clean-exit")]
+    CloseOnCleanExit,
+    /// Keep the tab open with the exit status.
+    #[serde(alias = "hold")]
+    Hold,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+pub struct Tabs {
+    /// Maximum amount of tabs, 0 means no limit.
+    #[serde(default = "usize::default")]
+    pub capacity: usize,
+    #[serde(default = "NewTabPosition::default", rename = "new-tab-position")]
+    pub new_tab_position: NewTabPosition,
+}
+
+/// Input written into a tab by a binding with `macro = "name"`.
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+pub struct Macro {
+    /// Text written at once.
+    #[serde(default = "String::default")]
+    pub input: String,
+    /// Text written after `input`, each step after its delay.
+    #[serde(default = "Vec::default")]
+    pub steps: Vec<MacroStep>,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+pub struct MacroStep {
+    /// Milliseconds to wait before writing `input`.
+    #[serde(default = "u64::default")]
+    pub delay: u64,
+    pub input: String,
+}
+
+impl Macro {
+    /// Add this macro as `name` to the `macros` of the configuration
+    /// `document`, written as `[macros.<name>]` tables or inline, the rest of
+    /// the document is kept as it is.
+    pub fn insert_into(&self, name: &str, document: &str) -> Result<String, String> {
+        use toml_edit::{Array, Document, InlineTable, Item, Table, Value};
+
+        let mut document = document
+            .parse::<Document>()
+            .map_err(|err| err.to_string())?;
+
+        let mut entry = InlineTable::new();
+        if !self.input.is_empty() {
+            entry.insert("input", Value::from(self.input.as_str()));
+        }
+        if !self.steps.is_empty() {
+            let mut steps = Array::new();
+            for step in &self.steps {
+                let mut table = InlineTable::new();
+                table.insert("delay", Value::from(step.delay as i64));
+                table.insert("input", Value::from(step.input.as_str()));
+                steps.push(table);
+            }
+            entry.insert("steps", Value::from(steps));
+        }
+
+        let macros = document.entry("macros").or_insert_with(|| {
+            let mut macros = Table::new();
+            macros.set_implicit(true);
+            Item::Table(macros)
+        });
+        match macros {
+            Item::Table(macros) => {
+                macros.insert(name, Item::Table(entry.into_table()));
+            }
+            Item::Value(Value::InlineTable(macros)) => {
+                macros.insert(name, Value::InlineTable(entry));
+            }
+            _ => return Err(String::from("`macros` is not a table")),
+        }
+        Ok(document.to_string())
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 pub struct Config {
     #[serde(rename = "window-opacity", default = "default_window_opacity")]
@@ -57,6 +470,24 @@ pub struct Config {
     pub option_as_alt: String,
     #[serde(default = "Colors::default")]
     pub colors: Colors,
+    #[serde(default = "Clipboard::default")]
+    pub clipboard: Clipboard,
+    #[serde(default = "Selection::default")]
+    pub selection: Selection,
+    #[serde(default = "Bindings::default")]
+    pub bindings: Bindings,
+    #[serde(default = "BTreeMap::default")]
+    pub macros: BTreeMap<String, Macro>,
+    #[serde(default = "Session::default")]
+    pub session: Session,
+    #[serde(default = "Tabs::default")]
+    pub tabs: Tabs,
+    #[serde(default = "TabBar::default", rename = "tab-bar")]
+    pub tab_bar: TabBar,
+    #[serde(default = "ExitBehavior::default", rename = "exit-behavior")]
+    pub exit_behavior: ExitBehavior,
+    #[serde(default = "CloseConfirmation::default", rename = "close-confirmation")]
+    pub close_confirmation: CloseConfirmation,
     #[serde(default = "Advanced::default")]
     pub advanced: Advanced,
     #[serde(default = "Developer::default")]
@@ -88,6 +519,16 @@ pub fn config_file_path() -> String {
     format!("{config_dir_path_str}/config.toml")
 }
 
+pub fn session_file_path() -> String {
+    let config_dir_path_str = config_dir_path();
+    format!("{config_dir_path_str}/sessions/session.json")
+}
+
+pub fn clipboard_history_file_path() -> String {
+    let config_dir_path_str = config_dir_path();
+    format!("{config_dir_path_str}/clipboard-history.json")
+}
+
 impl Config {
     #[cfg(test)]
     fn load_from_path(path: &str) -> Self {
@@ -144,37 +585,93 @@ impl Config {
     }
 
     pub fn load() -> Self {
-        let config_path_str = config_dir_path();
-        let path = format!("{config_path_str}/config.toml");
-        if std::path::Path::new(&path).exists() {
-            let content = std::fs::read_to_string(path).unwrap();
-            match toml::from_str::<Config>(&content) {
-                Ok(mut decoded) => {
-                    let theme = &decoded.theme;
-                    if theme.is_empty() {
-                        return decoded;
-                    }
-
-                    let path = format!("{config_path_str}/themes/{theme}.toml");
-                    if let Ok(loaded_theme) = Config::load_theme(&path) {
-                        decoded.colors = loaded_theme.colors;
-                    } else {
-                        warn!("failed to load theme: {}", theme);
-                    }
+        Config::load_with(None, &[])
+    }
 
-                    decoded
-                }
+    /// Load the config file at `path`, or else the default one, then apply
+    /// `overrides`. Each override is a `key=value` pair where `key` is a
+    /// dotted path into the file (e.g. `developer.log-level`) and `value`
+    /// a TOML value, bare words are read as strings.
+    pub fn load_with(path: Option<&str>, overrides: &[String]) -> Self {
+        let path = path.map(String::from).unwrap_or_else(config_file_path);
+        let mut table = match std::fs::read_to_string(&path) {
+            Ok(content) => match toml::from_str::<toml::Table>(&content) {
+                Ok(table) => table,
                 Err(err_message) => {
                     warn!("failure to parse config file, failling back to default...\n{err_message:?}");
-                    Config::default()
+                    toml::Table::new()
                 }
+            },
+            Err(_) => toml::Table::new(),
+        };
+
+        for config_override in overrides {
+            if let Err(err_message) = apply_override(&mut table, config_override) {
+                warn!("ignoring config override {config_override}: {err_message}");
             }
+        }
+
+        let mut decoded = match toml::Value::Table(table).try_into::<Config>() {
+            Ok(decoded) => decoded,
+            Err(err_message) => {
+                warn!("failure to parse config, failling back to default...\n{err_message:?}");
+                return Config::default();
+            }
+        };
+
+        let theme = &decoded.theme;
+        if theme.is_empty() {
+            return decoded;
+        }
+
+        let config_path_str = config_dir_path();
+        let path = format!("{config_path_str}/themes/{theme}.toml");
+        if let Ok(loaded_theme) = Config::load_theme(&path) {
+            decoded.colors = loaded_theme.colors;
         } else {
-            Config::default()
+            warn!("failed to load theme: {}", theme);
         }
+
+        decoded
     }
 }
 
+/// Set the value of a `key=value` override into `table`.
+fn apply_override(table: &mut toml::Table, config_override: &str) -> Result<(), String> {
+    let (key, value) = config_override
+        .split_once('=')
+        .ok_or_else(|| String::from("expected key=value"))?;
+    let value = match toml::from_str::<toml::Table>(&format!("value = {value}")) {
+        Ok(mut parsed) => parsed
+            .remove("value")
+            .ok_or_else(|| String::from("missing value"))?,
+        Err(_) => toml::Value::String(value.to_string()),
+    };
+
+    let mut keys = key.trim().split('.').peekable();
+    let mut current = table;
+    while let Some(key) = keys.next() {
+        if key.is_empty() {
+            return Err(String::from("empty key"));
+        }
+
+        if keys.peek().is_none() {
+            current.insert(key.to_string(), value);
+            return Ok(());
+        }
+
+        let entry = current
+            .entry(key.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        current = match entry {
+            toml::Value::Table(table) => table,
+            _ => return Err(format!("{key} is not a table")),
+        };
+    }
+
+    Err(String::from("empty key"))
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -188,6 +685,15 @@ impl Default for Config {
             cursor: default_cursor(),
             option_as_alt: default_option_as_alt(),
             colors: Colors::default(),
+            clipboard: Clipboard::default(),
+            selection: Selection::default(),
+            bindings: Bindings::default(),
+            macros: BTreeMap::default(),
+            session: Session::default(),
+            tabs: Tabs::default(),
+            tab_bar: TabBar::default(),
+            exit_behavior: ExitBehavior::default(),
+            close_confirmation: CloseConfirmation::default(),
             advanced: Advanced::default(),
             developer: Developer::default(),
         }
@@ -261,6 +767,33 @@ mod tests {
         // Colors
         assert_eq!(result.colors, Colors::default());
 
+        // Clipboard
+        assert_eq!(result.clipboard, Clipboard::default());
+
+        // Bindings
+        assert!(result.bindings.keys.is_empty());
+        assert!(result.bindings.mouse.is_empty());
+        assert!(result.bindings.leader.is_none());
+        assert_eq!(result.bindings.leader_timeout, default_leader_timeout());
+
+        // Macros
+        assert!(result.macros.is_empty());
+
+        // Selection
+        assert_eq!(result.selection, Selection::default());
+
+        // Session
+        assert_eq!(result.session, Session::default());
+
+        // Tabs
+        assert_eq!(result.tabs.capacity, 0);
+        assert_eq!(result.tabs.new_tab_position, NewTabPosition::End);
+
+        // Tab bar
+        assert_eq!(result.tab_bar, TabBar::default());
+        assert_eq!(result.tab_bar.position, TabBarPosition::Top);
+        assert!(result.tab_bar.hide_when_single);
+
         // Advanced
         assert!(!result.advanced.disable_render_when_unfocused);
 
@@ -646,6 +1179,378 @@ mod tests {
         assert_eq!(result.colors.cursor, colors::defaults::cursor());
     }
 
+    #[test]
+    fn test_change_session() {
+        let result = create_temporary_config(
+            "change-session",
+            r#"
+            [session]
+            restore = true
+            save-interval = 15
+        "#,
+        );
+
+        assert!(result.session.restore);
+        assert_eq!(result.session.save_interval, 15);
+
+        // Colors
+        assert_eq!(result.colors.background, colors::defaults::background());
+        assert_eq!(result.colors.foreground, colors::defaults::foreground());
+    }
+
+    #[test]
+    fn test_change_tabs() {
+        let result = create_temporary_config(
+            "change-tabs",
+            r#"
+            [tabs]
+            capacity = 9
+            new-tab-position = "next"
+        "#,
+        );
+
+        assert_eq!(result.tabs.capacity, 9);
+        assert_eq!(result.tabs.new_tab_position, NewTabPosition::Next);
+        assert_eq!(result.tab_bar, TabBar::default());
+    }
+
+    #[test]
+    fn test_change_exit_behavior() {
+        let result = create_temporary_config("default-exit-behavior", "");
+        assert_eq!(result.exit_behavior, ExitBehavior::CloseOnCleanExit);
+
+        let result = create_temporary_config(
+            "change-exit-behavior",
+            r#"
+            exit-behavior = "hold"
+        "#,
+        );
+        assert_eq!(result.exit_behavior, ExitBehavior::Hold);
+    }
+
+    #[test]
+    fn test_change_close_confirmation() {
+        let result = create_temporary_config("default-close-confirmation", "");
+        assert!(result.close_confirmation.asks_for("vim"));
+        assert!(!result.close_confirmation.asks_for("zsh"));
+
+        let result = create_temporary_config(
+            "change-close-confirmation",
+            r#"
+            [close-confirmation]
+            allowlist = ["zsh", "htop"]
+        "#,
+        );
+        assert!(result.close_confirmation.enabled);
+        assert!(!result.close_confirmation.asks_for("htop"));
+        assert!(result.close_confirmation.asks_for("bash"));
+
+        let result = create_temporary_config(
+            "disable-close-confirmation",
+            r#"
+            [close-confirmation]
+            enabled = false
+        "#,
+        );
+        assert!(!result.close_confirmation.asks_for("vim"));
+    }
+
+    #[test]
+    fn test_change_tab_bar() {
+        let result = create_temporary_config(
+            "change-tab-bar",
+            r#"
+            [tab-bar]
+            position = "bottom"
+            hide-when-single = false
+
+            [colors]
+            tabs-foreground = '#FFFFFF'
+            split = '#12B5E5'
+        "#,
+        );
+
+        assert_eq!(result.tab_bar.position, TabBarPosition::Bottom);
+        assert!(!result.tab_bar.hide_when_single);
+        assert_eq!(result.colors.tabs_foreground, hex_to_color_arr("#FFFFFF"));
+        assert_eq!(result.colors.split, hex_to_color_arr("#12B5E5"));
+        assert_eq!(
+            result.colors.tabs_active_foreground,
+            colors::defaults::tabs_active_foreground()
+        );
+    }
+
+    #[test]
+    fn test_change_clipboard() {
+        let result = create_temporary_config(
+            "change-clipboard",
+            r#"
+            [clipboard]
+            copy-format = "rich"
+            trim-trailing-whitespace = true
+        "#,
+        );
+
+        assert_eq!(result.clipboard.copy_format, CopyFormat::Rich);
+        assert!(result.clipboard.trim_trailing_whitespace);
+    }
+
+    #[test]
+    fn test_change_bindings() {
+        let result = create_temporary_config(
+            "change-bindings",
+            r#"
+            [bindings]
+            keys = [
+                { key = "V", mods = "Control|Shift", action = "Paste" },
+                { scancode = 36, mode = "~Vi", chars = "\u001b[13;2u" },
+            ]
+            mouse = [
+                { button = "Back", action = "ContextMenu" },
+            ]
+        "#,
+        );
+
+        assert_eq!(
+            result.bindings.keys,
+            vec![
+                KeyBinding {
+                    key: Some(String::from("V")),
+                    mods: String::from("Control|Shift"),
+                    action: Some(String::from("Paste")),
+                    ..KeyBinding::default()
+                },
+                KeyBinding {
+                    scancode: Some(36),
+                    mode: String::from("~Vi"),
+                    chars: Some(String::from("\x1b[13;2u")),
+                    ..KeyBinding::default()
+                },
+            ]
+        );
+        assert_eq!(
+            result.bindings.mouse,
+            vec![MouseBinding {
+                button: String::from("Back"),
+                action: Some(String::from("ContextMenu")),
+                ..MouseBinding::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_change_binding_commands() {
+        let result = create_temporary_config(
+            "change-binding-commands",
+            r#"
+            [bindings]
+            keys = [
+                { key = "E", mods = "Control|Shift", command = "nautilus" },
+                { key = "B", mods = "Control|Shift", command = { program = "htop", args = ["-d", "10"], target = "tab" } },
+            ]
+        "#,
+        );
+
+        let commands: Vec<&Program> = result
+            .bindings
+            .keys
+            .iter()
+            .filter_map(|binding| binding.command.as_ref())
+            .collect();
+        assert_eq!(commands[0], &Program::Just(String::from("nautilus")));
+        assert_eq!(commands[0].args(), &[] as &[String]);
+        assert_eq!(commands[0].target(), CommandTarget::Background);
+
+        assert_eq!(commands[1].program(), "htop");
+        assert_eq!(commands[1].args(), &["-d", "10"]);
+        assert_eq!(commands[1].target(), CommandTarget::Tab);
+    }
+
+    #[test]
+    fn test_change_key_tables() {
+        let result = create_temporary_config(
+            "change-key-tables",
+            r#"
+            [bindings]
+            leader = { key = "A", mods = "Control" }
+            leader-timeout = 2000
+            keys = [
+                { key = "C", table = "leader", action = "TabCreateNew" },
+                { key = "R", table = "leader", key-table = "resize" },
+            ]
+        "#,
+        );
+
+        assert_eq!(
+            result.bindings.leader,
+            Some(Leader {
+                key: String::from("A"),
+                mods: String::from("Control"),
+            })
+        );
+        assert_eq!(result.bindings.leader_timeout, 2000);
+        assert_eq!(
+            result.bindings.keys,
+            vec![
+                KeyBinding {
+                    key: Some(String::from("C")),
+                    table: Some(String::from("leader")),
+                    action: Some(String::from("TabCreateNew")),
+                    ..KeyBinding::default()
+                },
+                KeyBinding {
+                    key: Some(String::from("R")),
+                    table: Some(String::from("leader")),
+                    key_table: Some(String::from("resize")),
+                    ..KeyBinding::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_change_macros() {
+        let result = create_temporary_config(
+            "change-macros",
+            r#"
+            [macros]
+            setup = { input = "cd ~/project\r" }
+
+            [macros.demo]
+            steps = [
+                { input = "l" },
+                { delay = 120, input = "s\r" },
+            ]
+        "#,
+        );
+
+        assert_eq!(
+            result.macros.get("setup"),
+            Some(&Macro {
+                input: String::from("cd ~/project\r"),
+                steps: vec![],
+            })
+        );
+        assert_eq!(
+            result.macros.get("demo").unwrap().steps,
+            vec![
+                MacroStep {
+                    delay: 0,
+                    input: String::from("l"),
+                },
+                MacroStep {
+                    delay: 120,
+                    input: String::from("s\r"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_macro_insert_into() {
+        let recording = Macro {
+            input: String::new(),
+            steps: vec![
+                MacroStep {
+                    delay: 0,
+                    input: String::from("echo \"hi\""),
+                },
+                MacroStep {
+                    delay: 250,
+                    input: String::from("\r\u{1b}[A"),
+                },
+            ],
+        };
+        let repl = Macro {
+            input: String::from("python3\r"),
+            steps: vec![],
+        };
+
+        // The table reads back into the same macro, next to the others
+        for (document, others) in [
+            ("", None),
+            (
+                "font-size = 18\n\n[macros.repl]\ninput = \"python3\\r\"\n",
+                Some(&repl),
+            ),
+            (
+                "macros = { repl = { input = \"python3\\r\" } }\n",
+                Some(&repl),
+            ),
+        ] {
+            let document = recording.insert_into("recording-1", document).unwrap();
+            let config: Config = toml::from_str(&document).unwrap();
+            assert_eq!(config.macros.get("recording-1"), Some(&recording));
+            assert_eq!(config.macros.get("repl"), others);
+        }
+
+        let document = repl.insert_into("repl", "font-size = 18\n").unwrap();
+        assert_eq!(
+            document,
+            "font-size = 18\n\n[macros.repl]\ninput = \"python3\\r\"\n"
+        );
+        assert!(repl.insert_into("repl", "macros = 1").is_err());
+    }
+
+    #[test]
+    fn test_change_clipboard_history() {
+        let result = create_temporary_config(
+            "change-clipboard-history",
+            r#"
+            [clipboard]
+            history-size = 10
+            persist-history = true
+            history-exclude = ['^ghp_\w+$', 'password']
+        "#,
+        );
+
+        assert_eq!(result.clipboard.history_size, 10);
+        assert!(result.clipboard.persist_history);
+        assert_eq!(
+            result.clipboard.history_exclude,
+            vec![String::from(r"^ghp_\w+$"), String::from("password")]
+        );
+    }
+
+    #[test]
+    fn test_change_clipboard_policy() {
+        let result = create_temporary_config(
+            "change-clipboard-policy",
+            r#"
+            [clipboard]
+            osc52-read = "allow"
+            osc52-write = "ask"
+            osc52-max-size = 1024
+            copy-on-select = "primary"
+            middle-click-paste = false
+        "#,
+        );
+
+        assert_eq!(result.clipboard.osc52_read, ClipboardPermission::Allow);
+        assert_eq!(result.clipboard.osc52_write, ClipboardPermission::Ask);
+        assert_eq!(result.clipboard.osc52_max_size, 1024);
+        assert_eq!(result.clipboard.copy_on_select, CopyOnSelect::Primary);
+        assert!(!result.clipboard.middle_click_paste);
+        assert_eq!(result.clipboard.history_size, 50);
+        // Unset keys keep their defaults
+        assert_eq!(result.clipboard.copy_format, CopyFormat::Plain);
+    }
+
+    #[test]
+    fn test_change_selection() {
+        let result = create_temporary_config(
+            "change-selection",
+            r#"
+            [selection]
+            semantic-escape-chars = " \t"
+            smart-rules = ['\d+']
+        "#,
+        );
+
+        assert_eq!(result.selection.semantic_escape_chars, " \t");
+        assert_eq!(result.selection.smart_rules, vec![String::from("\\d+")]);
+    }
+
     #[test]
     fn test_change_developer() {
         let result = create_temporary_config(
@@ -670,4 +1575,51 @@ mod tests {
         assert_eq!(result.colors.tabs_active, colors::defaults::tabs_active());
         assert_eq!(result.colors.cursor, colors::defaults::cursor());
     }
+
+    #[test]
+    fn test_load_with_overrides() {
+        let tmp = tmp_dir();
+        let file_name = format!("{tmp}/test-rio-overrides-config.toml");
+        let mut file = std::fs::File::create(&file_name).unwrap();
+        writeln!(
+            file,
+            r#"
+            font-size = 14.0
+
+            [developer]
+            log-level = "INFO"
+        "#
+        )
+        .unwrap();
+
+        let result = Config::load_with(
+            Some(&file_name),
+            &[
+                String::from("font-size=18"),
+                String::from("cursor=_"),
+                String::from("developer.enable-fps-counter=true"),
+                String::from("tabs.capacity=4"),
+                String::from("not-a-pair"),
+            ],
+        );
+
+        assert_eq!(result.font_size, 18.0);
+        assert_eq!(result.cursor, '_');
+        assert_eq!(result.developer.log_level, String::from("INFO"));
+        assert!(result.developer.enable_fps_counter);
+        assert_eq!(result.tabs.capacity, 4);
+    }
+
+    #[test]
+    fn test_apply_override() {
+        let mut table = toml::Table::new();
+        apply_override(&mut table, "a.b=\"c\"").unwrap();
+        apply_override(&mut table, "a.d=1").unwrap();
+        assert_eq!(table["a"]["b"].as_str(), Some("c"));
+        assert_eq!(table["a"]["d"].as_integer(), Some(1));
+
+        assert!(apply_override(&mut table, "a.b.c=1").is_err());
+        assert!(apply_override(&mut table, "a..b=1").is_err());
+        assert!(apply_override(&mut table, "a").is_err());
+    }
 }
@@ -148,7 +148,7 @@ pub use event::Events;
 #[cfg(all(unix, not(target_os = "fuchsia")))]
 pub mod unix {
     //! Unix only extensions
-    pub use sys::unix::UnixReady;
+    pub use sys::unix::{UnixReady, UnixSocket};
     pub use sys::EventedFd;
 }
 
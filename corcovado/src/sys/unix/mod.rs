@@ -47,7 +47,6 @@ mod io;
 mod ready;
 mod uio;
 
-#[cfg(feature = "with-deprecated")]
 mod uds;
 
 pub use self::awakener::Awakener;
@@ -55,7 +54,6 @@ pub use self::eventedfd::EventedFd;
 pub use self::io::{set_nonblock, Io};
 pub use self::ready::{UnixReady, READY_ALL};
 
-#[cfg(feature = "with-deprecated")]
 pub use self::uds::UnixSocket;
 
 pub use iovec::IoVec;
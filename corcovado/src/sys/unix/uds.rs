@@ -47,7 +47,7 @@ unsafe fn sockaddr_un(path: &Path) -> io::Result<(libc::sockaddr_un, libc::sockl
     // struct
 
     let mut len = sun_path_offset() + bytes.len();
-    match bytes.get(0) {
+    match bytes.first() {
         Some(&0) | None => {}
         Some(_) => len += 1,
     }
@@ -57,7 +57,7 @@ unsafe fn sockaddr_un(path: &Path) -> io::Result<(libc::sockaddr_un, libc::sockl
 fn sun_path_offset() -> usize {
     unsafe {
         // Work with an actual instance of the type since using a null pointer is UB
-        let addr: libc::sockaddr_un = mem::uninitialized();
+        let addr: libc::sockaddr_un = mem::zeroed();
         let base = &addr as *const _ as usize;
         let path = &addr.sun_path as *const _ as usize;
         path - base
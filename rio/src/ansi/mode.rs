@@ -56,6 +56,11 @@ pub enum Mode {
     SwapScreenAndSetRestoreCursor = 1049,
     /// ?2004
     BracketedPaste = 2004,
+    /// ?2048
+    ///
+    /// The size of the terminal is reported as `CSI 48 ; rows ; columns ;
+    /// height ; width t` when the mode is set and on every resize.
+    InBandResize = 2048,
 }
 
 impl Mode {
@@ -85,6 +90,7 @@ impl Mode {
                 1042 => Mode::UrgencyHints,
                 1049 => Mode::SwapScreenAndSetRestoreCursor,
                 2004 => Mode::BracketedPaste,
+                2048 => Mode::InBandResize,
                 _ => {
                     warn!("[unimplemented] primitive mode: {}", num);
                     return None;
@@ -1,12 +1,112 @@
-use clap::{Args, Parser};
+use crate::screen::Backend;
+use clap::{Args, Parser, Subcommand};
 use serde::{Deserialize, Serialize};
+use teletypewriter::SerialSettings;
 
 #[derive(Parser, Default, Debug)]
 #[clap(author, about, version)]
 pub struct Options {
+    /// Configuration file to use instead of the default one.
+    #[clap(long)]
+    pub config_file: Option<String>,
+
+    /// Override configuration file options [example: `cursor=_`].
+    #[clap(short = 'o', long = "option", num_args = 1)]
+    pub config_options: Vec<String>,
+
     /// Options which can be passed via IPC.
     #[clap(flatten)]
     pub window_options: WindowOptions,
+
+    #[clap(subcommand)]
+    pub subcommand: Option<Subcommands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Subcommands {
+    /// Export a tab of the running instance, or of the last saved session.
+    Export(ExportOptions),
+
+    /// Send a request to a running Rio instance.
+    Msg(MsgOptions),
+
+    /// Run sessions in the background for windows to attach to.
+    Server(ServerOptions),
+
+    /// Open a window attached to the sessions of a server, starting one
+    /// when none is running.
+    Attach(AttachOptions),
+}
+
+#[derive(Args, Debug)]
+pub struct ServerOptions {
+    /// Socket to listen on, defaults to `rio-server.sock` in the runtime
+    /// directory.
+    #[clap(short, long)]
+    pub socket: Option<String>,
+
+    /// Print the sessions of the running server instead.
+    #[clap(long, conflicts_with = "kill")]
+    pub list: bool,
+
+    /// End a session of the running server instead.
+    #[clap(long, value_name = "SESSION")]
+    pub kill: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct AttachOptions {
+    /// Socket of the server, defaults to `rio-server.sock` in the runtime
+    /// directory.
+    #[clap(short, long)]
+    pub socket: Option<String>,
+
+    /// Session to attach to, every session gets a tab when omitted.
+    pub session: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct MsgOptions {
+    /// Socket of the instance, defaults to `$RIO_SOCKET` or else to the
+    /// first running instance found.
+    #[clap(short, long)]
+    pub socket: Option<String>,
+
+    #[clap(subcommand)]
+    pub request: crate::ipc::Request,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportOptions {
+    /// Output format: text, ansi, html or svg.
+    #[clap(short, long, default_value = "text")]
+    pub format: String,
+
+    /// What to export: history, screen or selection.
+    #[clap(long, default_value = "history")]
+    pub scope: String,
+
+    /// Window of the tab, defaults to the focused one.
+    #[clap(long, conflicts_with = "session")]
+    pub window: Option<u64>,
+
+    /// Tab to export, defaults to the current one.
+    #[clap(short, long)]
+    pub tab: Option<usize>,
+
+    /// Socket of the instance to export from, defaults to `RIO_SOCKET` or
+    /// the first running instance.
+    #[clap(long, conflicts_with = "session")]
+    pub socket: Option<String>,
+
+    /// Export the history of a saved session instead, an empty value reads
+    /// the one in the config directory.
+    #[clap(long, num_args = 0..=1, default_missing_value = "")]
+    pub session: Option<String>,
+
+    /// File to write, prints to stdout when omitted.
+    #[clap(short, long)]
+    pub output: Option<String>,
 }
 
 impl Options {
@@ -17,6 +117,14 @@ impl Options {
 
 #[derive(Serialize, Deserialize, Args, Default, Clone, Debug, PartialEq, Eq)]
 pub struct WindowOptions {
+    /// Defines the window title [default: Rio].
+    #[clap(short = 'T', long)]
+    pub title: Option<String>,
+
+    /// Defines the window class on X11 and the app id on Wayland.
+    #[clap(long, visible_alias = "app-id")]
+    pub class: Option<String>,
+
     /// Terminal options which can be passed via IPC.
     #[clap(flatten)]
     pub terminal_options: TerminalOptions,
@@ -24,7 +132,120 @@ pub struct WindowOptions {
 
 #[derive(Serialize, Deserialize, Args, Default, Debug, Clone, PartialEq, Eq)]
 pub struct TerminalOptions {
+    /// Start the shell in the specified working directory.
+    #[clap(long)]
+    pub working_directory: Option<String>,
+
+    /// Remain open after child process exit.
+    #[clap(long)]
+    pub hold: bool,
+
+    /// Connect to the Unix socket at this path instead of starting a shell.
+    #[clap(long, value_name = "SOCKET", conflicts_with_all = ["serial", "replay", "command"])]
+    pub connect: Option<String>,
+
+    /// Open a serial device instead of starting a shell [example: `/dev/ttyUSB0`].
+    #[clap(long, value_name = "DEVICE", conflicts_with_all = ["replay", "command"])]
+    pub serial: Option<String>,
+
+    /// Baud rate of the serial device [default: 115200].
+    #[clap(long, requires = "serial")]
+    pub baud_rate: Option<u32>,
+
+    /// Parity of the serial device [default: none].
+    #[clap(long, requires = "serial", value_parser = ["none", "odd", "even"])]
+    pub parity: Option<String>,
+
+    /// Show the output recorded in a file instead of starting a shell.
+    #[clap(long, value_name = "FILE", conflicts_with = "command")]
+    pub replay: Option<String>,
+
     /// Command and args to execute (must be last argument).
     #[clap(short = 'e', long, allow_hyphen_values = true, num_args = 1..)]
     pub command: Vec<String>,
 }
+
+impl TerminalOptions {
+    /// What drives the first context.
+    pub fn backend(&self) -> Backend {
+        if let Some(path) = &self.connect {
+            return Backend::Socket(path.to_owned());
+        }
+
+        if let Some(device) = &self.serial {
+            let defaults = SerialSettings::default();
+            return Backend::Serial {
+                device: device.to_owned(),
+                settings: SerialSettings {
+                    baud_rate: self.baud_rate.unwrap_or(defaults.baud_rate),
+                    parity: self
+                        .parity
+                        .as_deref()
+                        .and_then(|parity| parity.parse().ok())
+                        .unwrap_or_default(),
+                    ..defaults
+                },
+            };
+        }
+
+        match &self.replay {
+            Some(path) => Backend::Replay(path.to_owned()),
+            None => Backend::Pty,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend() {
+        let options = Options::parse_from(["rio"]);
+        assert_eq!(
+            options.window_options.terminal_options.backend(),
+            Backend::Pty
+        );
+
+        let options = Options::parse_from([
+            "rio",
+            "--serial",
+            "/dev/ttyUSB0",
+            "--baud-rate",
+            "9600",
+            "--parity",
+            "even",
+        ]);
+        assert_eq!(
+            options.window_options.terminal_options.backend(),
+            Backend::Serial {
+                device: String::from("/dev/ttyUSB0"),
+                settings: SerialSettings {
+                    baud_rate: 9600,
+                    parity: teletypewriter::Parity::Even,
+                    ..SerialSettings::default()
+                },
+            }
+        );
+
+        let options = Options::parse_from(["rio", "--connect", "/tmp/board.sock"]);
+        assert_eq!(
+            options.window_options.terminal_options.backend(),
+            Backend::Socket(String::from("/tmp/board.sock"))
+        );
+
+        let options = Options::parse_from(["rio", "attach", "--socket", "/tmp/s", "2"]);
+        match options.subcommand {
+            Some(Subcommands::Attach(attach)) => {
+                assert_eq!(attach.socket.as_deref(), Some("/tmp/s"));
+                assert_eq!(attach.session, Some(2));
+            }
+            subcommand => panic!("unexpected subcommand: {subcommand:?}"),
+        }
+
+        assert!(Options::try_parse_from(["rio", "--baud-rate", "9600"]).is_err());
+        assert!(
+            Options::try_parse_from(["rio", "--replay", "out.log", "-e", "ls"]).is_err()
+        );
+    }
+}
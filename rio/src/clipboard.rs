@@ -1,4 +1,6 @@
+use config::{ClipboardPermission, CopyOnSelect};
 use log::warn;
+use std::sync::Arc;
 
 #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
 use std::ffi::c_void;
@@ -19,9 +21,24 @@ use copypasta::x11_clipboard::{Primary as X11SelectionClipboard, X11ClipboardCon
 use copypasta::ClipboardContext;
 use copypasta::ClipboardProvider;
 
+/// Flavors stored by a rich copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RichText {
+    pub text: String,
+    pub html: String,
+    pub rtf: String,
+}
+
+/// Clipboard owner offering every flavor of a rich copy at once, returns
+/// false when the content couldn't be stored.
+pub trait RichProvider {
+    fn set_rich(&mut self, content: &RichText) -> bool;
+}
+
 pub struct Clipboard {
     clipboard: Box<dyn ClipboardProvider>,
     selection: Option<Box<dyn ClipboardProvider>>,
+    rich: Option<Box<dyn RichProvider>>,
 }
 
 impl Clipboard {
@@ -39,6 +56,8 @@ impl Clipboard {
                 Self {
                     clipboard: Box::new(clipboard),
                     selection: Some(Box::new(selection)),
+                    rich: crate::platform::wayland::data_source::DataSource::new(display)
+                        .map(|source| Box::new(source) as Box<dyn RichProvider>),
                 }
             }
             None => Self::default(),
@@ -53,16 +72,25 @@ impl Clipboard {
         Self {
             clipboard: Box::new(NopClipboardContext::new().unwrap()),
             selection: None,
+            rich: None,
         }
     }
 }
 
 impl Default for Clipboard {
     fn default() -> Self {
-        #[cfg(any(target_os = "macos", windows))]
+        #[cfg(target_os = "macos")]
+        return Self {
+            clipboard: Box::new(ClipboardContext::new().unwrap()),
+            selection: None,
+            rich: Some(Box::new(crate::platform::macos::pasteboard::Pasteboard)),
+        };
+
+        #[cfg(windows)]
         return Self {
             clipboard: Box::new(ClipboardContext::new().unwrap()),
             selection: None,
+            rich: None,
         };
 
         #[cfg(all(feature = "x11", not(any(target_os = "macos", windows))))]
@@ -71,6 +99,13 @@ impl Default for Clipboard {
             selection: Some(Box::new(
                 X11ClipboardContext::<X11SelectionClipboard>::new().unwrap(),
             )),
+            rich: match crate::platform::x11::selection::Selection::new() {
+                Ok(selection) => Some(Box::new(selection)),
+                Err(err) => {
+                    warn!("Unable to offer rich text in clipboard: {}", err);
+                    None
+                }
+            },
         };
 
         #[cfg(not(any(feature = "x11", target_os = "macos", windows)))]
@@ -91,6 +126,19 @@ impl Clipboard {
         });
     }
 
+    /// Store the text along with its HTML and RTF flavors. Platforms
+    /// without rich text support, and the selection buffer, only receive the
+    /// plain text.
+    pub fn set_rich(&mut self, ty: ClipboardType, content: RichText) {
+        if let (ClipboardType::Clipboard, Some(rich)) = (ty, &mut self.rich) {
+            if rich.set_rich(&content) {
+                return;
+            }
+        }
+
+        self.set(ty, content.text);
+    }
+
     pub fn get(&mut self, ty: ClipboardType) -> String {
         let clipboard = match (ty, &mut self.selection) {
             (ClipboardType::Selection, Some(provider)) => provider,
@@ -106,3 +154,112 @@ impl Clipboard {
         }
     }
 }
+
+/// Clipboard access requested by a program through OSC 52.
+#[derive(Clone)]
+pub enum ClipboardRequest {
+    /// Write the clipboard content into the PTY, using the attached formatter.
+    Load(
+        ClipboardType,
+        Arc<dyn Fn(&str) -> String + Sync + Send + 'static>,
+    ),
+    /// Replace the clipboard content.
+    Store(ClipboardType, String),
+}
+
+impl ClipboardRequest {
+    /// Permission configured for this request, payloads above the size
+    /// limit are always denied.
+    pub fn permission(&self, config: &config::Clipboard) -> ClipboardPermission {
+        match self {
+            ClipboardRequest::Load(..) => config.osc52_read,
+            ClipboardRequest::Store(_, text) if text.len() > config.osc52_max_size => {
+                ClipboardPermission::Deny
+            }
+            ClipboardRequest::Store(..) => config.osc52_write,
+        }
+    }
+
+    /// Human readable description, used by the permission prompt.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ClipboardRequest::Load(ClipboardType::Clipboard, _) => "read the clipboard",
+            ClipboardRequest::Load(ClipboardType::Selection, _) => {
+                "read the primary selection"
+            }
+            ClipboardRequest::Store(ClipboardType::Clipboard, _) => {
+                "write to the clipboard"
+            }
+            ClipboardRequest::Store(ClipboardType::Selection, _) => {
+                "write to the primary selection"
+            }
+        }
+    }
+}
+
+/// Clipboards that receive the selection once it is made.
+pub fn copy_on_select_targets(copy_on_select: CopyOnSelect) -> &'static [ClipboardType] {
+    match copy_on_select {
+        CopyOnSelect::None => &[],
+        CopyOnSelect::Primary => &[ClipboardType::Selection],
+        CopyOnSelect::Clipboard => &[ClipboardType::Clipboard],
+        CopyOnSelect::Both => &[ClipboardType::Selection, ClipboardType::Clipboard],
+    }
+}
+
+/// Remove trailing spaces and tabs of every line.
+pub fn trim_trailing_whitespace(text: &str) -> String {
+    text.split('\n')
+        .map(|line| line.trim_end_matches([' ', '\t']))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_trailing_whitespace() {
+        assert_eq!(trim_trailing_whitespace("a  \nb\t\n  c "), "a\nb\n  c");
+        assert_eq!(trim_trailing_whitespace("ls -la   \n"), "ls -la\n");
+    }
+
+    #[test]
+    fn test_clipboard_request_permission() {
+        let config = config::Clipboard {
+            osc52_read: ClipboardPermission::Ask,
+            osc52_write: ClipboardPermission::Allow,
+            osc52_max_size: 4,
+            ..config::Clipboard::default()
+        };
+
+        let load = ClipboardRequest::Load(
+            ClipboardType::Clipboard,
+            Arc::new(|text: &str| text.to_string()),
+        );
+        assert_eq!(load.permission(&config), ClipboardPermission::Ask);
+
+        let store =
+            ClipboardRequest::Store(ClipboardType::Clipboard, String::from("rio"));
+        assert_eq!(store.permission(&config), ClipboardPermission::Allow);
+
+        let store =
+            ClipboardRequest::Store(ClipboardType::Selection, String::from("rio!!"));
+        assert_eq!(store.permission(&config), ClipboardPermission::Deny);
+        assert_eq!(store.describe(), "write to the primary selection");
+    }
+
+    #[test]
+    fn test_copy_on_select_targets() {
+        assert!(copy_on_select_targets(CopyOnSelect::None).is_empty());
+        assert_eq!(
+            copy_on_select_targets(CopyOnSelect::Primary),
+            [ClipboardType::Selection]
+        );
+        assert_eq!(
+            copy_on_select_targets(CopyOnSelect::Both),
+            [ClipboardType::Selection, ClipboardType::Clipboard]
+        );
+    }
+}
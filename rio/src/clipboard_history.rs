@@ -0,0 +1,319 @@
+// Clipboard history.
+//
+// Every text Rio copies (selections and OSC 52 writes) is pushed into a
+// bounded ring, most recent first. When `[clipboard] persist-history = true`
+// the ring is kept in `<config dir>/clipboard-history.json`. Entries matching
+// one of the `history-exclude` regexes are never recorded.
+
+use regex::Regex;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Maximum number of entries listed by the picker at once.
+pub const PICKER_MAX_ENTRIES: usize = 10;
+
+pub struct ClipboardHistory {
+    entries: VecDeque<String>,
+    capacity: usize,
+    exclude: Vec<Regex>,
+}
+
+impl ClipboardHistory {
+    pub fn new(capacity: usize, exclude: Vec<Regex>) -> Self {
+        ClipboardHistory {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            exclude,
+        }
+    }
+
+    /// Entries, most recent first.
+    #[inline]
+    pub fn entries(&self) -> &VecDeque<String> {
+        &self.entries
+    }
+
+    /// Record `text`, moving it to the front if it is already known.
+    /// Returns false when the text was not recorded.
+    pub fn push(&mut self, text: &str) -> bool {
+        if self.capacity == 0
+            || text.trim().is_empty()
+            || self.exclude.iter().any(|regex| regex.is_match(text))
+        {
+            return false;
+        }
+
+        if let Some(index) = self.entries.iter().position(|entry| entry == text) {
+            if index == 0 {
+                return false;
+            }
+            self.entries.remove(index);
+        }
+
+        self.entries.push_front(text.to_string());
+        self.entries.truncate(self.capacity);
+        true
+    }
+
+    /// Load entries stored at `path`, still applying capacity and exclusions.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let content = fs::read_to_string(path)?;
+        let entries: Vec<String> = serde_json::from_str(&content)?;
+        for entry in entries.iter().rev() {
+            self.push(entry);
+        }
+        Ok(())
+    }
+
+    /// Write every entry into `path`, the file is replaced atomically.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_string(&self.entries)?)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+impl From<&config::Clipboard> for ClipboardHistory {
+    fn from(config: &config::Clipboard) -> Self {
+        let exclude = config
+            .history_exclude
+            .iter()
+            .filter_map(|rule| match Regex::new(rule) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    log::warn!("invalid clipboard history exclusion {rule:?}: {err}");
+                    None
+                }
+            })
+            .collect();
+
+        ClipboardHistory::new(config.history_size, exclude)
+    }
+}
+
+/// Score of `candidate` for the fuzzy `query`, or `None` when the query
+/// characters do not all appear in order. Higher is better.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut previous_matched = false;
+    let mut previous: Option<char> = None;
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+
+    for c in candidate.chars() {
+        let expected = match query.peek() {
+            Some(expected) => *expected,
+            None => break,
+        };
+
+        let is_match = c.to_lowercase().eq(std::iter::once(expected));
+        if is_match {
+            query.next();
+            score += 1;
+            if previous_matched {
+                score += 4;
+            }
+            if previous.map_or(true, |p| !p.is_alphanumeric()) {
+                score += 2;
+            }
+        }
+
+        previous_matched = is_match;
+        previous = Some(c);
+    }
+
+    match query.peek() {
+        Some(_) => None,
+        None => Some(score),
+    }
+}
+
+/// Overlay listing the history entries matching a fuzzy query.
+#[derive(Default)]
+pub struct ClipboardPicker {
+    pub query: String,
+    /// Indexes into the history entries, best match first.
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl ClipboardPicker {
+    pub fn new(history: &ClipboardHistory) -> Self {
+        let mut picker = ClipboardPicker::default();
+        picker.update(history);
+        picker
+    }
+
+    /// Recompute the matches after the query changed.
+    pub fn update(&mut self, history: &ClipboardHistory) {
+        let mut scored: Vec<(usize, usize)> = history
+            .entries()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                fuzzy_score(&self.query, entry).map(|score| (index, score))
+            })
+            .collect();
+        // Stable sort keeps the most recent entry first between equal scores.
+        scored.sort_by(|a, b| b.1.cmp(&a.1));
+
+        self.matches = scored.into_iter().map(|(index, _)| index).collect();
+        self.selected = 0;
+    }
+
+    pub fn push_char(&mut self, c: char, history: &ClipboardHistory) {
+        self.query.push(c);
+        self.update(history);
+    }
+
+    pub fn pop_char(&mut self, history: &ClipboardHistory) {
+        if self.query.pop().is_some() {
+            self.update(history);
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches.len().min(PICKER_MAX_ENTRIES) {
+            self.selected += 1;
+        }
+    }
+
+    /// Entry currently highlighted.
+    pub fn selected<'a>(&self, history: &'a ClipboardHistory) -> Option<&'a String> {
+        self.matches
+            .get(self.selected)
+            .and_then(|index| history.entries().get(*index))
+    }
+
+    /// Lines to draw, from top to bottom, and the highlighted line index.
+    pub fn lines(&self, history: &ClipboardHistory) -> (Vec<String>, Option<usize>) {
+        let mut lines: Vec<String> = self
+            .matches
+            .iter()
+            .take(PICKER_MAX_ENTRIES)
+            .filter_map(|index| history.entries().get(*index))
+            .map(|entry| entry.replace('\n', "⏎").replace('\t', " "))
+            .collect();
+        let selected = if lines.is_empty() {
+            None
+        } else {
+            Some(self.selected)
+        };
+        lines.push(format!("Clipboard history: {}", self.query));
+        (lines, selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(entries: &[&str]) -> ClipboardHistory {
+        let mut history = ClipboardHistory::new(5, vec![]);
+        for entry in entries {
+            history.push(entry);
+        }
+        history
+    }
+
+    #[test]
+    fn test_push_keeps_most_recent_first() {
+        let mut history = history(&["a", "b", "c"]);
+        assert_eq!(history.entries(), &["c", "b", "a"]);
+
+        // Re-copying moves the entry to the front instead of duplicating it
+        assert!(history.push("a"));
+        assert_eq!(history.entries(), &["a", "c", "b"]);
+        assert!(!history.push("a"));
+
+        assert!(!history.push("   "));
+        assert_eq!(history.entries().len(), 3);
+    }
+
+    #[test]
+    fn test_push_respects_capacity() {
+        let history = history(&["1", "2", "3", "4", "5", "6", "7"]);
+        assert_eq!(history.entries(), &["7", "6", "5", "4", "3"]);
+
+        let mut disabled = ClipboardHistory::new(0, vec![]);
+        assert!(!disabled.push("rio"));
+        assert!(disabled.entries().is_empty());
+    }
+
+    #[test]
+    fn test_push_excludes_secrets() {
+        let mut history =
+            ClipboardHistory::new(5, vec![Regex::new(r"^ghp_\w+$").unwrap()]);
+        assert!(!history.push("ghp_abcdef123456"));
+        assert!(history.push("git push"));
+        assert_eq!(history.entries(), &["git push"]);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "rio-clipboard-history-test-{}.json",
+            std::process::id()
+        ));
+        let saved = history(&["first", "second"]);
+        saved.save(&path).unwrap();
+
+        let mut loaded = ClipboardHistory::new(5, vec![Regex::new("first").unwrap()]);
+        loaded.load(&path).unwrap();
+        assert_eq!(loaded.entries(), &["second"]);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert!(fuzzy_score("abc", "acb").is_none());
+        assert!(fuzzy_score("UUID", "uuid-1234").is_some());
+
+        // Consecutive and word start matches score higher
+        let consecutive = fuzzy_score("log", "git log").unwrap();
+        let scattered = fuzzy_score("log", "lorem ipsum dolor sit amet grep").unwrap();
+        assert!(consecutive > scattered);
+    }
+
+    #[test]
+    fn test_picker() {
+        let history = history(&["cargo build", "d2f1-4c3a", "cargo test"]);
+        let mut picker = ClipboardPicker::new(&history);
+        assert_eq!(picker.selected(&history).unwrap(), "cargo test");
+
+        for c in "d2".chars() {
+            picker.push_char(c, &history);
+        }
+        assert_eq!(picker.selected(&history).unwrap(), "d2f1-4c3a");
+        let (lines, selected) = picker.lines(&history);
+        assert_eq!(lines, vec!["d2f1-4c3a", "Clipboard history: d2"]);
+        assert_eq!(selected, Some(0));
+
+        picker.pop_char(&history);
+        picker.pop_char(&history);
+        picker.push_char('c', &history);
+        picker.select_next();
+        assert_eq!(picker.selected(&history).unwrap(), "cargo build");
+        picker.select_next();
+        picker.select_next();
+        assert_eq!(picker.selected(&history).unwrap(), "d2f1-4c3a");
+        picker.select_previous();
+        assert_eq!(picker.selected(&history).unwrap(), "cargo build");
+
+        picker.push_char('z', &history);
+        assert!(picker.selected(&history).is_none());
+        assert_eq!(picker.lines(&history).1, None);
+    }
+}
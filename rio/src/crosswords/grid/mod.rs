@@ -61,6 +61,9 @@ pub struct Grid<T> {
 
     /// Maximum number of lines in history.
     max_scroll_limit: usize,
+
+    /// Lines scrolled into history since the grid was created, wrapping.
+    scrolled: usize,
 }
 
 impl<T: GridSquare + Default + PartialEq + Clone> Grid<T> {
@@ -73,6 +76,7 @@ impl<T: GridSquare + Default + PartialEq + Clone> Grid<T> {
             cursor: Cursor::default(),
             lines,
             columns,
+            scrolled: 0,
         }
     }
 
@@ -204,6 +208,9 @@ impl<T: GridSquare + Default + PartialEq + Clone> Grid<T> {
 
         // Create scrollback for the new lines.
         self.increase_scroll_limit(positions);
+        if self.max_scroll_limit > 0 {
+            self.scrolled = self.scrolled.wrapping_add(positions);
+        }
 
         // Swap the lines fixed at the top to their target positions after rotation.
         //
@@ -306,6 +313,24 @@ impl<T> Grid<T> {
         }
     }
 
+    /// Lines scrolled into history since the grid was created, the
+    /// difference between two calls tells how many were added. It keeps
+    /// counting once the history is full.
+    #[inline]
+    pub fn scrolled_lines(&self) -> usize {
+        self.scrolled
+    }
+
+    /// Drop the oldest lines of history until at most `history_size` are
+    /// left.
+    pub fn truncate_history(&mut self, history_size: usize) {
+        let current_history_size = self.history_size();
+        if current_history_size > history_size {
+            self.raw.shrink_lines(current_history_size - history_size);
+        }
+        self.display_offset = min(self.display_offset, history_size);
+    }
+
     #[inline]
     pub fn clear_history(&mut self) {
         // Explicitly purge all lines from history.
@@ -474,7 +499,6 @@ impl<G> Dimensions for Grid<G> {
     }
 }
 
-#[cfg(test)]
 impl Dimensions for (usize, usize) {
     fn total_lines(&self) -> usize {
         self.0
@@ -25,7 +25,7 @@ use crate::clipboard::ClipboardType;
 use crate::crosswords::grid::{BidirectionalIterator, Dimensions, Grid, Scroll};
 use crate::event::{EventListener, RioEvent};
 use crate::performer::handler::Handler;
-use crate::selection::{Selection, SelectionRange, SelectionType};
+use crate::selection::{Selection, SelectionRange, SelectionRules, SelectionType};
 use attr::*;
 use base64::{engine::general_purpose, Engine as _};
 use bitflags::bitflags;
@@ -75,6 +75,7 @@ bitflags! {
         const ALTERNATE_SCROLL    = 0b0000_1000_0000_0000_0000;
         const VI                  = 0b0001_0000_0000_0000_0000;
         const URGENCY_HINTS       = 0b0010_0000_0000_0000_0000;
+        const IN_BAND_RESIZE      = 0b0100_0000_0000_0000_0000;
         const ANY                 = u32::MAX;
     }
 }
@@ -108,7 +109,6 @@ impl LineDamageBounds {
     }
 
     #[inline]
-    #[allow(dead_code)]
     pub fn reset(&mut self, num_cols: usize) {
         *self = Self::undamaged(num_cols, self.line);
     }
@@ -120,12 +120,53 @@ impl LineDamageBounds {
     }
 
     #[inline]
-    #[allow(dead_code)]
     pub fn is_damaged(&self) -> bool {
         self.left <= self.right
     }
 }
 
+/// Damage of the terminal since the last [`Crosswords::reset_damage`].
+#[derive(Debug)]
+pub enum TermDamage<'a> {
+    /// The whole viewport.
+    Full,
+    /// Only these lines of the viewport.
+    Partial(TermDamageIterator<'a>),
+}
+
+/// Damaged lines of the viewport, see [`TermDamage::Partial`].
+#[derive(Debug, Clone)]
+pub struct TermDamageIterator<'a> {
+    line_damage: std::slice::Iter<'a, LineDamageBounds>,
+    display_offset: usize,
+}
+
+impl<'a> TermDamageIterator<'a> {
+    fn new(line_damage: &'a [LineDamageBounds], display_offset: usize) -> Self {
+        // Damage of lines scrolled out of the viewport is not visible.
+        let num_lines = line_damage.len();
+        let line_damage = &line_damage[..num_lines.saturating_sub(display_offset)];
+
+        Self {
+            display_offset,
+            line_damage: line_damage.iter(),
+        }
+    }
+}
+
+impl<'a> Iterator for TermDamageIterator<'a> {
+    type Item = LineDamageBounds;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.line_damage.find_map(|line| {
+            line.is_damaged().then_some(LineDamageBounds {
+                line: line.line + self.display_offset,
+                ..*line
+            })
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 struct TermDamageState {
     /// Hint whether terminal should be damaged entirely regardless of the actual damage changes.
@@ -274,7 +315,7 @@ where
     active_charset: CharsetIndex,
     mode: Mode,
     pub vi_mode_cursor: ViModeCursor,
-    semantic_escape_chars: String,
+    selection_rules: Arc<SelectionRules>,
     pub grid: Grid<Square>,
     inactive_grid: Grid<Square>,
     scroll_region: Range<Line>,
@@ -294,13 +335,12 @@ impl<U: EventListener> Crosswords<U> {
         let alt = Grid::new(rows, cols, 0);
 
         let scroll_region = Line(0)..Line(rows as i32);
-        let semantic_escape_chars = String::from(",│`|:\"' ()[]{}<>\t");
         let term_colors = TermColors::default();
         let colors = List::from(&term_colors);
 
         Crosswords {
             vi_mode_cursor: ViModeCursor::new(grid.cursor.pos),
-            semantic_escape_chars,
+            selection_rules: Arc::new(SelectionRules::default()),
             selection: None,
             grid,
             inactive_grid: alt,
@@ -323,11 +363,37 @@ impl<U: EventListener> Crosswords<U> {
         self.damage.is_fully_damaged = true;
     }
 
-    #[allow(dead_code)]
     pub fn reset_damage(&mut self) {
         self.damage.reset(self.grid.columns());
     }
 
+    /// What changed since the last [`Crosswords::reset_damage`], including
+    /// the cells the cursor left and moved to.
+    pub fn damage(&mut self) -> TermDamage<'_> {
+        // Characters are shifted by insert mode without damaging them.
+        if self.mode.contains(Mode::INSERT) {
+            self.mark_fully_damaged();
+        }
+
+        let previous_cursor =
+            mem::replace(&mut self.damage.last_cursor, self.grid.cursor.pos);
+
+        if self.damage.is_fully_damaged {
+            return TermDamage::Full;
+        }
+
+        if self.damage.last_cursor != previous_cursor {
+            // The cursor is always in the viewport, regardless of the
+            // display offset.
+            self.damage.damage_point(previous_cursor);
+        }
+        self.damage_cursor();
+
+        // Scrolling the viewport damages it fully.
+        let display_offset = self.grid.display_offset();
+        TermDamage::Partial(TermDamageIterator::new(&self.damage.lines, display_offset))
+    }
+
     pub fn display_offset(&mut self) -> usize {
         self.grid.display_offset()
     }
@@ -360,6 +426,84 @@ impl<U: EventListener> Crosswords<U> {
         self.colors
     }
 
+    #[inline]
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Grid of the primary screen, regardless of the alternate screen being active.
+    #[inline]
+    pub fn primary_grid(&self) -> &Grid<Square> {
+        if self.mode.contains(Mode::ALT_SCREEN) {
+            &self.inactive_grid
+        } else {
+            &self.grid
+        }
+    }
+
+    #[inline]
+    pub fn primary_grid_mut(&mut self) -> &mut Grid<Square> {
+        if self.mode.contains(Mode::ALT_SCREEN) {
+            &mut self.inactive_grid
+        } else {
+            &mut self.grid
+        }
+    }
+
+    /// Take the modes of the terminal this one mirrors, switching to the
+    /// alternate screen and back along with it.
+    pub fn mirror_mode(&mut self, mode: Mode) {
+        if mode.contains(Mode::ALT_SCREEN) != self.mode.contains(Mode::ALT_SCREEN) {
+            self.swap_alt();
+        }
+        self.mode = mode;
+    }
+
+    /// Write previously saved rows into the primary screen, pushing them into
+    /// the scrollback. The row of `rows` the cursor was saved on keeps its
+    /// line restored even when nothing was written on it. A dimmed
+    /// separator is added after the restored content, the cursor starts
+    /// the line below it.
+    pub fn restore_history(
+        &mut self,
+        mut rows: Vec<Row<Square>>,
+        cursor_row: Option<usize>,
+    ) {
+        if rows.is_empty() || self.mode.contains(Mode::ALT_SCREEN) {
+            return;
+        }
+
+        let columns = self.grid.columns();
+        if let Some(row) = cursor_row {
+            while rows.len() <= row {
+                rows.push(Row::new(0));
+            }
+        }
+        let mut separator: Row<Square> = Row::new(columns);
+        for square in &mut separator[..] {
+            square.c = '─';
+            square.flags.insert(square::Flags::DIM);
+        }
+
+        for row in rows.into_iter().chain(std::iter::once(separator)) {
+            let line = self.grid.cursor.pos.row;
+            let len = std::cmp::min(row.len(), columns);
+            for (index, square) in row[..].iter().take(len).enumerate() {
+                self.grid[line][Column(index)] = square.clone();
+            }
+            self.linefeed();
+        }
+
+        self.grid.cursor.pos.col = Column(0);
+        self.mark_fully_damaged();
+    }
+
+    /// Title of a restored session, until the program sets its own.
+    #[inline]
+    pub fn restore_title(&mut self, title: Option<String>) {
+        self.title = title;
+    }
+
     #[inline]
     pub fn exit(&mut self)
     where
@@ -528,7 +672,12 @@ impl<U: EventListener> Crosswords<U> {
 
     #[inline]
     pub fn semantic_escape_chars(&self) -> &str {
-        &self.semantic_escape_chars
+        &self.selection_rules.semantic_escape_chars
+    }
+
+    #[inline]
+    pub fn set_selection_rules(&mut self, selection_rules: Arc<SelectionRules>) {
+        self.selection_rules = selection_rules;
     }
 
     #[inline]
@@ -686,7 +835,8 @@ impl<U: EventListener> Crosswords<U> {
             | square::Flags::WIDE_CHAR_SPACER
             | square::Flags::LEADING_WIDE_CHAR_SPACER;
         while let Some(cell) = iter.prev() {
-            if !cell.flags.intersects(wide) && self.semantic_escape_chars.contains(cell.c)
+            if !cell.flags.intersects(wide)
+                && self.semantic_escape_chars().contains(cell.c)
             {
                 break;
             }
@@ -713,7 +863,8 @@ impl<U: EventListener> Crosswords<U> {
         let last_column = self.grid.columns() - 1;
 
         for cell in self.grid.iter_from(point) {
-            if !cell.flags.intersects(wide) && self.semantic_escape_chars.contains(cell.c)
+            if !cell.flags.intersects(wide)
+                && self.semantic_escape_chars().contains(cell.c)
             {
                 break;
             }
@@ -728,6 +879,123 @@ impl<U: EventListener> Crosswords<U> {
         point
     }
 
+    /// Hyperlink at `point`, either set through OSC 8 or a URL matched by
+    /// the smart selection rules.
+    pub fn link_at(&self, point: Pos) -> Option<String> {
+        if point.row < self.grid.topmost_line() || point.row > self.grid.bottommost_line()
+        {
+            return None;
+        }
+
+        if let Some(hyperlink) = self.grid[point].hyperlink() {
+            return Some(hyperlink.uri().to_owned());
+        }
+
+        let (start, end) = self.smart_search(point)?;
+        let text = self.bounds_to_string(start, end);
+        text.contains("://").then_some(text)
+    }
+
+    /// Find the first smart selection rule matching around `point`, the
+    /// logical line (including soft wraps) is matched as a whole.
+    ///
+    /// Matches within a single word come first. Those spanning several
+    /// words, like quoted strings, only apply when `point` isn't on a word,
+    /// so double clicking a word inside quotes still selects the word.
+    pub fn smart_search(&self, mut point: Pos) -> Option<(Pos, Pos)> {
+        if self.selection_rules.smart_rules.is_empty()
+            || point.row < self.grid.topmost_line()
+            || point.row > self.grid.bottommost_line()
+        {
+            return None;
+        }
+
+        let last_column = self.grid.last_column();
+        if point.col > Column(0)
+            && self.grid[point]
+                .flags
+                .contains(square::Flags::WIDE_CHAR_SPACER)
+        {
+            point.col -= 1;
+        }
+
+        let mut start = point.row;
+        while start > self.grid.topmost_line()
+            && self.grid[Line(start.0 - 1)][last_column]
+                .flags
+                .contains(square::Flags::WRAPLINE)
+        {
+            start -= 1;
+        }
+        let mut end = point.row;
+        while end < self.grid.bottommost_line()
+            && self.grid[end][last_column]
+                .flags
+                .contains(square::Flags::WRAPLINE)
+        {
+            end += 1;
+        }
+
+        // Text of the logical line along with the byte offset of every cell.
+        let mut text = String::new();
+        let mut offsets: Vec<(usize, Pos)> = Vec::new();
+        let spacers =
+            square::Flags::WIDE_CHAR_SPACER | square::Flags::LEADING_WIDE_CHAR_SPACER;
+        for line in start.0..=end.0 {
+            let row = &self.grid[Line(line)];
+            for column in 0..row.len() {
+                let cell = &row[Column(column)];
+                if cell.flags.intersects(spacers) {
+                    continue;
+                }
+
+                offsets.push((text.len(), Pos::new(Line(line), Column(column))));
+                text.push(cell.c);
+                if let Some(zerowidth) = cell.zerowidth() {
+                    text.extend(zerowidth);
+                }
+            }
+        }
+
+        let target = offsets.iter().find(|(_, pos)| *pos == point)?.0;
+        let pos_at = |offset: usize| {
+            let index = offsets.partition_point(|(start, _)| *start <= offset);
+            offsets[index.saturating_sub(1)].1
+        };
+
+        let bounds = |found: regex::Match| {
+            let start = pos_at(found.start());
+            let mut end = pos_at(found.end() - 1);
+            if self.grid[end].flags.contains(square::Flags::WIDE_CHAR) {
+                end.col += 1;
+            }
+            (start, end)
+        };
+
+        let mut phrase = None;
+        for rule in &self.selection_rules.smart_rules {
+            let found = rule
+                .find_iter(&text)
+                .find(|found| found.start() <= target && target < found.end());
+            match found {
+                Some(found) if !found.as_str().contains(char::is_whitespace) => {
+                    return Some(bounds(found));
+                }
+                Some(found) if phrase.is_none() => phrase = Some(found),
+                _ => {}
+            }
+        }
+
+        let on_word = !self
+            .selection_rules
+            .semantic_escape_chars
+            .contains(self.grid[point].c);
+        if on_word {
+            return None;
+        }
+        phrase.map(bounds)
+    }
+
     pub fn write_at_cursor(&mut self, c: char) {
         let c = self.grid.cursor.charsets[self.active_charset].map(c);
         let fg = self.grid.cursor.template.fg;
@@ -1038,6 +1306,7 @@ impl<U: EventListener> Handler for Crosswords<U> {
     fn set_mode(&mut self, mode: AnsiMode) {
         match mode {
             AnsiMode::UrgencyHints => self.mode.insert(Mode::URGENCY_HINTS),
+            AnsiMode::InBandResize => self.mode.insert(Mode::IN_BAND_RESIZE),
             AnsiMode::SwapScreenAndSetRestoreCursor => {
                 if !self.mode.contains(Mode::ALT_SCREEN) {
                     self.swap_alt();
@@ -1109,6 +1378,7 @@ impl<U: EventListener> Handler for Crosswords<U> {
     fn unset_mode(&mut self, mode: AnsiMode) {
         match mode {
             AnsiMode::UrgencyHints => self.mode.remove(Mode::URGENCY_HINTS),
+            AnsiMode::InBandResize => self.mode.remove(Mode::IN_BAND_RESIZE),
             AnsiMode::SwapScreenAndSetRestoreCursor => {
                 if self.mode.contains(Mode::ALT_SCREEN) {
                     self.swap_alt();
@@ -1447,11 +1717,11 @@ impl<U: EventListener> Handler for Crosswords<U> {
     fn set_title(&mut self, title: Option<String>) {
         self.title = title;
 
-        let _title: String = match &self.title {
-            Some(title) => title.to_string(),
-            None => String::from(""),
+        let event = match &self.title {
+            Some(title) => RioEvent::Title(title.to_string()),
+            None => RioEvent::ResetTitle,
         };
-        // title
+        self.event_proxy.send_event(event);
     }
 
     fn set_cursor_shape(&mut self, shape: CursorShape) {
@@ -1712,6 +1982,22 @@ impl<U: EventListener> Handler for Crosswords<U> {
         warn!("[unimplemented] Substitute");
     }
 
+    #[inline]
+    fn clipboard_store(&mut self, clipboard: u8, base64: &[u8]) {
+        let clipboard_type = match clipboard {
+            b'c' => ClipboardType::Clipboard,
+            b'p' | b's' => ClipboardType::Selection,
+            _ => return,
+        };
+
+        if let Ok(bytes) = general_purpose::STANDARD.decode(base64) {
+            if let Ok(text) = String::from_utf8(bytes) {
+                self.event_proxy
+                    .send_event(RioEvent::ClipboardStore(clipboard_type, text));
+            }
+        }
+    }
+
     #[inline]
     fn clipboard_load(&mut self, clipboard: u8, terminator: &str) {
         let clipboard_type = match clipboard {
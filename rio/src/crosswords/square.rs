@@ -53,7 +53,6 @@ impl Hyperlink {
         &self.inner.id
     }
 
-    #[allow(dead_code)]
     pub fn uri(&self) -> &str {
         &self.inner.uri
     }
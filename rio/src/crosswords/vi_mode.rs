@@ -5,9 +5,10 @@ use crate::crosswords::pos::{Boundary, Column, Direction, Line, Pos, Side};
 use crate::crosswords::square::Flags;
 use crate::crosswords::Crosswords;
 use crate::event::EventListener;
+use serde::Deserialize;
 
 /// Possible vi mode motion movements.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
 pub enum ViMotion {
     /// Move up.
     Up,
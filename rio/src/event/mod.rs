@@ -2,6 +2,8 @@ pub mod sync;
 
 use crate::clipboard::ClipboardType;
 use crate::crosswords::grid::Scroll;
+use crate::ipc;
+use crate::tmux;
 use colors::ColorRgb;
 use std::borrow::Cow;
 use std::fmt::Debug;
@@ -9,12 +11,17 @@ use std::fmt::Formatter;
 use std::sync::Arc;
 use teletypewriter::WinsizeBuilder;
 use winit::event_loop::EventLoopProxy;
+use winit::window::WindowId;
 
 #[derive(Debug)]
 pub enum Msg {
     /// Data that should be written to the PTY.
     Input(Cow<'static, [u8]>),
 
+    /// Command of the tmux gateway, written in control mode when input is
+    /// not.
+    Command(Cow<'static, [u8]>),
+
     #[allow(dead_code)]
     Shutdown,
 
@@ -81,6 +88,38 @@ pub enum RioEvent {
     /// Terminal bell ring.
     Bell,
 
+    /// Persist tabs into the session file.
+    SaveSession,
+
+    /// The key following the leader was not pressed in time.
+    KeyTableTimeout,
+
+    /// Open a new window.
+    CreateWindow,
+
+    /// Request received on the control socket, along with where its reply
+    /// goes.
+    Ipc(ipc::Request, std::sync::mpsc::Sender<ipc::Reply>),
+
+    /// The program of a tab exited and the tab is kept open, showing how
+    /// it exited.
+    ChildExited(Option<teletypewriter::ExitStatus>),
+
+    /// Close the window, the user agreed to it.
+    CloseWindow,
+
+    /// Quit Rio, asking first when programs run in its tabs unless the
+    /// user already agreed to it.
+    Quit {
+        confirmed: bool,
+    },
+
+    /// Look again for the programs running in the foreground of the tabs.
+    UpdateForegroundProcesses,
+
+    /// The tmux session shown through a gateway changed, its tabs follow.
+    Tmux(tmux::Gateway, tmux::Change),
+
     /// Shutdown request.
     Exit,
 }
@@ -104,6 +143,15 @@ impl Debug for RioEvent {
             RioEvent::Render => write!(f, "Render"),
             RioEvent::Scroll(scroll) => write!(f, "Scroll {scroll:?}"),
             RioEvent::Bell => write!(f, "Bell"),
+            RioEvent::SaveSession => write!(f, "SaveSession"),
+            RioEvent::KeyTableTimeout => write!(f, "KeyTableTimeout"),
+            RioEvent::CreateWindow => write!(f, "CreateWindow"),
+            RioEvent::Ipc(request, _) => write!(f, "Ipc({request:?})"),
+            RioEvent::ChildExited(status) => write!(f, "ChildExited({status:?})"),
+            RioEvent::CloseWindow => write!(f, "CloseWindow"),
+            RioEvent::Quit { confirmed } => write!(f, "Quit({confirmed})"),
+            RioEvent::UpdateForegroundProcesses => write!(f, "UpdateForegroundProcesses"),
+            RioEvent::Tmux(gateway, change) => write!(f, "Tmux({gateway:?}, {change:?})"),
             RioEvent::Exit => write!(f, "Exit"),
             RioEvent::UpdateConfig => write!(f, "ReloadConfiguration"),
         }
@@ -128,12 +176,28 @@ impl From<RioEvent> for RioEventType {
 pub struct EventP {
     /// Event payload.
     pub payload: RioEventType,
-    pub id: u8,
+    /// Route of the tab that emitted the event, zero when not tied to one.
+    pub id: usize,
+    /// Window the event is meant for, none when it concerns all of them.
+    pub window_id: Option<WindowId>,
 }
 
 impl EventP {
     pub fn new(payload: RioEventType) -> Self {
-        Self { payload, id: 0 }
+        Self {
+            payload,
+            id: 0,
+            window_id: None,
+        }
+    }
+
+    /// Event meant for the window `window_id`.
+    pub fn window(payload: RioEventType, window_id: WindowId) -> Self {
+        Self {
+            payload,
+            id: 0,
+            window_id: Some(window_id),
+        }
     }
 }
 
@@ -150,6 +214,15 @@ pub trait OnResize {
 /// Event Loop for notifying the renderer about terminal events.
 pub trait EventListener {
     fn send_event(&self, _event: RioEvent) {}
+
+    /// Listener tagging every event with `route_id`, so the receiver knows
+    /// which tab it came from.
+    fn route(&self, _route_id: usize) -> Self
+    where
+        Self: Sized + Clone,
+    {
+        self.clone()
+    }
 }
 
 #[derive(Clone)]
@@ -160,21 +233,52 @@ impl EventListener for VoidListener {}
 #[derive(Debug, Clone)]
 pub struct EventProxy {
     proxy: EventLoopProxy<EventP>,
+    route_id: usize,
+    window_id: Option<WindowId>,
 }
 
 impl EventProxy {
     pub fn new(proxy: EventLoopProxy<EventP>) -> Self {
-        Self { proxy }
+        Self {
+            proxy,
+            route_id: 0,
+            window_id: None,
+        }
+    }
+
+    /// Proxy tagging every event with `window_id`, so the receiver knows
+    /// which window it came from.
+    pub fn for_window(&self, window_id: WindowId) -> Self {
+        Self {
+            proxy: self.proxy.clone(),
+            route_id: self.route_id,
+            window_id: Some(window_id),
+        }
     }
 
-    #[allow(dead_code)]
     pub fn send_event(&self, event: RioEventType) {
-        let _ = self.proxy.send_event(EventP::new(event));
+        let _ = self.proxy.send_event(EventP {
+            payload: event,
+            id: self.route_id,
+            window_id: self.window_id,
+        });
     }
 }
 
 impl EventListener for EventProxy {
     fn send_event(&self, event: RioEvent) {
-        let _ = self.proxy.send_event(EventP::new(event.into()));
+        let _ = self.proxy.send_event(EventP {
+            payload: event.into(),
+            id: self.route_id,
+            window_id: self.window_id,
+        });
+    }
+
+    fn route(&self, route_id: usize) -> Self {
+        Self {
+            proxy: self.proxy.clone(),
+            route_id,
+            window_id: self.window_id,
+        }
     }
 }
@@ -0,0 +1,934 @@
+// Export of terminal content.
+//
+// Lines are collected out of the grid (history, visible screen or the
+// current selection) and rendered as plain text, ANSI, HTML or SVG.
+
+use crate::crosswords::grid::row::Row;
+use crate::crosswords::grid::{Dimensions, GridSquare};
+use crate::crosswords::pos::{Column, Line};
+use crate::crosswords::square::{Flags, Square};
+use crate::crosswords::Crosswords;
+use crate::event::EventListener;
+use crate::session::{self, ContextSnapshot};
+use colors::{AnsiColor, ColorArray, ColorRgb, NamedColor};
+use std::error::Error;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SVG_FONT_SIZE: f32 = 14.;
+const SVG_CELL_WIDTH: f32 = SVG_FONT_SIZE * 0.6;
+const SVG_CELL_HEIGHT: f32 = SVG_FONT_SIZE * 1.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Text,
+    Ansi,
+    Html,
+    Svg,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Text => "txt",
+            ExportFormat::Ansi => "ans",
+            ExportFormat::Html => "html",
+            ExportFormat::Svg => "svg",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" | "txt" => Ok(ExportFormat::Text),
+            "ansi" | "ans" => Ok(ExportFormat::Ansi),
+            "html" => Ok(ExportFormat::Html),
+            "svg" => Ok(ExportFormat::Svg),
+            _ => Err(format!("unknown export format: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportScope {
+    /// Scrollback and visible screen.
+    History,
+    /// Visible screen only.
+    Screen,
+    /// Current selection.
+    Selection,
+}
+
+impl ExportScope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportScope::History => "history",
+            ExportScope::Screen => "screen",
+            ExportScope::Selection => "selection",
+        }
+    }
+}
+
+impl FromStr for ExportScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "history" => Ok(ExportScope::History),
+            "screen" => Ok(ExportScope::Screen),
+            "selection" => Ok(ExportScope::Selection),
+            _ => Err(format!("unknown export scope: {s}")),
+        }
+    }
+}
+
+/// Resolves cell colors into the active theme.
+pub trait Palette {
+    fn color(&self, color: AnsiColor, flags: Flags) -> ColorArray;
+}
+
+/// A single grid row, `wrapped` is set when the row continues on the next one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportLine {
+    pub squares: Vec<Square>,
+    pub wrapped: bool,
+}
+
+impl ExportLine {
+    pub fn from_row(row: &Row<Square>, start: Column, end: Column) -> Self {
+        if row.len() == 0 {
+            return ExportLine {
+                squares: Vec::new(),
+                wrapped: false,
+            };
+        }
+
+        let last_column = Column(row.len() - 1);
+        let end = std::cmp::min(end, last_column);
+        let wrapped =
+            end == last_column && row[last_column].flags.contains(Flags::WRAPLINE);
+
+        let mut squares = if start <= end {
+            row[start..end + 1].to_vec()
+        } else {
+            Vec::new()
+        };
+
+        if !wrapped {
+            while squares.last().map_or(false, |square| square.is_empty()) {
+                squares.pop();
+            }
+        }
+
+        ExportLine { squares, wrapped }
+    }
+}
+
+/// Collect the lines of `scope` out of the terminal.
+pub fn lines<T: EventListener>(
+    terminal: &Crosswords<T>,
+    scope: ExportScope,
+) -> Vec<ExportLine> {
+    let grid = &terminal.grid;
+    let last_column = grid.last_column();
+
+    let mut lines = Vec::new();
+    match scope {
+        ExportScope::History => {
+            for line in grid.topmost_line().0..=grid.bottommost_line().0 {
+                lines.push(ExportLine::from_row(
+                    &grid[Line(line)],
+                    Column(0),
+                    last_column,
+                ));
+            }
+            while lines.last().map_or(false, |line: &ExportLine| {
+                line.squares.is_empty() && !line.wrapped
+            }) {
+                lines.pop();
+            }
+        }
+        ExportScope::Screen => {
+            let start = -(grid.display_offset() as i32);
+            for line in start..start + grid.screen_lines() as i32 {
+                lines.push(ExportLine::from_row(
+                    &grid[Line(line)],
+                    Column(0),
+                    last_column,
+                ));
+            }
+        }
+        ExportScope::Selection => {
+            let range = match terminal
+                .selection
+                .as_ref()
+                .and_then(|selection| selection.to_range(terminal))
+            {
+                Some(range) => range,
+                None => return lines,
+            };
+
+            for line in range.start.row.0..=range.end.row.0 {
+                let line = Line(line);
+                let (start, end) = if range.is_block {
+                    (range.start.col, range.end.col)
+                } else {
+                    (
+                        if line == range.start.row {
+                            range.start.col
+                        } else {
+                            Column(0)
+                        },
+                        if line == range.end.row {
+                            range.end.col
+                        } else {
+                            last_column
+                        },
+                    )
+                };
+                let mut export_line = ExportLine::from_row(&grid[line], start, end);
+                // Block selections never join rows.
+                export_line.wrapped &= !range.is_block;
+                lines.push(export_line);
+            }
+        }
+    }
+
+    lines
+}
+
+/// Lines of a tab saved in the session file.
+pub fn session_lines(context: &ContextSnapshot) -> Vec<ExportLine> {
+    context
+        .to_rows()
+        .iter()
+        .map(|row| ExportLine::from_row(row, Column(0), Column(row.len())))
+        .collect()
+}
+
+/// Render the scrollback of a tab of the first window saved in the session
+/// at `path`, `tab` defaults to the tab that was focused.
+pub fn render_session<P: AsRef<Path>>(
+    path: P,
+    tab: Option<usize>,
+    format: ExportFormat,
+    palette: &dyn Palette,
+) -> Result<String, Box<dyn Error>> {
+    let snapshot = session::load(path)?;
+    let window = snapshot
+        .windows
+        .first()
+        .ok_or("the session holds no window")?;
+    let tab = tab.unwrap_or(window.current);
+    let context = window
+        .tabs
+        .get(tab)
+        .and_then(|tab| tab.focused())
+        .ok_or_else(|| format!("tab {tab} does not exist in the session"))?;
+
+    Ok(render(&session_lines(context), format, palette))
+}
+
+/// Default destination of exports: `<home>/rio-<scope>-<timestamp>.<extension>`.
+pub fn default_path(scope: ExportScope, format: ExportFormat) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let directory = dirs::home_dir().unwrap_or_else(std::env::temp_dir);
+    directory.join(format!(
+        "rio-{}-{timestamp}.{}",
+        scope.name(),
+        format.extension()
+    ))
+}
+
+pub fn render(
+    lines: &[ExportLine],
+    format: ExportFormat,
+    palette: &dyn Palette,
+) -> String {
+    match format {
+        ExportFormat::Text => to_text(lines),
+        ExportFormat::Ansi => to_ansi(lines),
+        ExportFormat::Html => to_html(lines, palette),
+        ExportFormat::Svg => to_svg(lines, palette),
+    }
+}
+
+#[inline]
+fn is_spacer(square: &Square) -> bool {
+    square
+        .flags
+        .intersects(Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER)
+}
+
+#[inline]
+fn push_content(output: &mut String, square: &Square) {
+    output.push(square.c);
+    if let Some(zerowidth) = square.zerowidth() {
+        output.extend(zerowidth);
+    }
+}
+
+/// Plain text, soft wrapped rows are joined.
+pub fn to_text(lines: &[ExportLine]) -> String {
+    let mut output = String::new();
+    let mut current = String::new();
+    for line in lines {
+        for square in line.squares.iter().filter(|square| !is_spacer(square)) {
+            push_content(&mut current, square);
+        }
+
+        if !line.wrapped {
+            output.push_str(current.trim_end());
+            output.push('\n');
+            current.clear();
+        }
+    }
+    output.push_str(current.trim_end());
+    output
+}
+
+fn sgr_color(output: &mut String, color: AnsiColor, background: bool) {
+    let base = if background { 40 } else { 30 };
+    match color {
+        AnsiColor::Named(named) => {
+            // Dim variants are written as their regular color, DIM is a flag.
+            let index = if (named as usize) < 16 {
+                named as usize
+            } else {
+                named.to_light() as usize
+            };
+            match index {
+                0..=7 => {
+                    let _ = write!(output, ";{}", base + index);
+                }
+                8..=15 => {
+                    let _ = write!(output, ";{}", base + 60 + index - 8);
+                }
+                _ => {}
+            }
+        }
+        AnsiColor::Indexed(index) => {
+            let _ = write!(output, ";{};5;{index}", base + 8);
+        }
+        AnsiColor::Spec(rgb) => {
+            let _ = write!(output, ";{};2;{};{};{}", base + 8, rgb.r, rgb.g, rgb.b);
+        }
+    }
+}
+
+/// SGR sequence reproducing the attributes of `square`, always starting with a reset.
+fn sgr(square: &Square) -> String {
+    let mut output = String::from("\x1b[0");
+    let flags = square.flags;
+    if flags.contains(Flags::BOLD) {
+        output.push_str(";1");
+    }
+    if flags.contains(Flags::DIM) {
+        output.push_str(";2");
+    }
+    if flags.contains(Flags::ITALIC) {
+        output.push_str(";3");
+    }
+    if flags.contains(Flags::UNDERLINE) {
+        output.push_str(";4");
+    } else if flags.contains(Flags::DOUBLE_UNDERLINE) {
+        output.push_str(";21");
+    } else if flags.contains(Flags::UNDERCURL) {
+        output.push_str(";4:3");
+    } else if flags.contains(Flags::DOTTED_UNDERLINE) {
+        output.push_str(";4:4");
+    } else if flags.contains(Flags::DASHED_UNDERLINE) {
+        output.push_str(";4:5");
+    }
+    if flags.contains(Flags::INVERSE) {
+        output.push_str(";7");
+    }
+    if flags.contains(Flags::HIDDEN) {
+        output.push_str(";8");
+    }
+    if flags.contains(Flags::STRIKEOUT) {
+        output.push_str(";9");
+    }
+    sgr_color(&mut output, square.fg, false);
+    sgr_color(&mut output, square.bg, true);
+    output.push('m');
+    output
+}
+
+#[inline]
+fn style_flags(square: &Square) -> Flags {
+    square.flags
+        - (Flags::WRAPLINE
+            | Flags::WIDE_CHAR
+            | Flags::WIDE_CHAR_SPACER
+            | Flags::LEADING_WIDE_CHAR_SPACER)
+}
+
+#[inline]
+fn same_style(a: &Square, b: &Square) -> bool {
+    a.fg == b.fg && a.bg == b.bg && style_flags(a) == style_flags(b)
+}
+
+/// Text with SGR sequences rebuilt from the cell attributes.
+pub fn to_ansi(lines: &[ExportLine]) -> String {
+    let default = Square::default();
+    let mut output = String::new();
+    let mut previous = Square::default();
+    for line in lines {
+        for square in line.squares.iter().filter(|square| !is_spacer(square)) {
+            if !same_style(&previous, square) {
+                output.push_str(&sgr(square));
+                previous = square.clone();
+            }
+            push_content(&mut output, square);
+        }
+
+        if !line.wrapped {
+            if !same_style(&previous, &default) {
+                output.push_str("\x1b[0m");
+                previous = default.clone();
+            }
+            output.push('\n');
+        }
+    }
+
+    if !same_style(&previous, &default) {
+        output.push_str("\x1b[0m");
+    }
+    output
+}
+
+#[inline]
+fn hex(color: ColorArray) -> String {
+    let rgb = ColorRgb::from_color_arr(color);
+    format!("#{:02x}{:02x}{:02x}", rgb.r, rgb.g, rgb.b)
+}
+
+fn escape_xml(output: &mut String, c: char) {
+    match c {
+        '&' => output.push_str("&amp;"),
+        '<' => output.push_str("&lt;"),
+        '>' => output.push_str("&gt;"),
+        '"' => output.push_str("&quot;"),
+        '\t' => output.push(' '),
+        c => output.push(c),
+    }
+}
+
+/// Foreground and background of `square`, with inverse already applied.
+fn square_colors(square: &Square, palette: &dyn Palette) -> (ColorArray, ColorArray) {
+    let fg = palette.color(square.fg, square.flags);
+    let bg = palette.color(square.bg, Flags::empty());
+    if square.flags.contains(Flags::INVERSE) {
+        (bg, fg)
+    } else {
+        (fg, bg)
+    }
+}
+
+fn css(square: &Square, palette: &dyn Palette) -> String {
+    let (fg, bg) = square_colors(square, palette);
+    let mut style = format!("color:{};background:{}", hex(fg), hex(bg));
+    let flags = square.flags;
+    if flags.contains(Flags::BOLD) {
+        style.push_str(";font-weight:bold");
+    }
+    if flags.contains(Flags::ITALIC) {
+        style.push_str(";font-style:italic");
+    }
+    if flags.contains(Flags::DIM) {
+        style.push_str(";opacity:0.66");
+    }
+    if flags.contains(Flags::HIDDEN) {
+        style.push_str(";visibility:hidden");
+    }
+    match (
+        flags.intersects(Flags::ALL_UNDERLINES),
+        flags.contains(Flags::STRIKEOUT),
+    ) {
+        (true, true) => style.push_str(";text-decoration:underline line-through"),
+        (true, false) => style.push_str(";text-decoration:underline"),
+        (false, true) => style.push_str(";text-decoration:line-through"),
+        (false, false) => {}
+    }
+    style
+}
+
+/// Spans of the HTML body, without the surrounding document.
+pub fn to_html_fragment(lines: &[ExportLine], palette: &dyn Palette) -> String {
+    let default = Square::default();
+    let mut output = String::new();
+    let mut previous: Option<Square> = None;
+    for line in lines {
+        for square in line.squares.iter().filter(|square| !is_spacer(square)) {
+            if previous.as_ref().map_or(true, |p| !same_style(p, square)) {
+                if previous.is_some() {
+                    output.push_str("</span>");
+                }
+                previous = if same_style(square, &default) {
+                    output.push_str("<span>");
+                    Some(default.clone())
+                } else {
+                    let _ = write!(output, "<span style=\"{}\">", css(square, palette));
+                    Some(square.clone())
+                };
+            }
+            escape_xml(&mut output, square.c);
+            if let Some(zerowidth) = square.zerowidth() {
+                zerowidth.iter().for_each(|c| escape_xml(&mut output, *c));
+            }
+        }
+
+        if !line.wrapped {
+            if previous.take().is_some() {
+                output.push_str("</span>");
+            }
+            output.push('\n');
+        }
+    }
+
+    if previous.is_some() {
+        output.push_str("</span>");
+    }
+    output
+}
+
+/// Standalone HTML document using the theme colors.
+pub fn to_html(lines: &[ExportLine], palette: &dyn Palette) -> String {
+    let background =
+        hex(palette.color(AnsiColor::Named(NamedColor::Background), Flags::empty()));
+    let foreground =
+        hex(palette.color(AnsiColor::Named(NamedColor::Foreground), Flags::empty()));
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Rio</title>\n\
+         <style>body{{margin:0;background:{background};color:{foreground}}}\
+         pre{{margin:0;padding:1em;font-family:monospace;white-space:pre-wrap}}</style>\n\
+         </head>\n<body>\n<pre>{}</pre>\n</body>\n</html>\n",
+        to_html_fragment(lines, palette)
+    )
+}
+
+/// Self-contained `<pre>` block with inline styles, suited for the HTML
+/// flavor of the clipboard.
+pub fn to_html_snippet(lines: &[ExportLine], palette: &dyn Palette) -> String {
+    let background =
+        hex(palette.color(AnsiColor::Named(NamedColor::Background), Flags::empty()));
+    let foreground =
+        hex(palette.color(AnsiColor::Named(NamedColor::Foreground), Flags::empty()));
+
+    format!(
+        "<pre style=\"font-family:monospace;background:{background};color:{foreground}\">{}</pre>",
+        to_html_fragment(lines, palette).trim_end_matches('\n')
+    )
+}
+
+fn escape_rtf(output: &mut String, c: char) {
+    match c {
+        '\\' | '{' | '}' => {
+            output.push('\\');
+            output.push(c);
+        }
+        '\t' => output.push_str("\\tab "),
+        c if c.is_ascii() => output.push(c),
+        // Unicode escapes are signed 16 bits, followed by an ASCII fallback.
+        c => {
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                let _ = write!(output, "\\u{}?", *unit as i16);
+            }
+        }
+    }
+}
+
+/// RTF document with a color table built out of the cells, for the rich
+/// text flavor of the clipboard.
+pub fn to_rtf(lines: &[ExportLine], palette: &dyn Palette) -> String {
+    let mut colors: Vec<ColorArray> = Vec::new();
+    let mut color_index = |color: ColorArray| -> usize {
+        match colors.iter().position(|known| *known == color) {
+            Some(index) => index + 1,
+            None => {
+                colors.push(color);
+                colors.len()
+            }
+        }
+    };
+
+    let background = color_index(
+        palette.color(AnsiColor::Named(NamedColor::Background), Flags::empty()),
+    );
+    let foreground = color_index(
+        palette.color(AnsiColor::Named(NamedColor::Foreground), Flags::empty()),
+    );
+
+    let mut body = String::new();
+    let mut previous: Option<&Square> = None;
+    for line in lines {
+        for square in line.squares.iter().filter(|square| !is_spacer(square)) {
+            if previous.map_or(true, |p| !same_style(p, square)) {
+                if previous.is_some() {
+                    body.push('}');
+                }
+                let (fg, bg) = square_colors(square, palette);
+                let _ = write!(
+                    body,
+                    "{{\\cf{}\\cb{1}\\highlight{1}",
+                    color_index(fg),
+                    color_index(bg)
+                );
+                let flags = square.flags;
+                if flags.contains(Flags::BOLD) {
+                    body.push_str("\\b");
+                }
+                if flags.contains(Flags::ITALIC) {
+                    body.push_str("\\i");
+                }
+                if flags.intersects(Flags::ALL_UNDERLINES) {
+                    body.push_str("\\ul");
+                }
+                if flags.contains(Flags::STRIKEOUT) {
+                    body.push_str("\\strike");
+                }
+                body.push(' ');
+                previous = Some(square);
+            }
+            escape_rtf(&mut body, square.c);
+            if let Some(zerowidth) = square.zerowidth() {
+                zerowidth.iter().for_each(|c| escape_rtf(&mut body, *c));
+            }
+        }
+
+        if !line.wrapped {
+            if previous.take().is_some() {
+                body.push('}');
+            }
+            body.push_str("\\line\n");
+        }
+    }
+    if previous.is_some() {
+        body.push('}');
+    }
+    let body = body.strip_suffix("\\line\n").unwrap_or(&body);
+
+    let mut output = String::from(
+        "{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}{\\colortbl;",
+    );
+    for color in colors.iter() {
+        let rgb = ColorRgb::from_color_arr(*color);
+        let _ = write!(output, "\\red{}\\green{}\\blue{};", rgb.r, rgb.g, rgb.b);
+    }
+    let _ = write!(
+        output,
+        "}}\n\\f0\\fs24\\cf{foreground}\\cb{background}\\highlight{background} {body}}}"
+    );
+    output
+}
+
+/// Drop trailing blanks of every row that isn't soft wrapped.
+pub fn trim_trailing_whitespace(lines: &mut [ExportLine]) {
+    for line in lines.iter_mut().filter(|line| !line.wrapped) {
+        while line
+            .squares
+            .last()
+            .map_or(false, |square| square.c == ' ' || square.c == '\t')
+        {
+            line.squares.pop();
+        }
+    }
+}
+
+/// SVG reproducing the rows cell by cell.
+pub fn to_svg(lines: &[ExportLine], palette: &dyn Palette) -> String {
+    let columns = lines
+        .iter()
+        .map(|line| line.squares.len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let width = columns as f32 * SVG_CELL_WIDTH;
+    let height = lines.len().max(1) as f32 * SVG_CELL_HEIGHT;
+    let background =
+        palette.color(AnsiColor::Named(NamedColor::Background), Flags::empty());
+
+    let mut output = String::new();
+    let _ = write!(
+        output,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\" font-family=\"monospace\" font-size=\"{SVG_FONT_SIZE}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n",
+        hex(background)
+    );
+
+    for (row, line) in lines.iter().enumerate() {
+        let y = row as f32 * SVG_CELL_HEIGHT;
+        for (column, square) in line.squares.iter().enumerate() {
+            if is_spacer(square) {
+                continue;
+            }
+
+            let x = column as f32 * SVG_CELL_WIDTH;
+            let (fg, bg) = square_colors(square, palette);
+            let cell_width = if square.flags.contains(Flags::WIDE_CHAR) {
+                SVG_CELL_WIDTH * 2.
+            } else {
+                SVG_CELL_WIDTH
+            };
+            if bg != background {
+                let _ = writeln!(
+                    output,
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{cell_width}\" height=\"{SVG_CELL_HEIGHT}\" fill=\"{}\"/>",
+                    hex(bg)
+                );
+            }
+
+            if square.c == ' ' || square.c == '\t' || square.flags.contains(Flags::HIDDEN)
+            {
+                continue;
+            }
+
+            let mut attributes = format!("fill=\"{}\"", hex(fg));
+            if square.flags.contains(Flags::BOLD) {
+                attributes.push_str(" font-weight=\"bold\"");
+            }
+            if square.flags.contains(Flags::ITALIC) {
+                attributes.push_str(" font-style=\"italic\"");
+            }
+            if square.flags.contains(Flags::DIM) {
+                attributes.push_str(" opacity=\"0.66\"");
+            }
+            if square.flags.intersects(Flags::ALL_UNDERLINES) {
+                attributes.push_str(" text-decoration=\"underline\"");
+            } else if square.flags.contains(Flags::STRIKEOUT) {
+                attributes.push_str(" text-decoration=\"line-through\"");
+            }
+
+            let mut content = String::new();
+            escape_xml(&mut content, square.c);
+            if let Some(zerowidth) = square.zerowidth() {
+                zerowidth.iter().for_each(|c| escape_xml(&mut content, *c));
+            }
+            let _ = writeln!(
+                output,
+                "<text x=\"{x}\" y=\"{}\" {attributes}>{content}</text>",
+                y + SVG_FONT_SIZE
+            );
+        }
+    }
+
+    output.push_str("</svg>\n");
+    output
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::crosswords::pos::{Pos, Side};
+    use crate::event::VoidListener;
+    use crate::performer::handler::Handler;
+    use crate::selection::{Selection, SelectionType};
+
+    struct TestPalette;
+
+    impl Palette for TestPalette {
+        fn color(&self, color: AnsiColor, _flags: Flags) -> ColorArray {
+            match color {
+                AnsiColor::Named(NamedColor::Background) => [0., 0., 0., 1.],
+                AnsiColor::Named(NamedColor::Red) => [1., 0., 0., 1.],
+                _ => [1., 1., 1., 1.],
+            }
+        }
+    }
+
+    fn write_str(cw: &mut Crosswords<VoidListener>, text: &str) {
+        for c in text.chars() {
+            cw.input(c);
+        }
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!(ExportFormat::from_str("HTML"), Ok(ExportFormat::Html));
+        assert_eq!(ExportFormat::from_str("txt"), Ok(ExportFormat::Text));
+        assert!(ExportFormat::from_str("pdf").is_err());
+        assert_eq!(ExportScope::from_str("Screen"), Ok(ExportScope::Screen));
+        assert!(ExportScope::from_str("tab").is_err());
+    }
+
+    #[test]
+    fn test_text_joins_soft_wraps() {
+        let mut cw = Crosswords::new(5, 4, VoidListener {});
+        write_str(&mut cw, "abcdefgh");
+        cw.carriage_return();
+        cw.linefeed();
+        write_str(&mut cw, "ij");
+
+        let lines = lines(&cw, ExportScope::History);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].wrapped);
+        assert_eq!(to_text(&lines), "abcdefgh\nij\n");
+    }
+
+    #[test]
+    fn test_selection_lines() {
+        let mut cw = Crosswords::new(10, 3, VoidListener {});
+        write_str(&mut cw, "hello rio");
+        let mut selection = Selection::new(
+            SelectionType::Simple,
+            Pos::new(Line(0), Column(6)),
+            Side::Left,
+        );
+        selection.update(Pos::new(Line(0), Column(8)), Side::Right);
+        cw.selection = Some(selection);
+
+        let lines = lines(&cw, ExportScope::Selection);
+        assert_eq!(to_text(&lines), "rio\n");
+    }
+
+    #[test]
+    fn test_ansi_reconstructs_sgr() {
+        let mut bold_red = Square {
+            c: 'a',
+            fg: AnsiColor::Named(NamedColor::Red),
+            ..Square::default()
+        };
+        bold_red.flags.insert(Flags::BOLD);
+        let indexed = Square {
+            c: 'b',
+            bg: AnsiColor::Indexed(200),
+            ..Square::default()
+        };
+        let plain = Square {
+            c: 'c',
+            ..Square::default()
+        };
+        let lines = vec![ExportLine {
+            squares: vec![bold_red, indexed, plain],
+            wrapped: false,
+        }];
+
+        assert_eq!(to_ansi(&lines), "\x1b[0;1;31ma\x1b[0;48;5;200mb\x1b[0mc\n");
+    }
+
+    #[test]
+    fn test_ansi_light_and_truecolor() {
+        let lines = vec![ExportLine {
+            squares: vec![Square {
+                c: 'x',
+                fg: AnsiColor::Named(NamedColor::LightBlue),
+                bg: AnsiColor::Spec(ColorRgb { r: 1, g: 2, b: 3 }),
+                ..Square::default()
+            }],
+            wrapped: false,
+        }];
+        assert_eq!(to_ansi(&lines), "\x1b[0;94;48;2;1;2;3mx\x1b[0m\n");
+    }
+
+    #[test]
+    fn test_html_escapes_and_colors() {
+        let red = Square {
+            c: '<',
+            fg: AnsiColor::Named(NamedColor::Red),
+            ..Square::default()
+        };
+        let lines = vec![ExportLine {
+            squares: vec![
+                red,
+                Square {
+                    c: '&',
+                    ..Square::default()
+                },
+            ],
+            wrapped: false,
+        }];
+
+        assert_eq!(
+            to_html_fragment(&lines, &TestPalette),
+            "<span style=\"color:#ff0000;background:#000000\">&lt;</span><span>&amp;</span>\n"
+        );
+        let document = to_html(&lines, &TestPalette);
+        assert!(document.starts_with("<!DOCTYPE html>"));
+        assert!(document.contains("background:#000000;color:#ffffff"));
+    }
+
+    #[test]
+    fn test_html_snippet_trimmed() {
+        let blue_space = Square {
+            bg: AnsiColor::Named(NamedColor::Blue),
+            ..Square::default()
+        };
+        let mut lines = vec![ExportLine {
+            squares: vec![
+                Square {
+                    c: 'a',
+                    ..Square::default()
+                },
+                blue_space,
+            ],
+            wrapped: false,
+        }];
+        trim_trailing_whitespace(&mut lines);
+        assert_eq!(lines[0].squares.len(), 1);
+        assert_eq!(
+            to_html_snippet(&lines, &TestPalette),
+            "<pre style=\"font-family:monospace;background:#000000;color:#ffffff\"><span>a</span></pre>"
+        );
+    }
+
+    #[test]
+    fn test_rtf_colors_and_escapes() {
+        let red_bold = Square {
+            c: '{',
+            fg: AnsiColor::Named(NamedColor::Red),
+            flags: Flags::BOLD,
+            ..Square::default()
+        };
+        let lines = vec![
+            ExportLine {
+                squares: vec![
+                    red_bold,
+                    Square {
+                        c: 'é',
+                        ..Square::default()
+                    },
+                ],
+                wrapped: false,
+            },
+            ExportLine {
+                squares: vec![Square {
+                    c: '\\',
+                    ..Square::default()
+                }],
+                wrapped: false,
+            },
+        ];
+
+        assert_eq!(
+            to_rtf(&lines, &TestPalette),
+            "{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}\
+             {\\colortbl;\\red0\\green0\\blue0;\\red255\\green255\\blue255;\\red255\\green0\\blue0;}\n\
+             \\f0\\fs24\\cf2\\cb1\\highlight1 \
+             {\\cf3\\cb1\\highlight1\\b \\{}{\\cf2\\cb1\\highlight1 \\u233?}\\line\n\
+             {\\cf2\\cb1\\highlight1 \\\\}}"
+        );
+    }
+
+    #[test]
+    fn test_svg_wide_char() {
+        let mut cw = Crosswords::new(4, 1, VoidListener {});
+        write_str(&mut cw, "日");
+        let svg = to_svg(&lines(&cw, ExportScope::Screen), &TestPalette);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(">日</text>"));
+        assert_eq!(svg.matches("<text").count(), 1);
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
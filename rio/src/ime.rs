@@ -40,7 +40,7 @@ impl Ime {
     }
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Preedit {
     /// The preedit text.
     pub text: String,
@@ -73,4 +73,90 @@ impl Preedit {
             cursor_end_offset,
         }
     }
+
+    /// Cells taken by the preedit text, wide characters are followed by a
+    /// spacer. A cursor after the last character takes one more cell.
+    pub fn cells(&self) -> Vec<PreeditCell> {
+        let mut cells = vec![];
+        for (index, c) in self.text.char_indices() {
+            let cursor = self.cursor_byte_offset == Some(index);
+            cells.push(PreeditCell {
+                c,
+                spacer: false,
+                cursor,
+            });
+            if c.width() == Some(2) {
+                cells.push(PreeditCell {
+                    c: ' ',
+                    spacer: true,
+                    cursor,
+                });
+            }
+        }
+
+        if self.cursor_byte_offset == Some(self.text.len()) {
+            cells.push(PreeditCell {
+                c: ' ',
+                spacer: false,
+                cursor: true,
+            });
+        }
+        cells
+    }
+}
+
+/// A cell of the preedit drawn over the grid.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PreeditCell {
+    pub c: char,
+    /// Second cell of a wide character.
+    pub spacer: bool,
+    /// Whether the preedit cursor is on this cell.
+    pub cursor: bool,
+}
+
+/// First column of a preedit `width` cells wide typed at `column`. It moves
+/// left when it would not fit in the `columns` of the line.
+#[inline]
+pub fn preedit_start(column: usize, width: usize, columns: usize) -> usize {
+    column.min(columns.saturating_sub(width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preedit_cells() {
+        // "日本" with the cursor between both characters
+        let preedit = Preedit::new(String::from("日本"), Some(3));
+        let cells = preedit.cells();
+        assert_eq!(cells.len(), 4);
+        assert_eq!(cells.iter().filter(|cell| cell.spacer).count(), 2);
+        let cursor: Vec<bool> = cells.iter().map(|cell| cell.cursor).collect();
+        assert_eq!(cursor, vec![false, false, true, true]);
+
+        // The cursor at the end takes an extra cell
+        let preedit = Preedit::new(String::from("ni"), Some(2));
+        let cells = preedit.cells();
+        assert_eq!(cells.len(), 3);
+        assert_eq!(
+            cells[2],
+            PreeditCell {
+                c: ' ',
+                spacer: false,
+                cursor: true
+            }
+        );
+
+        let preedit = Preedit::new(String::from("ni"), None);
+        assert!(preedit.cells().iter().all(|cell| !cell.cursor));
+    }
+
+    #[test]
+    fn test_preedit_start() {
+        assert_eq!(preedit_start(10, 4, 80), 10);
+        assert_eq!(preedit_start(78, 4, 80), 76);
+        assert_eq!(preedit_start(5, 100, 80), 0);
+    }
 }
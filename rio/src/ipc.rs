@@ -0,0 +1,594 @@
+// Control socket, one per running Rio instance, used by `rio msg`.
+//
+// A client connects, writes a request as one line of JSON and reads the
+// reply as one line of JSON:
+//
+//  {"command":"create-tab","working-directory":"/tmp","command-line":["make"]}
+//  {"status":"ok"}
+//
+// Tabs are addressed by their window id (also exposed to the programs run
+// by bindings as `RIO_WINDOW_ID`) and their 0-based index in that window.
+//
+// Sockets live in a directory only the user can access and only accept
+// connections from processes of the same user.
+
+use crate::screen::ForegroundProcess;
+use clap::Subcommand;
+use corcovado::SetReadiness;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+/// Environment variable holding the socket of the Rio instance a shell
+/// runs in, `rio msg` talks to that instance by default.
+pub const SOCKET_ENV: &str = "RIO_SOCKET";
+
+#[derive(Subcommand, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Open a new window.
+    CreateWindow {
+        /// Directory the shell starts at.
+        #[clap(long)]
+        #[serde(default, rename = "working-directory")]
+        working_directory: Option<String>,
+
+        /// Command and args to run instead of the shell (must be last argument).
+        #[clap(short = 'e', long, allow_hyphen_values = true, num_args = 1..)]
+        #[serde(default, rename = "command-line")]
+        command: Vec<String>,
+    },
+
+    /// Open a new tab.
+    CreateTab {
+        /// Window to open the tab in, defaults to the focused one.
+        #[clap(long)]
+        #[serde(default)]
+        window: Option<u64>,
+
+        /// Directory the shell starts at, defaults to the one of the
+        /// current tab.
+        #[clap(long)]
+        #[serde(default, rename = "working-directory")]
+        working_directory: Option<String>,
+
+        /// Command and args to run instead of the shell (must be last argument).
+        #[clap(short = 'e', long, allow_hyphen_values = true, num_args = 1..)]
+        #[serde(default, rename = "command-line")]
+        command: Vec<String>,
+    },
+
+    /// Write text to a tab, as if it was typed.
+    SendText {
+        /// Window of the tab, defaults to the focused one.
+        #[clap(long)]
+        #[serde(default)]
+        window: Option<u64>,
+
+        /// Tab to write to, defaults to the current one.
+        #[clap(long)]
+        #[serde(default)]
+        tab: Option<usize>,
+
+        text: String,
+    },
+
+    /// List the tabs of every window.
+    ListTabs,
+
+    /// Make a tab the current one and focus its window.
+    FocusTab {
+        /// Window of the tab, defaults to the focused one.
+        #[clap(long)]
+        #[serde(default)]
+        window: Option<u64>,
+
+        tab: usize,
+    },
+
+    /// Reload the configuration file.
+    ReloadConfig,
+
+    /// Render the history, visible screen or selection of a tab.
+    Export {
+        /// Window of the tab, defaults to the focused one.
+        #[clap(long)]
+        #[serde(default)]
+        window: Option<u64>,
+
+        /// Tab to export, defaults to the current one.
+        #[clap(long)]
+        #[serde(default)]
+        tab: Option<usize>,
+
+        /// What to export: history, screen or selection.
+        #[clap(long, default_value = "history")]
+        #[serde(default = "default_export_scope")]
+        scope: String,
+
+        /// Output format: text, ansi, html or svg.
+        #[clap(long, default_value = "text")]
+        #[serde(default = "default_export_format")]
+        format: String,
+    },
+}
+
+fn default_export_scope() -> String {
+    String::from("history")
+}
+
+fn default_export_format() -> String {
+    String::from("text")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Reply {
+    Ok,
+    Tabs { tabs: Vec<TabInfo> },
+    Export { content: String },
+    Error { message: String },
+}
+
+impl Reply {
+    pub fn error<S: Into<String>>(message: S) -> Self {
+        Reply::Error {
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct TabInfo {
+    pub window: u64,
+    pub index: usize,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
+    /// Program running in the foreground of the focused pane, its shell
+    /// when nothing else runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<ForegroundProcess>,
+    /// Whether it is the current tab of its window.
+    pub current: bool,
+}
+
+/// Directory holding the sockets of every running instance of the user.
+pub fn socket_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    #[cfg(unix)]
+    return base.join(format!("rio-{}", unix::uid()));
+    #[cfg(not(unix))]
+    return base.join("rio");
+}
+
+/// Socket of the instance running as `pid`.
+pub fn socket_path(pid: u32) -> PathBuf {
+    socket_dir().join(format!("rio-{pid}.sock"))
+}
+
+/// Socket `rio msg` talks to when none is given: the one of the instance
+/// the command runs in, or else the first instance accepting connections.
+#[cfg(unix)]
+pub fn find_socket() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(SOCKET_ENV) {
+        let path = PathBuf::from(path);
+        return unix::is_own_socket(&path).then_some(path);
+    }
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(socket_dir())
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| unix::is_own_socket(path))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("rio-"))
+                .and_then(|name| name.strip_suffix(".sock"))
+                // Not the one of `rio server`.
+                .map_or(false, |pid| pid.parse::<u32>().is_ok())
+        })
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .find(|path| std::os::unix::net::UnixStream::connect(path).is_ok())
+}
+
+#[cfg(not(unix))]
+pub fn find_socket() -> Option<PathBuf> {
+    None
+}
+
+/// Socket accepting requests, the thread accepting connections stops and
+/// the socket file is removed once it is dropped.
+pub struct Listener {
+    path: PathBuf,
+    stop: SetReadiness,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Listener {
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = self.stop.set_readiness(corcovado::Ready::readable());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::{Listener, Reply, Request};
+    use corcovado::unix::UnixSocket;
+    use corcovado::{Events, Poll, PollOpt, Ready, Registration, Token};
+    use std::fs::{self, DirBuilder, Permissions};
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+    use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+    use std::os::unix::net::UnixStream;
+    use std::path::Path;
+    use std::time::Duration;
+
+    const LISTENER: Token = Token(0);
+    const STOP: Token = Token(1);
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Real user id of the process.
+    #[inline]
+    pub fn uid() -> u32 {
+        unsafe { libc::getuid() }
+    }
+
+    /// Create `dir`, and its parents, with only the user allowed in. Fails
+    /// when it belongs to another user.
+    pub fn create_private_dir(dir: &Path) -> io::Result<()> {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        let metadata = fs::metadata(dir)?;
+        if metadata.uid() != uid() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} belongs to another user", dir.display()),
+            ));
+        }
+        if metadata.mode() & 0o077 != 0 {
+            fs::set_permissions(dir, Permissions::from_mode(0o700))?;
+        }
+        Ok(())
+    }
+
+    /// Whether `path` is a socket created by the user.
+    pub fn is_own_socket(path: &Path) -> bool {
+        fs::metadata(path).map_or(false, |metadata| {
+            metadata.file_type().is_socket() && metadata.uid() == uid()
+        })
+    }
+
+    /// User id of the process at the other end of the socket `fd`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn peer_uid(fd: RawFd) -> io::Result<u32> {
+        let mut credentials: libc::ucred = unsafe { std::mem::zeroed() };
+        let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut credentials as *mut libc::ucred as *mut libc::c_void,
+                &mut length,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(credentials.uid)
+    }
+
+    /// User id of the process at the other end of the socket `fd`.
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn peer_uid(fd: RawFd) -> io::Result<u32> {
+        let mut uid: libc::uid_t = 0;
+        let mut gid: libc::gid_t = 0;
+        if unsafe { libc::getpeereid(fd, &mut uid, &mut gid) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(uid)
+    }
+
+    /// Whether the process at the other end of the socket `fd` runs as the
+    /// user, connections from other users are refused.
+    pub fn is_own_peer(fd: RawFd) -> bool {
+        match peer_uid(fd) {
+            Ok(peer) if peer == uid() => true,
+            Ok(peer) => {
+                log::warn!("refused connection from user {peer}");
+                false
+            }
+            Err(err) => {
+                log::warn!("unable to check the peer of a connection: {err}");
+                false
+            }
+        }
+    }
+
+    /// Make room for a socket at `path`. Its directory is created with
+    /// only the user allowed in, the default one must also belong to the
+    /// user, and a socket left behind by an instance that did not exit
+    /// cleanly is removed.
+    pub fn prepare_socket(path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            if parent == super::socket_dir() {
+                create_private_dir(parent)?;
+            } else {
+                // A directory given by the user is left as it is.
+                DirBuilder::new()
+                    .recursive(true)
+                    .mode(0o700)
+                    .create(parent)?;
+            }
+        }
+        let _ = fs::remove_file(path);
+        Ok(())
+    }
+
+    /// Allow only the user to connect to the socket at `path`.
+    #[inline]
+    pub fn restrict_socket(path: &Path) -> io::Result<()> {
+        fs::set_permissions(path, Permissions::from_mode(0o600))
+    }
+
+    fn bind(path: &Path) -> io::Result<UnixSocket> {
+        prepare_socket(path)?;
+        let socket = UnixSocket::stream()?;
+        socket.bind(path)?;
+        restrict_socket(path)?;
+        Ok(socket)
+    }
+
+    /// Accept connections on a socket bound at `path`, every request is
+    /// answered by `handler` on its own thread.
+    pub fn listen<F>(path: &Path, handler: F) -> io::Result<Listener>
+    where
+        F: Fn(Request) -> Reply + Clone + Send + 'static,
+    {
+        let socket = bind(path)?;
+        socket.listen(16)?;
+
+        let poll = Poll::new()?;
+        poll.register(&socket, LISTENER, Ready::readable(), PollOpt::edge())?;
+        let (registration, stop) = Registration::new2();
+        poll.register(&registration, STOP, Ready::readable(), PollOpt::edge())?;
+
+        let thread = std::thread::Builder::new()
+            .name(String::from("ipc listener"))
+            .spawn(move || {
+                // Kept registered for as long as the thread runs.
+                let _registration = registration;
+                let mut events = Events::with_capacity(16);
+                loop {
+                    if let Err(err) = poll.poll(&mut events, None) {
+                        if err.kind() == io::ErrorKind::Interrupted {
+                            continue;
+                        }
+                        log::error!("ipc socket stopped: {err}");
+                        return;
+                    }
+
+                    for event in events.iter() {
+                        if event.token() == STOP {
+                            return;
+                        }
+                        if event.token() != LISTENER {
+                            continue;
+                        }
+
+                        // Edge triggered, take every pending connection.
+                        loop {
+                            let connection = match socket.accept() {
+                                Ok(connection) => connection,
+                                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                                    break
+                                }
+                                Err(err) => {
+                                    log::warn!("unable to accept ipc connection: {err}");
+                                    break;
+                                }
+                            };
+                            if !is_own_peer(connection.as_raw_fd()) {
+                                continue;
+                            }
+
+                            let handler = handler.clone();
+                            std::thread::spawn(move || {
+                                let stream = unsafe {
+                                    UnixStream::from_raw_fd(connection.into_raw_fd())
+                                };
+                                if let Err(err) = serve(stream, &handler) {
+                                    log::warn!("ipc connection failed: {err}");
+                                }
+                            });
+                        }
+                    }
+                }
+            })?;
+
+        Ok(Listener {
+            path: path.to_path_buf(),
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn serve<F: Fn(Request) -> Reply>(stream: UnixStream, handler: &F) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => handler(request),
+            Err(err) => Reply::error(format!("invalid request: {err}")),
+        };
+
+        write_line(&stream, &reply)
+    }
+
+    fn write_line<T: serde::Serialize>(
+        mut stream: &UnixStream,
+        value: &T,
+    ) -> io::Result<()> {
+        let mut content = serde_json::to_string(value)?;
+        content.push('\n');
+        stream.write_all(content.as_bytes())
+    }
+
+    /// Send `request` to the instance listening at `path` and wait for its
+    /// reply.
+    pub fn send(path: &Path, request: &Request) -> io::Result<Reply> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        write_line(&stream, request)?;
+
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{is_own_peer, listen, prepare_socket, restrict_socket, send};
+
+#[cfg(not(unix))]
+pub fn listen<F>(_path: &Path, _handler: F) -> std::io::Result<Listener>
+where
+    F: Fn(Request) -> Reply + Clone + Send + 'static,
+{
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "the control socket is only available on Unix",
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn send(_path: &Path, _request: &Request) -> std::io::Result<Reply> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "the control socket is only available on Unix",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_request_format() {
+        let request: Request = serde_json::from_str(
+            r#"{"command":"create-tab","working-directory":"/tmp","command-line":["make","-j4"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request::CreateTab {
+                window: None,
+                working_directory: Some(String::from("/tmp")),
+                command: vec![String::from("make"), String::from("-j4")],
+            }
+        );
+
+        let request: Request =
+            serde_json::from_str(r#"{"command":"list-tabs"}"#).unwrap();
+        assert_eq!(request, Request::ListTabs);
+
+        let request: Request =
+            serde_json::from_str(r#"{"command":"export","tab":1,"format":"html"}"#)
+                .unwrap();
+        assert_eq!(
+            request,
+            Request::Export {
+                window: None,
+                tab: Some(1),
+                scope: String::from("history"),
+                format: String::from("html"),
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&Reply::Ok).unwrap(),
+            r#"{"status":"ok"}"#
+        );
+    }
+
+    #[test]
+    fn test_tab_info_format() {
+        let tab = TabInfo {
+            window: 1,
+            index: 0,
+            title: String::from("vim"),
+            working_directory: None,
+            process: Some(ForegroundProcess {
+                pid: 42,
+                name: String::from("vim"),
+                working_directory: Some(String::from("/tmp")),
+            }),
+            current: true,
+        };
+        assert_eq!(
+            serde_json::to_string(&tab).unwrap(),
+            r#"{"window":1,"index":0,"title":"vim","process":{"pid":42,"name":"vim","working-directory":"/tmp"},"current":true}"#
+        );
+    }
+
+    #[test]
+    fn test_listen_and_send() {
+        let dir =
+            std::env::temp_dir().join(format!("rio-ipc-test-{}", std::process::id()));
+        let path = dir.join("rio-1.sock");
+        let listener = listen(&path, |request| match request {
+            Request::ListTabs => Reply::Tabs {
+                tabs: vec![TabInfo {
+                    window: 1,
+                    index: 0,
+                    title: String::from("zsh"),
+                    working_directory: None,
+                    process: None,
+                    current: true,
+                }],
+            },
+            _ => Reply::error("unsupported"),
+        })
+        .unwrap();
+
+        match send(&path, &Request::ListTabs).unwrap() {
+            Reply::Tabs { tabs } => assert_eq!(tabs[0].title, "zsh"),
+            reply => panic!("unexpected reply {reply:?}"),
+        }
+        assert_eq!(
+            send(&path, &Request::ReloadConfig).unwrap(),
+            Reply::error("unsupported")
+        );
+
+        let metadata = std::fs::metadata(&dir).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o700);
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert!(unix::is_own_socket(&path));
+
+        // The thread accepting connections is gone along with the socket.
+        drop(listener);
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir(&dir);
+    }
+}
@@ -7,9 +7,12 @@
 mod ansi;
 mod cli;
 mod clipboard;
+mod clipboard_history;
 mod crosswords;
 mod event;
+mod export;
 mod ime;
+mod ipc;
 mod logger;
 #[cfg(windows)]
 mod panic;
@@ -19,6 +22,9 @@ mod scheduler;
 mod screen;
 mod selection;
 mod sequencer;
+mod server;
+mod session;
+mod tmux;
 mod utils;
 use crate::event::EventP;
 use crate::sequencer::Sequencer;
@@ -31,38 +37,139 @@ use windows_sys::Win32::System::Console::{
     AttachConsole, FreeConsole, ATTACH_PARENT_PROCESS,
 };
 
-pub fn setup_environment_variables(config: &config::Config) {
-    #[cfg(unix)]
-    let terminfo = if teletypewriter::terminfo_exists("rio") {
-        "rio"
-    } else {
-        "xterm-256color"
-    };
-
-    #[cfg(unix)]
-    info!("[setup_environment_variables] terminfo: {terminfo}");
-
-    #[cfg(unix)]
-    std::env::set_var("TERM", terminfo);
-    std::env::set_var("COLORTERM", "truecolor");
+/// Environment of Rio itself, the one of the programs running in its
+/// terminals is given by `utils::spawn::terminal_environment`.
+pub fn setup_environment_variables() {
     std::env::remove_var("DESKTOP_STARTUP_ID");
     #[cfg(target_os = "macos")]
     {
         platform::macos::set_locale_environment();
         std::env::set_current_dir(dirs::home_dir().unwrap()).unwrap();
     }
+}
 
-    // Set env vars from config.
-    for env_config in config.env_vars.iter() {
-        let mut env_vec = vec![];
-        for config in env_config.split('=') {
-            env_vec.push(config);
+fn run_export(
+    options: cli::ExportOptions,
+    config: config::Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = export::ExportFormat::from_str(&options.format)?;
+    let scope = export::ExportScope::from_str(&options.scope)?;
+    let content = match options.session {
+        Some(session) => {
+            if scope != export::ExportScope::History {
+                return Err("a saved session only holds the history".into());
+            }
+            let session = match session.is_empty() {
+                true => config::session_file_path(),
+                false => session,
+            };
+            let state = screen::state::State::new(&std::rc::Rc::new(config));
+            export::render_session(session, options.tab, format, &state)?
         }
-
-        if env_vec.len() == 2 {
-            std::env::set_var(env_vec[0], env_vec[1]);
+        None => {
+            let socket = options
+                .socket
+                .map(std::path::PathBuf::from)
+                .or_else(ipc::find_socket)
+                .ok_or(
+                    "no running Rio instance found, use --session to export a saved session",
+                )?;
+            let request = ipc::Request::Export {
+                window: options.window,
+                tab: options.tab,
+                scope: options.scope,
+                format: options.format,
+            };
+            match ipc::send(&socket, &request)? {
+                ipc::Reply::Export { content } => content,
+                ipc::Reply::Error { message } => return Err(message.into()),
+                _ => return Err("unexpected reply".into()),
+            }
         }
+    };
+
+    match options.output {
+        Some(output) => std::fs::write(output, content)?,
+        None => print!("{content}"),
+    }
+
+    Ok(())
+}
+
+fn run_msg(options: cli::MsgOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = options
+        .socket
+        .map(std::path::PathBuf::from)
+        .or_else(ipc::find_socket)
+        .ok_or("no running Rio instance found")?;
+
+    match ipc::send(&socket, &options.request)? {
+        ipc::Reply::Ok => {}
+        ipc::Reply::Tabs { tabs } => println!("{}", serde_json::to_string_pretty(&tabs)?),
+        ipc::Reply::Export { content } => print!("{content}"),
+        ipc::Reply::Error { message } => return Err(message.into()),
     }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn run_server(
+    options: cli::ServerOptions,
+    config: config::Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = options
+        .socket
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(server::socket_path);
+    if options.list {
+        let sessions = server::Client::list_sessions(&socket)?;
+        println!("{}", serde_json::to_string_pretty(&sessions)?);
+    } else if let Some(session) = options.kill {
+        server::Client::kill(&socket, session)?;
+    } else {
+        server::Server::bind(&socket, screen::Launch::defaults(&config))?.run()?;
+    }
+
+    Ok(())
+}
+
+/// Launches of the tabs attached to the sessions of the server, which is
+/// started when none is running.
+#[cfg(unix)]
+fn attach_launches(
+    options: cli::AttachOptions,
+    config_file: Option<&str>,
+) -> Result<Vec<screen::Launch>, Box<dyn std::error::Error>> {
+    let socket = options
+        .socket
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(server::socket_path);
+    let sessions = match server::Client::list_sessions(&socket) {
+        Ok(sessions) => sessions,
+        Err(_) => {
+            server::start(&socket, config_file)?;
+            vec![]
+        }
+    };
+
+    let attached: Vec<Option<usize>> = match options.session {
+        Some(id) if sessions.iter().any(|session| session.id == id) => vec![Some(id)],
+        Some(id) => return Err(format!("session {id} not found").into()),
+        None if sessions.is_empty() => vec![None],
+        None => sessions.iter().map(|session| Some(session.id)).collect(),
+    };
+    let socket = socket.to_string_lossy().to_string();
+    Ok(attached
+        .into_iter()
+        .map(|session| screen::Launch {
+            backend: screen::Backend::Server {
+                socket: socket.to_owned(),
+                session,
+            },
+            ..screen::Launch::default()
+        })
+        .collect())
 }
 
 static LOGGER: Logger = Logger;
@@ -87,9 +194,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Load command line options.
     let options = cli::Options::new();
-    let command = options.window_options.terminal_options.command;
 
-    let config = config::Config::load();
+    let config = config::Config::load_with(
+        options.config_file.as_deref(),
+        &options.config_options,
+    );
+
+    let subcommand = match options.subcommand {
+        Some(cli::Subcommands::Export(export_options)) => {
+            return run_export(export_options, config)
+        }
+        Some(cli::Subcommands::Msg(msg_options)) => return run_msg(msg_options),
+        subcommand => subcommand,
+    };
     let filter_level =
         LevelFilter::from_str(&config.developer.log_level).unwrap_or(LevelFilter::Off);
 
@@ -98,13 +215,32 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("unable to configure log level");
     }
 
-    setup_environment_variables(&config);
+    let attach = match subcommand {
+        #[cfg(unix)]
+        Some(cli::Subcommands::Server(server_options)) => {
+            return run_server(server_options, config)
+        }
+        #[cfg(unix)]
+        Some(cli::Subcommands::Attach(attach_options)) => {
+            attach_launches(attach_options, options.config_file.as_deref())?
+        }
+        #[cfg(not(unix))]
+        Some(cli::Subcommands::Server(_) | cli::Subcommands::Attach(_)) => {
+            return Err("servers are only available on Unix".into())
+        }
+        _ => vec![],
+    };
+
+    setup_environment_variables();
 
     let window_event_loop =
         winit::event_loop::EventLoopBuilder::<EventP>::with_user_event().build();
 
-    let mut sequencer = Sequencer::new(config);
-    let _ = sequencer.run(window_event_loop, command).await;
+    let mut sequencer =
+        Sequencer::new(config, options.config_file, options.config_options);
+    let _ = sequencer
+        .run(window_event_loop, options.window_options, attach)
+        .await;
 
     #[cfg(windows)]
     unsafe {
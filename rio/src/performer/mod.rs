@@ -1,8 +1,10 @@
 pub mod handler;
 
-use crate::crosswords::Crosswords;
+use crate::crosswords::grid::Dimensions;
+use crate::crosswords::{Crosswords, Mode};
 use crate::event::sync::FairMutex;
 use crate::event::EventListener;
+use crate::tmux;
 use corcovado::channel;
 #[cfg(unix)]
 use corcovado::unix::UnixReady;
@@ -10,6 +12,8 @@ use corcovado::{self, Events, PollOpt, Ready};
 use log::error;
 
 use crate::event::{Msg, RioEvent};
+use config::ExitBehavior;
+use teletypewriter::{ChildEvent, ExitStatus, WinsizeBuilder};
 
 use std::borrow::Cow;
 use std::collections::VecDeque;
@@ -31,6 +35,8 @@ pub struct Machine<T: teletypewriter::EventedPty, U: EventListener> {
     poll: corcovado::Poll,
     terminal: Arc<FairMutex<Crosswords<U>>>,
     event_proxy: U,
+    /// Whether the terminal is kept open once the child exited.
+    exit_behavior: ExitBehavior,
 }
 
 #[derive(Default)]
@@ -38,9 +44,81 @@ pub struct State {
     write_list: VecDeque<Cow<'static, [u8]>>,
     writing: Option<Writing>,
     parser: handler::ParserProcessor,
+    detector: tmux::Detector,
+    /// Set while tmux runs in control mode.
+    tmux: Option<(tmux::Parser, tmux::Gateway)>,
+    /// Last size the terminal was given.
+    winsize: Option<WinsizeBuilder>,
+    /// Whether the program asked to be told the size in-band, with the DEC
+    /// private mode 2048.
+    in_band_resize: bool,
 }
 
 impl State {
+    /// Process a byte read from the PTY, in control mode it goes to tmux
+    /// instead of the terminal.
+    #[inline]
+    fn advance<U: EventListener>(
+        &mut self,
+        terminal: &mut Crosswords<U>,
+        byte: u8,
+        sender: &channel::Sender<Msg>,
+        event_proxy: &U,
+    ) {
+        let Some((parser, gateway)) = &mut self.tmux else {
+            self.parser.advance(terminal, byte);
+            if self.detector.advance(byte) {
+                let gateway = tmux::Gateway::new(sender.clone());
+                gateway.start(terminal.grid.columns(), terminal.grid.screen_lines());
+                self.tmux = Some((tmux::Parser::default(), gateway));
+            }
+            return;
+        };
+
+        let Some(notification) = parser.advance(byte) else {
+            return;
+        };
+        let end = notification == tmux::Notification::End;
+        for change in gateway.handle(notification) {
+            event_proxy.send_event(RioEvent::Tmux(gateway.clone(), change));
+        }
+        if end {
+            self.tmux = None;
+            // Terminates the `DCS` the terminal saw when control mode started.
+            for byte in b"\x1b\\" {
+                self.parser.advance(terminal, *byte);
+            }
+        }
+    }
+
+    /// Follow the DEC private mode 2048 of the terminal, the program is
+    /// told the size as soon as it sets it.
+    fn update_in_band_resize(&mut self, mode: Mode) {
+        let in_band_resize = mode.contains(Mode::IN_BAND_RESIZE);
+        if in_band_resize && !self.in_band_resize {
+            self.in_band_resize = true;
+            self.report_size();
+        }
+        self.in_band_resize = in_band_resize;
+    }
+
+    /// Tell the program the size of the terminal in-band, when it asked to.
+    fn report_size(&mut self) {
+        if let (true, Some(winsize)) = (self.in_band_resize, &self.winsize) {
+            self.write_list
+                .push_back(in_band_resize_report(winsize).into_bytes().into());
+        }
+    }
+
+    /// Close the tabs of tmux, it can no longer be reached.
+    fn end_tmux<U: EventListener>(&mut self, event_proxy: &U) {
+        if let Some((_, gateway)) = self.tmux.take() {
+            for change in gateway.handle(tmux::Notification::End) {
+                event_proxy.send_event(RioEvent::Tmux(gateway.clone(), change));
+            }
+        }
+    }
+
     #[inline]
     fn ensure_next(&mut self) {
         if self.writing.is_none() {
@@ -108,6 +186,7 @@ where
         terminal: Arc<FairMutex<Crosswords<U>>>,
         pty: T,
         event_proxy: U,
+        exit_behavior: ExitBehavior,
     ) -> Result<Machine<T, U>, Box<dyn std::error::Error>> {
         // let (mut sender, mut receiver) = unbounded::<Msg>();
         let (sender, receiver) = channel::channel();
@@ -120,6 +199,7 @@ where
             pty,
             terminal,
             event_proxy,
+            exit_behavior,
         })
     }
 
@@ -164,11 +244,12 @@ where
 
             // Parse the incoming bytes.
             for byte in &buf[..unprocessed] {
-                state.parser.advance(&mut **terminal, *byte);
+                state.advance(&mut **terminal, *byte, &self.sender, &self.event_proxy);
             }
 
             processed += unprocessed;
             unprocessed = 0;
+            state.update_in_band_resize(terminal.mode());
 
             // Assure we're not blocking the terminal too long unnecessarily.
             if processed >= MAX_LOCKED_READ {
@@ -188,10 +269,21 @@ where
         while let Ok(msg) = self.receiver.try_recv() {
             match msg {
                 Msg::Input(input) => {
-                    state.write_list.push_back(input);
+                    // tmux would take it as a command in control mode.
+                    if state.tmux.is_none() {
+                        state.write_list.push_back(input);
+                    }
+                }
+                Msg::Command(command) => {
+                    state.write_list.push_back(command);
                 }
                 Msg::Resize(window_size) => {
-                    let _ = self.pty.set_winsize(window_size);
+                    if let Some((_, gateway)) = &state.tmux {
+                        gateway.resize(window_size.cols.into(), window_size.rows.into());
+                    }
+                    let _ = self.pty.set_winsize(window_size.clone());
+                    state.winsize = Some(window_size);
+                    state.report_size();
                 }
                 Msg::Shutdown => return false,
             }
@@ -306,19 +398,24 @@ where
                             }
                         }
                         token if token == self.pty.child_event_token() => {
-                            if let Some(teletypewriter::ChildEvent::Exited) =
+                            if let Some(ChildEvent::Exited(status)) =
                                 self.pty.next_child_event()
                             {
-                                // In the future allow configure exit
-                                // if self.hold {
-                                //     With hold enabled, make sure the PTY is drained.
-                                //     let _ = self.pty_read(&mut state, &mut buf);
-                                // } else {
-                                //     // Without hold, shutdown the terminal.
-                                //     self.terminal.lock().exit();
-                                // }
-
-                                self.terminal.lock().exit();
+                                // Make sure the PTY is drained.
+                                let _ = self.pty_read(&mut state, &mut buf);
+
+                                if should_hold(self.exit_behavior, status) {
+                                    let mut terminal = self.terminal.lock();
+                                    for byte in exit_message(status).bytes() {
+                                        state.parser.advance(&mut *terminal, byte);
+                                    }
+                                    drop(terminal);
+                                    self.event_proxy
+                                        .send_event(RioEvent::ChildExited(status));
+                                } else {
+                                    self.terminal.lock().exit();
+                                }
+
                                 self.event_proxy.send_event(RioEvent::Wakeup);
                                 break 'event_loop;
                             }
@@ -330,7 +427,9 @@ where
                         {
                             #[cfg(unix)]
                             if UnixReady::from(event.readiness()).is_hup() {
-                                // Don't try to do I/O on a dead PTY.
+                                // Read what is left without writing, streams
+                                // tell their end once they read it.
+                                let _ = self.pty_read(&mut state, &mut buf);
                                 continue;
                             }
                             if event.readiness().is_readable() {
@@ -373,6 +472,8 @@ where
                     .unwrap();
             }
 
+            state.end_tmux(&self.event_proxy);
+
             // The evented instances are not dropped here so deregister them explicitly.
             let _ = self.poll.deregister(&self.receiver);
             let _ = self.pty.deregister(&self.poll);
@@ -381,3 +482,252 @@ where
         });
     }
 }
+
+/// Whether the terminal is kept open once its child exited with `status`,
+/// an unknown status counts as a clean exit.
+fn should_hold(exit_behavior: ExitBehavior, status: Option<ExitStatus>) -> bool {
+    match exit_behavior {
+        ExitBehavior::Close => false,
+        ExitBehavior::CloseOnCleanExit => !status.map_or(true, |status| status.success()),
+        ExitBehavior::Hold => true,
+    }
+}
+
+/// In-band resize notification of the DEC private mode 2048.
+fn in_band_resize_report(winsize: &WinsizeBuilder) -> String {
+    format!(
+        "\x1b[48;{};{};{};{}t",
+        winsize.rows, winsize.cols, winsize.height, winsize.width
+    )
+}
+
+/// Text written in the terminal kept open once its child exited.
+fn exit_message(status: Option<ExitStatus>) -> String {
+    let status = match status {
+        Some(status) => format!("process {status}"),
+        None => String::from("process exited"),
+    };
+    format!("\r\n\x1b[0m[{status}]\r\nPress Enter to restart or Escape to close.\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_hold() {
+        let failed = Some(ExitStatus::Code(1));
+        let clean = Some(ExitStatus::Code(0));
+        let killed = Some(ExitStatus::Signal(9));
+
+        assert!(!should_hold(ExitBehavior::Close, failed));
+        assert!(should_hold(ExitBehavior::CloseOnCleanExit, failed));
+        assert!(should_hold(ExitBehavior::CloseOnCleanExit, killed));
+        assert!(!should_hold(ExitBehavior::CloseOnCleanExit, clean));
+        assert!(!should_hold(ExitBehavior::CloseOnCleanExit, None));
+        assert!(should_hold(ExitBehavior::Hold, clean));
+    }
+
+    #[test]
+    fn test_exit_message() {
+        assert!(exit_message(Some(ExitStatus::Code(3)))
+            .contains("[process exited with code 3]"));
+        assert!(exit_message(Some(ExitStatus::Signal(15)))
+            .contains("[process killed by signal 15]"));
+        assert!(exit_message(None).contains("[process exited]"));
+    }
+
+    #[derive(Clone)]
+    struct ChannelListener(Arc<std::sync::Mutex<std::sync::mpsc::Sender<RioEvent>>>);
+
+    impl EventListener for ChannelListener {
+        fn send_event(&self, event: RioEvent) {
+            let _ = self.0.lock().unwrap().send(event);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_stream_backend() {
+        use crate::crosswords::pos::{Column, Line, Pos};
+        use std::time::Duration;
+        use teletypewriter::WinsizeBuilder;
+
+        let (stream, mut peer) = teletypewriter::Stream::pair().unwrap();
+        let (sender, events) = std::sync::mpsc::channel();
+        let listener = ChannelListener(Arc::new(std::sync::Mutex::new(sender)));
+        let terminal =
+            Arc::new(FairMutex::new(Crosswords::new(80, 24, listener.clone())));
+        let machine =
+            Machine::new(terminal.clone(), stream, listener, ExitBehavior::Close)
+                .unwrap();
+        let channel = machine.channel();
+        machine.spawn();
+
+        channel
+            .send(Msg::Resize(WinsizeBuilder {
+                rows: 30,
+                cols: 100,
+                width: 0,
+                height: 0,
+            }))
+            .unwrap();
+        let winsize = peer.resizes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((winsize.rows, winsize.cols), (30, 100));
+
+        // Written right before the peer goes away, it is still read.
+        peer.stream.write_all(b"hello").unwrap();
+        drop(peer);
+        loop {
+            match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+                RioEvent::Exit => break,
+                _ => continue,
+            }
+        }
+
+        let text = terminal
+            .lock()
+            .bounds_to_string(Pos::new(Line(0), Column(0)), Pos::new(Line(0), Column(4)));
+        assert_eq!(text, "hello");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tmux_control_mode() {
+        use crate::crosswords::pos::{Column, Line, Pos};
+        use std::time::Duration;
+
+        let (stream, mut peer) = teletypewriter::Stream::pair().unwrap();
+        let (sender, events) = std::sync::mpsc::channel();
+        let listener = ChannelListener(Arc::new(std::sync::Mutex::new(sender)));
+        let terminal =
+            Arc::new(FairMutex::new(Crosswords::new(80, 24, listener.clone())));
+        let machine =
+            Machine::new(terminal.clone(), stream, listener, ExitBehavior::Close)
+                .unwrap();
+        let channel = machine.channel();
+        machine.spawn();
+
+        let transcript = include_bytes!("../tmux/transcript");
+        let exit = transcript
+            .windows(b"%exit".len())
+            .position(|window| window == b"%exit")
+            .unwrap();
+        peer.stream.write_all(&transcript[..exit]).unwrap();
+        // What the gateway asks tmux, up to the cursor of the last window,
+        // all of it is written before the peer goes away.
+        peer.stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut commands = String::new();
+        let mut buf = [0; 1024];
+        while !commands.ends_with("display-message -p -t %3 '#{cursor_x} #{cursor_y}'\n")
+        {
+            let read = peer.stream.read(&mut buf).unwrap();
+            commands.push_str(&String::from_utf8_lossy(&buf[..read]));
+        }
+        assert!(commands.starts_with("refresh-client -C 80x24\nlist-windows"));
+
+        // Typed in the tab running tmux, while control mode lasts.
+        channel.send(Msg::Input(Cow::Borrowed(b"typed\n"))).unwrap();
+        channel
+            .send(Msg::Command(Cow::Borrowed(b"list-sessions\n")))
+            .unwrap();
+        commands.clear();
+        while !commands.ends_with("list-sessions\n") {
+            let read = peer.stream.read(&mut buf).unwrap();
+            commands.push_str(&String::from_utf8_lossy(&buf[..read]));
+        }
+        assert_eq!(commands, "list-sessions\n");
+
+        peer.stream.write_all(&transcript[exit..]).unwrap();
+        drop(peer);
+
+        let mut changes = vec![];
+        loop {
+            match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+                RioEvent::Tmux(_, change) => changes.push(change),
+                RioEvent::Exit => break,
+                _ => continue,
+            }
+        }
+        assert!(matches!(changes[0], tmux::Change::Layout { window: 0, .. }));
+        assert!(changes.contains(&tmux::Change::Closed { window: 2 }));
+        assert_eq!(changes.last(), Some(&tmux::Change::Cleared));
+        assert_eq!(
+            changes
+                .iter()
+                .filter(|change| **change == tmux::Change::Cleared)
+                .count(),
+            1
+        );
+
+        // Drawn again once control mode ended.
+        let terminal = terminal.lock();
+        let line = |line| {
+            terminal.bounds_to_string(
+                Pos::new(Line(line), Column(0)),
+                Pos::new(Line(line), Column(79)),
+            )
+        };
+        assert_eq!(line(0).trim_end(), "$ tmux -CC attach");
+        assert_eq!(line(1).trim_end(), "$");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_in_band_resize() {
+        use std::time::Duration;
+
+        let (stream, mut peer) = teletypewriter::Stream::pair().unwrap();
+        let (sender, _events) = std::sync::mpsc::channel();
+        let listener = ChannelListener(Arc::new(std::sync::Mutex::new(sender)));
+        let terminal =
+            Arc::new(FairMutex::new(Crosswords::new(80, 24, listener.clone())));
+        let machine =
+            Machine::new(terminal, stream, listener, ExitBehavior::Close).unwrap();
+        let channel = machine.channel();
+        machine.spawn();
+
+        let resize = |rows, cols| {
+            channel
+                .send(Msg::Resize(WinsizeBuilder {
+                    rows,
+                    cols,
+                    width: cols * 8,
+                    height: rows * 16,
+                }))
+                .unwrap();
+            peer.resizes.recv_timeout(Duration::from_secs(5)).unwrap();
+        };
+        peer.stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let read = |stream: &mut std::os::unix::net::UnixStream, expected: &str| {
+            let mut output = String::new();
+            let mut buf = [0; 64];
+            while output.len() < expected.len() {
+                let len = stream.read(&mut buf).unwrap();
+                output.push_str(&String::from_utf8_lossy(&buf[..len]));
+            }
+            assert_eq!(output, expected);
+        };
+
+        // Nothing is written before the mode is set.
+        resize(24, 80);
+        peer.stream.write_all(b"\x1b[?2048h").unwrap();
+        read(&mut peer.stream, "\x1b[48;24;80;384;640t");
+        resize(30, 100);
+        read(&mut peer.stream, "\x1b[48;30;100;480;800t");
+
+        peer.stream.write_all(b"\x1b[?2048l").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        resize(40, 120);
+        peer.stream.set_nonblocking(true).unwrap();
+        let mut buf = [0; 64];
+        assert_eq!(
+            peer.stream.read(&mut buf).map_err(|err| err.kind()),
+            Err(ErrorKind::WouldBlock)
+        );
+    }
+}
@@ -0,0 +1,70 @@
+// Rich text flavors for the general pasteboard, copypasta only deals with
+// plain strings.
+
+use crate::clipboard::{RichProvider, RichText};
+use objc::runtime::{Class, Object, BOOL, NO};
+use objc::{msg_send, sel, sel_impl};
+
+const NS_UTF8_STRING_ENCODING: usize = 4;
+
+unsafe fn ns_string(value: &str) -> *mut Object {
+    let class = match Class::get("NSString") {
+        Some(class) => class,
+        None => return std::ptr::null_mut(),
+    };
+    let object: *mut Object = msg_send![class, alloc];
+    let object: *mut Object = msg_send![object,
+        initWithBytes: value.as_ptr()
+        length: value.len()
+        encoding: NS_UTF8_STRING_ENCODING];
+    msg_send![object, autorelease]
+}
+
+unsafe fn ns_data(value: &str) -> *mut Object {
+    let class = match Class::get("NSData") {
+        Some(class) => class,
+        None => return std::ptr::null_mut(),
+    };
+    msg_send![class, dataWithBytes: value.as_ptr() length: value.len()]
+}
+
+/// The general pasteboard.
+pub struct Pasteboard;
+
+impl RichProvider for Pasteboard {
+    /// Replace the general pasteboard contents with an HTML, an RTF and a
+    /// plain text representation.
+    fn set_rich(&mut self, content: &RichText) -> bool {
+        unsafe {
+            let class = match Class::get("NSPasteboard") {
+                Some(class) => class,
+                None => return false,
+            };
+            let pasteboard: *mut Object = msg_send![class, generalPasteboard];
+            if pasteboard.is_null() {
+                return false;
+            }
+
+            let html_type = ns_string("public.html");
+            let rtf_type = ns_string("public.rtf");
+            let text_type = ns_string("public.utf8-plain-text");
+            let html = ns_string(&content.html);
+            let rtf = ns_data(&content.rtf);
+            let text = ns_string(&content.text);
+            if [html_type, rtf_type, text_type, html, rtf, text]
+                .iter()
+                .any(|o| o.is_null())
+            {
+                return false;
+            }
+
+            let _: isize = msg_send![pasteboard, clearContents];
+            let html_stored: BOOL =
+                msg_send![pasteboard, setString: html forType: html_type];
+            let rtf_stored: BOOL = msg_send![pasteboard, setData: rtf forType: rtf_type];
+            let text_stored: BOOL =
+                msg_send![pasteboard, setString: text forType: text_type];
+            html_stored != NO && rtf_stored != NO && text_stored != NO
+        }
+    }
+}
@@ -1,2 +1,6 @@
 #[cfg(target_os = "macos")]
 pub mod macos;
+#[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
+pub mod wayland;
+#[cfg(all(feature = "x11", not(any(target_os = "macos", windows))))]
+pub mod x11;
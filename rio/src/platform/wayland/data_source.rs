@@ -0,0 +1,241 @@
+// Data source offering every flavor of a rich copy on the Wayland
+// clipboard, copypasta only offers plain text.
+//
+// Like smithay-clipboard, a thread with its own event queue on the window's
+// connection tracks the last input serial of every seat, which the
+// compositor requires to take the selection, and writes the requested
+// flavor whenever a client pastes.
+
+use crate::clipboard::{RichProvider, RichText};
+use log::warn;
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::fs::File;
+use std::io::Write;
+use std::os::unix::io::FromRawFd;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use wayland_client::protocol::wl_data_device_manager::WlDataDeviceManager;
+use wayland_client::protocol::wl_seat::{self, WlSeat};
+use wayland_client::protocol::{wl_data_device, wl_data_source, wl_keyboard, wl_pointer};
+use wayland_client::{Display, GlobalEvent, GlobalManager, Main};
+
+const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "UTF8_STRING",
+    "TEXT",
+    "STRING",
+];
+const HTML_MIME_TYPE: &str = "text/html";
+const RTF_MIME_TYPES: [&str; 2] = ["text/rtf", "application/rtf"];
+
+/// Interval between two dispatches of the queue, events are read from the
+/// socket by the window's event loop.
+const DISPATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+enum Command {
+    Store(RichText),
+    Exit,
+}
+
+pub struct DataSource {
+    sender: Sender<Command>,
+    replies: Receiver<bool>,
+}
+
+impl DataSource {
+    /// # Safety
+    ///
+    /// `display` must be a valid `wl_display` pointer, that outlives the
+    /// data source.
+    pub unsafe fn new(display: *mut c_void) -> Option<Self> {
+        let display = Display::from_external_display(display as *mut _);
+        let (sender, commands) = mpsc::channel();
+        let (reply_sender, replies) = mpsc::channel();
+
+        std::thread::Builder::new()
+            .name(String::from("wayland data source"))
+            .spawn(move || run(display, commands, reply_sender))
+            .ok()?;
+
+        Some(Self { sender, replies })
+    }
+}
+
+impl RichProvider for DataSource {
+    fn set_rich(&mut self, content: &RichText) -> bool {
+        self.sender.send(Command::Store(content.clone())).is_ok()
+            && self.replies.recv().unwrap_or(false)
+    }
+}
+
+impl Drop for DataSource {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Command::Exit);
+    }
+}
+
+/// Seat of the last input event and its serial.
+type LastSerial = Rc<Cell<Option<(usize, u32)>>>;
+
+fn track_seat(seat: &Main<WlSeat>, index: usize, last_serial: &LastSerial) {
+    let last_serial = last_serial.clone();
+    let mut keyboard: Option<Main<wl_keyboard::WlKeyboard>> = None;
+    let mut pointer: Option<Main<wl_pointer::WlPointer>> = None;
+
+    seat.quick_assign(move |seat, event, _| {
+        let capabilities = match event {
+            wl_seat::Event::Capabilities { capabilities } => capabilities,
+            _ => return,
+        };
+
+        if capabilities.contains(wl_seat::Capability::Keyboard) && keyboard.is_none() {
+            let last_serial = last_serial.clone();
+            let proxy = seat.get_keyboard();
+            proxy.quick_assign(move |_, event, _| match event {
+                wl_keyboard::Event::Enter { serial, .. }
+                | wl_keyboard::Event::Key { serial, .. } => {
+                    last_serial.set(Some((index, serial)));
+                }
+                _ => {}
+            });
+            keyboard = Some(proxy);
+        }
+
+        if capabilities.contains(wl_seat::Capability::Pointer) && pointer.is_none() {
+            let last_serial = last_serial.clone();
+            let proxy = seat.get_pointer();
+            proxy.quick_assign(move |_, event, _| match event {
+                wl_pointer::Event::Enter { serial, .. }
+                | wl_pointer::Event::Button { serial, .. } => {
+                    last_serial.set(Some((index, serial)));
+                }
+                _ => {}
+            });
+            pointer = Some(proxy);
+        }
+    });
+}
+
+fn data(content: &RichText, mime_type: &str) -> Option<Vec<u8>> {
+    if TEXT_MIME_TYPES.contains(&mime_type) {
+        Some(content.text.as_bytes().to_vec())
+    } else if mime_type == HTML_MIME_TYPE {
+        Some(content.html.as_bytes().to_vec())
+    } else if RTF_MIME_TYPES.contains(&mime_type) {
+        Some(content.rtf.as_bytes().to_vec())
+    } else {
+        None
+    }
+}
+
+fn run(display: Display, commands: Receiver<Command>, replies: Sender<bool>) {
+    let mut queue = display.create_event_queue();
+    let attached = display.attach(queue.token());
+
+    let seats: Rc<RefCell<Vec<Main<WlSeat>>>> = Rc::new(RefCell::new(Vec::new()));
+    let last_serial: LastSerial = Rc::new(Cell::new(None));
+    let globals = {
+        let seats = seats.clone();
+        let last_serial = last_serial.clone();
+        GlobalManager::new_with_cb(&attached, move |event, registry, _| {
+            if let GlobalEvent::New {
+                id,
+                interface,
+                version,
+            } = event
+            {
+                if interface == "wl_seat" {
+                    let seat = registry.bind::<WlSeat>(version.min(5), id);
+                    let mut seats = seats.borrow_mut();
+                    track_seat(&seat, seats.len(), &last_serial);
+                    seats.push(seat);
+                }
+            }
+        })
+    };
+
+    if queue.sync_roundtrip(&mut (), |_, _, _| {}).is_err() {
+        return;
+    }
+    let manager = match globals.instantiate_range::<WlDataDeviceManager>(1, 3) {
+        Ok(manager) => manager,
+        Err(err) => {
+            warn!("wayland rich clipboard is not available: {err}");
+            return;
+        }
+    };
+    let mut devices: Vec<Option<Main<wl_data_device::WlDataDevice>>> = Vec::new();
+
+    loop {
+        match commands.recv_timeout(DISPATCH_INTERVAL) {
+            Ok(Command::Store(content)) => {
+                // Pick up the serial of the input that triggered the copy.
+                let _ = queue.sync_roundtrip(&mut (), |_, _, _| {});
+                let (index, serial) = match last_serial.get() {
+                    Some(last) => last,
+                    None => {
+                        let _ = replies.send(false);
+                        continue;
+                    }
+                };
+
+                if devices.len() <= index {
+                    devices.resize(index + 1, None);
+                }
+                let device = devices[index].get_or_insert_with(|| {
+                    let device = manager.get_data_device(&seats.borrow()[index]);
+                    device.quick_assign(|_, event, _| match event {
+                        wl_data_device::Event::DataOffer { id } => {
+                            id.quick_assign(|_, _, _| {});
+                        }
+                        wl_data_device::Event::Selection { id: Some(offer) } => {
+                            offer.destroy();
+                        }
+                        _ => {}
+                    });
+                    device
+                });
+
+                let source = manager.create_data_source();
+                for mime_type in TEXT_MIME_TYPES
+                    .iter()
+                    .chain(&[HTML_MIME_TYPE])
+                    .chain(RTF_MIME_TYPES.iter())
+                {
+                    source.offer(mime_type.to_string());
+                }
+                source.quick_assign(move |source, event, _| match event {
+                    wl_data_source::Event::Send { mime_type, fd } => {
+                        let mut file = unsafe { File::from_raw_fd(fd) };
+                        if let Some(data) = data(&content, &mime_type) {
+                            if let Err(err) = file.write_all(&data) {
+                                warn!("unable to send {mime_type}: {err}");
+                            }
+                        }
+                    }
+                    wl_data_source::Event::Cancelled => source.destroy(),
+                    _ => {}
+                });
+                device.set_selection(Some(&source), serial);
+                let _ = display.flush();
+                let _ = replies.send(true);
+            }
+            Ok(Command::Exit) | Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        if queue.dispatch_pending(&mut (), |_, _, _| {}).is_err() {
+            break;
+        }
+    }
+
+    for device in devices.into_iter().flatten() {
+        if device.as_ref().version() >= 2 {
+            device.release();
+        }
+    }
+    let _ = queue.sync_roundtrip(&mut (), |_, _, _| {});
+}
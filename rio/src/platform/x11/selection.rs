@@ -0,0 +1,236 @@
+// Owner of the CLIPBOARD selection offering every flavor of a rich copy,
+// copypasta only stores `UTF8_STRING`.
+//
+// Requests are answered by a thread reading the events of a hidden window,
+// which stops once the window is destroyed. Contents too large for a single
+// request (INCR transfers) are refused.
+
+use crate::clipboard::{RichProvider, RichText};
+use log::warn;
+use parking_lot::Mutex;
+use std::error::Error;
+use std::sync::Arc;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt, CreateWindowAux, EventMask, PropMode,
+    SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass,
+    SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        UTF8_STRING,
+        TEXT,
+        TEXT_PLAIN: b"text/plain",
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        TEXT_HTML: b"text/html",
+        TEXT_RTF: b"text/rtf",
+        APPLICATION_RTF: b"application/rtf",
+    }
+}
+
+struct Context {
+    connection: RustConnection,
+    window: Window,
+    atoms: Atoms,
+    content: Mutex<Option<RichText>>,
+}
+
+pub struct Selection {
+    context: Arc<Context>,
+}
+
+impl Selection {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let (connection, screen) = RustConnection::connect(None)?;
+        let root = connection.setup().roots[screen].root;
+        let window = connection.generate_id()?;
+        connection.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+        let atoms = Atoms::new(&connection)?.reply()?;
+        connection.flush()?;
+
+        let context = Arc::new(Context {
+            connection,
+            window,
+            atoms,
+            content: Mutex::new(None),
+        });
+
+        let worker = context.clone();
+        std::thread::Builder::new()
+            .name(String::from("x11 selection"))
+            .spawn(move || worker.run())?;
+
+        Ok(Self { context })
+    }
+}
+
+impl RichProvider for Selection {
+    fn set_rich(&mut self, content: &RichText) -> bool {
+        let context = &self.context;
+        *context.content.lock() = Some(content.clone());
+
+        let owned = context
+            .connection
+            .set_selection_owner(
+                context.window,
+                context.atoms.CLIPBOARD,
+                x11rb::CURRENT_TIME,
+            )
+            .ok()
+            .and_then(|_| {
+                context
+                    .connection
+                    .get_selection_owner(context.atoms.CLIPBOARD)
+                    .ok()
+            })
+            .and_then(|cookie| cookie.reply().ok())
+            .map_or(false, |reply| reply.owner == context.window);
+
+        if !owned {
+            context.content.lock().take();
+        }
+        owned
+    }
+}
+
+impl Drop for Selection {
+    fn drop(&mut self) {
+        let context = &self.context;
+        let _ = context.connection.destroy_window(context.window);
+        let _ = context.connection.flush();
+    }
+}
+
+impl Context {
+    fn run(&self) {
+        loop {
+            let event = match self.connection.wait_for_event() {
+                Ok(event) => event,
+                Err(err) => {
+                    warn!("x11 selection connection closed: {err}");
+                    return;
+                }
+            };
+
+            match event {
+                Event::SelectionRequest(request) => {
+                    if let Err(err) = self.reply(&request) {
+                        warn!("unable to answer selection request: {err}");
+                    }
+                }
+                Event::SelectionClear(clear) if clear.owner == self.window => {
+                    self.content.lock().take();
+                }
+                Event::DestroyNotify(destroy) if destroy.window == self.window => {
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Content of `target`, None when it isn't offered.
+    fn data(&self, target: Atom, content: &RichText) -> Option<(Atom, Vec<u8>)> {
+        let atoms = &self.atoms;
+        if target == atoms.UTF8_STRING
+            || target == atoms.TEXT
+            || target == atoms.TEXT_PLAIN
+            || target == atoms.TEXT_PLAIN_UTF8
+            || target == u32::from(AtomEnum::STRING)
+        {
+            Some((target, content.text.as_bytes().to_vec()))
+        } else if target == atoms.TEXT_HTML {
+            Some((target, content.html.as_bytes().to_vec()))
+        } else if target == atoms.TEXT_RTF || target == atoms.APPLICATION_RTF {
+            Some((target, content.rtf.as_bytes().to_vec()))
+        } else {
+            None
+        }
+    }
+
+    fn reply(&self, request: &SelectionRequestEvent) -> Result<(), Box<dyn Error>> {
+        let atoms = &self.atoms;
+        // Obsolete clients leave the property unset.
+        let property = match request.property {
+            x11rb::NONE => request.target,
+            property => property,
+        };
+
+        let stored = match self.content.lock().as_ref() {
+            Some(_) if request.selection != atoms.CLIPBOARD => false,
+            Some(_) if request.target == atoms.TARGETS => {
+                let targets = [
+                    atoms.TARGETS,
+                    atoms.UTF8_STRING,
+                    atoms.TEXT_PLAIN_UTF8,
+                    atoms.TEXT_PLAIN,
+                    atoms.TEXT,
+                    u32::from(AtomEnum::STRING),
+                    atoms.TEXT_HTML,
+                    atoms.TEXT_RTF,
+                    atoms.APPLICATION_RTF,
+                ];
+                self.connection.change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    AtomEnum::ATOM,
+                    &targets,
+                )?;
+                true
+            }
+            Some(content) => match self.data(request.target, content) {
+                Some((ty, data))
+                    if data.len() + 32 < self.connection.maximum_request_bytes() =>
+                {
+                    self.connection.change_property8(
+                        PropMode::REPLACE,
+                        request.requestor,
+                        property,
+                        ty,
+                        &data,
+                    )?;
+                    true
+                }
+                _ => false,
+            },
+            None => false,
+        };
+
+        let event = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if stored { property } else { x11rb::NONE },
+        };
+        self.connection.send_event(
+            false,
+            request.requestor,
+            EventMask::NO_EVENT,
+            event,
+        )?;
+        self.connection.flush()?;
+        Ok(())
+    }
+}
@@ -8,11 +8,11 @@ use crate::event::EventP;
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct TimerId {
     topic: Topic,
-    id: u8,
+    id: u64,
 }
 
 impl TimerId {
-    pub fn new(topic: Topic, id: u8) -> Self {
+    pub fn new(topic: Topic, id: u64) -> Self {
         Self { topic, id }
     }
 }
@@ -23,6 +23,9 @@ pub enum Topic {
     #[allow(unused)]
     SelectionScrolling,
     Frame,
+    SessionSave,
+    KeyTable,
+    ForegroundProcesses,
 }
 
 /// Event scheduled to be emitted at a specific time.
@@ -112,12 +115,11 @@ impl Scheduler {
         self.timers.iter().any(|timer| timer.id == id)
     }
 
-    /// Remove all timers scheduled for a tab.
+    /// Remove all timers scheduled for a window.
     ///
-    /// This must be called when a tab is removed to ensure that timers on intervals do not
+    /// This must be called when a window is removed to ensure that timers on intervals do not
     /// stick around forever and cause a memory leak.
-    #[allow(dead_code)]
-    pub fn unschedule_tab(&mut self, id: u8) {
+    pub fn unschedule_window(&mut self, id: u64) {
         self.timers.retain(|timer| timer.id.id != id);
     }
 }
@@ -0,0 +1,240 @@
+// Key tables, tmux style multi key bindings.
+//
+// Pressing the leader key enters the "leader" table for a single key, it is
+// left after the next key press or once `leader-timeout` expires. Bindings
+// with `key-table = "name"` enter other tables, which stay active until
+// `PopKeyTable` or Escape. Keys without a binding in the current table are
+// swallowed.
+
+use super::{merge_key_table, parse_key, parse_mods, KeyBinding};
+use std::collections::HashMap;
+use std::time::Duration;
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+/// Table entered by the leader key.
+pub const LEADER_TABLE: &str = "leader";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ActiveKeyTable {
+    name: String,
+    one_shot: bool,
+}
+
+pub struct KeyTables {
+    leader: Option<(VirtualKeyCode, ModifiersState)>,
+    tables: HashMap<String, Vec<KeyBinding>>,
+    timeout: Duration,
+    active: Vec<ActiveKeyTable>,
+}
+
+impl KeyTables {
+    pub fn new(config: &config::Bindings) -> Self {
+        let leader = config.leader.as_ref().and_then(|leader| {
+            match (parse_key(&leader.key), parse_mods(&leader.mods)) {
+                (Ok(key), Ok(mods)) => Some((key, mods)),
+                (Err(reason), _) | (_, Err(reason)) => {
+                    log::warn!("leader key is invalid: {reason}");
+                    None
+                }
+            }
+        });
+
+        let mut tables = HashMap::new();
+        for name in config
+            .keys
+            .iter()
+            .filter_map(|entry| entry.table.as_deref())
+        {
+            if tables.contains_key(name) {
+                continue;
+            }
+
+            let (bindings, errors) = merge_key_table(name, &config.keys);
+            for error in errors {
+                log::warn!("{error}");
+            }
+            tables.insert(name.to_string(), bindings);
+        }
+
+        KeyTables {
+            leader,
+            tables,
+            timeout: Duration::from_millis(config.leader_timeout),
+            active: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn is_leader(&self, key: Option<VirtualKeyCode>, mods: ModifiersState) -> bool {
+        match (self.leader, key) {
+            (Some(leader), Some(key)) => leader == (key, mods),
+            _ => false,
+        }
+    }
+
+    /// Enter the leader table for the next key press.
+    pub fn activate_leader(&mut self) {
+        self.active.push(ActiveKeyTable {
+            name: LEADER_TABLE.to_string(),
+            one_shot: true,
+        });
+    }
+
+    /// Enter the table `name`, returns false when it has no binding.
+    pub fn activate(&mut self, name: &str) -> bool {
+        if !self.tables.contains_key(name) {
+            log::warn!("key table {name:?} has no binding");
+            return false;
+        }
+
+        self.active.push(ActiveKeyTable {
+            name: name.to_string(),
+            one_shot: false,
+        });
+        true
+    }
+
+    pub fn pop(&mut self) {
+        self.active.pop();
+    }
+
+    /// Leave the leader table, returns true when it was active.
+    pub fn cancel_leader(&mut self) -> bool {
+        if self.is_one_shot() {
+            self.active.pop();
+            return true;
+        }
+        false
+    }
+
+    /// Name of the table receiving the next key press.
+    #[inline]
+    pub fn current(&self) -> Option<&str> {
+        self.active.last().map(|table| table.name.as_str())
+    }
+
+    #[inline]
+    pub fn is_one_shot(&self) -> bool {
+        self.active.last().map_or(false, |table| table.one_shot)
+    }
+
+    /// Bindings of the table `name`.
+    pub fn bindings(&self, name: &str) -> &[KeyBinding] {
+        self.tables
+            .get(name)
+            .map_or(&[], |bindings| bindings.as_slice())
+    }
+
+    /// How long to wait for the key following the leader, when waiting for it.
+    pub fn timeout(&self) -> Option<Duration> {
+        if self.is_one_shot() && !self.timeout.is_zero() {
+            Some(self.timeout)
+        } else {
+            None
+        }
+    }
+
+    /// Text shown at the bottom of the screen while a table is active.
+    pub fn indicator(&self) -> Option<String> {
+        if self.active.is_empty() {
+            return None;
+        }
+
+        let names: Vec<&str> = self
+            .active
+            .iter()
+            .map(|table| table.name.as_str())
+            .collect();
+        let hint = if self.is_one_shot() {
+            "waiting for a key"
+        } else {
+            "Escape to leave"
+        };
+        Some(format!("[{}] {hint}", names.join(" > ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screen::bindings::Action;
+
+    fn entry(key: &str, table: &str, action: &str) -> config::KeyBinding {
+        config::KeyBinding {
+            key: Some(key.to_string()),
+            table: Some(table.to_string()),
+            action: Some(action.to_string()),
+            ..config::KeyBinding::default()
+        }
+    }
+
+    fn key_tables() -> KeyTables {
+        KeyTables::new(&config::Bindings {
+            leader: Some(config::Leader {
+                key: String::from("a"),
+                mods: String::from("Control"),
+            }),
+            keys: vec![
+                entry("c", LEADER_TABLE, "TabCreateNew"),
+                config::KeyBinding {
+                    key: Some(String::from("r")),
+                    table: Some(String::from(LEADER_TABLE)),
+                    key_table: Some(String::from("resize")),
+                    ..config::KeyBinding::default()
+                },
+                entry("Up", "resize", "IncreaseFontSize"),
+                config::KeyBinding {
+                    key: Some(String::from("v")),
+                    action: Some(String::from("Paste")),
+                    ..config::KeyBinding::default()
+                },
+            ],
+            ..config::Bindings::default()
+        })
+    }
+
+    #[test]
+    fn test_key_tables_from_config() {
+        let tables = key_tables();
+        assert!(tables.is_leader(Some(VirtualKeyCode::A), ModifiersState::CTRL));
+        assert!(!tables.is_leader(Some(VirtualKeyCode::A), ModifiersState::empty()));
+
+        let leader: Vec<Action> = tables
+            .bindings(LEADER_TABLE)
+            .iter()
+            .map(|binding| binding.action.clone())
+            .collect();
+        assert_eq!(
+            leader,
+            vec![
+                Action::TabCreateNew,
+                Action::ActivateKeyTable(String::from("resize"))
+            ]
+        );
+        assert_eq!(tables.bindings("resize").len(), 1);
+        assert!(tables.bindings("missing").is_empty());
+    }
+
+    #[test]
+    fn test_key_table_stack() {
+        let mut tables = key_tables();
+        assert_eq!(tables.current(), None);
+        assert_eq!(tables.indicator(), None);
+
+        tables.activate_leader();
+        assert_eq!(tables.current(), Some(LEADER_TABLE));
+        assert_eq!(tables.timeout(), Some(Duration::from_millis(1000)));
+        assert_eq!(tables.indicator().unwrap(), "[leader] waiting for a key");
+
+        // Leaving the leader table before entering a modal one
+        assert!(tables.cancel_leader());
+        assert!(tables.activate("resize"));
+        assert_eq!(tables.timeout(), None);
+        assert!(!tables.cancel_leader());
+        assert_eq!(tables.indicator().unwrap(), "[resize] Escape to leave");
+
+        assert!(!tables.activate("missing"));
+        tables.pop();
+        assert_eq!(tables.current(), None);
+    }
+}
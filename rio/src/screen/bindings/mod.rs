@@ -1,11 +1,19 @@
 // Cria os bindings e usa struct actions
 // https://github.com/alacritty/alacritty/blob/828fdab7470c8d16d2edbe2cec919169524cb2bb/alacritty/src/config/bindings.rs#L43
 
+pub mod key_table;
+
 use crate::crosswords::vi_mode::ViMotion;
 use crate::crosswords::Mode;
+use crate::export::{ExportFormat, ExportScope};
+use crate::screen::mouse::{BACK_BUTTON, FORWARD_BUTTON};
 use bitflags::bitflags;
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+use std::fmt;
 use std::fmt::Debug;
 use winit::event::ModifiersState;
+use winit::event::MouseButton;
 use winit::event::VirtualKeyCode;
 use winit::event::VirtualKeyCode::*;
 
@@ -54,7 +62,6 @@ impl<T: Eq> Binding<T> {
     }
 
     #[inline]
-    #[allow(unused)]
     pub fn triggers_match(&self, binding: &Binding<T>) -> bool {
         // Check the binding's key and modifiers.
         if self.trigger != binding.trigger || self.mods != binding.mods {
@@ -98,6 +105,9 @@ pub enum Key {
 pub type KeyBindings = Vec<KeyBinding>;
 pub type KeyBinding = Binding<Key>;
 
+pub type MouseBindings = Vec<MouseBinding>;
+pub type MouseBinding = Binding<MouseButton>;
+
 bitflags! {
     /// Modes available for key bindings.
     #[derive(Clone, Debug, Eq, PartialEq)]
@@ -121,24 +131,48 @@ impl BindingMode {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub enum Action {
     /// Write an escape sequence.
+    #[serde(skip)]
     Esc(String),
 
     /// Run given command.
-    // Command(Program),
+    #[serde(skip)]
+    Command(config::Program),
+
+    /// Enter the named key table, until `PopKeyTable` or Escape.
+    #[serde(skip)]
+    ActivateKeyTable(String),
+
+    /// Leave the current key table.
+    PopKeyTable,
+
+    /// Start recording the input written into the current tab.
+    StartMacroRecording,
+
+    /// Same as `StartMacroRecording`, keeping the delays between writes.
+    StartTimedMacroRecording,
+
+    /// Stop recording and save the macro into the configuration file.
+    StopMacroRecording,
+
+    /// Write the named macro into the current tab.
+    #[serde(skip)]
+    ReplayMacro(String),
 
     /// Regex keyboard hints.
     // Hint(Hint),
 
     // Move vi mode cursor.
+    #[serde(skip)]
     ViMotion(ViMotion),
 
     // Perform vi mode action.
     // Vi(ViAction),
     /// Perform mouse binding exclusive action.
-    // Mouse(MouseAction),
+    #[serde(skip)]
+    Mouse(MouseAction),
 
     /// Paste contents of system clipboard.
     Paste,
@@ -146,15 +180,22 @@ pub enum Action {
     /// Store current selection into clipboard.
     Copy,
 
-    #[cfg(not(any(target_os = "macos", windows)))]
-    #[allow(dead_code)]
+    /// Write the history, visible screen or selection into a file.
+    #[serde(skip)]
+    Export(ExportScope, ExportFormat),
+
     /// Store current selection into selection buffer.
+    #[allow(dead_code)]
     CopySelection,
 
     /// Paste contents of selection buffer.
     #[allow(dead_code)]
     PasteSelection,
 
+    /// Open the clipboard history picker.
+    #[allow(dead_code)]
+    ShowClipboardHistory,
+
     /// Increase font size.
     #[allow(dead_code)]
     IncreaseFontSize,
@@ -220,7 +261,6 @@ pub enum Action {
     SpawnNewInstance,
 
     /// Create a new Rio window.
-    #[allow(dead_code)]
     WindowCreateNew,
 
     /// Create a new Rio tab.
@@ -231,10 +271,72 @@ pub enum Action {
     #[allow(dead_code)]
     TabSwitchNext,
 
+    /// Switch to previous tab.
+    TabSwitchPrev,
+
+    /// Switch to the tab that was current before this one.
+    TabSwitchLast,
+
+    /// Switch to the tab at the given index, starting at 0. It is named
+    /// `TabSwitchTo<N>` in the configuration, starting at 1.
+    #[serde(skip)]
+    TabSwitchTo(usize),
+
+    /// Move the current tab one position to the left.
+    TabMoveLeft,
+
+    /// Move the current tab one position to the right.
+    TabMoveRight,
+
+    /// Ask for a new title for the current tab.
+    TabRename,
+
+    /// Open a new tab in the working directory of the current one.
+    TabDuplicate,
+
     /// Switch to next tab.
     #[allow(dead_code)]
     TabCloseCurrent,
 
+    /// Split the focused pane, the new pane goes to its right.
+    PaneSplitRight,
+
+    /// Split the focused pane, the new pane goes below it.
+    PaneSplitDown,
+
+    /// Close the focused pane, or the tab when it is the only pane.
+    PaneClose,
+
+    /// Focus the pane to the left of the focused one.
+    PaneFocusLeft,
+
+    /// Focus the pane to the right of the focused one.
+    PaneFocusRight,
+
+    /// Focus the pane above the focused one.
+    PaneFocusUp,
+
+    /// Focus the pane below the focused one.
+    PaneFocusDown,
+
+    /// Move the closest vertical border of the focused pane to the left.
+    PaneResizeLeft,
+
+    /// Move the closest vertical border of the focused pane to the right.
+    PaneResizeRight,
+
+    /// Move the closest horizontal border of the focused pane up.
+    PaneResizeUp,
+
+    /// Move the closest horizontal border of the focused pane down.
+    PaneResizeDown,
+
+    /// Make the focused pane take the whole tab, or restore the others.
+    PaneZoom,
+
+    /// Give every pane of the tab the same size.
+    PaneEqualize,
+
     /// Toggle fullscreen.
     #[allow(dead_code)]
     ToggleFullscreen,
@@ -263,6 +365,57 @@ pub enum Action {
     None,
 }
 
+/// Actions applied at the mouse position, mostly useful for mouse bindings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum MouseAction {
+    /// Expand the selection to the mouse position, by words or lines after
+    /// a double or triple click.
+    ExpandSelection,
+
+    /// Extend the selection to the mouse position, keeping its kind.
+    ExtendSelection,
+
+    /// Start a block selection.
+    BlockSelect,
+
+    /// Open the hyperlink or URL under the mouse.
+    OpenLink,
+
+    /// Show the context menu.
+    ContextMenu,
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    /// Parse an action name, mouse actions and vi motions are accepted by
+    /// their own name.
+    fn from_str(name: &str) -> Result<Action, String> {
+        let deserializer: value::StrDeserializer<value::Error> = name.into_deserializer();
+        if let Ok(action) = Action::deserialize(deserializer) {
+            return Ok(action);
+        }
+
+        let deserializer: value::StrDeserializer<value::Error> = name.into_deserializer();
+        if let Ok(action) = MouseAction::deserialize(deserializer) {
+            return Ok(Action::Mouse(action));
+        }
+
+        if let Some(index) = name
+            .strip_prefix("TabSwitchTo")
+            .and_then(|index| index.parse::<usize>().ok())
+            .filter(|index| *index > 0)
+        {
+            return Ok(Action::TabSwitchTo(index - 1));
+        }
+
+        let deserializer: value::StrDeserializer<value::Error> = name.into_deserializer();
+        ViMotion::deserialize(deserializer)
+            .map(Action::ViMotion)
+            .map_err(|_| format!("unknown action {name:?}"))
+    }
+}
+
 impl From<&'static str> for Action {
     fn from(s: &'static str) -> Action {
         Action::Esc(s.into())
@@ -275,6 +428,12 @@ impl From<ViMotion> for Action {
     }
 }
 
+impl From<MouseAction> for Action {
+    fn from(action: MouseAction) -> Self {
+        Self::Mouse(action)
+    }
+}
+
 macro_rules! bindings {
     (
         KeyBinding;
@@ -332,6 +491,378 @@ macro_rules! bindings {
     }};
 }
 
+/// Problem found while reading `[bindings]`, entries are numbered from one
+/// within their list (`keys` or `mouse`).
+#[derive(Debug, PartialEq, Eq)]
+pub enum BindingError {
+    /// The entry could not be parsed and was ignored.
+    Invalid {
+        kind: &'static str,
+        index: usize,
+        entry: String,
+        reason: String,
+    },
+    /// Both entries are triggered by the same input, the last one wins.
+    Conflict {
+        kind: &'static str,
+        index: usize,
+        other: usize,
+    },
+}
+
+impl fmt::Display for BindingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingError::Invalid {
+                kind,
+                index,
+                entry,
+                reason,
+            } => write!(f, "{kind} binding #{index} ({entry}) is invalid: {reason}"),
+            BindingError::Conflict { kind, index, other } => write!(
+                f,
+                "{kind} binding #{index} conflicts with #{other}, using #{other}"
+            ),
+        }
+    }
+}
+
+/// Short description of a key binding entry, used in error messages.
+fn describe_key_binding(binding: &config::KeyBinding) -> String {
+    let trigger = match (&binding.key, binding.scancode) {
+        (Some(key), _) => format!("key = {key:?}"),
+        (None, Some(scancode)) => format!("scancode = {scancode}"),
+        (None, None) => String::from("no key"),
+    };
+
+    if binding.mods.is_empty() {
+        trigger
+    } else {
+        format!("{trigger}, mods = {:?}", binding.mods)
+    }
+}
+
+fn parse_key(name: &str) -> Result<VirtualKeyCode, String> {
+    let mut chars = name.chars();
+    let key = match (chars.next(), chars.next()) {
+        // Single characters are case insensitive and digits map to `Key0`..`Key9`
+        (Some(c), None) if c.is_ascii_digit() => format!("Key{c}"),
+        (Some(c), None) if c.is_ascii_alphabetic() => c.to_ascii_uppercase().to_string(),
+        _ => name.to_string(),
+    };
+
+    let deserializer: value::StrDeserializer<value::Error> =
+        key.as_str().into_deserializer();
+    VirtualKeyCode::deserialize(deserializer).map_err(|_| format!("unknown key {name:?}"))
+}
+
+/// Short description of a mouse binding entry, used in error messages.
+fn describe_mouse_binding(binding: &config::MouseBinding) -> String {
+    if binding.mods.is_empty() {
+        format!("button = {:?}", binding.button)
+    } else {
+        format!("button = {:?}, mods = {:?}", binding.button, binding.mods)
+    }
+}
+
+/// Parse a mouse button, `Back` and `Forward` use the X11 numbering as
+/// done by `mouse::normalize_button`.
+fn parse_button(name: &str) -> Result<MouseButton, String> {
+    match name.to_lowercase().as_str() {
+        "left" => Ok(MouseButton::Left),
+        "middle" => Ok(MouseButton::Middle),
+        "right" => Ok(MouseButton::Right),
+        "back" => Ok(MouseButton::Other(BACK_BUTTON)),
+        "forward" => Ok(MouseButton::Other(FORWARD_BUTTON)),
+        _ => name
+            .parse::<u16>()
+            .map(MouseButton::Other)
+            .map_err(|_| format!("unknown mouse button {name:?}")),
+    }
+}
+
+fn parse_action(
+    action: &Option<String>,
+    chars: &Option<String>,
+    command: &Option<config::Program>,
+    macro_name: &Option<String>,
+    export: &Option<config::Export>,
+) -> Result<Action, String> {
+    match (action, chars, command, macro_name, export) {
+        (Some(action), None, None, None, None) => action.parse::<Action>(),
+        (None, Some(chars), None, None, None) => Ok(Action::Esc(chars.to_string())),
+        (None, None, Some(command), None, None) => Ok(Action::Command(command.clone())),
+        (None, None, None, Some(name), None) => Ok(Action::ReplayMacro(name.to_string())),
+        (None, None, None, None, Some(export)) => Ok(Action::Export(
+            export.scope.parse()?,
+            export.format.parse()?,
+        )),
+        (None, None, None, None, None) => Err(String::from(
+            "missing `action`, `chars`, `command`, `macro` or `export`",
+        )),
+        _ => Err(String::from(
+            "`action`, `chars`, `command`, `macro` and `export` are exclusive",
+        )),
+    }
+}
+
+fn parse_mods(mods: &str) -> Result<ModifiersState, String> {
+    let mut state = ModifiersState::empty();
+    for modifier in mods.split('|').map(str::trim).filter(|m| !m.is_empty()) {
+        match modifier.to_lowercase().as_str() {
+            "command" | "super" | "logo" => state.insert(ModifiersState::LOGO),
+            "shift" => state.insert(ModifiersState::SHIFT),
+            "alt" | "option" => state.insert(ModifiersState::ALT),
+            "control" | "ctrl" => state.insert(ModifiersState::CTRL),
+            "none" => (),
+            _ => return Err(format!("unknown modifier {modifier:?}")),
+        }
+    }
+    Ok(state)
+}
+
+fn parse_mode(modes: &str) -> Result<(BindingMode, BindingMode), String> {
+    let mut mode = BindingMode::empty();
+    let mut notmode = BindingMode::empty();
+    for name in modes.split('|').map(str::trim).filter(|m| !m.is_empty()) {
+        let (target, flag) = match name.strip_prefix('~') {
+            Some(flag) => (&mut notmode, flag),
+            None => (&mut mode, name),
+        };
+
+        match flag.to_lowercase().as_str() {
+            "appcursor" => target.insert(BindingMode::APP_CURSOR),
+            "appkeypad" => target.insert(BindingMode::APP_KEYPAD),
+            "alt" => target.insert(BindingMode::ALT_SCREEN),
+            "vi" => target.insert(BindingMode::VI),
+            "search" => target.insert(BindingMode::SEARCH),
+            _ => return Err(format!("unknown mode {name:?}")),
+        }
+    }
+    Ok((mode, notmode))
+}
+
+impl TryFrom<&config::KeyBinding> for KeyBinding {
+    type Error = String;
+
+    fn try_from(binding: &config::KeyBinding) -> Result<KeyBinding, String> {
+        let trigger = match (&binding.key, binding.scancode) {
+            (Some(_), Some(_)) => {
+                return Err(String::from("`key` and `scancode` are exclusive"))
+            }
+            (Some(key), None) => Key::Keycode(parse_key(key)?),
+            (None, Some(scancode)) => Key::Scancode(scancode),
+            (None, None) => return Err(String::from("missing `key` or `scancode`")),
+        };
+
+        let action = match &binding.key_table {
+            Some(table)
+                if binding.action.is_none()
+                    && binding.chars.is_none()
+                    && binding.command.is_none()
+                    && binding.macro_name.is_none()
+                    && binding.export.is_none() =>
+            {
+                Action::ActivateKeyTable(table.to_string())
+            }
+            Some(_) => return Err(String::from(
+                "`key-table` and `action`, `chars`, `command`, `macro` or `export` are exclusive",
+            )),
+            None => parse_action(
+                &binding.action,
+                &binding.chars,
+                &binding.command,
+                &binding.macro_name,
+                &binding.export,
+            )?,
+        };
+        let (mode, notmode) = parse_mode(&binding.mode)?;
+
+        Ok(KeyBinding {
+            trigger,
+            mods: parse_mods(&binding.mods)?,
+            mode,
+            notmode,
+            action,
+        })
+    }
+}
+
+impl TryFrom<&config::MouseBinding> for MouseBinding {
+    type Error = String;
+
+    fn try_from(binding: &config::MouseBinding) -> Result<MouseBinding, String> {
+        let trigger = parse_button(&binding.button)?;
+        let action = parse_action(
+            &binding.action,
+            &binding.chars,
+            &binding.command,
+            &binding.macro_name,
+            &binding.export,
+        )?;
+        let (mode, notmode) = parse_mode(&binding.mode)?;
+
+        Ok(MouseBinding {
+            trigger,
+            mods: parse_mods(&binding.mods)?,
+            mode,
+            notmode,
+            action,
+        })
+    }
+}
+
+/// Merge `entries` over `bindings`. An entry replaces every binding it
+/// conflicts with, so `ReceiveChar` and `None` unbind a default. Invalid
+/// entries are skipped and reported along with conflicts between entries.
+/// Entries left out by `include` are skipped but keep their index.
+fn merge_bindings<T: Eq, E>(
+    kind: &'static str,
+    mut bindings: Vec<Binding<T>>,
+    entries: &[E],
+    include: impl Fn(&E) -> bool,
+    parse: impl Fn(&E) -> Result<Binding<T>, String>,
+    describe: impl Fn(&E) -> String,
+) -> (Vec<Binding<T>>, Vec<BindingError>) {
+    let mut errors = vec![];
+    let mut user_bindings: Vec<(usize, Binding<T>)> = vec![];
+
+    for (position, entry) in entries.iter().enumerate() {
+        if !include(entry) {
+            continue;
+        }
+
+        let index = position + 1;
+        let binding = match parse(entry) {
+            Ok(binding) => binding,
+            Err(reason) => {
+                errors.push(BindingError::Invalid {
+                    kind,
+                    index,
+                    entry: describe(entry),
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        user_bindings.retain(|(other, user_binding)| {
+            let conflict = user_binding.triggers_match(&binding);
+            if conflict {
+                errors.push(BindingError::Conflict {
+                    kind,
+                    index: *other,
+                    other: index,
+                });
+            }
+            !conflict
+        });
+        user_bindings.push((index, binding));
+    }
+
+    bindings.retain(|binding| {
+        !user_bindings
+            .iter()
+            .any(|(_, user_binding)| user_binding.triggers_match(binding))
+    });
+    bindings.extend(user_bindings.into_iter().map(|(_, binding)| binding));
+
+    (bindings, errors)
+}
+
+/// Merge the `[bindings] keys` entries outside of key tables over `bindings`.
+pub fn merge_key_bindings(
+    bindings: KeyBindings,
+    entries: &[config::KeyBinding],
+) -> (KeyBindings, Vec<BindingError>) {
+    merge_bindings(
+        "key",
+        bindings,
+        entries,
+        |entry| entry.table.is_none(),
+        |entry| KeyBinding::try_from(entry),
+        describe_key_binding,
+    )
+}
+
+/// Bindings of the key table `table` among the `[bindings] keys` entries.
+pub fn merge_key_table(
+    table: &str,
+    entries: &[config::KeyBinding],
+) -> (KeyBindings, Vec<BindingError>) {
+    merge_bindings(
+        "key",
+        vec![],
+        entries,
+        |entry| entry.table.as_deref() == Some(table),
+        |entry| KeyBinding::try_from(entry),
+        describe_key_binding,
+    )
+}
+
+/// Merge the `[bindings] mouse` entries over `bindings`.
+pub fn merge_mouse_bindings(
+    bindings: MouseBindings,
+    entries: &[config::MouseBinding],
+) -> (MouseBindings, Vec<BindingError>) {
+    merge_bindings(
+        "mouse",
+        bindings,
+        entries,
+        |_| true,
+        |entry| MouseBinding::try_from(entry),
+        describe_mouse_binding,
+    )
+}
+
+/// Default bindings with the user ones from `[bindings]` merged over them.
+pub fn key_bindings(config: &config::Bindings) -> KeyBindings {
+    let (bindings, errors) = merge_key_bindings(default_key_bindings(), &config.keys);
+    for error in errors {
+        log::warn!("{error}");
+    }
+    bindings
+}
+
+/// Default mouse bindings with the user ones from `[bindings]` merged over
+/// them. Middle click paste is left out when disabled in `[clipboard]`.
+pub fn mouse_bindings(config: &config::Config) -> MouseBindings {
+    let mut bindings = default_mouse_bindings();
+    if !config.clipboard.middle_click_paste {
+        bindings.retain(|binding| binding.action != Action::PasteSelection);
+    }
+
+    let (bindings, errors) = merge_mouse_bindings(bindings, &config.bindings.mouse);
+    for error in errors {
+        log::warn!("{error}");
+    }
+    bindings
+}
+
+pub fn default_mouse_bindings() -> MouseBindings {
+    bindings!(
+        MouseBinding;
+        MouseButton::Right;                            MouseAction::ExpandSelection;
+        MouseButton::Right,  ModifiersState::CTRL;     MouseAction::ExpandSelection;
+        MouseButton::Left,   ModifiersState::SHIFT;    MouseAction::ExtendSelection;
+        MouseButton::Left,   ModifiersState::CTRL;     MouseAction::BlockSelect;
+        MouseButton::Left,   open_link_mods();         MouseAction::OpenLink;
+        MouseButton::Middle, ~BindingMode::VI;         Action::PasteSelection;
+    )
+}
+
+#[cfg(target_os = "macos")]
+#[inline]
+fn open_link_mods() -> ModifiersState {
+    ModifiersState::LOGO
+}
+
+#[cfg(not(target_os = "macos"))]
+#[inline]
+fn open_link_mods() -> ModifiersState {
+    ModifiersState::CTRL | ModifiersState::SHIFT
+}
+
 pub fn default_key_bindings() -> Vec<KeyBinding> {
     let mut bindings = bindings!(
         KeyBinding;
@@ -500,9 +1031,18 @@ pub fn default_key_bindings() -> Vec<KeyBinding> {
             ViMotion::Bracket;
         T, ModifiersState::LOGO; Action::TabCreateNew;
         Tab, ModifiersState::CTRL; Action::TabSwitchNext;
+        Tab, ModifiersState::CTRL | ModifiersState::SHIFT; Action::TabSwitchPrev;
         W, ModifiersState::LOGO; Action::TabCloseCurrent;
     );
 
+    let tab_keys = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    for (index, key) in tab_keys.into_iter().enumerate() {
+        bindings.extend(bindings!(
+            KeyBinding;
+            key, ModifiersState::ALT, ~BindingMode::VI; Action::TabSwitchTo(index);
+        ));
+    }
+
     //   Code     Modifiers
     // ---------+---------------------------
     //    2     | Shift
@@ -640,6 +1180,20 @@ pub fn platform_key_bindings() -> Vec<KeyBinding> {
         M, ModifiersState::LOGO; Action::Minimize;
         Q, ModifiersState::LOGO; Action::Quit;
         W, ModifiersState::LOGO; Action::Quit;
+        S, ModifiersState::LOGO | ModifiersState::SHIFT;
+            Action::Export(ExportScope::History, ExportFormat::Text);
+        H, ModifiersState::LOGO | ModifiersState::SHIFT; Action::ShowClipboardHistory;
+        D, ModifiersState::LOGO; Action::PaneSplitRight;
+        D, ModifiersState::LOGO | ModifiersState::SHIFT; Action::PaneSplitDown;
+        Left, ModifiersState::LOGO | ModifiersState::ALT; Action::PaneFocusLeft;
+        Right, ModifiersState::LOGO | ModifiersState::ALT; Action::PaneFocusRight;
+        Up, ModifiersState::LOGO | ModifiersState::ALT; Action::PaneFocusUp;
+        Down, ModifiersState::LOGO | ModifiersState::ALT; Action::PaneFocusDown;
+        Left, ModifiersState::LOGO | ModifiersState::CTRL; Action::PaneResizeLeft;
+        Right, ModifiersState::LOGO | ModifiersState::CTRL; Action::PaneResizeRight;
+        Up, ModifiersState::LOGO | ModifiersState::CTRL; Action::PaneResizeUp;
+        Down, ModifiersState::LOGO | ModifiersState::CTRL; Action::PaneResizeDown;
+        Return, ModifiersState::LOGO | ModifiersState::SHIFT; Action::PaneZoom;
     )
 }
 
@@ -658,6 +1212,26 @@ pub fn platform_key_bindings() -> Vec<KeyBinding> {
         NumpadAdd,      ModifiersState::CTRL;  Action::IncreaseFontSize;
         Minus,          ModifiersState::CTRL;  Action::DecreaseFontSize;
         NumpadSubtract, ModifiersState::CTRL;  Action::DecreaseFontSize;
+        S,        ModifiersState::CTRL | ModifiersState::SHIFT;
+            Action::Export(ExportScope::History, ExportFormat::Text);
+        H,        ModifiersState::CTRL | ModifiersState::SHIFT; Action::ShowClipboardHistory;
+        N,        ModifiersState::CTRL | ModifiersState::SHIFT; Action::WindowCreateNew;
+        D,        ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneSplitRight;
+        D,        ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneSplitDown;
+        Left,     ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneFocusLeft;
+        Right,    ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneFocusRight;
+        Up,       ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneFocusUp;
+        Down,     ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneFocusDown;
+        Left,     ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneResizeLeft;
+        Right,    ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneResizeRight;
+        Up,       ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneResizeUp;
+        Down,     ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneResizeDown;
+        Z,        ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneZoom;
     )
 }
 
@@ -677,6 +1251,26 @@ pub fn platform_key_bindings() -> Vec<KeyBinding> {
         Minus,          ModifiersState::CTRL;  Action::DecreaseFontSize;
         NumpadSubtract, ModifiersState::CTRL;  Action::DecreaseFontSize;
         Return, ModifiersState::ALT; Action::ToggleFullscreen;
+        S,        ModifiersState::CTRL | ModifiersState::SHIFT;
+            Action::Export(ExportScope::History, ExportFormat::Text);
+        H,        ModifiersState::CTRL | ModifiersState::SHIFT; Action::ShowClipboardHistory;
+        N,        ModifiersState::CTRL | ModifiersState::SHIFT; Action::WindowCreateNew;
+        D,        ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneSplitRight;
+        D,        ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneSplitDown;
+        Left,     ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneFocusLeft;
+        Right,    ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneFocusRight;
+        Up,       ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneFocusUp;
+        Down,     ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneFocusDown;
+        Left,     ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneResizeLeft;
+        Right,    ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneResizeRight;
+        Up,       ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneResizeUp;
+        Down,     ModifiersState::CTRL | ModifiersState::SHIFT | ModifiersState::ALT;
+            Action::PaneResizeDown;
+        Z,        ModifiersState::CTRL | ModifiersState::SHIFT; Action::PaneZoom;
     )
 }
 
@@ -684,3 +1278,243 @@ pub fn platform_key_bindings() -> Vec<KeyBinding> {
 pub fn platform_key_bindings() -> Vec<KeyBinding> {
     vec![]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, mods: &str, mode: &str, action: &str) -> config::KeyBinding {
+        config::KeyBinding {
+            key: Some(key.to_string()),
+            mods: mods.to_string(),
+            mode: mode.to_string(),
+            action: Some(action.to_string()),
+            ..config::KeyBinding::default()
+        }
+    }
+
+    fn triggered(
+        bindings: &KeyBindings,
+        key: VirtualKeyCode,
+        mods: ModifiersState,
+    ) -> Vec<Action> {
+        bindings
+            .iter()
+            .filter(|binding| {
+                binding.is_triggered_by(BindingMode::empty(), mods, &Key::Keycode(key))
+            })
+            .map(|binding| binding.action.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_binding() {
+        let binding = KeyBinding::try_from(&entry(
+            "t",
+            "Control|Shift",
+            "~Vi|AppCursor",
+            "TabCreateNew",
+        ))
+        .unwrap();
+        assert_eq!(binding.trigger, Key::Keycode(T));
+        assert_eq!(binding.mods, ModifiersState::CTRL | ModifiersState::SHIFT);
+        assert_eq!(binding.mode, BindingMode::APP_CURSOR);
+        assert_eq!(binding.notmode, BindingMode::VI);
+        assert_eq!(binding.action, Action::TabCreateNew);
+
+        let binding = KeyBinding::try_from(&entry("1", "", "", "SemanticRight")).unwrap();
+        assert_eq!(binding.trigger, Key::Keycode(Key1));
+        assert_eq!(binding.action, Action::ViMotion(ViMotion::SemanticRight));
+
+        let binding =
+            KeyBinding::try_from(&entry("3", "Alt", "", "TabSwitchTo3")).unwrap();
+        assert_eq!(binding.action, Action::TabSwitchTo(2));
+
+        let binding = KeyBinding::try_from(&config::KeyBinding {
+            scancode: Some(36),
+            chars: Some(String::from("\x1b[13;2u")),
+            ..config::KeyBinding::default()
+        })
+        .unwrap();
+        assert_eq!(binding.trigger, Key::Scancode(36));
+        assert_eq!(binding.action, Action::Esc(String::from("\x1b[13;2u")));
+
+        let program = config::Program::Just(String::from("nautilus"));
+        let binding = KeyBinding::try_from(&config::KeyBinding {
+            key: Some(String::from("E")),
+            command: Some(program.clone()),
+            ..config::KeyBinding::default()
+        })
+        .unwrap();
+        assert_eq!(binding.action, Action::Command(program));
+
+        let binding = KeyBinding::try_from(&config::KeyBinding {
+            key: Some(String::from("S")),
+            export: Some(config::Export {
+                scope: String::from("screen"),
+                format: String::from("html"),
+            }),
+            ..config::KeyBinding::default()
+        })
+        .unwrap();
+        assert_eq!(
+            binding.action,
+            Action::Export(ExportScope::Screen, ExportFormat::Html)
+        );
+    }
+
+    #[test]
+    fn test_parse_binding_errors() {
+        let error = |entry: &config::KeyBinding| KeyBinding::try_from(entry).unwrap_err();
+
+        assert_eq!(error(&entry("Foo", "", "", "Paste")), "unknown key \"Foo\"");
+        assert_eq!(
+            error(&entry("V", "Hyper", "", "Paste")),
+            "unknown modifier \"Hyper\""
+        );
+        assert_eq!(
+            error(&entry("V", "", "~Insert", "Paste")),
+            "unknown mode \"~Insert\""
+        );
+        assert_eq!(
+            error(&entry("V", "", "", "Explode")),
+            "unknown action \"Explode\""
+        );
+        // Actions carrying data can't be named
+        assert_eq!(error(&entry("V", "", "", "Esc")), "unknown action \"Esc\"");
+        assert_eq!(
+            error(&entry("V", "", "", "TabSwitchTo0")),
+            "unknown action \"TabSwitchTo0\""
+        );
+        assert_eq!(
+            error(&config::KeyBinding::default()),
+            "missing `key` or `scancode`"
+        );
+        assert_eq!(
+            error(&config::KeyBinding {
+                command: Some(config::Program::Just(String::from("htop"))),
+                ..entry("H", "", "", "Paste")
+            }),
+            "`action`, `chars`, `command`, `macro` and `export` are exclusive"
+        );
+        assert_eq!(
+            error(&config::KeyBinding {
+                key: Some(String::from("S")),
+                export: Some(config::Export {
+                    scope: String::from("tab"),
+                    format: String::from("html"),
+                }),
+                ..config::KeyBinding::default()
+            }),
+            "unknown export scope: tab"
+        );
+    }
+
+    #[test]
+    fn test_merge_overrides_defaults() {
+        let defaults = default_key_bindings();
+        let ctrl = ModifiersState::CTRL;
+        assert_eq!(
+            triggered(&defaults, L, ctrl),
+            vec![Action::ClearLogNotice, Action::Esc("\x0c".into())]
+        );
+
+        let (bindings, errors) =
+            merge_key_bindings(defaults, &[entry("l", "Control", "", "ClearHistory")]);
+        assert!(errors.is_empty());
+        assert_eq!(triggered(&bindings, L, ctrl), vec![Action::ClearHistory]);
+    }
+
+    #[test]
+    fn test_merge_unbind() {
+        let (bindings, _) = merge_key_bindings(
+            default_key_bindings(),
+            &[entry("l", "Control", "", "ReceiveChar")],
+        );
+        assert_eq!(
+            triggered(&bindings, L, ModifiersState::CTRL),
+            vec![Action::ReceiveChar]
+        );
+    }
+
+    #[test]
+    fn test_merge_reports_errors() {
+        let (bindings, errors) = merge_key_bindings(
+            vec![],
+            &[
+                entry("F1", "", "", "Paste"),
+                entry("F2", "", "", "Nope"),
+                entry("F1", "", "~Vi", "Copy"),
+            ],
+        );
+
+        assert_eq!(
+            triggered(&bindings, F1, ModifiersState::empty()),
+            vec![Action::Copy]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                BindingError::Invalid {
+                    kind: "key",
+                    index: 2,
+                    entry: String::from("key = \"F2\""),
+                    reason: String::from("unknown action \"Nope\""),
+                },
+                BindingError::Conflict {
+                    kind: "key",
+                    index: 1,
+                    other: 3,
+                },
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "key binding #2 (key = \"F2\") is invalid: unknown action \"Nope\""
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "key binding #1 conflicts with #3, using #3"
+        );
+    }
+
+    #[test]
+    fn test_mouse_bindings() {
+        let mouse = |button: &str, mods: &str, action: &str| config::MouseBinding {
+            button: button.to_string(),
+            mods: mods.to_string(),
+            action: Some(action.to_string()),
+            ..config::MouseBinding::default()
+        };
+
+        let binding = MouseBinding::try_from(&mouse("Back", "", "ContextMenu")).unwrap();
+        assert_eq!(binding.trigger, MouseButton::Other(BACK_BUTTON));
+        assert_eq!(binding.action, Action::Mouse(MouseAction::ContextMenu));
+
+        let binding = MouseBinding::try_from(&mouse("12", "", "Paste")).unwrap();
+        assert_eq!(binding.trigger, MouseButton::Other(12));
+        assert_eq!(
+            MouseBinding::try_from(&mouse("Wheel", "", "Paste")).unwrap_err(),
+            "unknown mouse button \"Wheel\""
+        );
+
+        // A user binding replaces the default on the same trigger
+        let (bindings, errors) = merge_mouse_bindings(
+            default_mouse_bindings(),
+            &[mouse("Right", "", "ContextMenu")],
+        );
+        assert!(errors.is_empty());
+        let triggered: Vec<Action> = bindings
+            .iter()
+            .filter(|b| {
+                b.is_triggered_by(
+                    BindingMode::empty(),
+                    ModifiersState::empty(),
+                    &MouseButton::Right,
+                )
+            })
+            .map(|b| b.action.clone())
+            .collect();
+        assert_eq!(triggered, vec![Action::Mouse(MouseAction::ContextMenu)]);
+    }
+}
@@ -1,29 +1,529 @@
+use crate::crosswords::grid::Dimensions;
 use crate::crosswords::pos::CursorState;
 use crate::event::sync::FairMutex;
-use crate::event::EventListener;
+use crate::event::{EventListener, Msg};
 use crate::performer::Machine;
+use crate::screen::panes::{Direction, PaneBorder, PaneRect, PaneTree, SplitDirection};
 use crate::screen::Crosswords;
 use crate::screen::Messenger;
-use std::borrow::Cow;
+use crate::selection::SelectionRules;
+#[cfg(not(target_os = "windows"))]
+use crate::server;
+use crate::session::{ContextSnapshot, TabSnapshot, WindowSnapshot};
+use crate::tmux;
+use config::{Config, ExitBehavior, NewTabPosition};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::sync::Arc;
-use teletypewriter::create_pty;
+use teletypewriter::{PtyBuilder, SerialSettings};
 
-const DEFAULT_CONTEXT_CAPACITY: usize = 6;
+/// Program running in the foreground of a context, its shell when nothing
+/// else runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ForegroundProcess {
+    pub pid: i32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
+}
+
+#[cfg(not(target_os = "windows"))]
+#[derive(Debug, Clone, Copy)]
+struct Process {
+    pid: i32,
+    /// Main side of the PTY.
+    main_fd: i32,
+}
 
 pub struct Context<T: EventListener> {
+    /// Identifier attached to every event sent by this context.
+    pub route_id: usize,
     pub terminal: Arc<FairMutex<Crosswords<T>>>,
     pub messenger: Messenger,
+    /// Program started in a pseudoterminal, none for other backends.
+    #[cfg(not(target_os = "windows"))]
+    process: Option<Process>,
+    /// Title set by the program through OSC 0 or 2.
+    title: Option<String>,
+    /// Process group in the foreground when the title was set.
+    title_pgrp: Option<i32>,
+    /// Last program seen in the foreground, see
+    /// [`Context::update_foreground_process`].
+    foreground: Option<ForegroundProcess>,
+    /// Name of the program started in this context.
+    program: String,
+    /// What was started in this context, used to restart it.
+    launch: Launch,
+    /// Whether the program exited and the context was kept open.
+    exited: bool,
 }
 
-pub struct ContextManager<T: EventListener> {
+impl<T: EventListener> Context<T> {
+    /// Title set by the program, its name when none was set. A program
+    /// started from the shell shows its name, until it sets a title.
+    #[inline]
+    pub fn title(&self) -> &str {
+        match (&self.foreground, &self.title) {
+            (Some(foreground), Some(_)) if self.title_pgrp != Some(foreground.pid) => {
+                &foreground.name
+            }
+            (_, Some(title)) => title,
+            (Some(foreground), None) => &foreground.name,
+            (None, None) => &self.program,
+        }
+    }
+
+    fn set_title(&mut self, title: Option<String>) {
+        #[cfg(not(target_os = "windows"))]
+        {
+            self.title_pgrp = self.process.and_then(|process| {
+                teletypewriter::foreground_process_group(process.main_fd)
+            });
+        }
+        self.title = title;
+    }
+
+    /// Program running in the foreground, the shell itself when nothing else
+    /// runs, as of the last [`Context::update_foreground_process`].
+    #[inline]
+    pub fn foreground_process(&self) -> Option<&ForegroundProcess> {
+        self.foreground.as_ref()
+    }
+
+    /// Look again for the program in the foreground, returns whether it
+    /// changed.
+    pub fn update_foreground_process(&mut self) -> bool {
+        #[cfg(not(target_os = "windows"))]
+        let foreground = self
+            .process
+            .and_then(|process| teletypewriter::foreground_process_group(process.main_fd))
+            .map(|pgrp| ForegroundProcess {
+                pid: pgrp,
+                name: teletypewriter::command_per_pid(pgrp),
+                working_directory: teletypewriter::working_directory_per_pid(pgrp)
+                    .map(|path| path.to_string_lossy().to_string()),
+            });
+        #[cfg(target_os = "windows")]
+        let foreground = None;
+
+        if foreground == self.foreground {
+            return false;
+        }
+        self.foreground = foreground;
+        true
+    }
+
+    /// What was started in this context.
+    #[inline]
+    pub fn launch(&self) -> &Launch {
+        &self.launch
+    }
+
+    /// Whether the program exited and the context is kept open.
+    #[inline]
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Current working directory of the shell running in this context.
+    pub fn working_directory(&self) -> Option<String> {
+        #[cfg(not(target_os = "windows"))]
+        {
+            self.process.and_then(|process| {
+                teletypewriter::working_directory_per_pid(process.pid)
+                    .map(|path| path.to_string_lossy().to_string())
+            })
+        }
+
+        #[cfg(target_os = "windows")]
+        None
+    }
+}
+
+/// A tab, its area is split between one or more contexts.
+pub struct Tab<T: EventListener> {
     contexts: Vec<Context<T>>,
+    panes: PaneTree,
+    /// Position in `contexts` of the focused context.
+    current: usize,
+    /// Whether the focused context takes the whole tab.
+    zoomed: bool,
+    /// Title given by the user, it takes precedence over the contexts' ones.
+    custom_title: Option<String>,
+}
+
+impl<T: EventListener> Tab<T> {
+    fn new(context: Context<T>) -> Tab<T> {
+        Tab {
+            panes: PaneTree::new(context.route_id),
+            contexts: vec![context],
+            current: 0,
+            zoomed: false,
+            custom_title: None,
+        }
+    }
+
+    /// Tab with the splits of `snapshot`, `create` makes the context of
+    /// each pane.
+    fn from_snapshot<F>(
+        snapshot: &TabSnapshot,
+        mut create: F,
+    ) -> Result<Tab<T>, Box<dyn Error>>
+    where
+        F: FnMut(&ContextSnapshot) -> Result<Context<T>, Box<dyn Error>>,
+    {
+        let mut contexts = Vec::new();
+        let panes = PaneTree::from_snapshot(&snapshot.panes, &mut |pane| {
+            let context = create(pane)?;
+            let route_id = context.route_id;
+            contexts.push(context);
+            Ok::<usize, Box<dyn Error>>(route_id)
+        })?;
+        let current = snapshot.current.min(contexts.len() - 1);
+        Ok(Tab {
+            contexts,
+            panes,
+            current,
+            zoomed: false,
+            custom_title: None,
+        })
+    }
+
+    /// Splits of the tab along with the snapshot of every context.
+    fn snapshot(&self) -> TabSnapshot {
+        let focused = self.current().route_id;
+        let mut position = 0;
+        let mut current = 0;
+        let panes = self.panes.snapshot(&mut |route_id| {
+            if route_id == focused {
+                current = position;
+            }
+            position += 1;
+
+            let context = &self.contexts[self.position(route_id).unwrap_or(self.current)];
+            let terminal = context.terminal.lock();
+            ContextSnapshot::new(&terminal, context.working_directory())
+        });
+        TabSnapshot { panes, current }
+    }
+
+    /// Title shown in the tab bar, the focused context one when the user
+    /// did not give one.
+    #[inline]
+    pub fn title(&self) -> &str {
+        self.custom_title
+            .as_deref()
+            .unwrap_or_else(|| self.current().title())
+    }
+
+    #[inline]
+    pub fn current(&self) -> &Context<T> {
+        &self.contexts[self.current]
+    }
+
+    #[inline]
+    pub fn current_mut(&mut self) -> &mut Context<T> {
+        &mut self.contexts[self.current]
+    }
+
+    #[inline]
+    pub fn contexts(&self) -> &[Context<T>] {
+        &self.contexts
+    }
+
+    #[inline]
+    pub fn contexts_mut(&mut self) -> &mut [Context<T>] {
+        &mut self.contexts
+    }
+
+    #[inline]
+    fn position(&self, route_id: usize) -> Option<usize> {
+        self.contexts
+            .iter()
+            .position(|context| context.route_id == route_id)
+    }
+
+    /// Contexts drawn in `area` and where, only the focused one while
+    /// zoomed.
+    pub fn layout(&self, area: PaneRect) -> Vec<(&Context<T>, PaneRect)> {
+        if self.zoomed {
+            return vec![(self.current(), area)];
+        }
+
+        self.panes
+            .panes(area)
+            .into_iter()
+            .filter_map(|(route_id, rect)| {
+                self.position(route_id)
+                    .map(|index| (&self.contexts[index], rect))
+            })
+            .collect()
+    }
+
+    /// Where the focused context is drawn in `area`.
+    #[inline]
+    pub fn current_rect(&self, area: PaneRect) -> PaneRect {
+        let route_id = self.current().route_id;
+        self.layout(area)
+            .into_iter()
+            .find(|(context, _)| context.route_id == route_id)
+            .map_or(area, |(_, rect)| rect)
+    }
+
+    /// Borders drawn between the contexts in `area`.
+    #[inline]
+    pub fn borders(&self, area: PaneRect) -> Vec<PaneBorder> {
+        if self.zoomed {
+            return vec![];
+        }
+
+        self.panes.borders(area)
+    }
+
+    /// Focus the context identified by `route_id`.
+    pub fn focus(&mut self, route_id: usize) -> bool {
+        match self.position(route_id) {
+            Some(index) if index != self.current => {
+                self.current = index;
+                self.zoomed = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Focus the context next to the focused one in `direction`.
+    pub fn focus_direction(&mut self, direction: Direction, area: PaneRect) -> bool {
+        match self
+            .panes
+            .neighbor(self.current().route_id, direction, area)
+        {
+            Some(route_id) => self.focus(route_id),
+            None => false,
+        }
+    }
+
+    /// Context drawn at `column` and `line` of `area`.
+    pub fn context_at(
+        &self,
+        column: usize,
+        line: usize,
+        area: PaneRect,
+    ) -> Option<usize> {
+        if self.zoomed {
+            return area.contains(column, line).then(|| self.current().route_id);
+        }
+
+        self.panes.pane_at(column, line, area)
+    }
+
+    #[inline]
+    pub fn border_at(
+        &self,
+        column: usize,
+        line: usize,
+        area: PaneRect,
+    ) -> Option<PaneBorder> {
+        if self.zoomed {
+            return None;
+        }
+
+        self.panes.border_at(column, line, area)
+    }
+
+    #[inline]
+    pub fn drag_border(
+        &mut self,
+        border: &PaneBorder,
+        column: usize,
+        line: usize,
+        area: PaneRect,
+    ) -> bool {
+        self.panes.drag(border, column, line, area)
+    }
+
+    /// Grow or shrink the focused context by moving its closest border in
+    /// `direction`.
+    pub fn resize_current(
+        &mut self,
+        direction: Direction,
+        cells: usize,
+        area: PaneRect,
+    ) -> bool {
+        if self.zoomed {
+            return false;
+        }
+
+        let route_id = self.current().route_id;
+        self.panes.resize(route_id, direction, cells, area)
+    }
+
+    /// Give every context the same size.
+    #[inline]
+    pub fn equalize(&mut self) {
+        self.panes.equalize();
+    }
+
+    /// Make the focused context take the whole tab, or give the others
+    /// their place back.
+    #[inline]
+    pub fn toggle_zoom(&mut self) {
+        self.zoomed = !self.zoomed && self.panes.len() > 1;
+    }
+
+    /// Place `context` next to the focused one and focus it.
+    fn split(&mut self, context: Context<T>, direction: SplitDirection) {
+        self.panes
+            .split(self.current().route_id, context.route_id, direction);
+        self.contexts.insert(self.current + 1, context);
+        self.current += 1;
+        self.zoomed = false;
+    }
+
+    /// Remove the context identified by `route_id`, the last one is never
+    /// removed.
+    fn remove(&mut self, route_id: usize) -> bool {
+        let index = match self.position(route_id) {
+            Some(index) if self.contexts.len() > 1 => index,
+            _ => return false,
+        };
+
+        self.panes.remove(route_id);
+        self.contexts.remove(index);
+        if self.current >= index && self.current > 0 {
+            self.current -= 1;
+        }
+        self.zoomed = false;
+        true
+    }
+}
+
+/// What drives a context.
+///
+/// A pseudoterminal is resized with the context and ends when its program
+/// exits. A socket can be told the size in-band with the DEC private mode
+/// 2048, the others have none, and they end when the other side closes, see
+/// [`teletypewriter::Stream`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// [`Launch::command`], or else the user shell, in a pseudoterminal.
+    #[default]
+    Pty,
+    /// Unix socket at this path.
+    Socket(String),
+    /// Serial device, e.g. `/dev/ttyUSB0`.
+    Serial {
+        device: String,
+        settings: SerialSettings,
+    },
+    /// File of recorded output, e.g. by `script`.
+    Replay(String),
+    /// Session of the `rio server` listening at `socket`, a new one unless
+    /// given, see [`crate::server`].
+    Server {
+        socket: String,
+        session: Option<usize>,
+    },
+    /// Pane of a tmux running in control mode in another context, see
+    /// [`crate::tmux`].
+    Tmux(tmux::Pane),
+}
+
+/// What a new context runs, the user shell unless a command is given.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Launch {
+    pub backend: Backend,
+    /// Program followed by its args, executed without a shell around it.
+    pub command: Vec<String>,
+    pub working_directory: Option<String>,
+    /// Whether the context is kept open once its program exited, the
+    /// configured behavior when none is given.
+    pub exit_behavior: Option<ExitBehavior>,
+    /// Variables set in the environment of the program only.
+    pub envs: Vec<(String, String)>,
+}
+
+impl Launch {
+    /// The user shell, started at `working_directory`.
+    #[inline]
+    pub fn shell(working_directory: Option<String>) -> Self {
+        Launch {
+            working_directory,
+            ..Launch::default()
+        }
+    }
+
+    /// What a copy of a context launched with this runs at
+    /// `working_directory`: it connects again to the same socket or server,
+    /// or replays the same recording. A serial device can only be opened
+    /// once and a tmux pane belongs to tmux, a shell is started instead.
+    pub fn duplicate(&self, working_directory: Option<String>) -> Self {
+        let backend = match &self.backend {
+            Backend::Socket(_) | Backend::Replay(_) => self.backend.clone(),
+            Backend::Server { socket, .. } => Backend::Server {
+                socket: socket.to_owned(),
+                session: None,
+            },
+            Backend::Pty | Backend::Serial { .. } | Backend::Tmux(_) => Backend::Pty,
+        };
+        Launch {
+            backend,
+            ..Launch::shell(working_directory)
+        }
+    }
+
+    /// What every launch inherits from `config`.
+    pub fn defaults(config: &Config) -> Self {
+        Launch {
+            envs: crate::utils::spawn::terminal_environment(config),
+            exit_behavior: Some(config.exit_behavior),
+            ..Launch::default()
+        }
+    }
+
+    /// Same launch with the variables of `defaults` added before its own
+    /// ones, and its exit behavior and backend when it has none.
+    fn with_defaults(&self, defaults: &Launch) -> Launch {
+        Launch {
+            backend: match self.backend {
+                Backend::Pty => defaults.backend.clone(),
+                _ => self.backend.clone(),
+            },
+            envs: defaults
+                .envs
+                .iter()
+                .chain(self.envs.iter())
+                .cloned()
+                .collect(),
+            exit_behavior: self.exit_behavior.or(defaults.exit_behavior),
+            ..self.clone()
+        }
+    }
+}
+
+pub struct ContextManager<T: EventListener> {
+    tabs: Vec<Tab<T>>,
     current_index: usize,
+    /// Route of the context that was current before the current one.
+    last_route_id: Option<usize>,
+    /// Maximum amount of contexts, 0 means no limit.
     capacity: usize,
+    new_tab_position: NewTabPosition,
     event_proxy: T,
+    next_route_id: usize,
+    selection_rules: Arc<SelectionRules>,
+    /// What every program started inherits, see [`Launch::defaults`].
+    defaults: Launch,
 }
 
-fn default_shell() -> String {
+/// Shell set in `envs` (e.g. through the `env-vars` config), or else in
+/// Rio's environment.
+fn default_shell(envs: &[(String, String)]) -> String {
+    if let Some((_, shell)) = envs.iter().rev().find(|(key, _)| key == "SHELL") {
+        return shell.to_owned();
+    }
+
     #[cfg(not(target_os = "windows"))]
     let shell = std::env::var("SHELL").unwrap_or_else(|_| String::from("bash"));
     #[cfg(target_os = "windows")]
@@ -32,42 +532,222 @@ fn default_shell() -> String {
     shell
 }
 
+/// Last component of `path`, names what a context runs.
+fn file_name(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_owned())
+}
+
+/// Start the command of `launch`, or else `shell`, in a pseudoterminal.
+fn spawn_pty(
+    shell: &str,
+    launch: &Launch,
+    session: Option<&ContextSnapshot>,
+    dimensions: (u32, u32),
+    columns: usize,
+    rows: usize,
+) -> std::io::Result<teletypewriter::Pty> {
+    let working_directory = launch
+        .working_directory
+        .as_deref()
+        .or_else(|| session.and_then(|session| session.working_directory.as_deref()));
+    let mut builder = match launch.command.split_first() {
+        Some((program, args)) => {
+            let mut builder = PtyBuilder::new(program.as_str());
+            builder.args(args.iter().cloned());
+            builder
+        }
+        None => {
+            let mut builder = PtyBuilder::new(shell);
+            builder.login(cfg!(target_os = "macos"));
+            builder
+        }
+    };
+    builder
+        .envs(launch.envs.iter().map(|(key, value)| (key, value)))
+        .size(columns as u16, rows as u16)
+        .pixel_size(dimensions.0 as u16, dimensions.1 as u16);
+    if let Some(dir) = working_directory {
+        builder.current_dir(dir);
+    }
+    builder.spawn()
+}
+
+/// Drive `terminal` with what `connection` reads, returns the channel to
+/// write to it.
+fn start_machine<T, P>(
+    terminal: &Arc<FairMutex<Crosswords<T>>>,
+    connection: P,
+    event_proxy: T,
+    exit_behavior: ExitBehavior,
+    spawn: bool,
+) -> Result<corcovado::channel::Sender<Msg>, Box<dyn Error>>
+where
+    T: EventListener + Send + 'static,
+    P: teletypewriter::EventedPty + Send + 'static,
+{
+    let machine =
+        Machine::new(Arc::clone(terminal), connection, event_proxy, exit_behavior)?;
+    let channel = machine.channel();
+    // The only case we don't spawn is for tests
+    if spawn {
+        machine.spawn();
+    }
+    Ok(channel)
+}
+
 impl<T: EventListener + Clone + std::marker::Send + 'static> ContextManager<T> {
+    #[allow(clippy::too_many_arguments)]
     pub fn create_context(
         dimensions: (u32, u32),
         columns: usize,
         rows: usize,
         cursor_state: CursorState,
         event_proxy: T,
+        route_id: usize,
         spawn: bool,
+        session: Option<&ContextSnapshot>,
+        launch: &Launch,
     ) -> Result<Context<T>, Box<dyn Error>> {
-        let shell = default_shell();
-
+        let event_proxy = event_proxy.route(route_id);
         let event_proxy_clone = event_proxy.clone();
         let mut terminal = Crosswords::new(columns, rows, event_proxy);
         terminal.cursor_shape = cursor_state.content;
+        if let Some(session) = session {
+            session.restore(&mut terminal);
+        }
         let terminal: Arc<FairMutex<Crosswords<T>>> = Arc::new(FairMutex::new(terminal));
 
-        let pty = create_pty(&Cow::Borrowed(&shell), columns as u16, rows as u16);
+        let exit_behavior = launch.exit_behavior.unwrap_or_default();
+        #[cfg(not(target_os = "windows"))]
+        let mut process = None;
+        let (channel, program) = match &launch.backend {
+            Backend::Pty => {
+                let shell = default_shell(&launch.envs);
+                let pty = spawn_pty(&shell, launch, session, dimensions, columns, rows)?;
+                #[cfg(not(target_os = "windows"))]
+                {
+                    process = Some(Process {
+                        pid: pty.child.pid(),
+                        main_fd: *pty.child,
+                    });
+                }
+                let executable = launch.command.first().unwrap_or(&shell);
+                let channel = start_machine(
+                    &terminal,
+                    pty,
+                    event_proxy_clone,
+                    exit_behavior,
+                    spawn,
+                )?;
+                (channel, file_name(executable))
+            }
+            #[cfg(not(target_os = "windows"))]
+            Backend::Socket(path) => (
+                start_machine(
+                    &terminal,
+                    teletypewriter::Stream::connect(path)?,
+                    event_proxy_clone,
+                    exit_behavior,
+                    spawn,
+                )?,
+                file_name(path),
+            ),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Serial { device, settings } => (
+                start_machine(
+                    &terminal,
+                    teletypewriter::Stream::serial(device, *settings)?,
+                    event_proxy_clone,
+                    exit_behavior,
+                    spawn,
+                )?,
+                file_name(device),
+            ),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Replay(path) => (
+                start_machine(
+                    &terminal,
+                    teletypewriter::Stream::replay(path)?,
+                    event_proxy_clone,
+                    exit_behavior,
+                    spawn,
+                )?,
+                file_name(path),
+            ),
+            #[cfg(not(target_os = "windows"))]
+            Backend::Server {
+                socket,
+                session: attached,
+            } => {
+                let size = server::Size {
+                    columns,
+                    rows,
+                    width: dimensions.0 as u16,
+                    height: dimensions.1 as u16,
+                };
+                let request = match attached {
+                    Some(session) => server::Request::Attach {
+                        session: *session,
+                        size,
+                    },
+                    None => server::Request::Create {
+                        command: launch.command.clone(),
+                        working_directory: launch.working_directory.clone(),
+                        envs: launch.envs.clone(),
+                        size,
+                    },
+                };
+                let (client, snapshot) =
+                    server::Client::open(std::path::Path::new(socket), &request)?;
+                server::apply_snapshot(&mut terminal.lock(), &snapshot);
+                let channel =
+                    server::mirror(client, Arc::clone(&terminal), event_proxy_clone)?;
+                (channel, snapshot.title)
+            }
+            Backend::Tmux(pane) => (
+                pane.attach(Arc::clone(&terminal), event_proxy_clone)?,
+                String::from("tmux"),
+            ),
+            #[cfg(target_os = "windows")]
+            _ => return Err(Box::from(
+                "sockets, serial devices, replays and servers are only available on Unix",
+            )),
+        };
 
-        let machine = Machine::new(Arc::clone(&terminal), pty, event_proxy_clone)?;
-        let channel = machine.channel();
-        // The only case we don't spawn is for tests
-        if spawn {
-            machine.spawn();
+        #[allow(unused_mut)]
+        let mut messenger = Messenger::new(channel);
+        #[cfg(not(target_os = "windows"))]
+        if let Some(process) = process {
+            messenger.set_main_fd(process.main_fd);
         }
-        let messenger = Messenger::new(channel);
 
         let width = dimensions.0 as u16;
         let height = dimensions.1 as u16;
         let _ = messenger.send_resize(width, height, columns as u16, rows as u16);
 
-        Ok(Context {
+        let mut context = Context {
+            route_id,
             messenger,
             terminal,
-        })
+            #[cfg(not(target_os = "windows"))]
+            process,
+            title: None,
+            title_pgrp: None,
+            foreground: None,
+            program,
+            launch: launch.clone(),
+            exited: false,
+        };
+        if let Some(session) = session {
+            context.set_title(session.title.clone());
+        }
+        Ok(context)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
         width: u32,
         height: u32,
@@ -75,31 +755,110 @@ impl<T: EventListener + Clone + std::marker::Send + 'static> ContextManager<T> {
         rows: usize,
         cursor_state: CursorState,
         event_proxy: T,
-        mut command: Vec<String>,
+        launch: &Launch,
+        defaults: Launch,
     ) -> Result<Self, Box<dyn Error>> {
-        let mut initial_context = ContextManager::create_context(
+        let initial_context = ContextManager::create_context(
             (width, height),
             columns,
             rows,
             cursor_state,
             event_proxy.clone(),
+            1,
             true,
+            None,
+            &launch.with_defaults(&defaults),
         )?;
 
-        if !command.is_empty() {
-            command.push(String::from("\n;"));
-            let command = command.join(" ");
-            initial_context.messenger.send_bytes(command.into());
+        Ok(ContextManager {
+            current_index: 0,
+            last_route_id: None,
+            tabs: vec![Tab::new(initial_context)],
+            capacity: 0,
+            new_tab_position: NewTabPosition::default(),
+            event_proxy,
+            next_route_id: 2,
+            selection_rules: Arc::new(SelectionRules::default()),
+            defaults,
+        })
+    }
+
+    pub fn start_from_session(
+        dimensions: (u32, u32),
+        columns: usize,
+        rows: usize,
+        cursor_state: CursorState,
+        event_proxy: T,
+        session: &WindowSnapshot,
+        defaults: Launch,
+    ) -> Result<Self, Box<dyn Error>> {
+        let launch = Launch::default().with_defaults(&defaults);
+        let mut next_route_id = 1;
+        let mut tabs = Vec::new();
+        for snapshot in session.tabs.iter() {
+            tabs.push(Tab::from_snapshot(snapshot, |pane| {
+                next_route_id += 1;
+                ContextManager::create_context(
+                    dimensions,
+                    columns,
+                    rows,
+                    cursor_state.clone(),
+                    event_proxy.clone(),
+                    next_route_id - 1,
+                    true,
+                    Some(pane),
+                    &launch,
+                )
+            })?);
+        }
+
+        if tabs.is_empty() {
+            tabs.push(Tab::new(ContextManager::create_context(
+                dimensions,
+                columns,
+                rows,
+                cursor_state,
+                event_proxy.clone(),
+                next_route_id,
+                true,
+                None,
+                &launch,
+            )?));
+            next_route_id += 1;
         }
 
         Ok(ContextManager {
-            current_index: 0,
-            contexts: vec![initial_context],
-            capacity: DEFAULT_CONTEXT_CAPACITY,
+            current_index: session.current.min(tabs.len() - 1),
+            last_route_id: None,
+            next_route_id,
+            tabs,
+            capacity: 0,
+            new_tab_position: NewTabPosition::default(),
             event_proxy,
+            selection_rules: Arc::new(SelectionRules::default()),
+            defaults,
         })
     }
 
+    /// Snapshot of every tab and its splits, used to persist the session.
+    /// Tabs showing tmux windows are left to tmux.
+    pub fn session_snapshot(&self) -> WindowSnapshot {
+        let is_tmux =
+            |tab: &&Tab<T>| matches!(tab.current().launch.backend, Backend::Tmux(_));
+        let current = self.tabs[..self.current_index]
+            .iter()
+            .filter(|tab| !is_tmux(tab))
+            .count();
+        let tabs = self
+            .tabs
+            .iter()
+            .filter(|tab| !is_tmux(tab))
+            .map(Tab::snapshot)
+            .collect();
+
+        WindowSnapshot::new(current, tabs)
+    }
+
     #[cfg(test)]
     pub fn start_with_capacity(
         capacity: usize,
@@ -111,24 +870,103 @@ impl<T: EventListener + Clone + std::marker::Send + 'static> ContextManager<T> {
             1,
             CursorState::default(),
             event_proxy.clone(),
+            1,
             false,
+            None,
+            &Launch::default(),
         )?;
         Ok(ContextManager {
             current_index: 0,
-            contexts: vec![initial_context],
+            last_route_id: None,
+            tabs: vec![Tab::new(initial_context)],
             capacity,
+            new_tab_position: NewTabPosition::default(),
             event_proxy,
+            next_route_id: 2,
+            selection_rules: Arc::new(SelectionRules::default()),
+            defaults: Launch::default(),
         })
     }
 
+    /// Apply semantic selection rules to every context, including the ones
+    /// created afterwards.
+    pub fn set_selection_rules(&mut self, selection_rules: SelectionRules) {
+        self.selection_rules = Arc::new(selection_rules);
+        for context in self.tabs.iter().flat_map(|tab| tab.contexts.iter()) {
+            let mut terminal = context.terminal.lock();
+            terminal.set_selection_rules(self.selection_rules.clone());
+        }
+    }
+
+    /// What every program started inherits.
+    #[inline]
+    pub fn defaults(&self) -> &Launch {
+        &self.defaults
+    }
+
+    /// What the programs started from now on inherit, the running ones
+    /// keep their environment.
+    #[inline]
+    pub fn set_defaults(&mut self, defaults: Launch) {
+        self.defaults = defaults;
+    }
+
+    /// Limit the amount of contexts to `capacity`, 0 means no limit.
+    /// Contexts already open are kept.
+    #[inline]
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+    }
+
+    #[inline]
+    pub fn set_new_tab_position(&mut self, new_tab_position: NewTabPosition) {
+        self.new_tab_position = new_tab_position;
+    }
+
+    #[inline]
+    fn has_capacity(&self) -> bool {
+        self.capacity == 0 || self.tabs.len() < self.capacity
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
-        self.contexts.len()
+        self.tabs.len()
     }
 
     #[inline]
-    pub fn contexts(&self) -> &Vec<Context<T>> {
-        &self.contexts
+    pub fn tabs(&self) -> &Vec<Tab<T>> {
+        &self.tabs
+    }
+
+    #[inline]
+    pub fn tabs_mut(&mut self) -> &mut Vec<Tab<T>> {
+        &mut self.tabs
+    }
+
+    /// Position of the tab holding the context identified by `route_id`,
+    /// if it is still open.
+    #[inline]
+    pub fn position(&self, route_id: usize) -> Option<usize> {
+        self.tabs
+            .iter()
+            .position(|tab| tab.position(route_id).is_some())
+    }
+
+    /// Context identified by `route_id`, in any tab.
+    #[inline]
+    pub fn context(&self, route_id: usize) -> Option<&Context<T>> {
+        self.tabs
+            .iter()
+            .flat_map(|tab| tab.contexts.iter())
+            .find(|context| context.route_id == route_id)
+    }
+
+    #[inline]
+    pub fn context_mut(&mut self, route_id: usize) -> Option<&mut Context<T>> {
+        self.tabs
+            .iter_mut()
+            .flat_map(|tab| tab.contexts.iter_mut())
+            .find(|context| context.route_id == route_id)
     }
 
     #[cfg(test)]
@@ -136,79 +974,278 @@ impl<T: EventListener + Clone + std::marker::Send + 'static> ContextManager<T> {
         self.capacity += inc_val;
     }
 
+    /// Make the context at `index` current, the previous one is kept for
+    /// `switch_to_last`.
+    #[inline]
+    fn select(&mut self, index: usize) {
+        if index != self.current_index {
+            self.last_route_id = Some(self.current().route_id);
+            self.current_index = index;
+        }
+    }
+
     #[inline]
     pub fn set_current(&mut self, context_id: usize) {
-        if context_id < self.contexts.len() {
-            self.current_index = context_id;
+        if context_id < self.tabs.len() {
+            self.select(context_id);
         }
     }
 
     #[inline]
     pub fn close_context(&mut self) {
-        if self.contexts.len() <= 1 {
+        self.close_context_at(self.current_index);
+    }
+
+    /// Close the context at `index`, the last context is never closed.
+    pub fn close_context_at(&mut self, index: usize) {
+        if self.tabs.len() <= 1 {
             self.current_index = 0;
             return;
         }
 
-        let index_to_remove = self.current_index;
-        if index_to_remove > 1 {
-            self.set_current(self.current_index - 1);
-        } else {
-            self.set_current(0);
+        if index >= self.tabs.len() {
+            return;
+        }
+
+        match index.cmp(&self.current_index) {
+            std::cmp::Ordering::Equal if index > 1 => self.current_index = index - 1,
+            std::cmp::Ordering::Equal => self.current_index = 0,
+            std::cmp::Ordering::Less => self.current_index -= 1,
+            std::cmp::Ordering::Greater => (),
         }
 
-        self.contexts.remove(index_to_remove);
+        self.tabs.remove(index);
     }
 
-    #[inline]
-    pub fn current_index(&self) -> usize {
-        self.current_index
+    /// Close the context identified by `route_id` when it shares its tab
+    /// with others, returns false when it is alone in its tab.
+    pub fn close_pane(&mut self, route_id: usize) -> bool {
+        match self.position(route_id) {
+            Some(index) => self.tabs[index].remove(route_id),
+            None => false,
+        }
+    }
+
+    /// Move the context at `from` to `to`, the current context stays
+    /// current.
+    pub fn move_context(&mut self, from: usize, to: usize) {
+        let len = self.tabs.len();
+        if from >= len || to >= len || from == to {
+            return;
+        }
+
+        let current_route_id = self.current().route_id;
+        let tab = self.tabs.remove(from);
+        self.tabs.insert(to, tab);
+        if let Some(index) = self.position(current_route_id) {
+            self.current_index = index;
+        }
     }
 
+    /// Move the current context one position to the left.
     #[inline]
-    pub fn current(&self) -> &Context<T> {
-        &self.contexts[self.current_index]
+    pub fn move_current_left(&mut self) {
+        if self.current_index > 0 {
+            self.move_context(self.current_index, self.current_index - 1);
+        }
     }
 
+    /// Move the current context one position to the right.
     #[inline]
-    pub fn current_mut(&mut self) -> &mut Context<T> {
-        &mut self.contexts[self.current_index]
+    pub fn move_current_right(&mut self) {
+        self.move_context(self.current_index, self.current_index + 1);
     }
 
+    /// Give the current tab a title of its own, `None` goes back to the
+    /// title set by the program.
     #[inline]
-    pub fn switch_to_next(&mut self) {
-        if self.contexts.len() - 1 == self.current_index {
-            self.current_index = 0;
-        } else {
-            self.current_index += 1;
+    pub fn rename_current(&mut self, title: Option<String>) {
+        self.current_tab_mut().custom_title = title;
+    }
+
+    /// Look again for the programs in the foreground of every context,
+    /// returns whether one changed.
+    pub fn update_foreground_processes(&mut self) -> bool {
+        let mut changed = false;
+        for context in self.tabs.iter_mut().flat_map(|tab| tab.contexts.iter_mut()) {
+            changed |= context.update_foreground_process();
         }
+        changed
     }
 
-    #[inline]
-    pub fn add_context(
+    /// Mark the context identified by `route_id` as kept open once its
+    /// program exited.
+    pub fn set_exited(&mut self, route_id: usize) {
+        if let Some(context) = self.context_mut(route_id) {
+            context.exited = true;
+        }
+    }
+
+    /// Start again what the exited context identified by `route_id` ran,
+    /// in its place and with the same route.
+    pub fn restart(
         &mut self,
-        redirect: bool,
+        route_id: usize,
         spawn: bool,
         dimensions: (u32, u32),
-        columns: usize,
-        rows: usize,
         cursor_state: CursorState,
-    ) {
-        let size = self.contexts.len();
-        if size < self.capacity {
-            let last_index = self.contexts.len();
-            match ContextManager::create_context(
-                dimensions,
-                columns,
-                rows,
-                cursor_state,
-                self.event_proxy.clone(),
-                spawn,
-            ) {
-                Ok(new_context) => {
-                    self.contexts.push(new_context);
-                    if redirect {
-                        self.current_index = last_index;
+    ) -> bool {
+        let (launch, columns, rows) = match self.context(route_id) {
+            Some(context) if context.has_exited() => {
+                let terminal = context.terminal.lock();
+                let grid = &terminal.grid;
+                (context.launch.clone(), grid.columns(), grid.screen_lines())
+            }
+            _ => return false,
+        };
+
+        match ContextManager::create_context(
+            dimensions,
+            columns,
+            rows,
+            cursor_state,
+            self.event_proxy.clone(),
+            route_id,
+            spawn,
+            None,
+            &launch,
+        ) {
+            Ok(new_context) => {
+                new_context
+                    .terminal
+                    .lock()
+                    .set_selection_rules(self.selection_rules.clone());
+                if let Some(context) = self.context_mut(route_id) {
+                    *context = new_context;
+                }
+                true
+            }
+            Err(..) => {
+                log::error!("not able to restart the context");
+                false
+            }
+        }
+    }
+
+    /// Set the title of the context identified by `route_id`, `None` goes
+    /// back to the program name.
+    pub fn set_title(&mut self, route_id: usize, title: Option<String>) {
+        if let Some(context) = self.context_mut(route_id) {
+            context.set_title(title);
+        }
+    }
+
+    #[inline]
+    pub fn current_index(&self) -> usize {
+        self.current_index
+    }
+
+    #[inline]
+    pub fn current_tab(&self) -> &Tab<T> {
+        &self.tabs[self.current_index]
+    }
+
+    #[inline]
+    pub fn current_tab_mut(&mut self) -> &mut Tab<T> {
+        &mut self.tabs[self.current_index]
+    }
+
+    /// Focused context of the current tab.
+    #[inline]
+    pub fn current(&self) -> &Context<T> {
+        self.current_tab().current()
+    }
+
+    #[inline]
+    pub fn current_mut(&mut self) -> &mut Context<T> {
+        self.current_tab_mut().current_mut()
+    }
+
+    #[inline]
+    pub fn switch_to_next(&mut self) {
+        if self.tabs.len() - 1 == self.current_index {
+            self.select(0);
+        } else {
+            self.select(self.current_index + 1);
+        }
+    }
+
+    #[inline]
+    pub fn switch_to_prev(&mut self) {
+        if self.current_index == 0 {
+            self.select(self.tabs.len() - 1);
+        } else {
+            self.select(self.current_index - 1);
+        }
+    }
+
+    /// Switch back to the context that was current before this one.
+    #[inline]
+    pub fn switch_to_last(&mut self) {
+        if let Some(index) = self.last_route_id.and_then(|id| self.position(id)) {
+            self.select(index);
+        }
+    }
+
+    #[inline]
+    pub fn add_context(
+        &mut self,
+        redirect: bool,
+        spawn: bool,
+        dimensions: (u32, u32),
+        columns: usize,
+        rows: usize,
+        cursor_state: CursorState,
+    ) {
+        self.add_context_with_launch(
+            redirect,
+            spawn,
+            dimensions,
+            columns,
+            rows,
+            cursor_state,
+            &Launch::default(),
+        );
+    }
+
+    /// Same as [`ContextManager::add_context`] but the new context runs
+    /// what `launch` describes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_context_with_launch(
+        &mut self,
+        redirect: bool,
+        spawn: bool,
+        dimensions: (u32, u32),
+        columns: usize,
+        rows: usize,
+        cursor_state: CursorState,
+        launch: &Launch,
+    ) {
+        if self.has_capacity() {
+            let index = match self.new_tab_position {
+                NewTabPosition::End => self.tabs.len(),
+                NewTabPosition::Next => self.current_index + 1,
+            };
+            match ContextManager::create_context(
+                dimensions,
+                columns,
+                rows,
+                cursor_state,
+                self.event_proxy.clone(),
+                self.next_route_id,
+                spawn,
+                None,
+                &launch.with_defaults(&self.defaults),
+            ) {
+                Ok(new_context) => {
+                    self.next_route_id += 1;
+                    new_context
+                        .terminal
+                        .lock()
+                        .set_selection_rules(self.selection_rules.clone());
+                    self.tabs.insert(index, Tab::new(new_context));
+                    if redirect {
+                        self.select(index);
                     }
                 }
                 Err(..) => {
@@ -217,6 +1254,79 @@ impl<T: EventListener + Clone + std::marker::Send + 'static> ContextManager<T> {
             }
         }
     }
+
+    /// Split the focused context of the current tab in `direction`, the
+    /// new context starts in the same working directory and gets the focus.
+    #[allow(clippy::too_many_arguments)]
+    pub fn split_current(
+        &mut self,
+        direction: SplitDirection,
+        spawn: bool,
+        dimensions: (u32, u32),
+        columns: usize,
+        rows: usize,
+        cursor_state: CursorState,
+    ) -> bool {
+        let route_id = self.current().route_id;
+        let launch = Launch::shell(self.current().working_directory());
+        self.split_context(
+            route_id,
+            direction,
+            spawn,
+            dimensions,
+            columns,
+            rows,
+            cursor_state,
+            &launch,
+        )
+    }
+
+    /// Split the context identified by `route_id` in `direction`, the new
+    /// context runs what `launch` describes and gets the focus.
+    #[allow(clippy::too_many_arguments)]
+    pub fn split_context(
+        &mut self,
+        route_id: usize,
+        direction: SplitDirection,
+        spawn: bool,
+        dimensions: (u32, u32),
+        columns: usize,
+        rows: usize,
+        cursor_state: CursorState,
+        launch: &Launch,
+    ) -> bool {
+        let index = match self.position(route_id) {
+            Some(index) => index,
+            None => return false,
+        };
+        match ContextManager::create_context(
+            dimensions,
+            columns,
+            rows,
+            cursor_state,
+            self.event_proxy.clone(),
+            self.next_route_id,
+            spawn,
+            None,
+            &launch.with_defaults(&self.defaults),
+        ) {
+            Ok(new_context) => {
+                self.next_route_id += 1;
+                new_context
+                    .terminal
+                    .lock()
+                    .set_selection_rules(self.selection_rules.clone());
+                let tab = &mut self.tabs[index];
+                tab.focus(route_id);
+                tab.split(new_context, direction);
+                true
+            }
+            Err(..) => {
+                log::error!("not able to split the context");
+                false
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -547,4 +1657,508 @@ pub mod test {
         context_manager.switch_to_next();
         assert_eq!(context_manager.current_index, 1);
     }
+
+    #[test]
+    fn test_switch_to_prev() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        let should_redirect = false;
+
+        for _ in 0..2 {
+            context_manager.add_context(
+                should_redirect,
+                false,
+                (100, 100),
+                1,
+                1,
+                CursorState::default(),
+            );
+        }
+        assert_eq!(context_manager.current_index, 0);
+
+        context_manager.switch_to_prev();
+        assert_eq!(context_manager.current_index, 2);
+        context_manager.switch_to_prev();
+        assert_eq!(context_manager.current_index, 1);
+    }
+
+    #[test]
+    fn test_close_context_at() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        let should_redirect = true;
+
+        for _ in 0..3 {
+            context_manager.add_context(
+                should_redirect,
+                false,
+                (100, 100),
+                1,
+                1,
+                CursorState::default(),
+            );
+        }
+        assert_eq!(context_manager.current_index, 3);
+
+        // Closing a context before the current one keeps the current one
+        context_manager.close_context_at(1);
+        assert_eq!(context_manager.len(), 3);
+        assert_eq!(context_manager.current().route_id, 4);
+
+        context_manager.close_context_at(8);
+        assert_eq!(context_manager.len(), 3);
+
+        context_manager.close_context_at(2);
+        assert_eq!(context_manager.current().route_id, 3);
+    }
+
+    #[test]
+    fn test_move_context() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        let should_redirect = false;
+
+        for _ in 0..2 {
+            context_manager.add_context(
+                should_redirect,
+                false,
+                (100, 100),
+                1,
+                1,
+                CursorState::default(),
+            );
+        }
+
+        context_manager.move_context(0, 2);
+        let route_ids: Vec<usize> = context_manager
+            .tabs()
+            .iter()
+            .map(|tab| tab.current().route_id)
+            .collect();
+        assert_eq!(route_ids, vec![2, 3, 1]);
+        assert_eq!(context_manager.current().route_id, 1);
+        assert_eq!(context_manager.current_index, 2);
+
+        context_manager.move_context(1, 0);
+        assert_eq!(context_manager.position(3), Some(0));
+        assert_eq!(context_manager.current_index, 2);
+    }
+
+    #[test]
+    fn test_set_title() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        let program = context_manager.current().title().to_string();
+        assert!(!program.is_empty());
+
+        context_manager.set_title(1, Some(String::from("vim")));
+        assert_eq!(context_manager.current().title(), "vim");
+
+        context_manager.set_title(1, None);
+        assert_eq!(context_manager.current().title(), program);
+    }
+
+    #[test]
+    fn test_unlimited_capacity() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(2, VoidListener {}).unwrap();
+        context_manager.set_capacity(0);
+
+        for _ in 0..10 {
+            context_manager.add_context(
+                false,
+                false,
+                (100, 100),
+                1,
+                1,
+                CursorState::default(),
+            );
+        }
+        assert_eq!(context_manager.len(), 11);
+
+        // Open contexts are kept when lowering the capacity
+        context_manager.set_capacity(4);
+        context_manager.add_context(
+            false,
+            false,
+            (100, 100),
+            1,
+            1,
+            CursorState::default(),
+        );
+        assert_eq!(context_manager.len(), 11);
+    }
+
+    #[test]
+    fn test_new_tab_position() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        for _ in 0..2 {
+            context_manager.add_context(
+                false,
+                false,
+                (100, 100),
+                1,
+                1,
+                CursorState::default(),
+            );
+        }
+
+        context_manager.set_new_tab_position(NewTabPosition::Next);
+        context_manager.add_context(
+            true,
+            false,
+            (100, 100),
+            1,
+            1,
+            CursorState::default(),
+        );
+        let route_ids: Vec<usize> = context_manager
+            .tabs()
+            .iter()
+            .map(|tab| tab.current().route_id)
+            .collect();
+        assert_eq!(route_ids, vec![1, 4, 2, 3]);
+        assert_eq!(context_manager.current_index, 1);
+    }
+
+    #[test]
+    fn test_switch_to_last() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        for _ in 0..3 {
+            context_manager.add_context(
+                false,
+                false,
+                (100, 100),
+                1,
+                1,
+                CursorState::default(),
+            );
+        }
+
+        // Nothing to go back to yet
+        context_manager.switch_to_last();
+        assert_eq!(context_manager.current_index, 0);
+
+        context_manager.set_current(3);
+        context_manager.switch_to_last();
+        assert_eq!(context_manager.current_index, 0);
+        context_manager.switch_to_last();
+        assert_eq!(context_manager.current_index, 3);
+
+        context_manager.switch_to_prev();
+        assert_eq!(context_manager.current_index, 2);
+        context_manager.switch_to_last();
+        assert_eq!(context_manager.current_index, 3);
+
+        // The last context is gone once closed
+        context_manager.set_current(1);
+        context_manager.set_current(2);
+        context_manager.set_current(1);
+        context_manager.close_context_at(2);
+        context_manager.switch_to_last();
+        assert_eq!(context_manager.current_index, 1);
+    }
+
+    #[test]
+    fn test_move_current() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        for _ in 0..2 {
+            context_manager.add_context(
+                false,
+                false,
+                (100, 100),
+                1,
+                1,
+                CursorState::default(),
+            );
+        }
+
+        context_manager.move_current_left();
+        assert_eq!(context_manager.current_index, 0);
+
+        context_manager.move_current_right();
+        context_manager.move_current_right();
+        assert_eq!(context_manager.current_index, 2);
+        assert_eq!(context_manager.current().route_id, 1);
+
+        context_manager.move_current_right();
+        assert_eq!(context_manager.current_index, 2);
+
+        context_manager.move_current_left();
+        assert_eq!(context_manager.current_index, 1);
+        assert_eq!(context_manager.position(1), Some(1));
+    }
+
+    #[test]
+    fn test_rename_current() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        context_manager.set_title(1, Some(String::from("vim")));
+
+        context_manager.rename_current(Some(String::from("editor")));
+        assert_eq!(context_manager.current_tab().title(), "editor");
+
+        // The program can't override a title given by the user
+        context_manager.set_title(1, Some(String::from("htop")));
+        assert_eq!(context_manager.current_tab().title(), "editor");
+
+        context_manager.rename_current(None);
+        assert_eq!(context_manager.current_tab().title(), "htop");
+    }
+
+    #[test]
+    fn test_route_ids() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        let should_redirect = true;
+
+        context_manager.add_context(
+            should_redirect,
+            false,
+            (100, 100),
+            1,
+            1,
+            CursorState::default(),
+        );
+        context_manager.add_context(
+            should_redirect,
+            false,
+            (100, 100),
+            1,
+            1,
+            CursorState::default(),
+        );
+        assert_eq!(context_manager.position(1), Some(0));
+        assert_eq!(context_manager.position(3), Some(2));
+
+        context_manager.set_current(1);
+        context_manager.close_context();
+        assert_eq!(context_manager.position(2), None);
+        assert_eq!(context_manager.position(3), Some(1));
+
+        // Route ids are never reused
+        context_manager.add_context(
+            should_redirect,
+            false,
+            (100, 100),
+            1,
+            1,
+            CursorState::default(),
+        );
+        assert_eq!(context_manager.current().route_id, 4);
+    }
+
+    #[test]
+    fn test_foreground_process_title() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        let context = context_manager.current_mut();
+        context.title = Some(String::from("~/rio"));
+        assert_eq!(context.title(), "~/rio");
+
+        // Set by the shell, before vim started.
+        context.foreground = Some(ForegroundProcess {
+            pid: 42,
+            name: String::from("vim"),
+            working_directory: None,
+        });
+        assert_eq!(context.title(), "vim");
+
+        // Set by vim.
+        context.title = Some(String::from("main.rs"));
+        context.title_pgrp = Some(42);
+        assert_eq!(context.title(), "main.rs");
+
+        context.title = None;
+        assert_eq!(context.title(), "vim");
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+
+        // Only exited contexts are restarted.
+        assert!(!context_manager.restart(1, false, (100, 100), CursorState::default()));
+        context_manager.set_exited(1);
+        assert!(context_manager.current().has_exited());
+
+        assert!(context_manager.restart(1, false, (100, 100), CursorState::default()));
+        assert_eq!(context_manager.len(), 1);
+        assert_eq!(context_manager.current().route_id, 1);
+        assert!(!context_manager.current().has_exited());
+        assert!(!context_manager.restart(7, false, (100, 100), CursorState::default()));
+    }
+
+    #[test]
+    fn test_split_current() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        let area = PaneRect::new(80, 24);
+
+        assert!(context_manager.split_current(
+            SplitDirection::Right,
+            false,
+            (100, 100),
+            1,
+            1,
+            CursorState::default(),
+        ));
+        assert_eq!(context_manager.len(), 1);
+        assert_eq!(context_manager.current().route_id, 2);
+        assert_eq!(context_manager.position(2), Some(0));
+        assert!(context_manager.context(2).is_some());
+
+        let tab = context_manager.current_tab();
+        let rects: Vec<(usize, PaneRect)> = tab
+            .layout(area)
+            .iter()
+            .map(|(context, rect)| (context.route_id, *rect))
+            .collect();
+        assert_eq!(
+            rects,
+            vec![
+                (
+                    1,
+                    PaneRect {
+                        column: 0,
+                        line: 0,
+                        columns: 40,
+                        lines: 24
+                    }
+                ),
+                (
+                    2,
+                    PaneRect {
+                        column: 41,
+                        line: 0,
+                        columns: 39,
+                        lines: 24
+                    }
+                ),
+            ]
+        );
+        assert_eq!(tab.current_rect(area), rects[1].1);
+        assert_eq!(tab.borders(area).len(), 1);
+        assert_eq!(tab.context_at(10, 3, area), Some(1));
+
+        // Splitting a context shows the tab title of the focused one
+        context_manager.set_title(2, Some(String::from("htop")));
+        assert_eq!(context_manager.current_tab().title(), "htop");
+
+        let tab = context_manager.current_tab_mut();
+        assert!(tab.focus_direction(Direction::Left, area));
+        assert_eq!(tab.current().route_id, 1);
+        assert!(!tab.focus_direction(Direction::Left, area));
+        assert!(tab.resize_current(Direction::Right, 4, area));
+        assert_eq!(tab.current_rect(area).columns, 44);
+        tab.equalize();
+        assert_eq!(tab.current_rect(area).columns, 40);
+    }
+
+    #[test]
+    fn test_zoom_and_close_pane() {
+        let mut context_manager =
+            ContextManager::start_with_capacity(5, VoidListener {}).unwrap();
+        let area = PaneRect::new(80, 24);
+        for direction in [SplitDirection::Right, SplitDirection::Down] {
+            context_manager.split_current(
+                direction,
+                false,
+                (100, 100),
+                1,
+                1,
+                CursorState::default(),
+            );
+        }
+
+        let tab = context_manager.current_tab_mut();
+        assert_eq!(tab.contexts().len(), 3);
+        tab.toggle_zoom();
+        assert!(tab.zoomed);
+        assert_eq!(tab.layout(area).len(), 1);
+        assert_eq!(tab.current_rect(area), area);
+        assert!(tab.borders(area).is_empty());
+        assert!(!tab.resize_current(Direction::Up, 1, area));
+
+        // Focusing another context gives the others their place back
+        assert!(tab.focus(1));
+        assert!(!tab.zoomed);
+        assert_eq!(tab.layout(area).len(), 3);
+
+        assert!(context_manager.close_pane(1));
+        assert_eq!(context_manager.current().route_id, 2);
+        assert!(context_manager.close_pane(3));
+        assert_eq!(context_manager.current_tab().contexts().len(), 1);
+        // The last context of a tab is closed with the tab
+        assert!(!context_manager.close_pane(2));
+        assert!(!context_manager.close_pane(7));
+
+        // A single context can't be zoomed
+        let tab = context_manager.current_tab_mut();
+        tab.toggle_zoom();
+        assert!(!tab.zoomed);
+    }
+
+    #[test]
+    fn test_launch_environment() {
+        let launch = Launch {
+            envs: vec![(String::from("RIO_WINDOW_ID"), String::from("7"))],
+            ..Launch::shell(Some(String::from("/tmp")))
+        };
+        let launch = launch.with_defaults(&Launch {
+            envs: vec![
+                (String::from("TERM"), String::from("rio")),
+                (String::from("SHELL"), String::from("/bin/fish")),
+            ],
+            exit_behavior: Some(ExitBehavior::Hold),
+            ..Launch::default()
+        });
+
+        assert_eq!(launch.working_directory.as_deref(), Some("/tmp"));
+        assert_eq!(launch.exit_behavior, Some(ExitBehavior::Hold));
+        assert_eq!(launch.envs.len(), 3);
+        assert_eq!(launch.envs[2].0, "RIO_WINDOW_ID");
+        assert_eq!(default_shell(&launch.envs), "/bin/fish");
+    }
+
+    #[test]
+    fn test_duplicate_launch() {
+        let serial = Launch {
+            backend: Backend::Serial {
+                device: String::from("/dev/ttyUSB0"),
+                settings: SerialSettings::default(),
+            },
+            ..Launch::default()
+        };
+        assert_eq!(serial.duplicate(None).backend, Backend::Pty);
+
+        let socket = Launch {
+            backend: Backend::Socket(String::from("/tmp/board.sock")),
+            ..Launch::default()
+        };
+        assert_eq!(socket.duplicate(None).backend, socket.backend);
+
+        let server = Launch {
+            backend: Backend::Server {
+                socket: String::from("/tmp/rio-server.sock"),
+                session: Some(2),
+            },
+            command: vec![String::from("htop")],
+            ..Launch::default()
+        };
+        let launch = server.duplicate(Some(String::from("/tmp")));
+        assert_eq!(
+            launch.backend,
+            Backend::Server {
+                socket: String::from("/tmp/rio-server.sock"),
+                session: None,
+            }
+        );
+        assert!(launch.command.is_empty());
+        assert_eq!(launch.working_directory.as_deref(), Some("/tmp"));
+    }
 }
@@ -0,0 +1,77 @@
+use crate::screen::bindings::Action;
+
+/// Entries of the context menu and the action each one runs.
+const ENTRIES: [(&str, Action); 6] = [
+    ("Copy", Action::Copy),
+    ("Paste", Action::Paste),
+    ("Paste selection", Action::PasteSelection),
+    ("Clipboard history", Action::ShowClipboardHistory),
+    ("New tab", Action::TabCreateNew),
+    ("Close tab", Action::TabCloseCurrent),
+];
+
+/// Context menu drawn over the bottom of the terminal, driven by the
+/// keyboard or by clicking one of its lines.
+#[derive(Debug, Default)]
+pub struct ContextMenu {
+    selected: usize,
+}
+
+impl ContextMenu {
+    #[inline]
+    pub fn len(&self) -> usize {
+        ENTRIES.len()
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < ENTRIES.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Action of the highlighted entry.
+    pub fn selected(&self) -> Action {
+        ENTRIES[self.selected].1.clone()
+    }
+
+    /// Action of the entry drawn at `index`.
+    pub fn action_at(&self, index: usize) -> Option<Action> {
+        ENTRIES.get(index).map(|(_, action)| action.clone())
+    }
+
+    /// Lines to draw, from top to bottom, and the highlighted line index.
+    pub fn lines(&self) -> (Vec<String>, Option<usize>) {
+        let lines = ENTRIES
+            .iter()
+            .map(|(label, _)| format!(" {label}"))
+            .collect();
+        (lines, Some(self.selected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_menu() {
+        let mut menu = ContextMenu::default();
+        assert_eq!(menu.selected(), Action::Copy);
+
+        menu.select_previous();
+        assert_eq!(menu.selected(), Action::Copy);
+
+        for _ in 0..10 {
+            menu.select_next();
+        }
+        assert_eq!(menu.selected(), Action::TabCloseCurrent);
+        assert_eq!(menu.lines().1, Some(menu.len() - 1));
+
+        assert_eq!(menu.action_at(1), Some(Action::Paste));
+        assert_eq!(menu.action_at(menu.len()), None);
+    }
+}
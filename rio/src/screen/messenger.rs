@@ -1,20 +1,94 @@
 use crate::event::Msg;
+use crate::screen::recorder::{self, MacroRecorder};
 use std::borrow::Cow;
 use teletypewriter::WinsizeBuilder;
 
 pub struct Messenger {
     channel: corcovado::channel::Sender<Msg>,
+    recorder: Option<MacroRecorder>,
+    /// Main side of the PTY, tells whether the terminal echoes input.
+    #[cfg(not(target_os = "windows"))]
+    main_fd: Option<i32>,
+}
+
+/// Stops the terminal program once its tab is gone.
+impl Drop for Messenger {
+    fn drop(&mut self) {
+        let _ = self.channel.send(Msg::Shutdown);
+    }
 }
 
 impl Messenger {
     pub fn new(channel: corcovado::channel::Sender<Msg>) -> Messenger {
-        Messenger { channel }
+        Messenger {
+            channel,
+            recorder: None,
+            #[cfg(not(target_os = "windows"))]
+            main_fd: None,
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    pub fn set_main_fd(&mut self, main_fd: i32) {
+        self.main_fd = Some(main_fd);
     }
 
     pub fn send_bytes(&mut self, string: Vec<u8>) {
+        if self.recorder.is_some() {
+            let password = self.reads_password();
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(&string, password);
+            }
+        }
         self.send_write(string);
     }
 
+    /// ConPTY does not expose the terminal modes, so input is always
+    /// recorded on Windows.
+    fn reads_password(&self) -> bool {
+        #[cfg(not(target_os = "windows"))]
+        return self.main_fd.map_or(false, teletypewriter::reads_password);
+
+        #[cfg(target_os = "windows")]
+        false
+    }
+
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub fn start_recording(&mut self, preserve_timing: bool) {
+        self.recorder = Some(MacroRecorder::new(preserve_timing));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<config::Macro> {
+        self.recorder.take().and_then(MacroRecorder::finish)
+    }
+
+    /// Write `recording` into the PTY, waiting between steps on another
+    /// thread. Replayed input is not recorded.
+    pub fn replay(&self, recording: &config::Macro) {
+        let writes = recorder::writes(recording);
+        if writes.iter().all(|(delay, _)| delay.is_zero()) {
+            for (_, bytes) in writes {
+                self.send_write(bytes);
+            }
+            return;
+        }
+
+        let channel = self.channel.clone();
+        std::thread::spawn(move || {
+            for (delay, bytes) in writes {
+                std::thread::sleep(delay);
+                if channel.send(Msg::Input(bytes.into())).is_err() {
+                    // The tab is gone.
+                    break;
+                }
+            }
+        });
+    }
+
     fn send_write<B: Into<Cow<'static, [u8]>>>(&self, data: B) {
         let _ = self.channel.send(Msg::Input(data.into()));
     }
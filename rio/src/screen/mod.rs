@@ -1,12 +1,28 @@
 mod bindings;
 mod constants;
 mod context;
+mod menu;
 mod messenger;
-mod mouse;
-mod state;
+pub mod mouse;
+pub mod panes;
+mod recorder;
+pub mod state;
 pub mod window;
 
-use crate::clipboard::{Clipboard, ClipboardType};
+pub use context::{Backend, Context, ContextManager, ForegroundProcess, Launch};
+
+/// Something closed only once the user agrees, when programs run there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseRequest {
+    /// The tab at this index.
+    Tab(usize),
+    Window,
+    /// Every window.
+    Quit,
+}
+
+use crate::clipboard::{self, Clipboard, ClipboardRequest, ClipboardType};
+use crate::clipboard_history::{ClipboardHistory, ClipboardPicker};
 use crate::crosswords::grid::Dimensions;
 use crate::crosswords::pos::{Column, Line};
 use crate::crosswords::{
@@ -14,24 +30,36 @@ use crate::crosswords::{
     pos::{Pos, Side},
     Crosswords, Mode, MIN_COLUMNS, MIN_LINES,
 };
-use crate::event::{ClickState, EventProxy};
+use crate::event::{ClickState, EventListener, EventProxy, RioEvent, RioEventType};
+use crate::export::{self, ExportFormat, ExportScope};
 use crate::ime::Ime;
 use crate::screen::{
-    bindings::{Action as Act, BindingMode, FontSizeAction, Key},
-    context::ContextManager,
+    bindings::{
+        key_table::KeyTables, Action as Act, BindingMode, FontSizeAction, Key,
+        MouseAction,
+    },
+    menu::ContextMenu,
     mouse::Mouse,
+    panes::{Direction, PaneBorder, PaneRect, SplitDirection},
 };
-use crate::selection::{Selection, SelectionType};
+use crate::selection::{Selection, SelectionRules, SelectionType};
+use crate::session::WindowSnapshot;
+use crate::tmux;
+use crate::utils;
 use colors::term::List;
+use config::{ClipboardPermission, CommandTarget};
 use messenger::Messenger;
 use state::State;
 use std::cmp::max;
 use std::cmp::min;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::os::raw::c_void;
 use std::rc::Rc;
+use sugarloaf::components::tabs::{TabBarHit, TabBarPosition};
 use sugarloaf::{layout::SugarloafLayout, Sugarloaf};
-use winit::event::{ElementState, ModifiersState};
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode};
 
 /// Minimum number of pixels at the bottom/top where selection scrolling is performed.
 const MIN_SELECTION_SCROLLING_HEIGHT: f32 = 5.;
@@ -39,6 +67,9 @@ const MIN_SELECTION_SCROLLING_HEIGHT: f32 = 5.;
 /// Number of pixels for increasing the selection scrolling speed factor by one.
 const SELECTION_SCROLLING_STEP: f32 = 30.;
 
+/// Cells a pane border moves by on each resize action.
+const PANE_RESIZE_STEP: usize = 2;
+
 impl Dimensions for SugarloafLayout {
     #[inline]
     fn columns(&self) -> usize {
@@ -58,14 +89,40 @@ impl Dimensions for SugarloafLayout {
 
 pub struct Screen {
     bindings: bindings::KeyBindings,
+    mouse_bindings: bindings::MouseBindings,
+    key_tables: KeyTables,
+    macros: BTreeMap<String, config::Macro>,
     clipboard: Clipboard,
+    clipboard_config: config::Clipboard,
+    /// OSC 52 request waiting for the user answer, along with the route of
+    /// the tab that made it and the message shown to the user.
+    clipboard_prompt: Option<(usize, ClipboardRequest, String)>,
+    close_confirmation: config::CloseConfirmation,
+    /// Close waiting for the user answer, along with the message shown to
+    /// the user.
+    close_prompt: Option<(CloseRequest, String)>,
+    clipboard_history: ClipboardHistory,
+    clipboard_picker: Option<ClipboardPicker>,
+    context_menu: Option<ContextMenu>,
+    /// Title being typed for the current tab.
+    tab_rename: Option<String>,
+    /// Index of the tab being dragged over the tab bar.
+    tab_drag: Option<usize>,
+    /// Border between panes being dragged.
+    pane_drag: Option<PaneBorder>,
+    /// Exposed as `RIO_WINDOW_ID` to the programs run by bindings.
+    window_id: u64,
     pub modifiers: ModifiersState,
     ignore_chars: bool,
     pub mouse: Mouse,
     pub ime: Ime,
+    /// Where the IME candidate window goes, along with whether the window
+    /// was already told about it.
+    ime_position: (PhysicalPosition<f64>, bool),
     pub state: State,
     pub sugarloaf: Sugarloaf,
     context_manager: context::ContextManager<EventProxy>,
+    event_proxy: EventProxy,
 }
 
 impl Screen {
@@ -74,7 +131,8 @@ impl Screen {
         config: &Rc<config::Config>,
         event_proxy: EventProxy,
         _display: Option<*mut c_void>,
-        command: Vec<String>,
+        launch: &Launch,
+        session: Option<WindowSnapshot>,
     ) -> Result<Screen, Box<dyn Error>> {
         let size = winit_window.inner_size();
         let scale = winit_window.scale_factor();
@@ -87,7 +145,7 @@ impl Screen {
         let sugarloaf_layout = SugarloafLayout::new(
             size.width as f32,
             size.height as f32,
-            (config.padding_x, 0),
+            (config.padding_x, 0.0),
             scale as f32,
             config.font_size,
             (MIN_COLUMNS, MIN_LINES),
@@ -108,29 +166,89 @@ impl Screen {
         #[cfg(any(not(feature = "wayland"), target_os = "macos", windows))]
         let clipboard = Clipboard::new();
 
-        let bindings = bindings::default_key_bindings();
+        let mut clipboard_history = ClipboardHistory::from(&config.clipboard);
+        if config.clipboard.persist_history {
+            if let Err(err) =
+                clipboard_history.load(config::clipboard_history_file_path())
+            {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("unable to load clipboard history: {err}");
+                }
+            }
+        }
+
+        let bindings = bindings::key_bindings(&config.bindings);
+        let mouse_bindings = bindings::mouse_bindings(config);
+        let key_tables = KeyTables::new(&config.bindings);
         let ime = Ime::new();
-        let context_manager = context::ContextManager::start(
-            sugarloaf.layout.width_u32,
-            sugarloaf.layout.height_u32,
-            sugarloaf.layout.columns,
-            sugarloaf.layout.lines,
-            state.get_cursor_state(),
-            event_proxy,
-            command,
-        )?;
+        let mut defaults = Launch::defaults(config);
+        if let Backend::Server { socket, .. } = &launch.backend {
+            // New tabs and splits are sessions of the same server.
+            defaults.backend = Backend::Server {
+                socket: socket.to_owned(),
+                session: None,
+            };
+        }
+        let mut context_manager = match session {
+            Some(session) => context::ContextManager::start_from_session(
+                (sugarloaf.layout.width_u32, sugarloaf.layout.height_u32),
+                sugarloaf.layout.columns,
+                sugarloaf.layout.lines,
+                state.get_cursor_state(),
+                event_proxy.clone(),
+                &session,
+                defaults,
+            )?,
+            None => context::ContextManager::start(
+                sugarloaf.layout.width_u32,
+                sugarloaf.layout.height_u32,
+                sugarloaf.layout.columns,
+                sugarloaf.layout.lines,
+                state.get_cursor_state(),
+                event_proxy.clone(),
+                launch,
+                defaults,
+            )?,
+        };
 
-        Ok(Screen {
+        context_manager.set_selection_rules(SelectionRules::from(&config.selection));
+        context_manager.set_capacity(config.tabs.capacity);
+        context_manager.set_new_tab_position(config.tabs.new_tab_position);
+
+        let mut screen = Screen {
             modifiers: ModifiersState::default(),
             context_manager,
+            event_proxy,
             ime,
             sugarloaf,
             mouse: Mouse::default(),
             state,
             bindings,
+            mouse_bindings,
+            key_tables,
+            macros: config.macros.clone(),
             clipboard,
+            clipboard_config: config.clipboard.clone(),
+            clipboard_prompt: None,
+            close_confirmation: config.close_confirmation.clone(),
+            close_prompt: None,
+            clipboard_history,
+            clipboard_picker: None,
+            context_menu: None,
+            tab_rename: None,
+            tab_drag: None,
+            pane_drag: None,
+            window_id: u64::from(winit_window.id()),
+            ime_position: (PhysicalPosition::default(), false),
             ignore_chars: false,
-        })
+        };
+        // Restored tabs may be split and the tab bar takes a line.
+        screen
+            .sugarloaf
+            .layout
+            .set_reserved_lines(screen.state.tab_bar_lines(screen.context_manager.len()));
+        screen.resize_panes();
+        Ok(screen)
     }
 
     #[inline]
@@ -153,19 +271,82 @@ impl Screen {
         self.mouse.accumulated_scroll = mouse::AccumulatedScroll::default();
     }
 
+    /// Column under the mouse.
     #[inline]
-    pub fn mouse_position(&self, display_offset: usize) -> Pos {
-        let layout = &self.sugarloaf.layout;
-        let text_scale = layout.style.text_scale as usize;
+    fn mouse_column(&self) -> usize {
+        let (x, _) = self.sugarloaf.grid_origin();
+        let (width, _) = self.sugarloaf.cell_size();
+        ((self.mouse.x as f32 - x).max(0.) / width) as usize
+    }
+
+    /// Line of the window under the mouse, counting the tab bar.
+    #[inline]
+    fn mouse_screen_line(&self) -> usize {
+        let (_, y) = self.sugarloaf.grid_origin();
+        let (_, height) = self.sugarloaf.cell_size();
+        ((self.mouse.y as f32 - y).max(0.) / height) as usize
+    }
+
+    /// Lines drawn above the grid.
+    #[inline]
+    fn grid_first_line(&self) -> usize {
+        match self.state.tab_bar_position() {
+            TabBarPosition::Top => self.sugarloaf.layout.reserved_lines,
+            TabBarPosition::Bottom => 0,
+        }
+    }
+
+    /// Line of the window where the tab bar is drawn, when it is visible.
+    #[inline]
+    fn tab_bar_line(&self) -> Option<usize> {
+        if self.sugarloaf.layout.reserved_lines == 0 {
+            return None;
+        }
+
+        match self.state.tab_bar_position() {
+            TabBarPosition::Top => Some(0),
+            TabBarPosition::Bottom => Some(self.sugarloaf.layout.lines),
+        }
+    }
+
+    /// Lines and columns of the grid, shared by the panes of a tab.
+    #[inline]
+    fn grid_area(&self) -> PaneRect {
+        PaneRect::new(self.sugarloaf.layout.columns, self.sugarloaf.layout.lines)
+    }
+
+    /// Where the focused pane of the current tab is drawn in the grid.
+    #[inline]
+    fn current_pane_rect(&self) -> PaneRect {
+        self.context_manager
+            .current_tab()
+            .current_rect(self.grid_area())
+    }
 
-        let col_fac = text_scale / 2;
-        let col = self.mouse.x.saturating_sub(layout.padding.x as usize) / col_fac;
-        let col = std::cmp::min(Column(col), Column(layout.columns));
+    /// Cell of the grid under the mouse, if it is over the grid.
+    #[inline]
+    fn mouse_grid_cell(&self) -> Option<(usize, usize)> {
+        let line = self
+            .mouse_screen_line()
+            .checked_sub(self.grid_first_line())?;
+        if line >= self.sugarloaf.layout.lines {
+            return None;
+        }
+
+        Some((self.mouse_column(), line))
+    }
+
+    /// Position of the mouse in the focused pane.
+    #[inline]
+    pub fn mouse_position(&self, display_offset: usize) -> Pos {
+        let rect = self.current_pane_rect();
+        let col = self.mouse_column().saturating_sub(rect.column);
+        let col = std::cmp::min(Column(col), Column(rect.columns));
 
-        let line = self.mouse.y.saturating_sub(
-            layout.padding.y as usize * (self.sugarloaf.layout.scale_factor as usize),
-        ) / text_scale;
-        let line = std::cmp::min(line, layout.lines - 1);
+        let line = self
+            .mouse_screen_line()
+            .saturating_sub(self.grid_first_line() + rect.line);
+        let line = std::cmp::min(line, rect.lines.saturating_sub(1));
 
         let point = Pos::new(line, col);
         let row = Line(point.row as i32) - (display_offset);
@@ -181,6 +362,29 @@ impl Screen {
         self.sugarloaf.update_font(config.font.to_string());
         self.sugarloaf.layout.update();
         self.state = State::new(config);
+        self.bindings = bindings::key_bindings(&config.bindings);
+        self.mouse_bindings = bindings::mouse_bindings(config);
+        self.key_tables = KeyTables::new(&config.bindings);
+        self.macros = config.macros.clone();
+        self.clipboard_config = config.clipboard.clone();
+        self.close_confirmation = config.close_confirmation.clone();
+        let mut clipboard_history = ClipboardHistory::from(&config.clipboard);
+        for entry in self.clipboard_history.entries().iter().rev() {
+            clipboard_history.push(entry);
+        }
+        self.clipboard_history = clipboard_history;
+        self.context_manager
+            .set_selection_rules(SelectionRules::from(&config.selection));
+        self.context_manager.set_capacity(config.tabs.capacity);
+        self.context_manager.set_defaults(Launch {
+            backend: self.context_manager.defaults().backend.clone(),
+            ..Launch::defaults(config)
+        });
+        self.context_manager
+            .set_new_tab_position(config.tabs.new_tab_position);
+        self.sugarloaf
+            .layout
+            .set_reserved_lines(self.state.tab_bar_lines(self.context_manager.len()));
 
         let width = self.sugarloaf.layout.width_u32 as u16;
         let height = self.sugarloaf.layout.height_u32 as u16;
@@ -245,6 +449,8 @@ impl Screen {
         self
     }
 
+    /// Resize every context to its place in a grid of `columns` and
+    /// `lines`, `width` and `height` being the size of the grid in pixels.
     #[inline]
     pub fn resize_all_contexts(
         &mut self,
@@ -253,24 +459,194 @@ impl Screen {
         columns: usize,
         lines: usize,
     ) {
-        for context in self.ctx().contexts() {
-            let mut terminal = context.terminal.lock();
-            terminal.cursor_shape = self.state.get_cursor_state().content;
-
-            terminal.resize::<SugarloafLayout>(columns, lines);
-            drop(terminal);
-            let _ = context.messenger.send_resize(
-                width,
-                height,
-                columns as u16,
-                lines as u16,
-            );
+        let area = PaneRect::new(columns, lines);
+        let cursor_shape = self.state.get_cursor_state().content;
+        for tab in self.ctx().tabs() {
+            for (context, rect) in tab.layout(area) {
+                let pane_columns = max(rect.columns, MIN_COLUMNS);
+                let pane_lines = max(rect.lines, MIN_LINES);
+                let mut terminal = context.terminal.lock();
+                terminal.cursor_shape = cursor_shape;
+
+                terminal.resize::<SugarloafLayout>(pane_columns, pane_lines);
+                drop(terminal);
+                let _ = context.messenger.send_resize(
+                    (width as usize * rect.columns / max(columns, 1)) as u16,
+                    (height as usize * rect.lines / max(lines, 1)) as u16,
+                    pane_columns as u16,
+                    pane_lines as u16,
+                );
+            }
         }
     }
 
+    /// Fit the contexts to the grid again, after the panes of a tab
+    /// changed.
     #[inline]
-    pub fn clipboard_get(&mut self, clipboard_type: ClipboardType) -> String {
-        self.clipboard.get(clipboard_type)
+    fn resize_panes(&mut self) {
+        self.resize_all_contexts(
+            self.sugarloaf.layout.width_u32 as u16,
+            self.sugarloaf.layout.height_u32 as u16,
+            self.sugarloaf.layout.columns,
+            self.sugarloaf.layout.lines,
+        );
+    }
+
+    /// Handle a clipboard access made through OSC 52 by the tab identified
+    /// by `route_id`, following the configured permissions.
+    pub fn clipboard_request(&mut self, route_id: usize, request: ClipboardRequest) {
+        match request.permission(&self.clipboard_config) {
+            ClipboardPermission::Allow => self.run_clipboard_request(route_id, request),
+            ClipboardPermission::Deny => {
+                log::warn!("denied clipboard request to {}", request.describe());
+            }
+            ClipboardPermission::Ask => {
+                if self.clipboard_prompt.is_some() {
+                    log::warn!(
+                        "denied clipboard request to {}, another one is pending",
+                        request.describe()
+                    );
+                    return;
+                }
+
+                let tab = match self.context_manager.position(route_id) {
+                    Some(index) => index,
+                    None => return,
+                };
+                let terminal = match self.context_manager.context(route_id) {
+                    Some(context) => context.terminal.lock(),
+                    None => return,
+                };
+                let message = match terminal.title().filter(|title| !title.is_empty()) {
+                    Some(title) => format!(
+                        "Tab {} ({title}) wants to {}. Allow? [y/n]",
+                        tab + 1,
+                        request.describe()
+                    ),
+                    None => {
+                        format!(
+                            "Tab {} wants to {}. Allow? [y/n]",
+                            tab + 1,
+                            request.describe()
+                        )
+                    }
+                };
+                drop(terminal);
+
+                self.clipboard_prompt = Some((route_id, request, message));
+            }
+        }
+    }
+
+    /// Names of the programs running where `request` closes and that need
+    /// a confirmation, following `[close-confirmation]`.
+    pub fn busy_processes(&mut self, request: CloseRequest) -> Vec<String> {
+        self.context_manager.update_foreground_processes();
+        let tabs = self.context_manager.tabs();
+        let tabs = match request {
+            CloseRequest::Tab(index) => tabs.get(index..=index).unwrap_or_default(),
+            CloseRequest::Window | CloseRequest::Quit => tabs,
+        };
+
+        let mut processes: Vec<String> = tabs
+            .iter()
+            .flat_map(|tab| tab.contexts())
+            .filter_map(|context| context.foreground_process())
+            .filter(|process| self.close_confirmation.asks_for(&process.name))
+            .map(|process| process.name.to_owned())
+            .collect();
+        processes.sort();
+        processes.dedup();
+        processes
+    }
+
+    /// Ask the user before closing what `request` targets, `processes`
+    /// being the programs running there.
+    pub fn ask_close(&mut self, request: CloseRequest, processes: &[String]) {
+        let question = match request {
+            CloseRequest::Tab(index) => format!("Close tab {}?", index + 1),
+            CloseRequest::Window => String::from("Close this window?"),
+            CloseRequest::Quit => String::from("Quit Rio?"),
+        };
+        let running = match processes {
+            [process] => format!("{process} is still running"),
+            _ => format!("{} are still running", processes.join(", ")),
+        };
+        self.close_prompt = Some((request, format!("{question} {running}. [y/n]")));
+    }
+
+    /// Close what `request` targets, asking the user first when programs
+    /// run there.
+    fn close(&mut self, request: CloseRequest) {
+        let processes = self.busy_processes(request);
+        if processes.is_empty() {
+            self.run_close(request);
+        } else {
+            self.ask_close(request, &processes);
+        }
+    }
+
+    /// Answer the pending close prompt.
+    fn resolve_close_prompt(&mut self, close: bool) {
+        if let Some((request, _)) = self.close_prompt.take() {
+            if close {
+                self.run_close(request);
+            }
+        }
+    }
+
+    fn run_close(&mut self, request: CloseRequest) {
+        match request {
+            CloseRequest::Tab(index) => {
+                self.context_manager.close_context_at(index);
+                self.update_tab_bar();
+                self.render();
+            }
+            CloseRequest::Window => {
+                self.event_proxy
+                    .send_event(RioEventType::Rio(RioEvent::CloseWindow));
+            }
+            CloseRequest::Quit => {
+                self.event_proxy
+                    .send_event(RioEventType::Rio(RioEvent::Quit { confirmed: true }));
+            }
+        }
+    }
+
+    /// Answer the pending clipboard prompt.
+    fn resolve_clipboard_prompt(&mut self, allow: bool) {
+        if let Some((route_id, request, _)) = self.clipboard_prompt.take() {
+            if allow {
+                self.run_clipboard_request(route_id, request);
+            }
+        }
+    }
+
+    fn run_clipboard_request(&mut self, route_id: usize, request: ClipboardRequest) {
+        match request {
+            ClipboardRequest::Store(ty, text) => {
+                self.record_clipboard(&text);
+                self.clipboard.set(ty, text);
+            }
+            ClipboardRequest::Load(ty, format) => {
+                let text = format(self.clipboard.get(ty).as_str());
+                if let Some(context) = self.context_manager.context_mut(route_id) {
+                    context.messenger.send_bytes(text.into_bytes());
+                }
+            }
+        }
+    }
+
+    /// Remember `text` in the clipboard history.
+    fn record_clipboard(&mut self, text: &str) {
+        if self.clipboard_history.push(text) && self.clipboard_config.persist_history {
+            if let Err(err) = self
+                .clipboard_history
+                .save(config::clipboard_history_file_path())
+            {
+                log::error!("unable to save clipboard history: {err}");
+            }
+        }
     }
 
     pub fn input_character(&mut self, character: char) {
@@ -278,6 +654,20 @@ impl Screen {
             return;
         }
 
+        if let Some(picker) = &mut self.clipboard_picker {
+            if !character.is_control() {
+                picker.push_char(character, &self.clipboard_history);
+            }
+            return;
+        }
+
+        if let Some(title) = &mut self.tab_rename {
+            if !character.is_control() {
+                title.push(character);
+            }
+            return;
+        }
+
         self.clear_selection();
         self.scroll_bottom_when_cursor_not_visible();
 
@@ -348,6 +738,55 @@ impl Screen {
             return;
         }
 
+        if self.close_prompt.is_some() {
+            // Every key goes to the prompt until it gets an answer.
+            self.ignore_chars = true;
+            match virtual_keycode {
+                Some(VirtualKeyCode::Y) => self.resolve_close_prompt(true),
+                Some(VirtualKeyCode::N | VirtualKeyCode::Escape) => {
+                    self.resolve_close_prompt(false)
+                }
+                _ => (),
+            }
+            return;
+        }
+
+        if self.clipboard_prompt.is_some() {
+            // Every key goes to the prompt until it gets an answer.
+            self.ignore_chars = true;
+            match virtual_keycode {
+                Some(VirtualKeyCode::Y) => self.resolve_clipboard_prompt(true),
+                Some(VirtualKeyCode::N | VirtualKeyCode::Escape) => {
+                    self.resolve_clipboard_prompt(false)
+                }
+                _ => (),
+            }
+            return;
+        }
+
+        if self.clipboard_picker.is_some() {
+            self.input_clipboard_picker(virtual_keycode);
+            return;
+        }
+
+        if self.tab_rename.is_some() {
+            self.input_tab_rename(virtual_keycode);
+            return;
+        }
+
+        if self.context_menu.is_some() {
+            self.input_context_menu(virtual_keycode);
+            return;
+        }
+
+        if self.ctx().current().has_exited() && self.input_exited(virtual_keycode) {
+            return;
+        }
+
+        if self.input_key_table(virtual_keycode, scancode) {
+            return;
+        }
+
         let mode = BindingMode::new(&self.get_mode());
         let mut ignore_chars = None;
 
@@ -363,92 +802,926 @@ impl Screen {
             if binding.is_triggered_by(mode.clone(), self.modifiers, &key) {
                 *ignore_chars.get_or_insert(true) &= binding.action != Act::ReceiveChar;
 
-                match &binding.action {
-                    Act::Esc(s) => {
-                        self.context_manager.current_mut().messenger.send_bytes(
-                            s.replace("\r\n", "\r").replace('\n', "\r").into_bytes(),
-                        );
-                    }
-                    Act::Paste => {
-                        let content = self.clipboard.get(ClipboardType::Clipboard);
-                        self.paste(&content, true);
-                    }
-                    Act::PasteSelection => {
-                        let content = self.clipboard.get(ClipboardType::Selection);
-                        self.paste(&content, true);
-                    }
-                    Act::Copy => {
-                        self.copy_selection(ClipboardType::Clipboard);
-                    }
-                    Act::ViMotion(motion) => {
-                        let mut terminal =
-                            self.context_manager.current_mut().terminal.lock();
-                        terminal.vi_motion(*motion);
-                        drop(terminal);
-                    }
-                    Act::TabCreateNew => {
-                        let redirect = true;
-                        let spawn = true;
-                        self.context_manager.add_context(
-                            redirect,
-                            spawn,
-                            (
-                                self.sugarloaf.layout.width_u32,
-                                self.sugarloaf.layout.height_u32,
-                            ),
-                            self.sugarloaf.layout.columns,
-                            self.sugarloaf.layout.lines,
-                            self.state.get_cursor_state(),
-                        );
-                        self.render();
-                    }
-                    Act::TabSwitchNext => {
-                        self.context_manager.switch_to_next();
-                        self.render();
-                    }
-                    Act::TabCloseCurrent => {
-                        self.context_manager.close_context();
-                        self.render();
-                    }
-                    Act::IncreaseFontSize => {
-                        self.change_font_size(FontSizeAction::Increase);
-                    }
-                    Act::DecreaseFontSize => {
-                        self.change_font_size(FontSizeAction::Decrease);
+                let action = binding.action.clone();
+                self.execute_action(&action);
+            }
+        }
+
+        self.ignore_chars = ignore_chars.unwrap_or(false);
+    }
+
+    /// Enter starts again the program of the focused context once it
+    /// exited, Escape closes the context. Other keys go through the
+    /// bindings.
+    fn input_exited(&mut self, virtual_keycode: Option<VirtualKeyCode>) -> bool {
+        let route_id = self.ctx().current().route_id;
+        match virtual_keycode {
+            Some(VirtualKeyCode::Return) => {
+                if self.context_manager.restart(
+                    route_id,
+                    true,
+                    (
+                        self.sugarloaf.layout.width_u32,
+                        self.sugarloaf.layout.height_u32,
+                    ),
+                    self.state.get_cursor_state(),
+                ) {
+                    self.resize_panes();
+                    self.render();
+                }
+            }
+            Some(VirtualKeyCode::Escape) => {
+                self.event_proxy
+                    .route(route_id)
+                    .send_event(RioEventType::Rio(RioEvent::Exit));
+            }
+            _ => return false,
+        }
+
+        self.ignore_chars = true;
+        true
+    }
+
+    fn execute_action(&mut self, action: &Act) {
+        match action {
+            Act::Esc(s) => {
+                self.context_manager
+                    .current_mut()
+                    .messenger
+                    .send_bytes(s.replace("\r\n", "\r").replace('\n', "\r").into_bytes());
+            }
+            Act::Paste => {
+                let content = self.clipboard.get(ClipboardType::Clipboard);
+                self.paste(&content, true);
+            }
+            Act::PasteSelection => {
+                let content = self.clipboard.get(ClipboardType::Selection);
+                self.paste(&content, true);
+            }
+            Act::Copy => {
+                self.copy_selection(ClipboardType::Clipboard);
+            }
+            Act::CopySelection => {
+                self.copy_selection(ClipboardType::Selection);
+            }
+            Act::ShowClipboardHistory => {
+                self.clipboard_picker =
+                    Some(ClipboardPicker::new(&self.clipboard_history));
+            }
+            Act::Export(scope, format) => {
+                self.export(*scope, *format);
+            }
+            Act::ViMotion(motion) => {
+                let mut terminal = self.context_manager.current_mut().terminal.lock();
+                terminal.vi_motion(*motion);
+                drop(terminal);
+            }
+            Act::WindowCreateNew => {
+                self.event_proxy
+                    .send_event(RioEventType::Rio(RioEvent::CreateWindow));
+            }
+            Act::TabCreateNew => {
+                let redirect = true;
+                let spawn = true;
+                self.context_manager.add_context(
+                    redirect,
+                    spawn,
+                    (
+                        self.sugarloaf.layout.width_u32,
+                        self.sugarloaf.layout.height_u32,
+                    ),
+                    self.sugarloaf.layout.columns,
+                    self.sugarloaf.layout.lines,
+                    self.state.get_cursor_state(),
+                );
+                self.update_tab_bar();
+                self.render();
+            }
+            Act::TabSwitchNext => {
+                self.context_manager.switch_to_next();
+                self.render();
+            }
+            Act::TabSwitchPrev => {
+                self.context_manager.switch_to_prev();
+                self.render();
+            }
+            Act::TabSwitchLast => {
+                self.context_manager.switch_to_last();
+                self.render();
+            }
+            Act::TabSwitchTo(index) => {
+                self.context_manager.set_current(*index);
+                self.render();
+            }
+            Act::TabMoveLeft => {
+                self.context_manager.move_current_left();
+                self.render();
+            }
+            Act::TabMoveRight => {
+                self.context_manager.move_current_right();
+                self.render();
+            }
+            Act::TabRename => {
+                let title = self.context_manager.current().title().to_string();
+                self.tab_rename = Some(title);
+            }
+            Act::TabDuplicate => {
+                let current = self.context_manager.current();
+                let launch = current.launch().duplicate(current.working_directory());
+                self.context_manager.add_context_with_launch(
+                    true,
+                    true,
+                    (
+                        self.sugarloaf.layout.width_u32,
+                        self.sugarloaf.layout.height_u32,
+                    ),
+                    self.sugarloaf.layout.columns,
+                    self.sugarloaf.layout.lines,
+                    self.state.get_cursor_state(),
+                    &launch,
+                );
+                self.update_tab_bar();
+                self.render();
+            }
+            Act::TabCloseCurrent => {
+                self.close(CloseRequest::Tab(self.context_manager.current_index()));
+            }
+            Act::Quit => {
+                self.event_proxy
+                    .send_event(RioEventType::Rio(RioEvent::Quit { confirmed: false }));
+            }
+            Act::PaneSplitRight => self.split_pane(SplitDirection::Right),
+            Act::PaneSplitDown => self.split_pane(SplitDirection::Down),
+            Act::PaneClose => self.close_pane(),
+            Act::PaneFocusLeft => self.focus_pane(Direction::Left),
+            Act::PaneFocusRight => self.focus_pane(Direction::Right),
+            Act::PaneFocusUp => self.focus_pane(Direction::Up),
+            Act::PaneFocusDown => self.focus_pane(Direction::Down),
+            Act::PaneResizeLeft => self.resize_pane(Direction::Left),
+            Act::PaneResizeRight => self.resize_pane(Direction::Right),
+            Act::PaneResizeUp => self.resize_pane(Direction::Up),
+            Act::PaneResizeDown => self.resize_pane(Direction::Down),
+            Act::PaneZoom => {
+                self.context_manager.current_tab_mut().toggle_zoom();
+                self.resize_panes();
+                self.render();
+            }
+            Act::PaneEqualize => {
+                self.context_manager.current_tab_mut().equalize();
+                self.resize_panes();
+                self.render();
+            }
+            Act::IncreaseFontSize => {
+                self.change_font_size(FontSizeAction::Increase);
+            }
+            Act::DecreaseFontSize => {
+                self.change_font_size(FontSizeAction::Decrease);
+            }
+            Act::ResetFontSize => {
+                self.change_font_size(FontSizeAction::Reset);
+            }
+            Act::Command(program) => self.run_command(program),
+            Act::ActivateKeyTable(name) => {
+                self.key_tables.activate(name);
+            }
+            Act::PopKeyTable => self.key_tables.pop(),
+            Act::StartMacroRecording => self.start_macro_recording(false),
+            Act::StartTimedMacroRecording => self.start_macro_recording(true),
+            Act::StopMacroRecording => self.stop_macro_recording(),
+            Act::ReplayMacro(name) => match self.macros.get(name) {
+                Some(recording) => {
+                    self.context_manager.current().messenger.replay(recording)
+                }
+                None => log::warn!("unknown macro {name:?}"),
+            },
+            Act::Mouse(mouse_action) => self.execute_mouse_action(*mouse_action),
+            Act::ReceiveChar | Act::None => (),
+            _ => (),
+        }
+    }
+
+    /// Keys pressed while a key table is active, or the leader key.
+    /// Returns false when the key goes through the regular bindings.
+    fn input_key_table(
+        &mut self,
+        virtual_keycode: Option<VirtualKeyCode>,
+        scancode: u32,
+    ) -> bool {
+        let name = match self.key_tables.current() {
+            Some(name) => name.to_string(),
+            None => {
+                if !self.key_tables.is_leader(virtual_keycode, self.modifiers) {
+                    return false;
+                }
+                self.ignore_chars = true;
+                self.key_tables.activate_leader();
+                return true;
+            }
+        };
+
+        // Modifiers alone don't complete a chord.
+        if let Some(
+            VirtualKeyCode::LShift
+            | VirtualKeyCode::RShift
+            | VirtualKeyCode::LControl
+            | VirtualKeyCode::RControl
+            | VirtualKeyCode::LAlt
+            | VirtualKeyCode::RAlt
+            | VirtualKeyCode::LWin
+            | VirtualKeyCode::RWin,
+        ) = virtual_keycode
+        {
+            return true;
+        }
+
+        // Pressing the leader twice sends it to the terminal.
+        if self.key_tables.is_one_shot()
+            && self.key_tables.is_leader(virtual_keycode, self.modifiers)
+        {
+            self.key_tables.pop();
+            self.ignore_chars = false;
+            return true;
+        }
+
+        let mode = BindingMode::new(&self.get_mode());
+        let actions: Vec<Act> = self
+            .key_tables
+            .bindings(&name)
+            .iter()
+            .filter(|binding| {
+                let key = match (binding.trigger, virtual_keycode) {
+                    (Key::Scancode(_), _) => Key::Scancode(scancode),
+                    (_, Some(key)) => Key::Keycode(key),
+                    _ => return false,
+                };
+                binding.is_triggered_by(mode.clone(), self.modifiers, &key)
+            })
+            .map(|binding| binding.action.clone())
+            .collect();
+
+        // The leader table only lasts one key, other tables until Escape.
+        let escape =
+            actions.is_empty() && virtual_keycode == Some(VirtualKeyCode::Escape);
+        if self.key_tables.is_one_shot() || escape {
+            self.key_tables.pop();
+        }
+
+        self.ignore_chars = true;
+        for action in actions {
+            self.execute_action(&action);
+        }
+        true
+    }
+
+    /// How long to wait for the key following the leader, if pending.
+    #[inline]
+    pub fn key_table_timeout(&self) -> Option<std::time::Duration> {
+        self.key_tables.timeout()
+    }
+
+    /// Give up on the key following the leader, returns true when one
+    /// was pending.
+    #[inline]
+    pub fn cancel_leader(&mut self) -> bool {
+        self.key_tables.cancel_leader()
+    }
+
+    fn start_macro_recording(&mut self, preserve_timing: bool) {
+        let recording = self
+            .context_manager
+            .tabs()
+            .iter()
+            .flat_map(|tab| tab.contexts())
+            .any(|context| context.messenger.is_recording());
+        if recording {
+            log::warn!("a macro is already being recorded");
+            return;
+        }
+
+        self.context_manager
+            .current_mut()
+            .messenger
+            .start_recording(preserve_timing);
+    }
+
+    /// Stop recording, the tab being recorded may not be the current one
+    /// anymore. The macro gets the first free `recording-<n>` name.
+    fn stop_macro_recording(&mut self) {
+        let recording = self
+            .context_manager
+            .tabs_mut()
+            .iter_mut()
+            .flat_map(|tab| tab.contexts_mut())
+            .find(|context| context.messenger.is_recording())
+            .and_then(|context| context.messenger.stop_recording());
+
+        if let Some(recording) = recording {
+            let name = recorder::next_macro_name(&self.macros);
+            match recorder::save(&name, &recording) {
+                Ok(()) => log::info!("macro saved as {name:?}"),
+                Err(err) => log::error!("unable to save macro {name:?}: {err}"),
+            }
+            self.macros.insert(name, recording);
+        }
+    }
+
+    /// Run a program bound with `command` from the current tab's directory,
+    /// with the window id and the selection in its environment.
+    fn run_command(&mut self, program: &config::Program) {
+        let working_directory = self.ctx().current().working_directory();
+        let mut envs = vec![(String::from("RIO_WINDOW_ID"), self.window_id.to_string())];
+        let terminal = self.ctx().current().terminal.lock();
+        if let Some(selection) = terminal.selection_to_string().filter(|s| !s.is_empty())
+        {
+            envs.push((String::from("RIO_SELECTION"), selection));
+        }
+        drop(terminal);
+
+        match program.target() {
+            CommandTarget::Background => {
+                if let Err(err) = utils::spawn::spawn_daemon(
+                    program.program(),
+                    program.args(),
+                    working_directory.as_deref(),
+                    self.context_manager
+                        .defaults()
+                        .envs
+                        .iter()
+                        .chain(envs.iter())
+                        .map(|(key, value)| (key, value)),
+                ) {
+                    log::error!("unable to run {}: {err}", program.program());
+                }
+            }
+            CommandTarget::Tab => {
+                let launch = Launch {
+                    command: std::iter::once(program.program().to_string())
+                        .chain(program.args().iter().cloned())
+                        .collect(),
+                    working_directory,
+                    envs,
+                    ..Launch::default()
+                };
+                self.open_tab(&launch);
+                self.render();
+            }
+        }
+    }
+
+    /// Open a tab running what `launch` describes, returns false when there
+    /// is no room for another tab.
+    pub fn open_tab(&mut self, launch: &Launch) -> bool {
+        let previous_len = self.context_manager.len();
+        self.context_manager.add_context_with_launch(
+            true,
+            true,
+            (
+                self.sugarloaf.layout.width_u32,
+                self.sugarloaf.layout.height_u32,
+            ),
+            self.sugarloaf.layout.columns,
+            self.sugarloaf.layout.lines,
+            self.state.get_cursor_state(),
+            launch,
+        );
+        self.update_tab_bar();
+        self.context_manager.len() > previous_len
+    }
+
+    /// Open a tab at `working_directory`, or else at the one of the current
+    /// tab, running `command` instead of the shell when given. Returns
+    /// false when there is no room for another tab.
+    pub fn create_tab(
+        &mut self,
+        working_directory: Option<&str>,
+        command: &[String],
+    ) -> bool {
+        let launch = Launch {
+            command: command.to_vec(),
+            working_directory: working_directory
+                .map(String::from)
+                .or_else(|| self.ctx().current().working_directory()),
+            ..Launch::default()
+        };
+        if !self.open_tab(&launch) {
+            return false;
+        }
+
+        self.render();
+        true
+    }
+
+    /// Write `text` to the tab at `index`, or to the current one, with new
+    /// lines sent as carriage returns. Returns false when there is no such
+    /// tab.
+    pub fn send_text(&mut self, index: Option<usize>, text: &str) -> bool {
+        let index = index.unwrap_or_else(|| self.context_manager.current_index());
+        match self.context_manager.tabs_mut().get_mut(index) {
+            Some(tab) => {
+                tab.current_mut().messenger.send_bytes(
+                    text.replace("\r\n", "\r").replace('\n', "\r").into_bytes(),
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Make the tab at `index` the current one, returns false when there is
+    /// no such tab.
+    pub fn focus_tab(&mut self, index: usize) -> bool {
+        if index >= self.context_manager.len() {
+            return false;
+        }
+
+        self.context_manager.set_current(index);
+        self.render();
+        true
+    }
+
+    /// Run the mouse bindings triggered by `button`, returns true when at
+    /// least one of them matched.
+    pub fn process_mouse_bindings(&mut self, button: MouseButton) -> bool {
+        let mode = BindingMode::new(&self.get_mode());
+        let mouse_mode = self.mouse_mode();
+        let mut mods = self.modifiers;
+
+        // Shift is used to bypass the mouse mode, so it should not prevent
+        // the bindings without it from matching.
+        if mouse_mode {
+            mods.remove(ModifiersState::SHIFT);
+        }
+
+        let mut triggered = false;
+        for i in 0..self.mouse_bindings.len() {
+            let binding = &self.mouse_bindings[i];
+            if binding.is_triggered_by(mode.clone(), mods, &button) {
+                triggered = true;
+                let action = binding.action.clone();
+                self.execute_action(&action);
+            }
+        }
+
+        triggered
+    }
+
+    fn execute_mouse_action(&mut self, action: MouseAction) {
+        let display_offset = self.display_offset();
+        let point = self.mouse_position(display_offset);
+        let side = self.mouse.square_side;
+
+        match action {
+            MouseAction::ExpandSelection => {
+                let mut terminal = self.context_manager.current().terminal.lock();
+                let has_selection = match &mut terminal.selection {
+                    Some(selection) => {
+                        selection.ty = match self.mouse.click_state {
+                            ClickState::DoubleClick => SelectionType::Semantic,
+                            ClickState::TripleClick => SelectionType::Lines,
+                            _ => selection.ty,
+                        };
+                        true
                     }
-                    Act::ResetFontSize => {
-                        self.change_font_size(FontSizeAction::Reset);
+                    None => false,
+                };
+                drop(terminal);
+
+                if has_selection {
+                    self.update_selection(point, side);
+                }
+            }
+            MouseAction::ExtendSelection => {
+                if self.selection_is_empty() {
+                    self.start_selection(SelectionType::Simple, point, side);
+                } else {
+                    self.update_selection(point, side);
+                }
+            }
+            MouseAction::BlockSelect => {
+                self.clear_selection();
+                self.start_selection(SelectionType::Block, point, side);
+            }
+            MouseAction::OpenLink => {
+                let terminal = self.ctx().current().terminal.lock();
+                let link = terminal.link_at(point);
+                drop(terminal);
+
+                if let Some(link) = link {
+                    utils::open::open(&link);
+                }
+            }
+            MouseAction::ContextMenu => {
+                self.context_menu = Some(ContextMenu::default());
+            }
+        }
+    }
+
+    /// Keys driving the context menu.
+    fn input_context_menu(&mut self, virtual_keycode: Option<VirtualKeyCode>) {
+        let menu = match &mut self.context_menu {
+            Some(menu) => menu,
+            None => return,
+        };
+
+        self.ignore_chars = true;
+        match virtual_keycode {
+            Some(VirtualKeyCode::Escape) => self.context_menu = None,
+            Some(VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter) => {
+                let action = menu.selected();
+                self.context_menu = None;
+                self.execute_action(&action);
+            }
+            Some(VirtualKeyCode::Up) => menu.select_previous(),
+            Some(VirtualKeyCode::Down) => menu.select_next(),
+            _ => (),
+        }
+    }
+
+    /// Handle a click while the context menu is open, running the entry
+    /// under the mouse. Returns false when there is no menu.
+    pub fn context_menu_click(&mut self) -> bool {
+        let menu = match self.context_menu.take() {
+            Some(menu) => menu,
+            None => return false,
+        };
+
+        // The menu is drawn over the bottom rows of the screen.
+        let row = self.mouse_position(0).row.0.max(0) as usize;
+        let first_row = self.sugarloaf.layout.lines.saturating_sub(menu.len());
+        if let Some(action) = row
+            .checked_sub(first_row)
+            .and_then(|index| menu.action_at(index))
+        {
+            self.execute_action(&action);
+        }
+
+        true
+    }
+
+    /// Keys driving the clipboard history picker, printable characters go
+    /// to its query through `input_character`.
+    fn input_clipboard_picker(&mut self, virtual_keycode: Option<VirtualKeyCode>) {
+        let picker = match &mut self.clipboard_picker {
+            Some(picker) => picker,
+            None => return,
+        };
+
+        self.ignore_chars = true;
+        match virtual_keycode {
+            Some(VirtualKeyCode::Escape) => self.clipboard_picker = None,
+            Some(VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter) => {
+                let text = picker.selected(&self.clipboard_history).cloned();
+                self.clipboard_picker = None;
+                if let Some(text) = text {
+                    self.record_clipboard(&text);
+                    self.paste(&text, true);
+                }
+            }
+            Some(VirtualKeyCode::Up) => picker.select_previous(),
+            Some(VirtualKeyCode::Down) => picker.select_next(),
+            Some(VirtualKeyCode::Back) => picker.pop_char(&self.clipboard_history),
+            _ => self.ignore_chars = false,
+        }
+    }
+
+    /// Keys driving the tab rename prompt, printable characters go to the
+    /// title through `input_character`. An empty title goes back to the
+    /// one set by the program.
+    fn input_tab_rename(&mut self, virtual_keycode: Option<VirtualKeyCode>) {
+        let title = match &mut self.tab_rename {
+            Some(title) => title,
+            None => return,
+        };
+
+        self.ignore_chars = true;
+        match virtual_keycode {
+            Some(VirtualKeyCode::Escape) => self.tab_rename = None,
+            Some(VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter) => {
+                let title = title.trim().to_string();
+                self.tab_rename = None;
+                self.context_manager
+                    .rename_current(Some(title).filter(|title| !title.is_empty()));
+            }
+            Some(VirtualKeyCode::Back) => {
+                title.pop();
+            }
+            _ => self.ignore_chars = false,
+        }
+    }
+
+    /// Close the context identified by `route_id` once its program
+    /// exited, along with its tab when it was alone in it. Returns false
+    /// when it was the last context left.
+    pub fn try_close_existent_context(&mut self, route_id: usize) -> bool {
+        if self.context_manager.close_pane(route_id) {
+            self.resize_panes();
+            return true;
+        }
+
+        match self.context_manager.position(route_id) {
+            Some(index) if self.context_manager.len() > 1 => {
+                self.context_manager.close_context_at(index);
+                self.update_tab_bar();
+                true
+            }
+            Some(_) => false,
+            None => true,
+        }
+    }
+
+    /// Follow a change of the tmux session shown through `gateway`: every
+    /// tmux window gets a tab and every pane a context.
+    pub fn tmux(&mut self, gateway: &tmux::Gateway, change: tmux::Change) {
+        match change {
+            tmux::Change::Layout { window, layout } => {
+                self.tmux_layout(gateway, window, &layout)
+            }
+            tmux::Change::Renamed { window, name } => {
+                let routes: Vec<usize> = self
+                    .tmux_tab(gateway, Some(window))
+                    .map(|index| {
+                        self.context_manager.tabs()[index]
+                            .contexts()
+                            .iter()
+                            .map(|context| context.route_id)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                for route_id in routes {
+                    self.context_manager.set_title(route_id, Some(name.clone()));
+                }
+            }
+            tmux::Change::Closed { window } => {
+                self.close_tmux_tabs(gateway, Some(window))
+            }
+            tmux::Change::Cleared => self.close_tmux_tabs(gateway, None),
+        }
+        self.resize_panes();
+    }
+
+    /// Tab showing panes of `gateway`, of `window` when given.
+    fn tmux_tab(&self, gateway: &tmux::Gateway, window: Option<usize>) -> Option<usize> {
+        self.context_manager.tabs().iter().position(|tab| {
+            tab.contexts()
+                .iter()
+                .any(|context| match &context.launch().backend {
+                    Backend::Tmux(pane) => {
+                        pane.gateway == *gateway
+                            && window.map_or(true, |window| pane.window == window)
                     }
-                    Act::ReceiveChar | Act::None => (),
-                    _ => (),
+                    _ => false,
+                })
+        })
+    }
+
+    /// Panes shown in the tab at `index`, along with their route.
+    fn tmux_panes(&self, index: usize) -> Vec<(usize, usize)> {
+        self.context_manager.tabs()[index]
+            .contexts()
+            .iter()
+            .filter_map(|context| match &context.launch().backend {
+                Backend::Tmux(pane) => Some((pane.id, context.route_id)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Show `window` as `layout` tells, in a new tab unless one shows it
+    /// already.
+    fn tmux_layout(
+        &mut self,
+        gateway: &tmux::Gateway,
+        window: usize,
+        layout: &tmux::Layout,
+    ) {
+        let launch = |id| Launch {
+            backend: Backend::Tmux(tmux::Pane {
+                gateway: gateway.clone(),
+                window,
+                id,
+            }),
+            ..Launch::default()
+        };
+        let panes = layout.panes();
+
+        let mut index = self.tmux_tab(gateway, Some(window));
+        // None of its panes is left, the tab is built again.
+        if let Some(tab) = index {
+            if !self
+                .tmux_panes(tab)
+                .iter()
+                .any(|(id, _)| panes.contains(id))
+            {
+                self.context_manager.close_context_at(tab);
+                self.update_tab_bar();
+                index = None;
+            }
+        }
+        let index = match index {
+            Some(index) => index,
+            None if self.open_tab(&launch(layout.first_pane())) => {
+                self.context_manager.current_index()
+            }
+            None => return,
+        };
+
+        for (id, route_id) in self.tmux_panes(index) {
+            if !panes.contains(&id) {
+                self.context_manager.close_pane(route_id);
+            }
+        }
+        for (anchor, id, horizontal) in layout.splits() {
+            let shown = self.tmux_panes(index);
+            let route_id = match shown.iter().find(|(pane, _)| *pane == anchor) {
+                Some((_, route_id)) if !shown.iter().any(|(pane, _)| *pane == id) => {
+                    *route_id
                 }
+                _ => continue,
+            };
+            let direction = if horizontal {
+                SplitDirection::Right
+            } else {
+                SplitDirection::Down
+            };
+            self.context_manager.split_context(
+                route_id,
+                direction,
+                true,
+                (
+                    self.sugarloaf.layout.width_u32,
+                    self.sugarloaf.layout.height_u32,
+                ),
+                self.sugarloaf.layout.columns,
+                self.sugarloaf.layout.lines,
+                self.state.get_cursor_state(),
+                &launch(id),
+            );
+        }
+        self.context_manager.tabs_mut()[index].equalize();
+    }
+
+    /// Close the tabs showing panes of `gateway`, of `window` when given.
+    fn close_tmux_tabs(&mut self, gateway: &tmux::Gateway, window: Option<usize>) {
+        while let Some(index) = self.tmux_tab(gateway, window) {
+            if self.context_manager.len() <= 1 {
+                break;
             }
+            self.context_manager.close_context_at(index);
         }
+        self.update_tab_bar();
+    }
 
-        self.ignore_chars = ignore_chars.unwrap_or(false);
+    /// Split the focused pane, the new pane gets the focus.
+    fn split_pane(&mut self, direction: SplitDirection) {
+        let rect = self.current_pane_rect();
+        self.context_manager.split_current(
+            direction,
+            true,
+            (
+                self.sugarloaf.layout.width_u32,
+                self.sugarloaf.layout.height_u32,
+            ),
+            max(rect.columns, MIN_COLUMNS),
+            max(rect.lines, MIN_LINES),
+            self.state.get_cursor_state(),
+        );
+        self.resize_panes();
+        self.render();
     }
 
-    pub fn try_close_existent_tab(&mut self) -> bool {
-        if self.context_manager.len() > 1 {
+    /// Close the focused pane, or the tab when it is the only pane.
+    fn close_pane(&mut self) {
+        let route_id = self.context_manager.current().route_id;
+        if !self.context_manager.close_pane(route_id) {
             self.context_manager.close_context();
-            return true;
+            self.update_tab_bar();
         }
+        self.resize_panes();
+        self.render();
+    }
 
-        false
+    fn focus_pane(&mut self, direction: Direction) {
+        let area = self.grid_area();
+        if self
+            .context_manager
+            .current_tab_mut()
+            .focus_direction(direction, area)
+        {
+            self.render();
+        }
+    }
+
+    fn resize_pane(&mut self, direction: Direction) {
+        let area = self.grid_area();
+        if self.context_manager.current_tab_mut().resize_current(
+            direction,
+            PANE_RESIZE_STEP,
+            area,
+        ) {
+            self.resize_panes();
+            self.render();
+        }
     }
 
     pub fn copy_selection(&mut self, ty: ClipboardType) {
+        if let Some((text, rich)) = self.selection_content() {
+            self.set_clipboard(ty, text, rich);
+        }
+    }
+
+    /// Copy the selection into the clipboards picked by `copy-on-select`.
+    pub fn copy_on_select(&mut self) {
+        let targets =
+            clipboard::copy_on_select_targets(self.clipboard_config.copy_on_select);
+        if targets.is_empty() {
+            return;
+        }
+
+        if let Some((text, rich)) = self.selection_content() {
+            for ty in targets {
+                self.set_clipboard(*ty, text.clone(), rich.clone());
+            }
+        }
+    }
+
+    /// Selection as plain text, plus its HTML and RTF flavors when
+    /// `copy-format` is rich.
+    fn selection_content(&self) -> Option<(String, Option<(String, String)>)> {
         let terminal = self.ctx().current().terminal.lock();
-        let text = match terminal.selection_to_string().filter(|s| !s.is_empty()) {
-            Some(text) => text,
-            None => return,
+        let mut text = terminal.selection_to_string().filter(|s| !s.is_empty())?;
+
+        let trim = self.clipboard_config.trim_trailing_whitespace;
+        let rich = match self.clipboard_config.copy_format {
+            config::CopyFormat::Plain => None,
+            config::CopyFormat::Rich => {
+                let mut lines = export::lines(&terminal, ExportScope::Selection);
+                if trim {
+                    export::trim_trailing_whitespace(&mut lines);
+                }
+                Some((
+                    export::to_html_snippet(&lines, &self.state),
+                    export::to_rtf(&lines, &self.state),
+                ))
+            }
         };
         drop(terminal);
 
-        if ty == ClipboardType::Selection {
-            self.clipboard.set(ClipboardType::Clipboard, text.clone());
+        if trim {
+            text = clipboard::trim_trailing_whitespace(&text);
+        }
+
+        Some((text, rich))
+    }
+
+    #[inline]
+    fn set_clipboard(
+        &mut self,
+        ty: ClipboardType,
+        text: String,
+        rich: Option<(String, String)>,
+    ) {
+        self.record_clipboard(&text);
+        match rich {
+            Some((html, rtf)) => self
+                .clipboard
+                .set_rich(ty, clipboard::RichText { text, html, rtf }),
+            None => self.clipboard.set(ty, text),
+        }
+    }
+
+    /// Render the `scope` of the tab at `index`, defaults to the current
+    /// tab, returns None when there is no such tab.
+    pub fn export_tab(
+        &self,
+        index: Option<usize>,
+        scope: ExportScope,
+        format: ExportFormat,
+    ) -> Option<String> {
+        let index = index.unwrap_or_else(|| self.context_manager.current_index());
+        let tab = self.context_manager.tabs().get(index)?;
+        let terminal = tab.current().terminal.lock();
+        let lines = export::lines(&terminal, scope);
+        drop(terminal);
+
+        Some(export::render(&lines, format, &self.state))
+    }
+
+    /// Write the current tab's `scope` into a file in the home directory.
+    pub fn export(&self, scope: ExportScope, format: ExportFormat) {
+        let terminal = self.ctx().current().terminal.lock();
+        let lines = export::lines(&terminal, scope);
+        drop(terminal);
+
+        if lines.is_empty() {
+            return;
+        }
+
+        let content = export::render(&lines, format, &self.state);
+        let path = export::default_path(scope, format);
+        match std::fs::write(&path, content) {
+            Ok(()) => log::info!("exported {} to {}", scope.name(), path.display()),
+            Err(err) => log::error!("unable to export to {}: {err}", path.display()),
         }
-        self.clipboard.set(ty, text);
     }
 
     #[inline]
@@ -461,9 +1734,10 @@ impl Screen {
     }
 
     fn start_selection(&mut self, ty: SelectionType, point: Pos, side: Side) {
-        self.copy_selection(ClipboardType::Selection);
+        self.copy_on_select();
         let mut terminal = self.context_manager.current().terminal.lock();
-        let selection = Selection::new(ty, point, side);
+        let mut selection = Selection::new(ty, point, side);
+        selection.find_smart(&terminal);
         self.state.set_selection(selection.to_range(&terminal));
         terminal.selection = Some(selection);
         drop(terminal);
@@ -524,13 +1798,15 @@ impl Screen {
     #[inline]
     pub fn contains_point(&self, x: usize, y: usize) -> bool {
         let width = self.sugarloaf.layout.style.text_scale / 2.0;
+        let top = self.sugarloaf.layout.padding.y
+            + self.grid_first_line() as f32 * self.sugarloaf.layout.font_size;
         x <= (self.sugarloaf.layout.padding.x
             + self.sugarloaf.layout.columns as f32 * width) as usize
             && x > self.sugarloaf.layout.padding.x as usize
-            && y <= (self.sugarloaf.layout.padding.y
+            && y <= (top
                 + self.sugarloaf.layout.lines as f32 * self.sugarloaf.layout.font_size)
                 as usize
-            && y > self.sugarloaf.layout.padding.y as usize
+            && y > top as usize
     }
 
     #[inline]
@@ -574,11 +1850,7 @@ impl Screen {
                 self.clear_selection();
 
                 // Start new empty selection.
-                if self.modifiers.ctrl() {
-                    self.start_selection(SelectionType::Block, point, side);
-                } else {
-                    self.start_selection(SelectionType::Simple, point, side);
-                }
+                self.start_selection(SelectionType::Simple, point, side);
             }
             ClickState::DoubleClick => {
                 self.start_selection(SelectionType::Semantic, point, side);
@@ -645,27 +1917,251 @@ impl Screen {
         }
     }
 
+    /// Show or hide the tab bar for the amount of tabs, the tabs are resized
+    /// to the lines left.
+    fn update_tab_bar(&mut self) {
+        let lines = self.state.tab_bar_lines(self.context_manager.len());
+        if self.sugarloaf.layout.set_reserved_lines(lines) {
+            self.resize_all_contexts(
+                self.sugarloaf.layout.width_u32 as u16,
+                self.sugarloaf.layout.height_u32 as u16,
+                self.sugarloaf.layout.columns,
+                self.sugarloaf.layout.lines,
+            );
+        }
+    }
+
+    /// Whether the mouse is over the tab bar.
+    #[inline]
+    pub fn mouse_in_tab_bar(&self) -> bool {
+        self.tab_bar_line() == Some(self.mouse_screen_line())
+    }
+
+    /// Handle a press over the tab bar: left click switches to the tab and
+    /// starts dragging it, middle click or the close button closes it.
+    /// Returns false when the mouse is not over the tab bar.
+    pub fn tab_bar_click(&mut self, button: MouseButton) -> bool {
+        if !self.mouse_in_tab_bar() {
+            return false;
+        }
+
+        match (button, self.state.tab_bar_hit(self.mouse_column())) {
+            (MouseButton::Left, Some(TabBarHit::Tab(index))) => {
+                self.context_manager.set_current(index);
+                self.tab_drag = Some(index);
+            }
+            (MouseButton::Left, Some(TabBarHit::Scroll(index))) => {
+                self.context_manager.set_current(index);
+            }
+            (MouseButton::Left, Some(TabBarHit::Close(index)))
+            | (
+                MouseButton::Middle,
+                Some(TabBarHit::Tab(index) | TabBarHit::Close(index)),
+            ) => {
+                self.close(CloseRequest::Tab(index));
+            }
+            _ => (),
+        }
+        true
+    }
+
+    /// Move the dragged tab to the tab under the mouse, returns false when
+    /// no tab is being dragged.
+    pub fn drag_tab(&mut self) -> bool {
+        let from = match self.tab_drag {
+            Some(from) => from,
+            None => return false,
+        };
+
+        if let Some(TabBarHit::Tab(to) | TabBarHit::Close(to) | TabBarHit::Scroll(to)) =
+            self.state.tab_bar_hit(self.mouse_column())
+        {
+            if to != from {
+                self.context_manager.move_context(from, to);
+                self.tab_drag = Some(to);
+            }
+        }
+        true
+    }
+
+    /// Handle a left click over the grid: over a border between panes it
+    /// starts dragging it, otherwise the pane under the mouse gets the
+    /// focus. Returns true when the click was taken by a border.
+    pub fn pane_click(&mut self, button: MouseButton) -> bool {
+        if button != MouseButton::Left {
+            return false;
+        }
+
+        let (column, line) = match self.mouse_grid_cell() {
+            Some(cell) => cell,
+            None => return false,
+        };
+
+        let area = self.grid_area();
+        let tab = self.context_manager.current_tab_mut();
+        if let Some(border) = tab.border_at(column, line, area) {
+            self.pane_drag = Some(border);
+            return true;
+        }
+
+        if let Some(route_id) = tab.context_at(column, line, area) {
+            tab.focus(route_id);
+        }
+        false
+    }
+
+    /// Move the dragged border under the mouse, returns false when no
+    /// border is being dragged.
+    pub fn drag_pane_border(&mut self) -> bool {
+        let border = match &self.pane_drag {
+            Some(border) => border.clone(),
+            None => return false,
+        };
+
+        let (column, line) = match self.mouse_grid_cell() {
+            Some(cell) => cell,
+            None => return true,
+        };
+
+        let area = self.grid_area();
+        if self
+            .context_manager
+            .current_tab_mut()
+            .drag_border(&border, column, line, area)
+        {
+            self.resize_panes();
+        }
+        true
+    }
+
+    /// Drop the dragged border, returns true when one was being dragged.
+    #[inline]
+    pub fn end_pane_drag(&mut self) -> bool {
+        self.pane_drag.take().is_some()
+    }
+
+    /// Drop the dragged tab, returns true when a tab was being dragged.
+    #[inline]
+    pub fn end_tab_drag(&mut self) -> bool {
+        self.tab_drag.take().is_some()
+    }
+
+    /// Scrolling over the tab bar cycles through the tabs, returns false
+    /// when the mouse is not over the tab bar.
+    pub fn scroll_tab_bar(&mut self, new_scroll_y_px: f64) -> bool {
+        if !self.mouse_in_tab_bar() {
+            return false;
+        }
+
+        let step = self.sugarloaf.layout.font_size as f64;
+        self.mouse.accumulated_scroll.y += new_scroll_y_px;
+        let tabs = (self.mouse.accumulated_scroll.y / step) as i32;
+        for _ in 0..tabs.abs() {
+            if tabs > 0 {
+                self.context_manager.switch_to_prev();
+            } else {
+                self.context_manager.switch_to_next();
+            }
+        }
+        self.mouse.accumulated_scroll.y %= step;
+        true
+    }
+
     #[inline]
     pub fn render(&mut self) {
-        let mut terminal = self.ctx().current().terminal.lock();
-        let visible_rows = terminal.visible_rows();
-        let cursor = terminal.cursor();
-        let display_offset = terminal.display_offset();
-        drop(terminal);
+        let area = self.grid_area();
+        let tab = self.context_manager.current_tab();
+        let focused = tab.current().route_id;
+        let mut panes = vec![];
+        for (context, rect) in tab.layout(area) {
+            let mut terminal = context.terminal.lock();
+            let selection = terminal
+                .selection
+                .as_ref()
+                .and_then(|selection| selection.to_range(&terminal));
+            panes.push(state::Pane {
+                rows: terminal.visible_rows(),
+                cursor: terminal.cursor(),
+                display_offset: terminal.display_offset() as i32,
+                selection,
+                rect,
+                focused: context.route_id == focused,
+            });
+        }
+        let borders = tab.borders(area);
 
+        if let Some(pane) = panes.iter().find(|pane| pane.focused) {
+            let cursor = &pane.cursor;
+            self.update_ime_position(
+                pane.rect.column + cursor.pos.col.0,
+                pane.rect.line + cursor.pos.row.0.max(0) as usize,
+            );
+        }
         self.state.set_ime(self.ime.preedit());
+        if let Some((_, message)) = &self.close_prompt {
+            self.state.set_overlay(vec![message.to_owned()], Some(0));
+        } else {
+            match (
+                &self.clipboard_prompt,
+                &self.clipboard_picker,
+                &self.tab_rename,
+                &self.context_menu,
+            ) {
+                (Some((_, _, message)), _, _, _) => {
+                    self.state.set_overlay(vec![message.to_owned()], Some(0))
+                }
+                (None, Some(picker), _, _) => {
+                    let (lines, highlight) = picker.lines(&self.clipboard_history);
+                    self.state.set_overlay(lines, highlight);
+                }
+                (None, None, Some(title), _) => self
+                    .state
+                    .set_overlay(vec![format!("Rename tab: {title}")], Some(0)),
+                (None, None, None, Some(menu)) => {
+                    let (lines, highlight) = menu.lines();
+                    self.state.set_overlay(lines, highlight);
+                }
+                (None, None, None, None) => match self.key_tables.indicator() {
+                    Some(indicator) => self.state.set_overlay(vec![indicator], Some(0)),
+                    None => self.state.set_overlay(vec![], None),
+                },
+            }
+        }
 
-        self.state.update(
-            visible_rows,
-            cursor,
-            &mut self.sugarloaf,
-            &self.context_manager,
-            display_offset as i32,
-        );
+        self.state
+            .update(panes, borders, &mut self.sugarloaf, &self.context_manager);
 
         self.sugarloaf.render();
     }
 
+    /// Place the IME candidate window under the cell at `col` and `row`.
+    #[inline]
+    fn update_ime_position(&mut self, col: usize, row: usize) {
+        let (x, y) = self.sugarloaf.grid_origin();
+        let (width, height) = self.sugarloaf.cell_size();
+        let line = row + self.grid_first_line() + 1;
+        let position = PhysicalPosition::new(
+            (x + col as f32 * width) as f64,
+            (y + line as f32 * height) as f64,
+        );
+
+        if self.ime_position.0 != position {
+            self.ime_position = (position, false);
+        }
+    }
+
+    /// Position of the IME candidate window when it moved since the last
+    /// call.
+    #[inline]
+    pub fn take_ime_position(&mut self) -> Option<PhysicalPosition<f64>> {
+        if self.ime_position.1 {
+            return None;
+        }
+
+        self.ime_position.1 = true;
+        Some(self.ime_position.0)
+    }
+
     fn sgr_mouse_report(&mut self, pos: Pos, button: u8, state: ElementState) {
         let c = match state {
             ElementState::Pressed => 'M',
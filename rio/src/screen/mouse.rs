@@ -4,6 +4,57 @@ use std::time::Instant;
 use winit::event::ElementState;
 use winit::event::MouseButton;
 
+/// Back and forward buttons, numbered as X11 and xterm do.
+pub const BACK_BUTTON: u16 = 8;
+pub const FORWARD_BUTTON: u16 = 9;
+
+/// Map the platform numbering of the back and forward buttons to
+/// `BACK_BUTTON` and `FORWARD_BUTTON`, other buttons are left untouched.
+pub fn normalize_button(button: MouseButton) -> MouseButton {
+    let code = match button {
+        MouseButton::Other(code) => code,
+        button => return button,
+    };
+
+    // Wayland reports the evdev codes, BTN_SIDE/BTN_BACK and BTN_EXTRA/BTN_FORWARD.
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let code = match code {
+        0x113 | 0x116 => BACK_BUTTON,
+        0x114 | 0x115 => FORWARD_BUTTON,
+        code => code,
+    };
+
+    #[cfg(target_os = "macos")]
+    let code = match code {
+        3 => BACK_BUTTON,
+        4 => FORWARD_BUTTON,
+        code => code,
+    };
+
+    // XBUTTON1 and XBUTTON2.
+    #[cfg(windows)]
+    let code = match code {
+        1 => BACK_BUTTON,
+        2 => FORWARD_BUTTON,
+        code => code,
+    };
+
+    MouseButton::Other(code)
+}
+
+/// Button number used by mouse reports, `None` for buttons that can't be
+/// reported.
+pub fn report_code(button: MouseButton) -> Option<u8> {
+    match button {
+        MouseButton::Left => Some(0),
+        MouseButton::Middle => Some(1),
+        MouseButton::Right => Some(2),
+        MouseButton::Other(BACK_BUTTON) => Some(128),
+        MouseButton::Other(FORWARD_BUTTON) => Some(129),
+        MouseButton::Other(_) => None,
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct AccumulatedScroll {
     /// Scroll we should perform along `x` axis.
@@ -49,3 +100,34 @@ impl Default for Mouse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_code() {
+        assert_eq!(report_code(MouseButton::Left), Some(0));
+        assert_eq!(report_code(MouseButton::Right), Some(2));
+        assert_eq!(report_code(MouseButton::Other(BACK_BUTTON)), Some(128));
+        assert_eq!(report_code(MouseButton::Other(FORWARD_BUTTON)), Some(129));
+        assert_eq!(report_code(MouseButton::Other(12)), None);
+    }
+
+    #[test]
+    fn test_normalize_button() {
+        assert_eq!(normalize_button(MouseButton::Middle), MouseButton::Middle);
+
+        #[cfg(not(any(target_os = "macos", windows)))]
+        {
+            assert_eq!(
+                normalize_button(MouseButton::Other(8)),
+                MouseButton::Other(BACK_BUTTON)
+            );
+            assert_eq!(
+                normalize_button(MouseButton::Other(0x114)),
+                MouseButton::Other(FORWARD_BUTTON)
+            );
+        }
+    }
+}
@@ -0,0 +1,760 @@
+// Panes of a tab.
+//
+// A tab is divided by a binary tree: every split shares its area between two
+// children, side by side or one above the other, keeping one cell in between
+// for the border. Leaves hold the route id of the context drawn there. Sizes
+// are kept as ratios so they follow the window when it is resized.
+
+use crate::session::{ContextSnapshot, PaneSnapshot};
+use serde::{Deserialize, Serialize};
+
+/// How a split shares its area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SplitDirection {
+    /// Children side by side, separated by a vertical border.
+    Right,
+    /// Children one above the other, separated by a horizontal border.
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    #[inline]
+    fn split_direction(self) -> SplitDirection {
+        match self {
+            Direction::Left | Direction::Right => SplitDirection::Right,
+            Direction::Up | Direction::Down => SplitDirection::Down,
+        }
+    }
+}
+
+/// Area of the grid in cells.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PaneRect {
+    pub column: usize,
+    pub line: usize,
+    pub columns: usize,
+    pub lines: usize,
+}
+
+impl PaneRect {
+    #[inline]
+    pub fn new(columns: usize, lines: usize) -> PaneRect {
+        PaneRect {
+            column: 0,
+            line: 0,
+            columns,
+            lines,
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, column: usize, line: usize) -> bool {
+        column >= self.column
+            && column < self.column + self.columns
+            && line >= self.line
+            && line < self.line + self.lines
+    }
+}
+
+/// Border between the two children of a split.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneBorder {
+    /// Branches taken from the root to reach the split, `false` for the
+    /// first child.
+    path: Vec<bool>,
+    pub direction: SplitDirection,
+    /// Cells taken by the border.
+    pub rect: PaneRect,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Pane(usize),
+    Split {
+        direction: SplitDirection,
+        /// Part of the area, without the border, given to `first`.
+        ratio: f32,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    #[inline]
+    fn is_pane(&self, id: usize) -> bool {
+        matches!(self, Node::Pane(pane) if *pane == id)
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Pane(_) => 1,
+            Node::Split { first, second, .. } => first.len() + second.len(),
+        }
+    }
+
+    /// Panes along `direction`, used to give each of them the same size.
+    fn weight(&self, direction: SplitDirection) -> usize {
+        match self {
+            Node::Split {
+                direction: split,
+                first,
+                second,
+                ..
+            } if *split == direction => {
+                first.weight(direction) + second.weight(direction)
+            }
+            _ => 1,
+        }
+    }
+}
+
+/// Cells along `direction` given to the first child, each child keeps at
+/// least one.
+#[inline]
+fn first_size(size: usize, ratio: f32) -> usize {
+    let available = size.saturating_sub(1);
+    ((available as f32 * ratio).round() as usize)
+        .clamp(1, available.saturating_sub(1).max(1))
+}
+
+#[inline]
+fn size_along(area: &PaneRect, direction: SplitDirection) -> usize {
+    match direction {
+        SplitDirection::Right => area.columns,
+        SplitDirection::Down => area.lines,
+    }
+}
+
+/// Areas of the first child, the border and the second child.
+fn divide(
+    area: PaneRect,
+    direction: SplitDirection,
+    ratio: f32,
+) -> (PaneRect, PaneRect, PaneRect) {
+    let size = size_along(&area, direction);
+    let first = first_size(size, ratio);
+    let second = size.saturating_sub(1).saturating_sub(first);
+    match direction {
+        SplitDirection::Right => (
+            PaneRect {
+                columns: first,
+                ..area
+            },
+            PaneRect {
+                column: area.column + first,
+                columns: 1,
+                ..area
+            },
+            PaneRect {
+                column: area.column + first + 1,
+                columns: second,
+                ..area
+            },
+        ),
+        SplitDirection::Down => (
+            PaneRect {
+                lines: first,
+                ..area
+            },
+            PaneRect {
+                line: area.line + first,
+                lines: 1,
+                ..area
+            },
+            PaneRect {
+                line: area.line + first + 1,
+                lines: second,
+                ..area
+            },
+        ),
+    }
+}
+
+fn walk(
+    node: &Node,
+    area: PaneRect,
+    path: &mut Vec<bool>,
+    panes: &mut Vec<(usize, PaneRect)>,
+    borders: &mut Vec<PaneBorder>,
+) {
+    match node {
+        Node::Pane(id) => panes.push((*id, area)),
+        Node::Split {
+            direction,
+            ratio,
+            first,
+            second,
+        } => {
+            let (first_area, border, second_area) = divide(area, *direction, *ratio);
+            borders.push(PaneBorder {
+                path: path.clone(),
+                direction: *direction,
+                rect: border,
+            });
+            path.push(false);
+            walk(first, first_area, path, panes, borders);
+            path.pop();
+            path.push(true);
+            walk(second, second_area, path, panes, borders);
+            path.pop();
+        }
+    }
+}
+
+fn snapshot<F>(node: &Node, pane: &mut F) -> PaneSnapshot
+where
+    F: FnMut(usize) -> ContextSnapshot,
+{
+    match node {
+        Node::Pane(id) => PaneSnapshot::Context(pane(*id)),
+        Node::Split {
+            direction,
+            ratio,
+            first,
+            second,
+        } => PaneSnapshot::Split {
+            direction: *direction,
+            ratio: *ratio,
+            first: Box::new(snapshot(first, pane)),
+            second: Box::new(snapshot(second, pane)),
+        },
+    }
+}
+
+fn from_snapshot<F, E>(snapshot: &PaneSnapshot, pane: &mut F) -> Result<Node, E>
+where
+    F: FnMut(&ContextSnapshot) -> Result<usize, E>,
+{
+    Ok(match snapshot {
+        PaneSnapshot::Context(context) => Node::Pane(pane(context)?),
+        PaneSnapshot::Split {
+            direction,
+            ratio,
+            first,
+            second,
+        } => Node::Split {
+            direction: *direction,
+            ratio: ratio.clamp(0.0, 1.0),
+            first: Box::new(from_snapshot(first, pane)?),
+            second: Box::new(from_snapshot(second, pane)?),
+        },
+    })
+}
+
+fn split(node: &mut Node, id: usize, new_id: usize, direction: SplitDirection) -> bool {
+    match node {
+        Node::Pane(pane) if *pane == id => {
+            *node = Node::Split {
+                direction,
+                ratio: 0.5,
+                first: Box::new(Node::Pane(id)),
+                second: Box::new(Node::Pane(new_id)),
+            };
+            true
+        }
+        Node::Pane(_) => false,
+        Node::Split { first, second, .. } => {
+            split(first, id, new_id, direction) || split(second, id, new_id, direction)
+        }
+    }
+}
+
+fn remove(node: &mut Node, id: usize) -> bool {
+    let sibling = match node {
+        Node::Split { first, second, .. } if first.is_pane(id) => {
+            Some(std::mem::replace(second.as_mut(), Node::Pane(0)))
+        }
+        Node::Split { first, second, .. } if second.is_pane(id) => {
+            Some(std::mem::replace(first.as_mut(), Node::Pane(0)))
+        }
+        _ => None,
+    };
+
+    if let Some(sibling) = sibling {
+        *node = sibling;
+        return true;
+    }
+
+    match node {
+        Node::Pane(_) => false,
+        Node::Split { first, second, .. } => remove(first, id) || remove(second, id),
+    }
+}
+
+fn equalize(node: &mut Node) {
+    if let Node::Split {
+        direction,
+        ratio,
+        first,
+        second,
+    } = node
+    {
+        let first_weight = first.weight(*direction);
+        let second_weight = second.weight(*direction);
+        *ratio = first_weight as f32 / (first_weight + second_weight) as f32;
+        equalize(first);
+        equalize(second);
+    }
+}
+
+/// Branches taken from `node` to reach the pane `id`, along with the
+/// direction of each split.
+fn path_to(node: &Node, id: usize, path: &mut Vec<(bool, SplitDirection)>) -> bool {
+    match node {
+        Node::Pane(pane) => *pane == id,
+        Node::Split {
+            direction,
+            first,
+            second,
+            ..
+        } => {
+            path.push((false, *direction));
+            if path_to(first, id, path) {
+                return true;
+            }
+            path.pop();
+            path.push((true, *direction));
+            if path_to(second, id, path) {
+                return true;
+            }
+            path.pop();
+            false
+        }
+    }
+}
+
+/// Node reached by following `path` from `node`, along with its area.
+fn node_at<'a>(
+    node: &'a mut Node,
+    path: &[bool],
+    area: PaneRect,
+) -> Option<(&'a mut Node, PaneRect)> {
+    let mut node = node;
+    let mut area = area;
+    for second_child in path {
+        match node {
+            Node::Pane(_) => return None,
+            Node::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_area, _, second_area) = divide(area, *direction, *ratio);
+                if *second_child {
+                    node = second.as_mut();
+                    area = second_area;
+                } else {
+                    node = first.as_mut();
+                    area = first_area;
+                }
+            }
+        }
+    }
+
+    Some((node, area))
+}
+
+/// Move the border of `node` by `delta` cells, returns true when it moved.
+fn move_border(node: &mut Node, area: PaneRect, delta: isize) -> bool {
+    if let Node::Split {
+        direction, ratio, ..
+    } = node
+    {
+        let available = size_along(&area, *direction).saturating_sub(1);
+        if available < 2 {
+            return false;
+        }
+
+        let current = first_size(available + 1, *ratio);
+        let size = (current as isize + delta).clamp(1, available as isize - 1) as usize;
+        *ratio = size as f32 / available as f32;
+        return size != current;
+    }
+
+    false
+}
+
+pub struct PaneTree {
+    root: Node,
+}
+
+impl PaneTree {
+    pub fn new(id: usize) -> PaneTree {
+        PaneTree {
+            root: Node::Pane(id),
+        }
+    }
+
+    /// Every pane and where it goes in `area`.
+    pub fn panes(&self, area: PaneRect) -> Vec<(usize, PaneRect)> {
+        let mut panes = vec![];
+        walk(&self.root, area, &mut vec![], &mut panes, &mut vec![]);
+        panes
+    }
+
+    /// Borders drawn between the panes in `area`.
+    pub fn borders(&self, area: PaneRect) -> Vec<PaneBorder> {
+        let mut borders = vec![];
+        walk(&self.root, area, &mut vec![], &mut vec![], &mut borders);
+        borders
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    /// Shape of the tree, `pane` gives the snapshot of each pane.
+    pub fn snapshot<F>(&self, pane: &mut F) -> PaneSnapshot
+    where
+        F: FnMut(usize) -> ContextSnapshot,
+    {
+        snapshot(&self.root, pane)
+    }
+
+    /// Tree shaped as `snapshot`, `pane` creates the context of each pane
+    /// in the order they are drawn and returns its id.
+    pub fn from_snapshot<F, E>(
+        snapshot: &PaneSnapshot,
+        pane: &mut F,
+    ) -> Result<PaneTree, E>
+    where
+        F: FnMut(&ContextSnapshot) -> Result<usize, E>,
+    {
+        Ok(PaneTree {
+            root: from_snapshot(snapshot, pane)?,
+        })
+    }
+
+    /// Share the place of the pane `id` with a new pane `new_id`, placed to
+    /// its right or below it.
+    #[inline]
+    pub fn split(&mut self, id: usize, new_id: usize, direction: SplitDirection) -> bool {
+        split(&mut self.root, id, new_id, direction)
+    }
+
+    /// Remove the pane `id`, its sibling takes its place. The last pane is
+    /// never removed.
+    #[inline]
+    pub fn remove(&mut self, id: usize) -> bool {
+        remove(&mut self.root, id)
+    }
+
+    /// Pane next to `id` in `direction`, the one sharing the most cells
+    /// with it when there are several.
+    pub fn neighbor(
+        &self,
+        id: usize,
+        direction: Direction,
+        area: PaneRect,
+    ) -> Option<usize> {
+        let panes = self.panes(area);
+        let (_, current) = panes.iter().find(|(pane, _)| *pane == id)?;
+        let overlap =
+            |start: usize, size: usize, other_start: usize, other_size: usize| {
+                (start + size)
+                    .min(other_start + other_size)
+                    .saturating_sub(start.max(other_start))
+            };
+
+        let mut neighbor: Option<(usize, usize)> = None;
+        for (pane, rect) in panes.iter().filter(|(pane, _)| *pane != id) {
+            let (adjacent, shared) = match direction {
+                Direction::Left => (
+                    rect.column + rect.columns + 1 == current.column,
+                    overlap(rect.line, rect.lines, current.line, current.lines),
+                ),
+                Direction::Right => (
+                    current.column + current.columns + 1 == rect.column,
+                    overlap(rect.line, rect.lines, current.line, current.lines),
+                ),
+                Direction::Up => (
+                    rect.line + rect.lines + 1 == current.line,
+                    overlap(rect.column, rect.columns, current.column, current.columns),
+                ),
+                Direction::Down => (
+                    current.line + current.lines + 1 == rect.line,
+                    overlap(rect.column, rect.columns, current.column, current.columns),
+                ),
+            };
+
+            if adjacent && shared > neighbor.map_or(0, |(_, shared)| shared) {
+                neighbor = Some((*pane, shared));
+            }
+        }
+
+        neighbor.map(|(pane, _)| pane)
+    }
+
+    /// Move the closest border of the pane `id` that can go in
+    /// `direction` by `cells`, returns true when it moved.
+    pub fn resize(
+        &mut self,
+        id: usize,
+        direction: Direction,
+        cells: usize,
+        area: PaneRect,
+    ) -> bool {
+        let mut path = vec![];
+        if !path_to(&self.root, id, &mut path) {
+            return false;
+        }
+
+        let split_direction = direction.split_direction();
+        let depth = match path
+            .iter()
+            .rposition(|(_, direction)| *direction == split_direction)
+        {
+            Some(depth) => depth,
+            None => return false,
+        };
+
+        let path: Vec<bool> = path[..depth].iter().map(|(second, _)| *second).collect();
+        let delta = match direction {
+            Direction::Left | Direction::Up => -(cells as isize),
+            Direction::Right | Direction::Down => cells as isize,
+        };
+        match node_at(&mut self.root, &path, area) {
+            Some((node, node_area)) => move_border(node, node_area, delta),
+            None => false,
+        }
+    }
+
+    /// Give every pane the same size along each split.
+    #[inline]
+    pub fn equalize(&mut self) {
+        equalize(&mut self.root);
+    }
+
+    /// Pane drawn at `column` and `line` of `area`.
+    pub fn pane_at(&self, column: usize, line: usize, area: PaneRect) -> Option<usize> {
+        self.panes(area)
+            .into_iter()
+            .find(|(_, rect)| rect.contains(column, line))
+            .map(|(pane, _)| pane)
+    }
+
+    /// Border drawn at `column` and `line` of `area`.
+    pub fn border_at(
+        &self,
+        column: usize,
+        line: usize,
+        area: PaneRect,
+    ) -> Option<PaneBorder> {
+        self.borders(area)
+            .into_iter()
+            .find(|border| border.rect.contains(column, line))
+    }
+
+    /// Move `border` under `column` and `line`, returns true when it moved.
+    pub fn drag(
+        &mut self,
+        border: &PaneBorder,
+        column: usize,
+        line: usize,
+        area: PaneRect,
+    ) -> bool {
+        let (node, node_area) = match node_at(&mut self.root, &border.path, area) {
+            Some(found) => found,
+            None => return false,
+        };
+
+        let delta = match node {
+            Node::Split {
+                direction, ratio, ..
+            } => {
+                let (_, current, _) = divide(node_area, *direction, *ratio);
+                match direction {
+                    SplitDirection::Right => column as isize - current.column as isize,
+                    SplitDirection::Down => line as isize - current.line as isize,
+                }
+            }
+            Node::Pane(_) => return false,
+        };
+
+        delta != 0 && move_border(node, node_area, delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: PaneRect = PaneRect {
+        column: 0,
+        line: 0,
+        columns: 80,
+        lines: 24,
+    };
+
+    fn rect(column: usize, line: usize, columns: usize, lines: usize) -> PaneRect {
+        PaneRect {
+            column,
+            line,
+            columns,
+            lines,
+        }
+    }
+
+    // 1 | 2
+    //   | -
+    //   | 3
+    fn three_panes() -> PaneTree {
+        let mut tree = PaneTree::new(1);
+        assert!(tree.split(1, 2, SplitDirection::Right));
+        assert!(tree.split(2, 3, SplitDirection::Down));
+        tree
+    }
+
+    #[test]
+    fn test_split() {
+        let tree = three_panes();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(
+            tree.panes(AREA),
+            vec![
+                (1, rect(0, 0, 40, 24)),
+                (2, rect(41, 0, 39, 12)),
+                (3, rect(41, 13, 39, 11)),
+            ]
+        );
+
+        let borders = tree.borders(AREA);
+        assert_eq!(borders.len(), 2);
+        assert_eq!(borders[0].direction, SplitDirection::Right);
+        assert_eq!(borders[0].rect, rect(40, 0, 1, 24));
+        assert_eq!(borders[1].direction, SplitDirection::Down);
+        assert_eq!(borders[1].rect, rect(41, 12, 39, 1));
+
+        let mut tree = tree;
+        assert!(!tree.split(4, 5, SplitDirection::Right));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree = three_panes();
+        assert!(tree.remove(2));
+        assert_eq!(
+            tree.panes(AREA),
+            vec![(1, rect(0, 0, 40, 24)), (3, rect(41, 0, 39, 24))]
+        );
+        assert!(!tree.remove(2));
+        assert!(tree.remove(1));
+        assert_eq!(tree.panes(AREA), vec![(3, AREA)]);
+        // The last pane stays
+        assert!(!tree.remove(3));
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn test_neighbor() {
+        let tree = three_panes();
+        assert_eq!(tree.neighbor(1, Direction::Right, AREA), Some(2));
+        assert_eq!(tree.neighbor(1, Direction::Left, AREA), None);
+        assert_eq!(tree.neighbor(3, Direction::Left, AREA), Some(1));
+        assert_eq!(tree.neighbor(2, Direction::Down, AREA), Some(3));
+        assert_eq!(tree.neighbor(3, Direction::Up, AREA), Some(2));
+        assert_eq!(tree.neighbor(3, Direction::Down, AREA), None);
+        assert_eq!(tree.neighbor(4, Direction::Down, AREA), None);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut tree = three_panes();
+        assert!(tree.resize(1, Direction::Right, 5, AREA));
+        assert_eq!(tree.panes(AREA)[0], (1, rect(0, 0, 45, 24)));
+        // The closest border along the direction moves, even from the
+        // other side
+        assert!(tree.resize(3, Direction::Left, 10, AREA));
+        assert_eq!(tree.panes(AREA)[0], (1, rect(0, 0, 35, 24)));
+        assert!(tree.resize(3, Direction::Up, 2, AREA));
+        assert_eq!(tree.panes(AREA)[2], (3, rect(36, 11, 44, 13)));
+        // Panes keep at least one cell
+        assert!(tree.resize(2, Direction::Left, 100, AREA));
+        assert_eq!(tree.panes(AREA)[0], (1, rect(0, 0, 1, 24)));
+        assert!(!tree.resize(2, Direction::Left, 1, AREA));
+        // No split along that direction
+        assert!(!PaneTree::new(1).resize(1, Direction::Up, 1, AREA));
+        assert!(!tree.resize(1, Direction::Up, 1, AREA));
+    }
+
+    #[test]
+    fn test_equalize() {
+        let mut tree = PaneTree::new(1);
+        tree.split(1, 2, SplitDirection::Right);
+        tree.split(2, 3, SplitDirection::Right);
+        let columns: Vec<usize> = tree
+            .panes(AREA)
+            .iter()
+            .map(|(_, rect)| rect.columns)
+            .collect();
+        assert_eq!(columns, vec![40, 19, 19]);
+
+        tree.equalize();
+        let columns: Vec<usize> = tree
+            .panes(AREA)
+            .iter()
+            .map(|(_, rect)| rect.columns)
+            .collect();
+        assert_eq!(columns, vec![26, 26, 26]);
+    }
+
+    #[test]
+    fn test_hit_and_drag() {
+        let mut tree = three_panes();
+        assert_eq!(tree.pane_at(0, 0, AREA), Some(1));
+        assert_eq!(tree.pane_at(50, 20, AREA), Some(3));
+        assert_eq!(tree.pane_at(40, 0, AREA), None);
+        assert_eq!(tree.border_at(10, 0, AREA), None);
+
+        let border = tree.border_at(40, 5, AREA).unwrap();
+        assert_eq!(border.direction, SplitDirection::Right);
+        assert!(tree.drag(&border, 30, 5, AREA));
+        assert_eq!(tree.panes(AREA)[0], (1, rect(0, 0, 30, 24)));
+        assert!(!tree.drag(&border, 30, 5, AREA));
+
+        let border = tree.border_at(60, 12, AREA).unwrap();
+        assert_eq!(border.direction, SplitDirection::Down);
+        assert!(tree.drag(&border, 60, 20, AREA));
+        assert_eq!(tree.panes(AREA)[1], (2, rect(31, 0, 49, 20)));
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let mut tree = three_panes();
+        let border = tree.border_at(40, 5, AREA).unwrap();
+        assert!(tree.drag(&border, 30, 5, AREA));
+
+        let mut context = |id: usize| ContextSnapshot {
+            title: Some(id.to_string()),
+            working_directory: None,
+            rows: vec![],
+            cursor: None,
+        };
+        let snapshot = tree.snapshot(&mut context);
+
+        let mut next = 10;
+        let restored = PaneTree::from_snapshot(&snapshot, &mut |pane| {
+            next += 1;
+            assert_eq!(pane.title, Some((next - 10).to_string()));
+            Ok::<usize, ()>(next)
+        })
+        .unwrap();
+        let shape = |tree: &PaneTree| -> Vec<PaneRect> {
+            tree.panes(AREA).into_iter().map(|(_, rect)| rect).collect()
+        };
+        assert_eq!(shape(&restored), shape(&tree));
+        let ids: Vec<usize> = restored.panes(AREA).iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![11, 12, 13]);
+    }
+}
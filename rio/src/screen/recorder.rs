@@ -0,0 +1,159 @@
+// Keyboard macros.
+//
+// While recording, every write Rio makes into the PTY is kept, along with
+// the delay since the previous write when timing is preserved. Writes made
+// while the terminal reads a password (line editing without echo) are
+// dropped. A recording is added to the `macros` of the configuration file
+// and replayed into any tab by a binding with `macro = "<name>"`.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{Duration, Instant};
+
+pub struct MacroRecorder {
+    preserve_timing: bool,
+    steps: Vec<config::MacroStep>,
+    last_write: Option<Instant>,
+}
+
+impl MacroRecorder {
+    pub fn new(preserve_timing: bool) -> Self {
+        MacroRecorder {
+            preserve_timing,
+            steps: vec![],
+            last_write: None,
+        }
+    }
+
+    /// Keep `bytes` unless they are typed at a `password` prompt.
+    pub fn record(&mut self, bytes: &[u8], password: bool) {
+        if password || bytes.is_empty() {
+            return;
+        }
+
+        let input = String::from_utf8_lossy(bytes);
+        let now = Instant::now();
+        let delay = match self.last_write {
+            Some(last_write) if self.preserve_timing => {
+                now.duration_since(last_write).as_millis() as u64
+            }
+            _ => 0,
+        };
+        self.last_write = Some(now);
+
+        match self.steps.last_mut() {
+            Some(step) if !self.preserve_timing => step.input.push_str(&input),
+            _ => self.steps.push(config::MacroStep {
+                delay,
+                input: input.to_string(),
+            }),
+        }
+    }
+
+    /// The recorded macro, `None` when nothing was recorded.
+    pub fn finish(self) -> Option<config::Macro> {
+        if self.steps.is_empty() {
+            return None;
+        }
+
+        if self.preserve_timing {
+            Some(config::Macro {
+                input: String::new(),
+                steps: self.steps,
+            })
+        } else {
+            let input = self.steps.into_iter().map(|step| step.input).collect();
+            Some(config::Macro {
+                input,
+                steps: vec![],
+            })
+        }
+    }
+}
+
+/// First `recording-<n>` name not used by `macros`.
+pub fn next_macro_name(macros: &BTreeMap<String, config::Macro>) -> String {
+    (1..)
+        .map(|index| format!("recording-{index}"))
+        .find(|name| !macros.contains_key(name))
+        .unwrap_or_default()
+}
+
+/// Add `recording` to the configuration file as the macro `name`, the rest
+/// of the file is kept as it is.
+pub fn save(name: &str, recording: &config::Macro) -> io::Result<()> {
+    let path = config::config_file_path();
+    let document = match std::fs::read_to_string(&path) {
+        Ok(document) => document,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let document = recording
+        .insert_into(name, &document)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    std::fs::write(path, document)
+}
+
+/// Writes of `recording`, each with the delay to wait before it.
+pub fn writes(recording: &config::Macro) -> Vec<(Duration, Vec<u8>)> {
+    let mut writes = vec![];
+    if !recording.input.is_empty() {
+        writes.push((Duration::ZERO, recording.input.as_bytes().to_vec()));
+    }
+    writes.extend(recording.steps.iter().map(|step| {
+        (
+            Duration::from_millis(step.delay),
+            step.input.as_bytes().to_vec(),
+        )
+    }));
+    writes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_without_timing() {
+        let mut recorder = MacroRecorder::new(false);
+        recorder.record(b"ls", false);
+        recorder.record(b"secret", true);
+        recorder.record(b"\r", false);
+
+        let recording = recorder.finish().unwrap();
+        assert_eq!(recording.input, "ls\r");
+        assert!(recording.steps.is_empty());
+        assert_eq!(writes(&recording), vec![(Duration::ZERO, b"ls\r".to_vec())]);
+    }
+
+    #[test]
+    fn test_record_with_timing() {
+        let mut recorder = MacroRecorder::new(true);
+        recorder.record(b"l", false);
+        std::thread::sleep(Duration::from_millis(20));
+        recorder.record(b"s", false);
+
+        let recording = recorder.finish().unwrap();
+        assert!(recording.input.is_empty());
+        assert_eq!(recording.steps.len(), 2);
+        assert_eq!(recording.steps[0].delay, 0);
+        assert!(recording.steps[1].delay >= 20);
+    }
+
+    #[test]
+    fn test_empty_recording() {
+        let mut recorder = MacroRecorder::new(false);
+        recorder.record(b"hunter2", true);
+        assert!(recorder.finish().is_none());
+    }
+
+    #[test]
+    fn test_next_macro_name() {
+        let mut macros = BTreeMap::new();
+        assert_eq!(next_macro_name(&macros), "recording-1");
+
+        macros.insert(String::from("recording-1"), config::Macro::default());
+        macros.insert(String::from("recording-3"), config::Macro::default());
+        assert_eq!(next_macro_name(&macros), "recording-2");
+    }
+}
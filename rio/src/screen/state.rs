@@ -3,35 +3,49 @@ use crate::crosswords::grid::row::Row;
 use crate::crosswords::pos;
 use crate::crosswords::pos::CursorState;
 use crate::crosswords::square::{Flags, Square};
-use crate::ime::Preedit;
-use crate::screen::{constants, context, EventProxy};
+use crate::export::Palette;
+use crate::ime::{self, Preedit, PreeditCell};
+use crate::screen::panes::{PaneBorder, PaneRect, SplitDirection};
+use crate::screen::{context, EventProxy};
 use crate::selection::SelectionRange;
 use colors::{
     term::{List, TermColors},
-    AnsiColor, Colors, NamedColor,
+    AnsiColor, ColorArray, Colors, NamedColor,
 };
 use config::Config;
 use std::rc::Rc;
-use sugarloaf::components::rect::Rect;
-use sugarloaf::core::{Sugar, SugarDecoration, SugarStack, SugarStyle};
+use sugarloaf::components::tabs::{TabBar, TabBarColors, TabBarHit, TabBarPosition};
+use sugarloaf::core::{Sugar, SugarDecoration, SugarStack, SugarStyle, SugarViewport};
 use sugarloaf::Sugarloaf;
 
-const PADDING_X_TABS: f32 = 30.;
+/// What a context shows and where, drawn by [`State::update`].
+pub struct Pane {
+    pub rows: Vec<Row<Square>>,
+    pub cursor: CursorState,
+    pub display_offset: i32,
+    pub selection: Option<SelectionRange>,
+    pub rect: PaneRect,
+    /// Only the focused pane draws the cursor and the IME preedit.
+    pub focused: bool,
+}
 
 #[derive(Default)]
 struct Cursor {
     state: CursorState,
-    content: char,
-    content_ref: char,
 }
 
 pub struct State {
     pub option_as_alt: bool,
-    is_ime_enabled: bool,
+    ime_preedit: Option<Preedit>,
     named_colors: Colors,
     pub colors: List,
     cursor: Cursor,
     pub selection_range: Option<SelectionRange>,
+    overlay: Vec<String>,
+    overlay_highlight: Option<usize>,
+    tab_bar: TabBar,
+    tab_bar_position: TabBarPosition,
+    tab_bar_hide_when_single: bool,
 }
 
 // TODO: Finish from
@@ -61,6 +75,13 @@ impl From<Square> for Sugar {
     }
 }
 
+impl Palette for State {
+    #[inline]
+    fn color(&self, color: AnsiColor, flags: Flags) -> ColorArray {
+        self.compute_color(color, flags)
+    }
+}
+
 impl State {
     pub fn new(config: &Rc<Config>) -> State {
         let term_colors = TermColors::default();
@@ -73,28 +94,75 @@ impl State {
 
         State {
             option_as_alt,
-            is_ime_enabled: false,
+            ime_preedit: None,
             colors,
             selection_range: None,
+            overlay: vec![],
+            overlay_highlight: None,
+            tab_bar: TabBar::default(),
+            tab_bar_position: match config.tab_bar.position {
+                config::TabBarPosition::Top => TabBarPosition::Top,
+                config::TabBarPosition::Bottom => TabBarPosition::Bottom,
+            },
+            tab_bar_hide_when_single: config.tab_bar.hide_when_single,
             named_colors: config.colors,
             cursor: Cursor {
-                content: config.cursor,
-                content_ref: config.cursor,
                 state: CursorState::new(config),
             },
         }
     }
 
+    /// Lines taken by the tab bar while `tabs` tabs are open.
+    #[inline]
+    pub fn tab_bar_lines(&self, tabs: usize) -> usize {
+        if self.tab_bar_hide_when_single && tabs <= 1 {
+            0
+        } else {
+            1
+        }
+    }
+
+    #[inline]
+    pub fn tab_bar_position(&self) -> TabBarPosition {
+        self.tab_bar_position
+    }
+
+    /// Tab drawn at `column` of the tab bar.
+    #[inline]
+    pub fn tab_bar_hit(&self, column: usize) -> Option<TabBarHit> {
+        self.tab_bar.hit(column)
+    }
+
+    fn create_tab_bar_sugar_stack(
+        &mut self,
+        context_manager: &context::ContextManager<EventProxy>,
+        columns: usize,
+    ) -> SugarStack {
+        let titles: Vec<&str> = context_manager
+            .tabs()
+            .iter()
+            .map(|tab| tab.title())
+            .collect();
+        let colors = TabBarColors {
+            bar: self.named_colors.background.0,
+            background: self.named_colors.tabs,
+            foreground: self.named_colors.tabs_foreground,
+            active_background: self.named_colors.tabs_active,
+            active_foreground: self.named_colors.tabs_active_foreground,
+        };
+
+        self.tab_bar
+            .build(&titles, context_manager.current_index(), columns, &colors)
+    }
+
     pub fn get_cursor_state(&self) -> CursorState {
         self.cursor.state.clone()
     }
 
-    // TODO: Square.into()
+    /// Resolve an ANSI color into the configured theme.
     #[inline]
-    fn create_sugar(&self, square: &Square) -> Sugar {
-        let flags = square.flags;
-
-        let foreground_color = match square.fg {
+    pub fn compute_color(&self, color: AnsiColor, flags: Flags) -> ColorArray {
+        match color {
             AnsiColor::Named(NamedColor::Black) => self.named_colors.black,
             AnsiColor::Named(NamedColor::Background) => self.named_colors.background.0,
             AnsiColor::Named(NamedColor::Blue) => self.named_colors.blue,
@@ -144,45 +212,17 @@ impl State {
 
                 self.colors[index]
             }
-        };
+        }
+    }
 
-        let background_color = match square.bg {
-            AnsiColor::Named(NamedColor::Black) => self.named_colors.black,
-            AnsiColor::Named(NamedColor::Background) => self.named_colors.background.0,
-            AnsiColor::Named(NamedColor::Blue) => self.named_colors.blue,
-            AnsiColor::Named(NamedColor::LightBlack) => self.named_colors.light_black,
-            AnsiColor::Named(NamedColor::LightBlue) => self.named_colors.light_blue,
-            AnsiColor::Named(NamedColor::LightCyan) => self.named_colors.light_cyan,
-            AnsiColor::Named(NamedColor::LightForeground) => {
-                self.named_colors.light_foreground
-            }
-            AnsiColor::Named(NamedColor::LightGreen) => self.named_colors.light_green,
-            AnsiColor::Named(NamedColor::LightMagenta) => self.named_colors.light_magenta,
-            AnsiColor::Named(NamedColor::LightRed) => self.named_colors.light_red,
-            AnsiColor::Named(NamedColor::LightWhite) => self.named_colors.light_white,
-            AnsiColor::Named(NamedColor::LightYellow) => self.named_colors.light_yellow,
-            AnsiColor::Named(NamedColor::Cursor) => self.named_colors.cursor,
-            AnsiColor::Named(NamedColor::Cyan) => self.named_colors.cyan,
-            AnsiColor::Named(NamedColor::DimBlack) => self.named_colors.dim_black,
-            AnsiColor::Named(NamedColor::DimBlue) => self.named_colors.dim_blue,
-            AnsiColor::Named(NamedColor::DimCyan) => self.named_colors.dim_cyan,
-            AnsiColor::Named(NamedColor::DimForeground) => {
-                self.named_colors.dim_foreground
-            }
-            AnsiColor::Named(NamedColor::DimGreen) => self.named_colors.dim_green,
-            AnsiColor::Named(NamedColor::DimMagenta) => self.named_colors.dim_magenta,
-            AnsiColor::Named(NamedColor::DimRed) => self.named_colors.dim_red,
-            AnsiColor::Named(NamedColor::DimWhite) => self.named_colors.dim_white,
-            AnsiColor::Named(NamedColor::DimYellow) => self.named_colors.dim_yellow,
-            AnsiColor::Named(NamedColor::Foreground) => self.named_colors.foreground,
-            AnsiColor::Named(NamedColor::Green) => self.named_colors.green,
-            AnsiColor::Named(NamedColor::Magenta) => self.named_colors.magenta,
-            AnsiColor::Named(NamedColor::Red) => self.named_colors.red,
-            AnsiColor::Named(NamedColor::White) => self.named_colors.white,
-            AnsiColor::Named(NamedColor::Yellow) => self.named_colors.yellow,
-            AnsiColor::Spec(rgb) => rgb.to_arr(),
-            AnsiColor::Indexed(idx) => self.colors[idx as usize],
-        };
+    // TODO: Square.into()
+    #[inline]
+    fn create_sugar(&self, square: &Square) -> Sugar {
+        let flags = square.flags;
+
+        let foreground_color = self.compute_color(square.fg, flags);
+
+        let background_color = self.compute_color(square.bg, Flags::empty());
 
         let content = if square.c == '\t' || flags.contains(Flags::HIDDEN) {
             ' '
@@ -336,14 +376,9 @@ impl State {
     fn create_cursor(&self, square: &Square) -> Sugar {
         let mut cloned_square = square.clone();
 
-        // If IME is enabled we get the current content to cursor
-        if self.is_ime_enabled {
-            cloned_square.c = self.cursor.content;
-        }
-
-        // If IME is enabled or is a block cursor, put background color
-        // when cursor is over the character
-        if self.is_ime_enabled || self.cursor.state.content == CursorShape::Block {
+        // If is a block cursor, put background color when cursor is over
+        // the character
+        if self.cursor.state.content == CursorShape::Block {
             cloned_square.fg = AnsiColor::Named(NamedColor::Background);
         }
 
@@ -352,17 +387,88 @@ impl State {
         sugar
     }
 
+    #[inline]
     pub fn set_ime(&mut self, ime_preedit: Option<&Preedit>) {
-        if let Some(preedit) = ime_preedit {
-            if let Some(content) = preedit.text.chars().next() {
-                self.cursor.content = content;
-                self.is_ime_enabled = true;
-                return;
-            }
+        self.ime_preedit = ime_preedit.cloned();
+    }
+
+    #[inline]
+    fn create_preedit_sugar(&self, cell: &PreeditCell) -> Sugar {
+        let (foreground_color, background_color) = if cell.cursor {
+            (self.named_colors.background.0, self.named_colors.cursor)
+        } else {
+            (self.named_colors.foreground, self.named_colors.background.0)
+        };
+
+        Sugar {
+            content: if cell.spacer { ' ' } else { cell.c },
+            foreground_color,
+            background_color,
+            style: None,
+            decoration: Some(SugarDecoration {
+                position: (0.0, 0.92),
+                size: (1.0, 0.05),
+                color: self.named_colors.foreground,
+            }),
         }
+    }
+
+    /// Draw the IME preedit inline from the cursor column, the following
+    /// cells are pushed to the right and the ones going past the last
+    /// column are not drawn.
+    #[inline]
+    fn draw_preedit(&self, stack: &mut SugarStack) {
+        let preedit = match &self.ime_preedit {
+            Some(preedit) => preedit,
+            None => return,
+        };
+
+        let columns = stack.len();
+        let cells = preedit.cells();
+        let start = ime::preedit_start(self.cursor.state.pos.col.0, cells.len(), columns);
+        let sugars: Vec<Sugar> = cells
+            .iter()
+            .take(columns)
+            .map(|cell| self.create_preedit_sugar(cell))
+            .collect();
+        stack.splice(start..start, sugars);
+        stack.truncate(columns);
+    }
+
+    /// Lines drawn over the bottom of the terminal, e.g. a permission
+    /// request or the clipboard history picker. `highlight` is an index
+    /// into `lines`.
+    #[inline]
+    pub fn set_overlay(&mut self, lines: Vec<String>, highlight: Option<usize>) {
+        self.overlay = lines;
+        self.overlay_highlight = highlight;
+    }
+
+    #[inline]
+    fn create_overlay_sugar_stack(
+        &self,
+        line: &str,
+        highlight: bool,
+        columns: usize,
+    ) -> SugarStack {
+        let background_color = if highlight {
+            self.named_colors.yellow
+        } else {
+            self.named_colors.foreground
+        };
 
-        self.is_ime_enabled = false;
-        self.cursor.content = self.cursor.content_ref;
+        let mut content = line.chars();
+        let mut stack: Vec<Sugar> = vec![];
+        for _ in 0..columns {
+            stack.push(Sugar {
+                content: content.next().unwrap_or(' '),
+                foreground_color: self.named_colors.background.0,
+                background_color,
+                style: None,
+                decoration: None,
+            })
+        }
+        stack
     }
 
     #[inline]
@@ -370,64 +476,171 @@ impl State {
         self.selection_range = selection_range;
     }
 
+    /// Border cells between panes, `rect` is the border itself.
+    fn create_border_sugar_stacks(&self, border: &PaneBorder) -> Vec<SugarStack> {
+        let sugar = |content: char| Sugar {
+            content,
+            foreground_color: self.named_colors.split,
+            background_color: self.named_colors.background.0,
+            style: None,
+            decoration: None,
+        };
+
+        match border.direction {
+            SplitDirection::Right => {
+                (0..border.rect.lines).map(|_| vec![sugar('│')]).collect()
+            }
+            SplitDirection::Down => {
+                vec![(0..border.rect.columns).map(|_| sugar('─')).collect()]
+            }
+        }
+    }
+
+    /// Rows of `pane`, the cursor and the IME preedit are only drawn in
+    /// the focused one.
+    fn stack_pane(&mut self, pane: &Pane, lines: usize, sugarloaf: &mut Sugarloaf) {
+        self.cursor.state = pane.cursor.clone();
+        // The preedit draws its own cursor.
+        let is_cursor_visible =
+            pane.focused && self.cursor.state.is_visible() && self.ime_preedit.is_none();
+
+        for (i, row) in pane.rows.iter().take(lines).enumerate() {
+            let has_cursor = is_cursor_visible && self.cursor.state.pos.row == i;
+            let mut sugar_stack = match &pane.selection {
+                Some(selection) => self.create_sugar_stack_with_selection(
+                    row,
+                    has_cursor,
+                    selection,
+                    pos::Line(i as i32),
+                    pane.display_offset,
+                ),
+                None => self.create_sugar_stack(row, has_cursor),
+            };
+            if pane.focused && self.cursor.state.pos.row == i {
+                self.draw_preedit(&mut sugar_stack);
+            }
+            sugarloaf.stack(sugar_stack);
+        }
+    }
+
     #[inline]
     pub fn update(
         &mut self,
-        rows: Vec<Row<Square>>,
-        cursor: CursorState,
+        panes: Vec<Pane>,
+        borders: Vec<PaneBorder>,
         sugarloaf: &mut Sugarloaf,
         context_manager: &context::ContextManager<EventProxy>,
-        display_offset: i32,
     ) {
-        self.cursor.state = cursor;
-        let is_cursor_visible = self.cursor.state.is_visible();
+        let columns = sugarloaf.layout.columns;
+        let lines = sugarloaf.layout.lines;
+        let viewport = |line: usize, rect: &PaneRect, rect_lines: usize| SugarViewport {
+            column: rect.column,
+            line: line + rect.line,
+            columns: rect.columns,
+            lines: rect_lines,
+        };
 
-        if let Some(active_selection) = self.selection_range {
-            for (i, row) in rows.iter().enumerate() {
-                let has_cursor = is_cursor_visible && self.cursor.state.pos.row == i;
-                let sugar_stack = self.create_sugar_stack_with_selection(
-                    row,
-                    has_cursor,
-                    &active_selection,
-                    pos::Line(i as i32),
-                    display_offset,
-                );
+        // Overlay lines replace the last lines of the grid, keeping the
+        // bottom most ones when there is not enough room.
+        let overlay_size = self.overlay.len().min(lines);
+        let overlay_start = lines - overlay_size;
+        let skipped = self.overlay.len() - overlay_size;
+        let overlay: Vec<SugarStack> = self
+            .overlay
+            .iter()
+            .enumerate()
+            .skip(skipped)
+            .map(|(i, line)| {
+                let highlight = self.overlay_highlight == Some(i);
+                self.create_overlay_sugar_stack(line, highlight, columns)
+            })
+            .collect();
+
+        let tab_bar = if self.tab_bar_lines(context_manager.len()) > 0 {
+            Some(self.create_tab_bar_sugar_stack(context_manager, columns))
+        } else {
+            None
+        };
+        let whole_line = |line: usize| SugarViewport {
+            column: 0,
+            line,
+            columns,
+            lines: 1,
+        };
+
+        let reserved_lines = usize::from(tab_bar.is_some());
+        // Grid lines start below a top tab bar.
+        let mut first_line = 0;
+        let mut bottom_tab_bar = None;
+        match (self.tab_bar_position, tab_bar) {
+            (TabBarPosition::Top, Some(tab_bar)) => {
+                sugarloaf.set_viewport(Some(whole_line(0)));
+                sugarloaf.stack(tab_bar);
+                first_line = 1;
+            }
+            (TabBarPosition::Bottom, Some(tab_bar)) => bottom_tab_bar = Some(tab_bar),
+            (_, None) => (),
+        }
+
+        let mut focused_cursor = None;
+        for pane in panes.iter() {
+            let pane_lines = pane
+                .rect
+                .lines
+                .min(overlay_start.saturating_sub(pane.rect.line));
+            if pane.focused {
+                focused_cursor = Some(pane.cursor.clone());
+            }
+            if pane_lines == 0 {
+                continue;
+            }
+
+            sugarloaf.set_viewport(Some(viewport(first_line, &pane.rect, pane_lines)));
+            self.stack_pane(pane, pane_lines, sugarloaf);
+        }
+        if let Some(cursor) = focused_cursor {
+            self.cursor.state = cursor;
+        }
+
+        for border in borders.iter() {
+            let border_lines = border
+                .rect
+                .lines
+                .min(overlay_start.saturating_sub(border.rect.line));
+            if border_lines == 0 {
+                continue;
+            }
+
+            sugarloaf.set_viewport(Some(viewport(
+                first_line,
+                &border.rect,
+                border_lines,
+            )));
+            for sugar_stack in self.create_border_sugar_stacks(border) {
                 sugarloaf.stack(sugar_stack);
             }
-        } else {
-            for (i, row) in rows.iter().enumerate() {
-                let has_cursor = is_cursor_visible && self.cursor.state.pos.row == i;
-                let sugar_stack = self.create_sugar_stack(row, has_cursor);
+        }
+
+        if !overlay.is_empty() {
+            sugarloaf.set_viewport(Some(SugarViewport {
+                column: 0,
+                line: first_line + overlay_start,
+                columns,
+                lines: overlay.len(),
+            }));
+            for sugar_stack in overlay {
                 sugarloaf.stack(sugar_stack);
             }
         }
 
+        if let Some(tab_bar) = bottom_tab_bar {
+            sugarloaf.set_viewport(Some(whole_line(lines)));
+            sugarloaf.stack(tab_bar);
+        }
+
         // This is a fake row created only for visual purposes
-        let empty_last_line =
-            self.create_empty_sugar_stack_from_columns(sugarloaf.layout.columns);
+        let empty_last_line = self.create_empty_sugar_stack_from_columns(columns);
+        sugarloaf.set_viewport(Some(whole_line(lines + reserved_lines)));
         sugarloaf.stack(empty_last_line);
-
-        if context_manager.len() > 1 {
-            let mut renderable_tabs = vec![];
-            let mut initial_position =
-                (sugarloaf.layout.width / sugarloaf.layout.scale_factor) - PADDING_X_TABS;
-            let position_modifier = 20.;
-            for (i, _) in context_manager.contexts().iter().enumerate() {
-                let mut color = self.named_colors.tabs;
-                let mut size = constants::INACTIVE_TAB_WIDTH_SIZE;
-                if i == context_manager.current_index() {
-                    color = self.named_colors.tabs_active;
-                    size = constants::ACTIVE_TAB_WIDTH_SIZE;
-                }
-                let renderable = Rect {
-                    position: [initial_position, 0.0],
-                    color,
-                    size: [30.0, size],
-                };
-                initial_position -= position_modifier;
-                renderable_tabs.push(renderable);
-            }
-            sugarloaf.pile_rect(renderable_tabs);
-        }
     }
 }
@@ -7,7 +7,12 @@ pub const DEFAULT_MINIMUM_WINDOW_WIDTH: i32 = 300;
 pub const DEFAULT_HEIGHT: i32 = 400;
 pub const DEFAULT_WIDTH: i32 = 600;
 
-pub fn create_window_builder(title: &str) -> winit::window::WindowBuilder {
+/// Builder of every window, `class` is the window class on X11 and the app
+/// id on Wayland.
+pub fn create_window_builder(
+    title: &str,
+    #[allow(unused_variables)] class: Option<&str>,
+) -> winit::window::WindowBuilder {
     use winit::window::Icon;
 
     let image_icon = image::load_from_memory(LOGO_ICON).unwrap();
@@ -43,5 +48,17 @@ pub fn create_window_builder(title: &str) -> winit::window::WindowBuilder {
             .with_fullsize_content_view(true);
     }
 
+    #[cfg(all(feature = "x11", not(any(target_os = "macos", windows))))]
+    if let Some(class) = class {
+        use winit::platform::x11::WindowBuilderExtX11;
+        window_builder = window_builder.with_name(class, class);
+    }
+
+    #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
+    if let Some(class) = class {
+        use winit::platform::wayland::WindowBuilderExtWayland;
+        window_builder = window_builder.with_name(class, class);
+    }
+
     window_builder
 }
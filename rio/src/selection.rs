@@ -7,6 +7,7 @@
 //! when text is added/removed/scrolled on the screen. The selection should
 //! also be cleared if the user clicks off of the selection.
 
+use regex::Regex;
 use std::cmp::min;
 use std::mem;
 use std::ops::{Bound, Range, RangeBounds};
@@ -18,6 +19,48 @@ use crate::crosswords::square::{Flags, Square};
 use crate::crosswords::Crosswords;
 use crate::event::EventListener;
 
+/// Rules used to expand semantic selections.
+///
+/// Smart rules are tried in order, the first regex matching around the
+/// clicked point wins over the plain expansion by `semantic_escape_chars`.
+#[derive(Debug, Clone)]
+pub struct SelectionRules {
+    pub semantic_escape_chars: String,
+    pub smart_rules: Vec<Regex>,
+}
+
+impl SelectionRules {
+    pub fn new(semantic_escape_chars: String, smart_rules: &[String]) -> Self {
+        let smart_rules = smart_rules
+            .iter()
+            .filter_map(|rule| match Regex::new(rule) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    log::warn!("invalid smart selection rule {rule:?}: {err}");
+                    None
+                }
+            })
+            .collect();
+
+        SelectionRules {
+            semantic_escape_chars,
+            smart_rules,
+        }
+    }
+}
+
+impl From<&config::Selection> for SelectionRules {
+    fn from(config: &config::Selection) -> Self {
+        SelectionRules::new(config.semantic_escape_chars.clone(), &config.smart_rules)
+    }
+}
+
+impl Default for SelectionRules {
+    fn default() -> Self {
+        SelectionRules::from(&config::Selection::default())
+    }
+}
+
 /// A Pos and side within that point.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Anchor {
@@ -126,6 +169,9 @@ pub enum SelectionType {
 pub struct Selection {
     pub ty: SelectionType,
     region: Range<Anchor>,
+    /// Smart selection match around the starting point of a semantic
+    /// selection, looked up once by [`Selection::find_smart`].
+    smart: Option<(Pos, Pos)>,
 }
 
 impl Selection {
@@ -136,6 +182,15 @@ impl Selection {
                 end: Anchor::new(location, side),
             },
             ty,
+            smart: None,
+        }
+    }
+
+    /// Run the smart selection rules around the starting point, semantic
+    /// selections then keep at least the matched text while being extended.
+    pub fn find_smart<T: EventListener>(&mut self, term: &Crosswords<T>) {
+        if self.ty == SelectionType::Semantic {
+            self.smart = term.smart_search(self.region.start.point);
         }
     }
 
@@ -154,6 +209,18 @@ impl Selection {
         let range_bottom = range.end;
         let range_top = range.start;
 
+        // Drop the smart match unless it moves along with the region.
+        let inside =
+            |row: Line| (row >= range_top || range_top == 0) && row < range_bottom;
+        self.smart = self.smart.and_then(|(start, end)| {
+            if !inside(start.row) || !inside(end.row) {
+                return None;
+            }
+            let start = Pos::new(start.row - delta, start.col);
+            let end = Pos::new(end.row - delta, end.col);
+            (inside(start.row) && inside(end.row)).then_some((start, end))
+        });
+
         let (mut start, mut end) = (&mut self.region.start, &mut self.region.end);
         if start.point > end.point {
             mem::swap(&mut start, &mut end);
@@ -307,12 +374,13 @@ impl Selection {
             SelectionType::Block => self.range_block(start, end),
             SelectionType::Lines => Some(Self::range_lines(term, start.point, end.point)),
             SelectionType::Semantic => {
-                Some(Self::range_semantic(term, start.point, end.point))
+                Some(self.range_semantic(term, start.point, end.point))
             }
         }
     }
 
     fn range_semantic<T: EventListener>(
+        &self,
         term: &Crosswords<T>,
         mut start: Pos,
         mut end: Pos,
@@ -335,8 +403,19 @@ impl Selection {
             }
         }
 
-        let start = term.semantic_search_left(start);
-        let end = term.semantic_search_right(end);
+        let smart = |point: Pos| {
+            self.smart.filter(|(smart_start, smart_end)| {
+                *smart_start <= point && point <= *smart_end
+            })
+        };
+        let start = match smart(start) {
+            Some((start, _)) => start,
+            None => term.semantic_search_left(start),
+        };
+        let end = match smart(end) {
+            Some((_, end)) => end,
+            None => term.semantic_search_right(end),
+        };
 
         SelectionRange {
             start,
@@ -653,6 +732,170 @@ mod tests {
         );
     }
 
+    /// Double click `needle` in `text` and return the selected string.
+    fn double_click(text: &str, columns: usize, needle: &str) -> String {
+        use crate::performer::handler::Handler;
+
+        let mut term = term(3, columns);
+        for c in text.chars() {
+            term.input(c);
+        }
+
+        let index = text.find(needle).unwrap();
+        let point = Pos::new(Line((index / columns) as i32), Column(index % columns));
+        let mut selection = Selection::new(SelectionType::Semantic, point, Side::Left);
+        selection.update(point, Side::Right);
+        selection.find_smart(&term);
+        term.selection = Some(selection);
+        term.selection_to_string().unwrap()
+    }
+
+    #[test]
+    fn smart_selection_url() {
+        assert_eq!(
+            double_click(
+                "see https://github.com/raphamorim/rio/issues?q=is:open, ok",
+                80,
+                "raphamorim"
+            ),
+            "https://github.com/raphamorim/rio/issues?q=is:open"
+        );
+    }
+
+    #[test]
+    fn link_at_url() {
+        use crate::performer::handler::Handler;
+
+        let mut term = term(1, 40);
+        for c in "open https://raphamorim.io/rio now".chars() {
+            term.input(c);
+        }
+
+        assert_eq!(
+            term.link_at(Pos::new(Line(0), Column(12))).as_deref(),
+            Some("https://raphamorim.io/rio")
+        );
+        assert_eq!(term.link_at(Pos::new(Line(0), Column(1))), None);
+    }
+
+    #[test]
+    fn smart_selection_wrapped_url() {
+        assert_eq!(
+            double_click("go to https://raphamorim.io/rio/docs now", 16, "docs"),
+            "https://raphamorim.io/rio/docs"
+        );
+    }
+
+    #[test]
+    fn smart_selection_path_line_column() {
+        assert_eq!(
+            double_click("error: src/screen/mod.rs:120:5: oops", 80, "mod"),
+            "src/screen/mod.rs:120:5"
+        );
+        assert_eq!(
+            double_click("at ./main.go:7 (x)", 80, "main"),
+            "./main.go:7"
+        );
+    }
+
+    #[test]
+    fn smart_selection_email() {
+        assert_eq!(
+            double_click("author: <rapha850@gmail.com>", 80, "gmail"),
+            "rapha850@gmail.com"
+        );
+    }
+
+    #[test]
+    fn smart_selection_uuid() {
+        assert_eq!(
+            double_click("id=123e4567-e89b-12d3-a456-426614174000;", 80, "e89b"),
+            "123e4567-e89b-12d3-a456-426614174000"
+        );
+    }
+
+    #[test]
+    fn smart_selection_ipv6() {
+        assert_eq!(
+            double_click("inet6 fe80::1ff:fe23:4567:890a/64", 80, "fe23"),
+            "fe80::1ff:fe23:4567:890a"
+        );
+        assert_eq!(
+            double_click("ssh [2001:db8:85a3:0:0:8a2e:370:7334]", 80, "85a3"),
+            "2001:db8:85a3:0:0:8a2e:370:7334"
+        );
+    }
+
+    #[test]
+    fn smart_selection_quoted_string() {
+        assert_eq!(
+            double_click("git commit -m \"fix the bug\"", 80, "\"fix"),
+            "\"fix the bug\""
+        );
+        assert_eq!(
+            double_click("git commit -m \"fix the bug\"", 80, "the"),
+            "the"
+        );
+        assert_eq!(
+            double_click("echo \"see src/main.rs:12\"", 80, "main"),
+            "src/main.rs:12"
+        );
+    }
+
+    #[test]
+    fn smart_selection_found_once() {
+        use crate::performer::handler::Handler;
+
+        let mut term = term(1, 40);
+        for c in "see https://raphamorim.io/rio ok".chars() {
+            term.input(c);
+        }
+
+        let point = Pos::new(Line(0), Column(12));
+        let mut selection = Selection::new(SelectionType::Semantic, point, Side::Left);
+        selection.find_smart(&term);
+        term.set_selection_rules(std::sync::Arc::new(SelectionRules::new(
+            String::from(" "),
+            &[],
+        )));
+        term.selection = Some(selection);
+        assert_eq!(
+            term.selection_to_string().unwrap(),
+            "https://raphamorim.io/rio"
+        );
+    }
+
+    #[test]
+    fn smart_selection_falls_back_to_semantic() {
+        assert_eq!(double_click("cargo build --release", 80, "build"), "build");
+        assert_eq!(double_click("a,b,c", 80, "b"), "b");
+    }
+
+    #[test]
+    fn custom_semantic_escape_chars() {
+        use crate::performer::handler::Handler;
+
+        let mut term = term(1, 20);
+        term.set_selection_rules(std::sync::Arc::new(SelectionRules::new(
+            String::from(" "),
+            &[],
+        )));
+        for c in "key:value x".chars() {
+            term.input(c);
+        }
+
+        let point = Pos::new(Line(0), Column(1));
+        term.selection = Some(Selection::new(SelectionType::Semantic, point, Side::Left));
+        assert_eq!(term.selection_to_string().unwrap(), "key:value");
+    }
+
+    #[test]
+    fn invalid_smart_rules_are_skipped() {
+        let rules =
+            SelectionRules::new(String::new(), &[String::from("("), String::from("a+")]);
+        assert_eq!(rules.smart_rules.len(), 1);
+    }
+
     #[test]
     fn block_selection() {
         let size = (10, 5);
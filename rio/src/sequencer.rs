@@ -1,17 +1,23 @@
 #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
 use {
     wayland_client::protocol::wl_surface::WlSurface,
-    wayland_client::{Display as WaylandDisplay, Proxy},
+    wayland_client::{Attached, Display as WaylandDisplay, EventQueue, Proxy},
     winit::platform::wayland::{EventLoopWindowTargetExtWayland, WindowExtWayland},
 };
 
-use crate::clipboard::ClipboardType;
+use crate::cli::WindowOptions;
+use crate::clipboard::ClipboardRequest;
 use crate::event::{ClickState, EventP, EventProxy, RioEvent, RioEventType};
 use crate::ime::Preedit;
+use crate::ipc::{self, Reply, Request, TabInfo};
 use crate::scheduler::{Scheduler, TimerId, Topic};
-use crate::screen::{window::create_window_builder, Screen};
+use crate::screen::{
+    mouse, window::create_window_builder, Backend, CloseRequest, Launch, Screen,
+};
+use crate::session::{self, SessionSnapshot, WindowSnapshot};
 use crate::utils::watch::watch;
 use colors::ColorRgb;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::error::Error;
 use std::os::raw::c_void;
@@ -20,53 +26,235 @@ use std::time::{Duration, Instant};
 use winit::event::{
     ElementState, Event, Ime, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent,
 };
-use winit::event_loop::{DeviceEventFilter, EventLoop};
+use winit::event_loop::{DeviceEventFilter, EventLoop, EventLoopWindowTarget};
 use winit::platform::run_return::EventLoopExtRunReturn;
 use winit::window::{CursorIcon, ImePurpose, Window, WindowId};
 
+/// How often the programs running in the foreground of the tabs are looked
+/// for, they show up in the tab titles.
+const FOREGROUND_PROCESSES_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Window along with the screen drawn into it.
+struct SequencerWindow {
+    window: Window,
+    screen: Screen,
+    /// Order the window was opened in, windows are saved into the session
+    /// following it.
+    index: usize,
+    is_focused: bool,
+    is_occluded: bool,
+    has_render_updates: bool,
+    #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
+    _wayland_surface: Option<Attached<WlSurface>>,
+}
+
+impl SequencerWindow {
+    #[inline]
+    fn render(&mut self) {
+        self.screen.render();
+        if let Some(position) = self.screen.take_ime_position() {
+            self.window.set_ime_position(position);
+        }
+    }
+}
+
 pub struct Sequencer {
     config: Rc<config::Config>,
-    windows: HashMap<WindowId, Window>,
-    is_window_focused: bool,
-    has_render_updates: bool,
-    is_occluded: bool,
+    /// Config file given on the command line, used on reloads as well.
+    config_file: Option<String>,
+    /// `key=value` overrides given on the command line, applied on every
+    /// reload.
+    config_overrides: Vec<String>,
+    /// Title and class given on the command line to every window.
+    window_title: Option<String>,
+    window_class: Option<String>,
+    windows: HashMap<WindowId, SequencerWindow>,
+    /// Windows opened so far, used to order them.
+    opened_windows: usize,
+    #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
+    wayland_event_queue: Option<EventQueue>,
     #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
     has_wayland_forcefully_reloaded: bool,
 }
 
 impl Sequencer {
-    pub fn new(config: config::Config) -> Sequencer {
+    pub fn new(
+        config: config::Config,
+        config_file: Option<String>,
+        config_overrides: Vec<String>,
+    ) -> Sequencer {
         Sequencer {
             config: Rc::new(config),
-            is_window_focused: false,
-            has_render_updates: false,
-            is_occluded: false,
+            config_file,
+            config_overrides,
+            window_title: None,
+            window_class: None,
             windows: HashMap::new(),
+            opened_windows: 0,
+            #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
+            wayland_event_queue: None,
             #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
             has_wayland_forcefully_reloaded: false,
         }
     }
 
-    pub async fn run(
+    /// Session to restore, each of its windows into a window of its own.
+    /// There is none to restore when a command, a working directory or
+    /// another backend was given.
+    fn load_session(&self, launch: &Launch) -> Option<SessionSnapshot> {
+        if !self.config.session.restore
+            || launch.backend != Backend::Pty
+            || !launch.command.is_empty()
+            || launch.working_directory.is_some()
+        {
+            return None;
+        }
+
+        match session::load(config::session_file_path()) {
+            Ok(session) => Some(session),
+            Err(err) => {
+                log::warn!("{err}");
+                None
+            }
+        }
+    }
+
+    /// Windows in the order they were opened.
+    fn ordered_windows(&self) -> Vec<(&WindowId, &SequencerWindow)> {
+        let mut windows: Vec<(&WindowId, &SequencerWindow)> =
+            self.windows.iter().collect();
+        windows.sort_by_key(|(_, window)| window.index);
+        windows
+    }
+
+    /// Window with the id `window`, or else the focused one, or else the
+    /// first one opened.
+    fn target_window(&mut self, window: Option<u64>) -> Option<&mut SequencerWindow> {
+        match window {
+            Some(window) => self
+                .windows
+                .iter_mut()
+                .find(|(id, _)| u64::from(**id) == window)
+                .map(|(_, window)| window),
+            None => self
+                .windows
+                .values_mut()
+                .max_by_key(|window| (window.is_focused, Reverse(window.index))),
+        }
+    }
+
+    fn reload_config(&mut self) {
+        let config = config::Config::load_with(
+            self.config_file.as_deref(),
+            &self.config_overrides,
+        );
+        self.config = config.into();
+        for window in self.windows.values_mut() {
+            window.screen.update_config(&self.config);
+            window.has_render_updates = true;
+        }
+    }
+
+    /// Close the window `window_id`, returns true when it was the last one.
+    fn close_window(&mut self, window_id: WindowId, scheduler: &mut Scheduler) -> bool {
+        // The last window is kept in the session, the ones closed before it
+        // are taken out of it.
+        let last = self.windows.len() == 1;
+        if self.config.session.restore && last {
+            self.save_session();
+        }
+        self.windows.remove(&window_id);
+        scheduler.unschedule_window(u64::from(window_id));
+        if self.config.session.restore && !last {
+            self.save_session();
+        }
+        self.windows.is_empty()
+    }
+
+    /// Ask the user in the window `window_id`, or else in any window,
+    /// before quitting when programs run in the tabs. Returns false when
+    /// there is nothing to ask.
+    fn ask_quit(&mut self, window_id: Option<WindowId>) -> bool {
+        let mut processes = vec![];
+        for window in self.windows.values_mut() {
+            processes.extend(window.screen.busy_processes(CloseRequest::Quit));
+        }
+        if processes.is_empty() {
+            return false;
+        }
+        processes.sort();
+        processes.dedup();
+
+        let window =
+            match window_id.and_then(|window_id| self.windows.get_mut(&window_id)) {
+                Some(window) => window,
+                None => match self.windows.values_mut().next() {
+                    Some(window) => window,
+                    None => return false,
+                },
+            };
+        window.screen.ask_close(CloseRequest::Quit, &processes);
+        window.has_render_updates = true;
+        true
+    }
+
+    /// Write the tabs of every window into the session file, in the order
+    /// the windows were opened.
+    fn save_session(&self) {
+        let windows: Vec<WindowSnapshot> = self
+            .ordered_windows()
+            .into_iter()
+            // The server keeps the sessions of the attached windows.
+            .filter(|(_, window)| {
+                !matches!(
+                    window.screen.ctx().defaults().backend,
+                    Backend::Server { .. }
+                )
+            })
+            .map(|(_, window)| window.screen.ctx().session_snapshot())
+            .collect();
+        if windows.is_empty() {
+            return;
+        }
+
+        let snapshot = SessionSnapshot::new(windows);
+        if let Err(err) = session::save(config::session_file_path(), &snapshot) {
+            log::error!("{err}");
+        }
+    }
+
+    /// Open a window from inside the event loop, running what `launch`
+    /// describes.
+    fn open_window(
         &mut self,
-        mut event_loop: EventLoop<EventP>,
-        command: Vec<String>,
-    ) -> Result<(), Box<dyn Error>> {
-        #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
-        let mut wayland_event_queue = event_loop.wayland_display().map(|display| {
-            let display = unsafe { WaylandDisplay::from_external_display(display as _) };
-            display.create_event_queue()
-        });
+        event_loop: &EventLoopWindowTarget<EventP>,
+        event_proxy: &EventProxy,
+        launch: &Launch,
+    ) -> Result<WindowId, Box<dyn Error>> {
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.create_window(
+                event_loop,
+                event_proxy,
+                launch,
+                None,
+            ))
+        })
+    }
 
-        let proxy = event_loop.create_proxy();
-        let event_proxy = EventProxy::new(proxy.clone());
-        let event_proxy_clone = event_proxy.clone();
-        let mut scheduler = Scheduler::new(proxy);
-        let window_builder = create_window_builder("Rio");
-        let winit_window = window_builder.build(&event_loop).unwrap();
+    async fn create_window(
+        &mut self,
+        event_loop: &EventLoopWindowTarget<EventP>,
+        event_proxy: &EventProxy,
+        launch: &Launch,
+        session: Option<WindowSnapshot>,
+    ) -> Result<WindowId, Box<dyn Error>> {
+        let window_builder = create_window_builder(
+            self.window_title.as_deref().unwrap_or("Rio"),
+            self.window_class.as_deref(),
+        );
+        let winit_window = window_builder.build(event_loop)?;
 
-        let current_mouse_cursor = CursorIcon::Text;
-        winit_window.set_cursor_icon(current_mouse_cursor);
+        winit_window.set_cursor_icon(CursorIcon::Text);
 
         // https://docs.rs/winit/latest/winit;/window/enum.ImePurpose.html#variant.Terminal
         winit_window.set_ime_purpose(ImePurpose::Terminal);
@@ -74,9 +262,6 @@ impl Sequencer {
 
         winit_window.set_transparent(self.config.window_opacity < 1.);
 
-        // TODO: Update ime position based on cursor
-        // winit_window.set_ime_position(winit::dpi::PhysicalPosition::new(500.0, 500.0));
-
         // This will ignore diacritical marks and accent characters from
         // being processed as received characters. Instead, the input
         // device's raw character will be placed in event queues with the
@@ -98,13 +283,14 @@ impl Sequencer {
         }
 
         #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
-        let _wayland_surface = if event_loop.is_wayland() {
+        let wayland_surface = match self.wayland_event_queue.as_ref() {
             // Attach surface to Rio internal wayland queue to handle frame callbacks.
-            let surface = winit_window.wayland_surface().unwrap();
-            let proxy: Proxy<WlSurface> = unsafe { Proxy::from_c_ptr(surface as _) };
-            Some(proxy.attach(wayland_event_queue.as_ref().unwrap().token()))
-        } else {
-            None
+            Some(queue) if event_loop.is_wayland() => {
+                let surface = winit_window.wayland_surface().unwrap();
+                let proxy: Proxy<WlSurface> = unsafe { Proxy::from_c_ptr(surface as _) };
+                Some(proxy.attach(queue.token()))
+            }
+            _ => None,
         };
 
         #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
@@ -112,110 +298,441 @@ impl Sequencer {
         #[cfg(any(not(feature = "wayland"), target_os = "macos", windows))]
         let display: Option<*mut c_void> = Option::None;
 
-        let _ = watch(config::config_dir_path(), event_proxy_clone);
-        let mut screen =
-            Screen::new(&winit_window, &self.config, event_proxy, display, command)
-                .await?;
+        let window_id = winit_window.id();
+        let mut screen = Screen::new(
+            &winit_window,
+            &self.config,
+            event_proxy.for_window(window_id),
+            display,
+            launch,
+            session,
+        )
+        .await?;
 
         screen.init(self.config.colors.background.1);
-        event_loop.set_device_event_filter(DeviceEventFilter::Always);
 
-        // let mut windows = HashMap::new();
-        // let mut create_window = || {
-        //     let window_builder = create_window_builder("Rio");
-        //     let winit_window = window_builder.build(&event_loop).unwrap();
-        //     windows.insert(winit_window.id(), winit_window);
-        // };
+        self.windows.insert(
+            window_id,
+            SequencerWindow {
+                window: winit_window,
+                screen,
+                index: self.opened_windows,
+                is_focused: false,
+                is_occluded: false,
+                has_render_updates: false,
+                #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
+                _wayland_surface: wayland_surface,
+            },
+        );
+        self.opened_windows += 1;
+
+        Ok(window_id)
+    }
 
-        event_loop.run_return(move |event, _, control_flow| {
+    /// Answer a request received on the control socket.
+    fn handle_ipc(
+        &mut self,
+        request: Request,
+        event_loop: &EventLoopWindowTarget<EventP>,
+        event_proxy: &EventProxy,
+    ) -> Reply {
+        let window_not_found = || Reply::error("window not found");
+        match request {
+            Request::CreateWindow {
+                working_directory,
+                command,
+            } => {
+                let launch = Launch {
+                    command,
+                    working_directory,
+                    ..Launch::default()
+                };
+                match self.open_window(event_loop, event_proxy, &launch) {
+                    Ok(_) => Reply::Ok,
+                    Err(err) => {
+                        Reply::error(format!("unable to create a new window: {err}"))
+                    }
+                }
+            }
+            Request::CreateTab {
+                window,
+                working_directory,
+                command,
+            } => match self.target_window(window) {
+                Some(window) => {
+                    if window
+                        .screen
+                        .create_tab(working_directory.as_deref(), &command)
+                    {
+                        window.has_render_updates = true;
+                        Reply::Ok
+                    } else {
+                        Reply::error("there is no room for another tab")
+                    }
+                }
+                None => window_not_found(),
+            },
+            Request::SendText { window, tab, text } => match self.target_window(window) {
+                Some(window) => {
+                    if window.screen.send_text(tab, &text) {
+                        Reply::Ok
+                    } else {
+                        Reply::error("tab not found")
+                    }
+                }
+                None => window_not_found(),
+            },
+            Request::ListTabs => {
+                let mut tabs = vec![];
+                for (window_id, window) in self.ordered_windows() {
+                    let context_manager = window.screen.ctx();
+                    for (index, tab) in context_manager.tabs().iter().enumerate() {
+                        tabs.push(TabInfo {
+                            window: u64::from(*window_id),
+                            index,
+                            title: tab.title().to_string(),
+                            working_directory: tab.current().working_directory(),
+                            process: tab.current().foreground_process().cloned(),
+                            current: index == context_manager.current_index(),
+                        });
+                    }
+                }
+                Reply::Tabs { tabs }
+            }
+            Request::FocusTab { window, tab } => match self.target_window(window) {
+                Some(window) => {
+                    if !window.screen.focus_tab(tab) {
+                        return Reply::error("tab not found");
+                    }
+                    window.window.focus_window();
+                    window.has_render_updates = true;
+                    Reply::Ok
+                }
+                None => window_not_found(),
+            },
+            Request::ReloadConfig => {
+                self.reload_config();
+                Reply::Ok
+            }
+            Request::Export {
+                window,
+                tab,
+                scope,
+                format,
+            } => {
+                let (scope, format) = match (scope.parse(), format.parse()) {
+                    (Ok(scope), Ok(format)) => (scope, format),
+                    (Err(err), _) | (_, Err(err)) => return Reply::error(err),
+                };
+                match self.target_window(window) {
+                    Some(window) => match window.screen.export_tab(tab, scope, format) {
+                        Some(content) => Reply::Export { content },
+                        None => Reply::error("tab not found"),
+                    },
+                    None => window_not_found(),
+                }
+            }
+        }
+    }
+
+    pub async fn run(
+        &mut self,
+        mut event_loop: EventLoop<EventP>,
+        window_options: WindowOptions,
+        attach: Vec<Launch>,
+    ) -> Result<(), Box<dyn Error>> {
+        #[cfg(all(feature = "wayland", not(any(target_os = "macos", windows))))]
+        {
+            self.wayland_event_queue = event_loop.wayland_display().map(|display| {
+                let display =
+                    unsafe { WaylandDisplay::from_external_display(display as _) };
+                display.create_event_queue()
+            });
+        }
+
+        let proxy = event_loop.create_proxy();
+        let event_proxy = EventProxy::new(proxy.clone());
+        let ipc_proxy = proxy.clone();
+        let mut scheduler = Scheduler::new(proxy);
+
+        // Requests are answered from the event loop, the connection waits
+        // for the reply.
+        let socket = ipc::socket_path(std::process::id());
+        let mut ipc_listener = match ipc::listen(&socket, move |request| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let event = EventP::new(RioEventType::Rio(RioEvent::Ipc(request, sender)));
+            if ipc_proxy.send_event(event).is_err() {
+                return Reply::error("rio is shutting down");
+            }
+            receiver
+                .recv()
+                .unwrap_or_else(|_| Reply::error("request was dropped"))
+        }) {
+            Ok(listener) => {
+                std::env::set_var(ipc::SOCKET_ENV, listener.path());
+                Some(listener)
+            }
+            Err(err) => {
+                log::warn!("unable to open the control socket: {err}");
+                None
+            }
+        };
+
+        let config_dir = match &self.config_file {
+            Some(config_file) => std::path::Path::new(config_file)
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .map_or_else(
+                    || String::from("."),
+                    |dir| dir.to_string_lossy().to_string(),
+                ),
+            None => config::config_dir_path(),
+        };
+        let _ = watch(config_dir, event_proxy.clone());
+
+        self.window_title = window_options.title;
+        self.window_class = window_options.class;
+        let terminal_options = window_options.terminal_options;
+        // Sessions of a server get a tab each, see `rio attach`.
+        let mut attach = attach.into_iter();
+        let launch = attach.next().unwrap_or_else(|| Launch {
+            backend: terminal_options.backend(),
+            command: terminal_options.command,
+            working_directory: terminal_options.working_directory,
+            exit_behavior: terminal_options.hold.then_some(config::ExitBehavior::Hold),
+            envs: vec![],
+        });
+        let mut windows = self
+            .load_session(&launch)
+            .map(|session| session.windows)
+            .unwrap_or_default()
+            .into_iter();
+        let window_id = self
+            .create_window(&event_loop, &event_proxy, &launch, windows.next())
+            .await?;
+        if let Some(window) = self.windows.get_mut(&window_id) {
+            for launch in attach {
+                window.screen.open_tab(&launch);
+            }
+        }
+        for snapshot in windows {
+            if let Err(err) = self
+                .create_window(&event_loop, &event_proxy, &launch, Some(snapshot))
+                .await
+            {
+                log::error!("unable to restore a window: {err}");
+            }
+        }
+
+        if self.config.session.restore && self.config.session.save_interval > 0 {
+            scheduler.schedule(
+                EventP::new(RioEventType::Rio(RioEvent::SaveSession)),
+                Duration::from_secs(self.config.session.save_interval),
+                true,
+                TimerId::new(Topic::SessionSave, 0),
+            );
+        }
+        scheduler.schedule(
+            EventP::new(RioEventType::Rio(RioEvent::UpdateForegroundProcesses)),
+            FOREGROUND_PROCESSES_INTERVAL,
+            true,
+            TimerId::new(Topic::ForegroundProcesses, 0),
+        );
+        event_loop.set_device_event_filter(DeviceEventFilter::Always);
+
+        event_loop.run_return(move |event, event_loop, control_flow| {
             match event {
-                Event::UserEvent(EventP { payload, .. }) => {
-                    if let RioEventType::Rio(event) = payload {
-                        match event {
-                            RioEvent::Wakeup => {
-                                self.has_render_updates = true;
+                Event::UserEvent(EventP {
+                    payload: RioEventType::Rio(event),
+                    id,
+                    window_id,
+                }) => {
+                    match event {
+                        // Events below concern every window.
+                        RioEvent::UpdateConfig => {
+                            self.reload_config();
+                            return;
+                        }
+                        RioEvent::Ipc(request, reply) => {
+                            let _ = reply.send(self.handle_ipc(
+                                request,
+                                event_loop,
+                                &event_proxy,
+                            ));
+                            return;
+                        }
+                        RioEvent::SaveSession => {
+                            if self.config.session.restore {
+                                self.save_session();
                             }
-                            RioEvent::Render => {
-                                if self.config.advanced.disable_render_when_unfocused
-                                    && self.is_window_focused
-                                {
-                                    return;
+                            return;
+                        }
+                        RioEvent::UpdateForegroundProcesses => {
+                            for window in self.windows.values_mut() {
+                                if window.screen.ctx_mut().update_foreground_processes() {
+                                    window.has_render_updates = true;
                                 }
-                                screen.render();
                             }
-                            RioEvent::UpdateConfig => {
-                                let config = config::Config::load();
-                                self.config = config.into();
-                                screen.update_config(&self.config);
-                                self.has_render_updates = true;
+                            return;
+                        }
+                        RioEvent::Quit { confirmed } => {
+                            if !confirmed && self.ask_quit(window_id) {
+                                return;
                             }
-                            RioEvent::Exit => {
-                                if !screen.try_close_existent_tab() {
-                                    *control_flow = winit::event_loop::ControlFlow::Exit;
-                                }
+                            if self.config.session.restore {
+                                self.save_session();
                             }
-                            RioEvent::PrepareRender(millis) => {
-                                let timer_id = TimerId::new(Topic::Frame, 0);
-                                let event =
-                                    EventP::new(RioEventType::Rio(RioEvent::Render));
+                            *control_flow = winit::event_loop::ControlFlow::Exit;
+                            return;
+                        }
+                        _ => {}
+                    }
 
-                                if !scheduler.scheduled(timer_id) {
-                                    scheduler.schedule(
-                                        event,
-                                        Duration::from_millis(millis),
-                                        false,
-                                        timer_id,
-                                    );
-                                }
-                            }
-                            RioEvent::Title(_title) => {
-                                // if !self.ctx.preserve_title && self.ctx.config.window.dynamic_title {
-                                // self.ctx.window().set_title(title);
-                                // }
+                    let window_id = match window_id {
+                        Some(window_id) if self.windows.contains_key(&window_id) => {
+                            window_id
+                        }
+                        _ => return,
+                    };
+
+                    match event {
+                        RioEvent::CreateWindow => {
+                            if let Err(err) = self.open_window(
+                                event_loop,
+                                &event_proxy,
+                                &Launch::default(),
+                            ) {
+                                log::error!("unable to create a new window: {err}");
                             }
-                            RioEvent::MouseCursorDirty => {
-                                screen.reset_mouse();
+                            return;
+                        }
+                        RioEvent::CloseWindow => {
+                            if self.close_window(window_id, &mut scheduler) {
+                                *control_flow = winit::event_loop::ControlFlow::Exit;
                             }
-                            RioEvent::Scroll(scroll) => {
-                                let mut terminal = screen.ctx().current().terminal.lock();
-                                terminal.scroll_display(scroll);
-                                drop(terminal);
+                            return;
+                        }
+                        RioEvent::Exit => {
+                            let window = self.windows.get_mut(&window_id).unwrap();
+                            if window.screen.try_close_existent_context(id) {
+                                window.has_render_updates = true;
+                                return;
                             }
-                            RioEvent::ClipboardLoad(clipboard_type, format) => {
-                                if self.is_window_focused {
-                                    let text = format(
-                                        screen.clipboard_get(clipboard_type).as_str(),
-                                    );
-                                    screen
-                                        .ctx_mut()
-                                        .current_mut()
-                                        .messenger
-                                        .send_bytes(text.into_bytes());
+
+                            self.windows.remove(&window_id);
+                            scheduler.unschedule_window(u64::from(window_id));
+                            if self.windows.is_empty() {
+                                // Last shell exited on its own, there is nothing
+                                // left worth restoring.
+                                if self.config.session.restore {
+                                    session::clear(config::session_file_path());
                                 }
+                                *control_flow = winit::event_loop::ControlFlow::Exit;
+                            }
+                            return;
+                        }
+                        _ => {}
+                    }
+
+                    let window = self.windows.get_mut(&window_id).unwrap();
+                    let screen = &mut window.screen;
+                    match event {
+                        RioEvent::Wakeup => {
+                            window.has_render_updates = true;
+                        }
+                        RioEvent::Render => {
+                            if self.config.advanced.disable_render_when_unfocused
+                                && window.is_focused
+                            {
+                                return;
                             }
-                            RioEvent::ColorRequest(index, format) => {
-                                // TODO: colors could be coming terminal as well
-                                // if colors has been declaratively changed
-                                // Rio doesn't cover this case yet.
-                                //
-                                // In the future should try first get
-                                // from Crosswords then state colors
-                                // screen.colors()[index] or screen.state.colors[index]
-                                let color = screen.state.colors[index];
-                                let rgb = ColorRgb::from_color_arr(color);
-                                screen
-                                    .ctx_mut()
-                                    .current_mut()
-                                    .messenger
-                                    .send_bytes(format(rgb).into_bytes());
+                            window.render();
+                        }
+                        RioEvent::KeyTableTimeout => {
+                            if screen.cancel_leader() {
+                                window.has_render_updates = true;
+                            }
+                        }
+                        RioEvent::PrepareRender(millis) => {
+                            let timer_id =
+                                TimerId::new(Topic::Frame, u64::from(window_id));
+                            let event = EventP::window(
+                                RioEventType::Rio(RioEvent::Render),
+                                window_id,
+                            );
+
+                            if !scheduler.scheduled(timer_id) {
+                                scheduler.schedule(
+                                    event,
+                                    Duration::from_millis(millis),
+                                    false,
+                                    timer_id,
+                                );
+                            }
+                        }
+                        RioEvent::Title(title) => {
+                            screen.ctx_mut().set_title(id, Some(title));
+                            window.has_render_updates = true;
+                        }
+                        RioEvent::ChildExited(status) => {
+                            log::info!(
+                                "tab {id} kept open, its program exited: {status:?}"
+                            );
+                            screen.ctx_mut().set_exited(id);
+                            window.has_render_updates = true;
+                        }
+                        RioEvent::ResetTitle => {
+                            screen.ctx_mut().set_title(id, None);
+                            window.has_render_updates = true;
+                        }
+                        RioEvent::Tmux(gateway, change) => {
+                            screen.tmux(&gateway, change);
+                            window.has_render_updates = true;
+                        }
+                        RioEvent::MouseCursorDirty => {
+                            screen.reset_mouse();
+                        }
+                        RioEvent::Scroll(scroll) => {
+                            let mut terminal = screen.ctx().current().terminal.lock();
+                            terminal.scroll_display(scroll);
+                            drop(terminal);
+                        }
+                        RioEvent::ClipboardStore(clipboard_type, text) => {
+                            screen.clipboard_request(
+                                id,
+                                ClipboardRequest::Store(clipboard_type, text),
+                            );
+                            window.has_render_updates = true;
+                        }
+                        RioEvent::ClipboardLoad(clipboard_type, format) => {
+                            if window.is_focused {
+                                screen.clipboard_request(
+                                    id,
+                                    ClipboardRequest::Load(clipboard_type, format),
+                                );
+                                window.has_render_updates = true;
                             }
-                            _ => {}
                         }
+                        RioEvent::ColorRequest(index, format) => {
+                            // TODO: colors could be coming terminal as well
+                            // if colors has been declaratively changed
+                            // Rio doesn't cover this case yet.
+                            //
+                            // In the future should try first get
+                            // from Crosswords then state colors
+                            // screen.colors()[index] or screen.state.colors[index]
+                            let color = screen.state.colors[index];
+                            let rgb = ColorRgb::from_color_arr(color);
+                            screen
+                                .ctx_mut()
+                                .current_mut()
+                                .messenger
+                                .send_bytes(format(rgb).into_bytes());
+                        }
+                        _ => {}
                     }
                 }
                 Event::Resumed => {
-                    // self.windows.insert(winit_window.id(), winit_window);
-
                     // Emitted when the application has been resumed.
                     // This is a hack to avoid an odd scenario in wayland window initialization
                     // wayland windows starts with the wrong width/height.
@@ -230,232 +747,282 @@ impl Sequencer {
                     ))]
                     {
                         if !self.has_wayland_forcefully_reloaded {
-                            screen.update_config(&self.config);
-                            self.has_render_updates = true;
+                            for window in self.windows.values_mut() {
+                                window.screen.update_config(&self.config);
+                                window.has_render_updates = true;
+                            }
                             self.has_wayland_forcefully_reloaded = true;
                         }
                     }
                 }
 
                 Event::WindowEvent {
-                    event: winit::event::WindowEvent::CloseRequested,
+                    event: WindowEvent::CloseRequested,
                     window_id,
                     ..
                 } => {
-                    self.windows.remove(&window_id);
+                    if let Some(window) = self.windows.get_mut(&window_id) {
+                        let processes =
+                            window.screen.busy_processes(CloseRequest::Window);
+                        if !processes.is_empty() {
+                            window.screen.ask_close(CloseRequest::Window, &processes);
+                            window.has_render_updates = true;
+                            return;
+                        }
+                    }
 
-                    if self.windows.is_empty() {
+                    if self.close_window(window_id, &mut scheduler) {
                         *control_flow = winit::event_loop::ControlFlow::Exit;
                     }
                 }
 
-                Event::WindowEvent {
-                    event: winit::event::WindowEvent::ModifiersChanged(modifiers),
-                    ..
-                } => screen.set_modifiers(modifiers),
+                Event::WindowEvent { event, window_id } => {
+                    let window = match self.windows.get_mut(&window_id) {
+                        Some(window) => window,
+                        None => return,
+                    };
+                    let screen = &mut window.screen;
+                    let winit_window = &window.window;
 
-                Event::WindowEvent {
-                    event: WindowEvent::MouseInput { state, button, .. },
-                    ..
-                } => {
-                    winit_window.set_cursor_visible(true);
+                    match event {
+                        WindowEvent::ModifiersChanged(modifiers) => {
+                            screen.set_modifiers(modifiers)
+                        }
 
-                    match button {
-                        MouseButton::Left => screen.mouse.left_button_state = state,
-                        MouseButton::Middle => screen.mouse.middle_button_state = state,
-                        MouseButton::Right => screen.mouse.right_button_state = state,
-                        _ => (),
-                    }
+                        WindowEvent::MouseInput { state, button, .. } => {
+                            winit_window.set_cursor_visible(true);
 
-                    match state {
-                        ElementState::Pressed => {
-                            // Process mouse press before bindings to update the `click_state`.
-                            if !screen.modifiers.shift() && screen.mouse_mode() {
-                                screen.mouse.click_state = ClickState::None;
-
-                                let code = match button {
-                                    MouseButton::Left => 0,
-                                    MouseButton::Middle => 1,
-                                    MouseButton::Right => 2,
-                                    // Can't properly report more than three buttons..
-                                    MouseButton::Other(_) => return,
-                                };
+                            let button = mouse::normalize_button(button);
+                            match button {
+                                MouseButton::Left => {
+                                    screen.mouse.left_button_state = state
+                                }
+                                MouseButton::Middle => {
+                                    screen.mouse.middle_button_state = state
+                                }
+                                MouseButton::Right => {
+                                    screen.mouse.right_button_state = state
+                                }
+                                _ => (),
+                            }
 
-                                screen.mouse_report(code, ElementState::Pressed);
-                            } else {
-                                // Calculate time since the last click to handle double/triple clicks.
-                                let now = Instant::now();
-                                let elapsed = now - screen.mouse.last_click_timestamp;
-                                screen.mouse.last_click_timestamp = now;
-
-                                let threshold = Duration::from_millis(300);
-                                let mouse = &screen.mouse;
-                                screen.mouse.click_state = match mouse.click_state {
-                                    // Reset click state if button has changed.
-                                    _ if button != mouse.last_click_button => {
-                                        screen.mouse.last_click_button = button;
-                                        ClickState::Click
-                                    }
-                                    ClickState::Click if elapsed < threshold => {
-                                        ClickState::DoubleClick
+                            match state {
+                                ElementState::Pressed => {
+                                    if screen.context_menu_click()
+                                        || screen.tab_bar_click(button)
+                                        || screen.pane_click(button)
+                                    {
+                                        window.has_render_updates = true;
+                                        return;
                                     }
-                                    ClickState::DoubleClick if elapsed < threshold => {
-                                        ClickState::TripleClick
-                                    }
-                                    _ => ClickState::Click,
-                                };
 
-                                // Load mouse point, treating message bar and padding as the closest square.
-                                let display_offset = screen.display_offset();
+                                    // Process mouse press before bindings to update the `click_state`.
+                                    if !screen.modifiers.shift() && screen.mouse_mode() {
+                                        screen.mouse.click_state = ClickState::None;
+
+                                        let code = match mouse::report_code(button) {
+                                            Some(code) => code,
+                                            None => return,
+                                        };
+
+                                        screen.mouse_report(code, ElementState::Pressed);
+                                    } else {
+                                        // Calculate time since the last click to handle double/triple clicks.
+                                        let now = Instant::now();
+                                        let elapsed =
+                                            now - screen.mouse.last_click_timestamp;
+                                        screen.mouse.last_click_timestamp = now;
 
-                                if let MouseButton::Left = button {
-                                    let point = screen.mouse_position(display_offset);
-                                    screen.on_left_click(point);
+                                        let threshold = Duration::from_millis(300);
+                                        let mouse = &screen.mouse;
+                                        screen.mouse.click_state = match mouse.click_state
+                                        {
+                                            // Reset click state if button has changed.
+                                            _ if button != mouse.last_click_button => {
+                                                screen.mouse.last_click_button = button;
+                                                ClickState::Click
+                                            }
+                                            ClickState::Click if elapsed < threshold => {
+                                                ClickState::DoubleClick
+                                            }
+                                            ClickState::DoubleClick
+                                                if elapsed < threshold =>
+                                            {
+                                                ClickState::TripleClick
+                                            }
+                                            _ => ClickState::Click,
+                                        };
+
+                                        if !screen.process_mouse_bindings(button)
+                                            && button == MouseButton::Left
+                                        {
+                                            // Load mouse point, treating message bar and padding as the closest square.
+                                            let display_offset = screen.display_offset();
+                                            let point =
+                                                screen.mouse_position(display_offset);
+                                            screen.on_left_click(point);
+                                        }
+
+                                        window.has_render_updates = true;
+                                    }
                                 }
+                                ElementState::Released => {
+                                    if button == MouseButton::Left
+                                        && (screen.end_tab_drag()
+                                            || screen.end_pane_drag())
+                                    {
+                                        return;
+                                    }
 
-                                self.has_render_updates = true;
-                            }
-                            // screen.process_mouse_bindings(button);
-                        }
-                        ElementState::Released => {
-                            if !screen.modifiers.shift() && screen.mouse_mode() {
-                                let code = match button {
-                                    MouseButton::Left => 0,
-                                    MouseButton::Middle => 1,
-                                    MouseButton::Right => 2,
-                                    // Can't properly report more than three buttons.
-                                    MouseButton::Other(_) => return,
-                                };
-                                screen.mouse_report(code, ElementState::Released);
-                                return;
-                            }
+                                    if !screen.modifiers.shift() && screen.mouse_mode() {
+                                        let code = match mouse::report_code(button) {
+                                            Some(code) => code,
+                                            None => return,
+                                        };
+                                        screen.mouse_report(code, ElementState::Released);
+                                        return;
+                                    }
 
-                            if let MouseButton::Left | MouseButton::Right = button {
-                                // Copy selection on release, to prevent flooding the display server.
-                                screen.copy_selection(ClipboardType::Selection);
+                                    if let MouseButton::Left | MouseButton::Right = button
+                                    {
+                                        // Copy selection on release, to prevent flooding the display server.
+                                        screen.copy_on_select();
+                                    }
+                                }
                             }
                         }
-                    }
-                }
 
-                Event::WindowEvent {
-                    event: WindowEvent::CursorMoved { position, .. },
-                    ..
-                } => {
-                    winit_window.set_cursor_visible(true);
-                    let x = position.x;
-                    let y = position.y;
-
-                    let lmb_pressed =
-                        screen.mouse.left_button_state == ElementState::Pressed;
-                    let rmb_pressed =
-                        screen.mouse.right_button_state == ElementState::Pressed;
-
-                    if !screen.selection_is_empty() && (lmb_pressed || rmb_pressed) {
-                        screen.update_selection_scrolling(y);
-                        self.has_render_updates = true;
-                    }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            winit_window.set_cursor_visible(true);
+                            let x = position.x;
+                            let y = position.y;
 
-                    let display_offset = screen.display_offset();
-                    let old_point = screen.mouse_position(display_offset);
+                            let lmb_pressed =
+                                screen.mouse.left_button_state == ElementState::Pressed;
+                            let rmb_pressed =
+                                screen.mouse.right_button_state == ElementState::Pressed;
 
-                    let x = x.clamp(0.0, screen.sugarloaf.layout.width.into()) as usize;
-                    let y = y.clamp(0.0, screen.sugarloaf.layout.height.into()) as usize;
-                    screen.mouse.x = x;
-                    screen.mouse.y = y;
+                            if !screen.selection_is_empty()
+                                && (lmb_pressed || rmb_pressed)
+                            {
+                                screen.update_selection_scrolling(y);
+                                window.has_render_updates = true;
+                            }
 
-                    let point = screen.mouse_position(display_offset);
-                    let square_changed = old_point != point;
+                            let display_offset = screen.display_offset();
+                            let old_point = screen.mouse_position(display_offset);
 
-                    let inside_text_area = screen.contains_point(x, y);
-                    let square_side = screen.side_by_pos(x);
+                            let x = x.clamp(0.0, screen.sugarloaf.layout.width.into())
+                                as usize;
+                            let y = y.clamp(0.0, screen.sugarloaf.layout.height.into())
+                                as usize;
+                            screen.mouse.x = x;
+                            screen.mouse.y = y;
 
-                    // If the mouse hasn't changed cells, do nothing.
-                    if !square_changed
-                        && screen.mouse.square_side == square_side
-                        && screen.mouse.inside_text_area == inside_text_area
-                    {
-                        return;
-                    }
+                            if screen.drag_tab() || screen.drag_pane_border() {
+                                window.has_render_updates = true;
+                                return;
+                            }
 
-                    screen.mouse.inside_text_area = inside_text_area;
-                    screen.mouse.square_side = square_side;
+                            let point = screen.mouse_position(display_offset);
+                            let square_changed = old_point != point;
 
-                    let cursor_icon = if !screen.modifiers.shift() && screen.mouse_mode()
-                    {
-                        CursorIcon::Default
-                    } else {
-                        CursorIcon::Text
-                    };
-                    winit_window.set_cursor_icon(cursor_icon);
+                            let inside_text_area = screen.contains_point(x, y);
+                            let square_side = screen.side_by_pos(x);
 
-                    if (lmb_pressed || rmb_pressed)
-                        && (screen.modifiers.shift() || !screen.mouse_mode())
-                    {
-                        screen.update_selection(point, square_side);
-                    } else if square_changed && screen.has_mouse_motion_and_drag() {
-                        if lmb_pressed {
-                            screen.mouse_report(32, ElementState::Pressed);
-                        } else if screen.mouse.middle_button_state
-                            == ElementState::Pressed
-                        {
-                            screen.mouse_report(33, ElementState::Pressed);
-                        } else if screen.mouse.right_button_state == ElementState::Pressed
-                        {
-                            screen.mouse_report(34, ElementState::Pressed);
-                        } else if screen.has_mouse_motion() {
-                            screen.mouse_report(35, ElementState::Pressed);
-                        }
-                    }
+                            // If the mouse hasn't changed cells, do nothing.
+                            if !square_changed
+                                && screen.mouse.square_side == square_side
+                                && screen.mouse.inside_text_area == inside_text_area
+                            {
+                                return;
+                            }
 
-                    self.has_render_updates = true;
-                }
+                            screen.mouse.inside_text_area = inside_text_area;
+                            screen.mouse.square_side = square_side;
 
-                Event::WindowEvent {
-                    event: WindowEvent::MouseWheel { delta, phase, .. },
-                    ..
-                } => {
-                    winit_window.set_cursor_visible(true);
-                    match delta {
-                        MouseScrollDelta::LineDelta(columns, lines) => {
-                            let new_scroll_px_x =
-                                columns * screen.sugarloaf.layout.font_size;
-                            let new_scroll_px_y =
-                                lines * screen.sugarloaf.layout.font_size;
-                            screen.scroll(new_scroll_px_x as f64, new_scroll_px_y as f64);
-                        }
-                        MouseScrollDelta::PixelDelta(mut lpos) => {
-                            match phase {
-                                TouchPhase::Started => {
-                                    // Reset offset to zero.
-                                    screen.mouse.accumulated_scroll = Default::default();
+                            let cursor_icon =
+                                if !screen.modifiers.shift() && screen.mouse_mode() {
+                                    CursorIcon::Default
+                                } else {
+                                    CursorIcon::Text
+                                };
+                            winit_window.set_cursor_icon(cursor_icon);
+
+                            if (lmb_pressed || rmb_pressed)
+                                && (screen.modifiers.shift() || !screen.mouse_mode())
+                            {
+                                screen.update_selection(point, square_side);
+                            } else if square_changed && screen.has_mouse_motion_and_drag()
+                            {
+                                if lmb_pressed {
+                                    screen.mouse_report(32, ElementState::Pressed);
+                                } else if screen.mouse.middle_button_state
+                                    == ElementState::Pressed
+                                {
+                                    screen.mouse_report(33, ElementState::Pressed);
+                                } else if screen.mouse.right_button_state
+                                    == ElementState::Pressed
+                                {
+                                    screen.mouse_report(34, ElementState::Pressed);
+                                } else if screen.has_mouse_motion() {
+                                    screen.mouse_report(35, ElementState::Pressed);
                                 }
-                                TouchPhase::Moved => {
-                                    // When the angle between (x, 0) and (x, y) is lower than ~25 degrees
-                                    // (cosine is larger that 0.9) we consider this scrolling as horizontal.
-                                    if lpos.x.abs() / lpos.x.hypot(lpos.y) > 0.9 {
-                                        lpos.y = 0.;
-                                    } else {
-                                        lpos.x = 0.;
+                            }
+
+                            window.has_render_updates = true;
+                        }
+
+                        WindowEvent::MouseWheel { delta, phase, .. } => {
+                            winit_window.set_cursor_visible(true);
+                            match delta {
+                                MouseScrollDelta::LineDelta(columns, lines) => {
+                                    let new_scroll_px_x =
+                                        columns * screen.sugarloaf.layout.font_size;
+                                    let new_scroll_px_y =
+                                        lines * screen.sugarloaf.layout.font_size;
+                                    if screen.scroll_tab_bar(new_scroll_px_y as f64) {
+                                        window.has_render_updates = true;
+                                        return;
                                     }
+                                    screen.scroll(
+                                        new_scroll_px_x as f64,
+                                        new_scroll_px_y as f64,
+                                    );
+                                }
+                                MouseScrollDelta::PixelDelta(mut lpos) => {
+                                    match phase {
+                                        TouchPhase::Started => {
+                                            // Reset offset to zero.
+                                            screen.mouse.accumulated_scroll =
+                                                Default::default();
+                                        }
+                                        TouchPhase::Moved => {
+                                            // When the angle between (x, 0) and (x, y) is lower than ~25 degrees
+                                            // (cosine is larger that 0.9) we consider this scrolling as horizontal.
+                                            if lpos.x.abs() / lpos.x.hypot(lpos.y) > 0.9 {
+                                                lpos.y = 0.;
+                                            } else {
+                                                lpos.x = 0.;
+                                            }
 
-                                    screen.scroll(lpos.x, lpos.y);
+                                            if screen.scroll_tab_bar(lpos.y) {
+                                                window.has_render_updates = true;
+                                                return;
+                                            }
+                                            screen.scroll(lpos.x, lpos.y);
+                                        }
+                                        _ => (),
+                                    }
                                 }
-                                _ => (),
                             }
                         }
-                    }
-                }
-                Event::WindowEvent {
-                    event: winit::event::WindowEvent::ReceivedCharacter(character),
-                    ..
-                } => {
-                    screen.input_character(character);
-                }
 
-                Event::WindowEvent {
-                    event:
-                        winit::event::WindowEvent::KeyboardInput {
+                        WindowEvent::ReceivedCharacter(character) => {
+                            screen.input_character(character);
+                        }
+
+                        WindowEvent::KeyboardInput {
                             is_synthetic: false,
                             input:
                                 winit::event::KeyboardInput {
@@ -465,136 +1032,135 @@ impl Sequencer {
                                     ..
                                 },
                             ..
+                        } => match state {
+                            ElementState::Pressed => {
+                                winit_window.set_cursor_visible(false);
+                                screen.input_keycode(virtual_keycode, scancode);
+
+                                // Restart the wait for the key following the leader.
+                                let timer_id =
+                                    TimerId::new(Topic::KeyTable, u64::from(window_id));
+                                scheduler.unschedule(timer_id);
+                                if let Some(timeout) = screen.key_table_timeout() {
+                                    scheduler.schedule(
+                                        EventP::window(
+                                            RioEventType::Rio(RioEvent::KeyTableTimeout),
+                                            window_id,
+                                        ),
+                                        timeout,
+                                        false,
+                                        timer_id,
+                                    );
+                                }
+                            }
+
+                            ElementState::Released => {
+                                window.has_render_updates = true;
+                            }
                         },
-                    ..
-                } => match state {
-                    ElementState::Pressed => {
-                        winit_window.set_cursor_visible(false);
-                        screen.input_keycode(virtual_keycode, scancode);
-
-                        // create_window();
-                        // for _ in 0..3 {
-                        // let window = Window::new(&event_loop).unwrap();
-                        // println!("Opened a new window: {:?}", winit_window.id());
-                        // let new_window_builder = winit::window::WindowBuilder::new()
-                        // .with_title("");
-                        // let new_window = new_window_builder.build(&event_loop).unwrap();
-                        // self.windows.insert(new_window.id(), new_window);
-                        // }
-                    }
 
-                    ElementState::Released => {
-                        self.has_render_updates = true;
-                        // winit_window.request_redraw();
-                    }
-                },
+                        WindowEvent::Ime(ime) => match ime {
+                            Ime::Commit(text) => {
+                                screen.paste(&text, true);
+                            }
+                            Ime::Preedit(text, cursor_offset) => {
+                                let preedit = if text.is_empty() {
+                                    None
+                                } else {
+                                    Some(Preedit::new(
+                                        text,
+                                        cursor_offset.map(|offset| offset.0),
+                                    ))
+                                };
 
-                Event::WindowEvent {
-                    event: WindowEvent::Ime(ime),
-                    ..
-                } => match ime {
-                    Ime::Commit(text) => {
-                        screen.paste(&text, true);
-                    }
-                    Ime::Preedit(text, cursor_offset) => {
-                        let preedit = if text.is_empty() {
-                            None
-                        } else {
-                            Some(Preedit::new(text, cursor_offset.map(|offset| offset.0)))
-                        };
+                                if screen.ime.preedit() != preedit.as_ref() {
+                                    screen.ime.set_preedit(preedit);
+                                    window.render();
+                                }
+                            }
+                            Ime::Enabled => {
+                                screen.ime.set_enabled(true);
+                            }
+                            Ime::Disabled => {
+                                screen.ime.set_enabled(false);
+                            }
+                        },
 
-                        if screen.ime.preedit() != preedit.as_ref() {
-                            screen.ime.set_preedit(preedit);
-                            screen.render();
+                        WindowEvent::Focused(focused) => {
+                            winit_window.set_cursor_visible(true);
+                            window.is_focused = focused;
                         }
-                    }
-                    Ime::Enabled => {
-                        screen.ime.set_enabled(true);
-                    }
-                    Ime::Disabled => {
-                        screen.ime.set_enabled(false);
-                    }
-                },
 
-                Event::WindowEvent {
-                    event: winit::event::WindowEvent::Focused(focused),
-                    ..
-                } => {
-                    winit_window.set_cursor_visible(true);
-                    self.is_window_focused = focused;
-                }
-
-                Event::WindowEvent {
-                    event: winit::event::WindowEvent::Occluded(occluded),
-                    ..
-                } => {
-                    self.is_occluded = occluded;
-                }
+                        WindowEvent::Occluded(occluded) => {
+                            window.is_occluded = occluded;
+                        }
 
-                Event::WindowEvent {
-                    event: winit::event::WindowEvent::DroppedFile(path),
-                    ..
-                } => {
-                    let path: String = path.to_string_lossy().into();
-                    screen.paste(&(path + " "), true);
-                }
+                        WindowEvent::DroppedFile(path) => {
+                            let path: String = path.to_string_lossy().into();
+                            screen.paste(&(path + " "), true);
+                        }
 
-                Event::WindowEvent {
-                    event: winit::event::WindowEvent::Resized(new_size),
-                    ..
-                } => {
-                    if new_size.width == 0 || new_size.height == 0 {
-                        return;
-                    }
+                        WindowEvent::Resized(new_size) => {
+                            if new_size.width == 0 || new_size.height == 0 {
+                                return;
+                            }
 
-                    screen.resize(new_size);
-                    self.has_render_updates = true;
-                }
+                            screen.resize(new_size);
+                            window.has_render_updates = true;
+                        }
 
-                Event::WindowEvent {
-                    event:
-                        winit::event::WindowEvent::ScaleFactorChanged {
+                        WindowEvent::ScaleFactorChanged {
                             new_inner_size,
                             scale_factor,
-                        },
-                    ..
-                } => {
-                    screen.set_scale(scale_factor as f32, *new_inner_size);
-                    self.has_render_updates = true;
+                        } => {
+                            screen.set_scale(scale_factor as f32, *new_inner_size);
+                            window.has_render_updates = true;
+                        }
+
+                        _ => {
+                            *control_flow = winit::event_loop::ControlFlow::Wait;
+                        }
+                    }
                 }
 
                 // Emitted when the event loop is being shut down.
                 // This is irreversible - if this event is emitted, it is guaranteed to be the last event that gets emitted.
                 // You generally want to treat this as an “do on quit” event.
                 Event::LoopDestroyed { .. } => {
+                    // The process exits right away, destructors do not run.
+                    drop(ipc_listener.take());
+
                     // TODO: Now we are forcing an exit operation
                     // but it should be revaluated since CloseRequested in MacOs
                     // not necessarily exit the process
                     std::process::exit(0);
                 }
                 Event::RedrawEventsCleared { .. } => {
-                    // Skip render for macos and x11 windows that are fully occluded
-                    if self.is_occluded {
-                        return;
-                    }
-
                     #[cfg(all(
                         feature = "wayland",
                         not(any(target_os = "macos", target_os = "windows"))
                     ))]
-                    if let Some(w_event_queue) = wayland_event_queue.as_mut() {
+                    if let Some(w_event_queue) = self.wayland_event_queue.as_mut() {
                         w_event_queue
                             .dispatch_pending(&mut (), |_, _, _| {})
                             .expect("failed to dispatch wayland event queue");
                     }
 
-                    if self.has_render_updates {
-                        screen.render();
-                        self.has_render_updates = false;
-                        return;
+                    let mut has_rendered = false;
+                    for window in self.windows.values_mut() {
+                        // Skip render for macos and x11 windows that are fully occluded
+                        if window.is_occluded || !window.has_render_updates {
+                            continue;
+                        }
+
+                        window.render();
+                        window.has_render_updates = false;
+                        has_rendered = true;
                     }
 
-                    scheduler.update();
+                    if !has_rendered {
+                        scheduler.update();
+                    }
                 }
                 Event::MainEventsCleared { .. } => {}
                 Event::RedrawRequested { .. } => {}
@@ -0,0 +1,368 @@
+use super::host::write_line;
+use super::{
+    apply_snapshot, apply_update, Message, Request, SessionInfo, Size, Snapshot,
+};
+use crate::crosswords::Crosswords;
+use crate::event::sync::FairMutex;
+use crate::event::{EventListener, Msg, RioEvent};
+use corcovado::channel;
+use corcovado::{Events, Poll, PollOpt, Ready, Token};
+use std::io::{self, BufRead, BufReader};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a server started by [`start`] gets to listen.
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connection to a server, one session can be attached per connection.
+pub struct Client {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+}
+
+impl Client {
+    /// Connect to the server listening at `path`, which must run as the
+    /// user.
+    pub fn connect(path: &Path) -> io::Result<Client> {
+        let stream = UnixStream::connect(path)?;
+        if !crate::ipc::is_own_peer(stream.as_raw_fd()) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("the server at {} runs as another user", path.display()),
+            ));
+        }
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Client { stream, reader })
+    }
+
+    pub fn send(&self, request: &Request) -> io::Result<()> {
+        write_line(&self.stream, request)
+    }
+
+    /// Next message of the server, `None` once it closed the connection.
+    pub fn receive(&mut self) -> io::Result<Option<Message>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        serde_json::from_str(&line)
+            .map(Some)
+            .map_err(io::Error::from)
+    }
+
+    /// Next message, the connection closing or an error is an error.
+    fn expect(&mut self) -> io::Result<Message> {
+        match self.receive()? {
+            Some(Message::Error { message }) => {
+                Err(io::Error::new(io::ErrorKind::Other, message))
+            }
+            Some(message) => Ok(message),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server closed the connection",
+            )),
+        }
+    }
+
+    pub fn list_sessions(path: &Path) -> io::Result<Vec<SessionInfo>> {
+        let mut client = Client::connect(path)?;
+        client.send(&Request::ListSessions)?;
+        match client.expect()? {
+            Message::Sessions { sessions } => Ok(sessions),
+            message => Err(unexpected(&message)),
+        }
+    }
+
+    pub fn kill(path: &Path, session: usize) -> io::Result<()> {
+        let mut client = Client::connect(path)?;
+        client.send(&Request::Kill { session })?;
+        match client.expect()? {
+            Message::Ok => Ok(()),
+            message => Err(unexpected(&message)),
+        }
+    }
+
+    /// Send `request`, a [`Request::Create`] or [`Request::Attach`], and
+    /// wait for the snapshot of the session.
+    pub fn open(path: &Path, request: &Request) -> io::Result<(Client, Snapshot)> {
+        let mut client = Client::connect(path)?;
+        client.send(request)?;
+        loop {
+            match client.expect()? {
+                Message::Snapshot(snapshot) => return Ok((client, snapshot)),
+                // Sent to every client, before it got its snapshot.
+                Message::Update(_) | Message::Bell => continue,
+                message => return Err(unexpected(&message)),
+            }
+        }
+    }
+}
+
+fn unexpected(message: &Message) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected message from the server: {message:?}"),
+    )
+}
+
+/// Keep `terminal` a mirror of the session `client` is attached to,
+/// returns the channel to write to it, as `Machine::channel` does.
+///
+/// The session is detached once a `Msg::Shutdown` is sent, and the
+/// terminal exits when the session ends.
+pub fn mirror<U>(
+    client: Client,
+    terminal: Arc<FairMutex<Crosswords<U>>>,
+    event_proxy: U,
+) -> io::Result<channel::Sender<Msg>>
+where
+    U: EventListener + Send + 'static,
+{
+    let Client { stream, mut reader } = client;
+    let (sender, receiver) = channel::channel::<Msg>();
+    let detached = Arc::new(AtomicBool::new(false));
+    let poll = Poll::new()?;
+    poll.register(&receiver, Token(0), Ready::readable(), PollOpt::level())?;
+
+    let reader_detached = detached.clone();
+    std::thread::Builder::new()
+        .name(String::from("server client reader"))
+        .spawn(move || {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let message = match reader.read_line(&mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => match serde_json::from_str::<Message>(&line) {
+                        Ok(message) => message,
+                        Err(err) => {
+                            log::warn!("invalid message from the server: {err}");
+                            continue;
+                        }
+                    },
+                };
+                match message {
+                    Message::Snapshot(snapshot) => {
+                        apply_snapshot(&mut terminal.lock(), &snapshot)
+                    }
+                    Message::Update(update) => {
+                        apply_update(&mut terminal.lock(), &update)
+                    }
+                    Message::Bell => event_proxy.send_event(RioEvent::Bell),
+                    Message::Exited => break,
+                    Message::Error { message } => log::warn!("server error: {message}"),
+                    Message::Ok | Message::Sessions { .. } => {}
+                }
+                event_proxy.send_event(RioEvent::Wakeup);
+            }
+
+            if !reader_detached.load(Ordering::Relaxed) {
+                terminal.lock().exit();
+                event_proxy.send_event(RioEvent::Wakeup);
+            }
+        })?;
+
+    std::thread::Builder::new()
+        .name(String::from("server client writer"))
+        .spawn(move || {
+            let mut events = Events::with_capacity(16);
+            loop {
+                if let Err(err) = poll.poll(&mut events, None) {
+                    if err.kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    log::error!("unable to poll the server client channel: {err}");
+                    break;
+                }
+                while let Ok(msg) = receiver.try_recv() {
+                    let request = match msg {
+                        Msg::Input(bytes) | Msg::Command(bytes) => Request::Input {
+                            bytes: bytes.into_owned(),
+                        },
+                        Msg::Resize(winsize) => Request::Resize {
+                            size: Size {
+                                columns: winsize.cols as usize,
+                                rows: winsize.rows as usize,
+                                width: winsize.width,
+                                height: winsize.height,
+                            },
+                        },
+                        Msg::Shutdown => {
+                            detached.store(true, Ordering::Relaxed);
+                            let _ = stream.shutdown(std::net::Shutdown::Both);
+                            return;
+                        }
+                    };
+                    if write_line(&stream, &request).is_err() {
+                        return;
+                    }
+                }
+            }
+        })?;
+
+    Ok(sender)
+}
+
+/// Start a server listening at `path` in the background, with the
+/// configuration at `config_file` or else the default one.
+pub fn start(path: &Path, config_file: Option<&str>) -> io::Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let mut command = Command::new(std::env::current_exe()?);
+    if let Some(config_file) = config_file {
+        command.arg("--config-file").arg(config_file);
+    }
+    command
+        .arg("server")
+        .arg("--socket")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // Outlives the terminal it was started from.
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    command.spawn()?;
+
+    let started = Instant::now();
+    while UnixStream::connect(path).is_err() {
+        if started.elapsed() > START_TIMEOUT {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("the server did not start listening at {}", path.display()),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::text;
+    use super::*;
+    use crate::event::VoidListener;
+    use crate::screen::Launch;
+    use crate::server::Server;
+    use std::sync::mpsc;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    const SIZE: Size = Size {
+        columns: 40,
+        rows: 10,
+        width: 400,
+        height: 200,
+    };
+
+    #[derive(Clone)]
+    struct ChannelListener(Arc<std::sync::Mutex<mpsc::Sender<RioEvent>>>);
+
+    impl EventListener for ChannelListener {
+        fn send_event(&self, event: RioEvent) {
+            let _ = self.0.lock().unwrap().send(event);
+        }
+    }
+
+    fn contains<U: EventListener>(terminal: &Crosswords<U>, line: &str) -> bool {
+        text(terminal).iter().any(|text| text == line)
+    }
+
+    /// Apply what the server sends to `terminal` until it shows `line`.
+    fn wait_for(
+        client: &mut Client,
+        terminal: &mut Crosswords<VoidListener>,
+        line: &str,
+    ) {
+        client.stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        while !contains(terminal, line) {
+            match client.receive().unwrap() {
+                Some(Message::Snapshot(snapshot)) => apply_snapshot(terminal, &snapshot),
+                Some(Message::Update(update)) => apply_update(terminal, &update),
+                Some(_) => {}
+                None => panic!("the server closed the connection"),
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_detach_and_attach() {
+        let dir =
+            std::env::temp_dir().join(format!("rio-server-test-{}", std::process::id()));
+        let path = dir.join("rio-server.sock");
+        let server = Server::bind(&path, Launch::default()).unwrap();
+        let running = std::thread::spawn(move || server.run());
+
+        let (mut client, snapshot) = Client::open(
+            &path,
+            &Request::Create {
+                command: vec![String::from("/bin/sh")],
+                working_directory: None,
+                envs: vec![(String::from("PS1"), String::from("$ "))],
+                size: SIZE,
+            },
+        )
+        .unwrap();
+        let session = snapshot.session;
+        let mut terminal = Crosswords::new(SIZE.columns, SIZE.rows, VoidListener);
+        apply_snapshot(&mut terminal, &snapshot);
+
+        let input = b"seq 1 30; echo done\r".to_vec();
+        client.send(&Request::Input { bytes: input }).unwrap();
+        wait_for(&mut client, &mut terminal, "done");
+        assert!(contains(&terminal, "1"));
+
+        // Detached, the shell keeps running.
+        drop(client);
+        let sessions = Client::list_sessions(&path).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, session);
+
+        // Attached again, with the whole scrollback.
+        let (client, snapshot) = Client::open(
+            &path,
+            &Request::Attach {
+                session,
+                size: SIZE,
+            },
+        )
+        .unwrap();
+        let (sender, events) = mpsc::channel();
+        let listener = ChannelListener(Arc::new(std::sync::Mutex::new(sender)));
+        let mirrored = Arc::new(FairMutex::new(Crosswords::new(
+            SIZE.columns,
+            SIZE.rows,
+            listener.clone(),
+        )));
+        apply_snapshot(&mut mirrored.lock(), &snapshot);
+        assert_eq!(text(&mirrored.lock()), text(&terminal));
+
+        let channel = mirror(client, mirrored.clone(), listener).unwrap();
+        channel
+            .send(Msg::Input(b"echo again\r".as_slice().into()))
+            .unwrap();
+        while !contains(&mirrored.lock(), "again") {
+            events.recv_timeout(TIMEOUT).unwrap();
+        }
+
+        // Killed, the mirror exits and so does the server.
+        Client::kill(&path, session).unwrap();
+        loop {
+            if let RioEvent::Exit = events.recv_timeout(TIMEOUT).unwrap() {
+                break;
+            }
+        }
+        running.join().unwrap().unwrap();
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir(&dir);
+    }
+}
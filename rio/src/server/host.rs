@@ -0,0 +1,508 @@
+use super::{update, Message, Request, SessionInfo, Size};
+use crate::crosswords::grid::Dimensions;
+use crate::crosswords::pos::CursorState;
+use crate::event::{EventListener, RioEvent};
+use crate::ipc;
+use crate::screen::{Context, ContextManager, Launch};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Clients too slow to take a message for that long are detached.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Messages waiting to be written to a client, it is detached when more
+/// are sent.
+const WRITE_QUEUE: usize = 256;
+
+enum Event {
+    Connected(usize, mpsc::SyncSender<String>),
+    Request(usize, Request),
+    Invalid(usize, String),
+    Disconnected(usize),
+    Session(usize, RioEvent),
+}
+
+/// Sends the events of a session to the server loop.
+#[derive(Clone)]
+struct SessionProxy {
+    sender: mpsc::Sender<Event>,
+    session: usize,
+}
+
+impl EventListener for SessionProxy {
+    fn send_event(&self, event: RioEvent) {
+        let _ = self.sender.send(Event::Session(self.session, event));
+    }
+
+    fn route(&self, route_id: usize) -> Self {
+        SessionProxy {
+            sender: self.sender.clone(),
+            session: route_id,
+        }
+    }
+}
+
+struct Session {
+    context: Context<SessionProxy>,
+    /// Name of the program, the title until it sets one.
+    program: String,
+    /// Title the clients were told about.
+    title: String,
+    /// Lines scrolled into history as of the last update.
+    scrolled: usize,
+    clients: Vec<usize>,
+}
+
+impl Session {
+    fn title(&self) -> String {
+        let terminal = self.context.terminal.lock();
+        terminal.title().unwrap_or(&self.program).to_owned()
+    }
+}
+
+/// Connection of a client, its messages are written by a thread of its
+/// own so a slow client does not hold the others up.
+struct Connection {
+    /// Messages for the writing thread, the connection is closed once the
+    /// ones queued are written and this is dropped.
+    queue: mpsc::SyncSender<String>,
+    /// Session it is attached to.
+    session: Option<usize>,
+}
+
+/// Owns the sessions and serves the clients attached to them, see the
+/// module documentation.
+pub struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+    /// What every session inherits, see [`Launch::defaults`].
+    defaults: Launch,
+    runtime: tokio::runtime::Handle,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Server {
+    /// Listen at `path`, sessions are run by the current tokio runtime.
+    /// Only the user can connect, see [`ipc::prepare_socket`].
+    ///
+    /// # Panics
+    ///
+    /// When called outside of a tokio runtime.
+    pub fn bind(path: &Path, defaults: Launch) -> io::Result<Server> {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("a server is already running at {}", path.display()),
+            ));
+        }
+        ipc::prepare_socket(path)?;
+        let listener = UnixListener::bind(path)?;
+        ipc::restrict_socket(path)?;
+
+        Ok(Server {
+            listener,
+            path: path.to_path_buf(),
+            defaults,
+            runtime: tokio::runtime::Handle::current(),
+        })
+    }
+
+    /// Serve clients until the last session ends.
+    pub fn run(self) -> io::Result<()> {
+        let (sender, events) = mpsc::channel();
+        let listener = self.listener.try_clone()?;
+        let accept_sender = sender.clone();
+        std::thread::Builder::new()
+            .name(String::from("server listener"))
+            .spawn(move || accept(listener, accept_sender))?;
+
+        let _runtime = self.runtime.enter();
+        let mut host = Host {
+            defaults: self.defaults.clone(),
+            proxy: SessionProxy { sender, session: 0 },
+            sessions: HashMap::new(),
+            connections: HashMap::new(),
+            next_session: 1,
+            started: false,
+        };
+        while let Ok(event) = events.recv() {
+            host.handle(event);
+            if host.started && host.sessions.is_empty() {
+                log::info!("last session ended, stopping the server");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn accept(listener: UnixListener, sender: mpsc::Sender<Event>) {
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("unable to accept server connection: {err}");
+                continue;
+            }
+        };
+        if !ipc::is_own_peer(stream.as_raw_fd()) {
+            continue;
+        }
+        let reader = match stream.try_clone() {
+            Ok(reader) => reader,
+            Err(err) => {
+                log::warn!("unable to read server connection: {err}");
+                continue;
+            }
+        };
+        let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+        let (queue, messages) = mpsc::sync_channel(WRITE_QUEUE);
+        if let Err(err) = std::thread::Builder::new()
+            .name(String::from("server writer"))
+            .spawn(move || write_messages(stream, messages))
+        {
+            log::warn!("unable to write to server connection: {err}");
+            continue;
+        }
+        if sender.send(Event::Connected(id, queue)).is_err() {
+            return;
+        }
+
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let event = match line {
+                    Ok(line) => match serde_json::from_str::<Request>(&line) {
+                        Ok(request) => Event::Request(id, request),
+                        Err(err) => Event::Invalid(id, format!("invalid request: {err}")),
+                    },
+                    Err(_) => break,
+                };
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+            let _ = sender.send(Event::Disconnected(id));
+        });
+    }
+}
+
+/// Write the lines received from `messages` to `stream` until the queue
+/// is dropped or a write fails, then close the connection.
+fn write_messages(mut stream: UnixStream, messages: mpsc::Receiver<String>) {
+    for message in messages {
+        if let Err(err) = stream.write_all(message.as_bytes()) {
+            log::warn!("unable to write to server connection: {err}");
+            break;
+        }
+    }
+    // Ends the reading thread as well.
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+}
+
+struct Host {
+    defaults: Launch,
+    proxy: SessionProxy,
+    sessions: HashMap<usize, Session>,
+    connections: HashMap<usize, Connection>,
+    next_session: usize,
+    /// Whether a session was created, the server stops once they all end.
+    started: bool,
+}
+
+impl Host {
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Connected(id, queue) => {
+                self.connections.insert(
+                    id,
+                    Connection {
+                        queue,
+                        session: None,
+                    },
+                );
+            }
+            Event::Request(id, request) => self.request(id, request),
+            Event::Invalid(id, message) => {
+                self.send(id, &Message::Error { message });
+            }
+            Event::Disconnected(id) => self.disconnect(id),
+            Event::Session(session, event) => match event {
+                RioEvent::Wakeup | RioEvent::Title(_) | RioEvent::ResetTitle => {
+                    self.flush(session)
+                }
+                RioEvent::Bell => self.broadcast(session, &Message::Bell),
+                // Replies of the terminal, e.g. to a cursor position report.
+                RioEvent::PtyWrite(text) => {
+                    if let Some(session) = self.sessions.get_mut(&session) {
+                        session.context.messenger.send_bytes(text.into_bytes());
+                    }
+                }
+                RioEvent::Exit | RioEvent::ChildExited(_) => self.end(session),
+                _ => {}
+            },
+        }
+    }
+
+    fn request(&mut self, id: usize, request: Request) {
+        match request {
+            Request::ListSessions => {
+                let mut sessions: Vec<SessionInfo> = self
+                    .sessions
+                    .iter()
+                    .map(|(id, session)| SessionInfo {
+                        id: *id,
+                        title: session.title(),
+                        attached: session.clients.len(),
+                    })
+                    .collect();
+                sessions.sort_by_key(|session| session.id);
+                self.send(id, &Message::Sessions { sessions });
+            }
+            Request::Create {
+                command,
+                working_directory,
+                envs,
+                size,
+            } => {
+                let launch = Launch {
+                    command,
+                    working_directory,
+                    envs: self.defaults.envs.iter().cloned().chain(envs).collect(),
+                    // Clients are told about the end of the session.
+                    exit_behavior: Some(config::ExitBehavior::Close),
+                    ..Launch::default()
+                };
+                let session = self.next_session;
+                match ContextManager::create_context(
+                    (size.width as u32, size.height as u32),
+                    size.columns,
+                    size.rows,
+                    CursorState::default(),
+                    self.proxy.clone(),
+                    session,
+                    true,
+                    None,
+                    &launch,
+                ) {
+                    Ok(context) => {
+                        self.next_session += 1;
+                        self.started = true;
+                        let program = context.title().to_owned();
+                        self.sessions.insert(
+                            session,
+                            Session {
+                                context,
+                                title: program.clone(),
+                                program,
+                                scrolled: 0,
+                                clients: vec![],
+                            },
+                        );
+                        self.attach(id, session, size);
+                    }
+                    Err(err) => self.send(
+                        id,
+                        &Message::Error {
+                            message: format!("unable to start the session: {err}"),
+                        },
+                    ),
+                }
+            }
+            Request::Attach { session, size } => {
+                if self.sessions.contains_key(&session) {
+                    self.attach(id, session, size);
+                } else {
+                    self.send(id, &no_session(session));
+                }
+            }
+            Request::Kill { session } => {
+                if self.sessions.contains_key(&session) {
+                    self.end(session);
+                    self.send(id, &Message::Ok);
+                } else {
+                    self.send(id, &no_session(session));
+                }
+            }
+            Request::Input { bytes } => {
+                if let Some(session) = self.attached_session(id) {
+                    session.context.messenger.send_bytes(bytes);
+                }
+            }
+            Request::Resize { size } => {
+                if let Some(session) = self.connection_session(id) {
+                    self.resize(session, size);
+                }
+            }
+        }
+    }
+
+    /// Session the connection `id` is attached to.
+    fn connection_session(&self, id: usize) -> Option<usize> {
+        self.connections
+            .get(&id)
+            .and_then(|connection| connection.session)
+    }
+
+    fn attached_session(&mut self, id: usize) -> Option<&mut Session> {
+        let session = self.connection_session(id)?;
+        self.sessions.get_mut(&session)
+    }
+
+    fn attach(&mut self, id: usize, session: usize, size: Size) {
+        if let Some(old) = self.connection_session(id) {
+            self.detach(id, old);
+        }
+        // Clients attached already get what changed before the snapshot.
+        self.flush(session);
+        self.resize(session, size);
+
+        let snapshot = match self.sessions.get_mut(&session) {
+            Some(attached) => {
+                attached.clients.push(id);
+                let title = attached.title();
+                let terminal = attached.context.terminal.lock();
+                super::snapshot(session, &title, &terminal)
+            }
+            None => return,
+        };
+        if let Some(connection) = self.connections.get_mut(&id) {
+            connection.session = Some(session);
+        }
+        self.send(id, &Message::Snapshot(snapshot));
+    }
+
+    /// Resize `session`, its clients get a new snapshot.
+    fn resize(&mut self, session: usize, size: Size) {
+        let snapshot = {
+            let Some(resized) = self.sessions.get_mut(&session) else {
+                return;
+            };
+            let mut terminal = resized.context.terminal.lock();
+            if terminal.grid.columns() == size.columns
+                && terminal.grid.screen_lines() == size.rows
+            {
+                return;
+            }
+            terminal.resize::<(usize, usize)>(size.columns, size.rows);
+            let _ = resized.context.messenger.send_resize(
+                size.width,
+                size.height,
+                size.columns as u16,
+                size.rows as u16,
+            );
+            // Reflowed, what the clients have is stale.
+            terminal.reset_damage();
+            resized.scrolled = terminal.primary_grid().scrolled_lines();
+            drop(terminal);
+            let title = resized.title();
+            let terminal = resized.context.terminal.lock();
+            super::snapshot(session, &title, &terminal)
+        };
+        self.broadcast(session, &Message::Snapshot(snapshot));
+    }
+
+    /// Send the changes of `session` to its clients.
+    fn flush(&mut self, session: usize) {
+        let Some(flushed) = self.sessions.get_mut(&session) else {
+            return;
+        };
+        let title = flushed.title();
+        let mut update = {
+            let mut terminal = flushed.context.terminal.lock();
+            update(&mut terminal, &mut flushed.scrolled)
+        };
+        if title != flushed.title {
+            update.title = Some(title.clone());
+            flushed.title = title;
+        }
+        self.broadcast(session, &Message::Update(update));
+    }
+
+    /// End `session`, hanging up its program.
+    fn end(&mut self, session: usize) {
+        if !self.sessions.contains_key(&session) {
+            return;
+        }
+        self.flush(session);
+        self.broadcast(session, &Message::Exited);
+        if let Some(ended) = self.sessions.remove(&session) {
+            for client in ended.clients {
+                // Closed once told about the end.
+                self.connections.remove(&client);
+            }
+        }
+    }
+
+    fn detach(&mut self, id: usize, session: usize) {
+        if let Some(session) = self.sessions.get_mut(&session) {
+            session.clients.retain(|client| *client != id);
+        }
+    }
+
+    fn disconnect(&mut self, id: usize) {
+        if let Some(connection) = self.connections.remove(&id) {
+            if let Some(session) = connection.session {
+                self.detach(id, session);
+            }
+        }
+    }
+
+    fn broadcast(&mut self, session: usize, message: &Message) {
+        let clients = match self.sessions.get(&session) {
+            Some(session) => session.clients.clone(),
+            None => return,
+        };
+        for client in clients {
+            self.send(client, message);
+        }
+    }
+
+    /// Queue `message` for the connection `id`, it is closed when it
+    /// does not keep up.
+    fn send(&mut self, id: usize, message: &Message) {
+        let Some(connection) = self.connections.get(&id) else {
+            return;
+        };
+        let mut line = match serde_json::to_string(message) {
+            Ok(line) => line,
+            Err(err) => {
+                log::error!("unable to serialize server message: {err}");
+                return;
+            }
+        };
+        line.push('\n');
+        match connection.queue.try_send(line) {
+            Ok(()) => {}
+            Err(mpsc::TrySendError::Full(_)) => {
+                log::warn!("server connection {id} is too slow, closing it");
+                self.disconnect(id);
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => self.disconnect(id),
+        }
+    }
+}
+
+fn no_session(session: usize) -> Message {
+    Message::Error {
+        message: format!("session {session} not found"),
+    }
+}
+
+pub(super) fn write_line<T: serde::Serialize>(
+    mut stream: &UnixStream,
+    value: &T,
+) -> io::Result<()> {
+    let mut content = serde_json::to_string(value)?;
+    content.push('\n');
+    stream.write_all(content.as_bytes())
+}
@@ -0,0 +1,466 @@
+// Detachable sessions.
+//
+// `rio server` owns the terminals of its sessions, their programs and the
+// `Crosswords` fed by them, and keeps them running while no window shows
+// them. A client attaches to one session per connection, over a Unix
+// socket, and keeps a mirror of its terminal: it first gets a snapshot of
+// the whole terminal, scrollback included, then updates made of the lines
+// damaged since the last update and the lines scrolled into history,
+// rather than the output of the program.
+//
+// Every message is one line of JSON:
+//
+//  {"command":"attach","session":1,"size":{"columns":80,"rows":24,"width":640,"height":480}}
+//  {"kind":"snapshot","session":1,"title":"zsh","columns":80,"rows":24,...}
+//  {"kind":"update","history-size":120,"lines":[{"line":3,"left":0,"right":5,"squares":[...]}],...}
+//  {"command":"input","bytes":[108,115,13]}
+//
+// Closing the connection detaches from the session, which ends once its
+// program exits or it is killed.
+
+#[cfg(unix)]
+mod client;
+#[cfg(unix)]
+mod host;
+
+#[cfg(unix)]
+pub use client::{mirror, start, Client};
+#[cfg(unix)]
+pub use host::Server;
+
+use crate::crosswords::grid::row::Row;
+use crate::crosswords::grid::{Dimensions, Grid, GridSquare};
+use crate::crosswords::pos::{Column, Line, Pos};
+use crate::crosswords::square::Square;
+use crate::crosswords::{Crosswords, Mode, TermDamage};
+use crate::event::EventListener;
+use crate::performer::handler::Handler;
+use crate::session::SquareSnapshot;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Socket the server listens on by default.
+pub fn socket_path() -> PathBuf {
+    crate::ipc::socket_dir().join("rio-server.sock")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub columns: usize,
+    pub rows: usize,
+    /// Size in pixels, given to the programs.
+    pub width: u16,
+    pub height: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    ListSessions,
+    /// Start a session and attach to it, running the user shell unless a
+    /// command is given.
+    Create {
+        #[serde(default, rename = "command-line")]
+        command: Vec<String>,
+        #[serde(default, rename = "working-directory")]
+        working_directory: Option<String>,
+        /// Variables set in the environment of the program only.
+        #[serde(default)]
+        envs: Vec<(String, String)>,
+        size: Size,
+    },
+    Attach {
+        session: usize,
+        size: Size,
+    },
+    /// End a session, hanging up its program.
+    Kill {
+        session: usize,
+    },
+    /// Write to the program of the attached session.
+    Input {
+        bytes: Vec<u8>,
+    },
+    /// Resize the attached session.
+    Resize {
+        size: Size,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Message {
+    Ok,
+    Sessions {
+        sessions: Vec<SessionInfo>,
+    },
+    /// Sent once attached, and again when the session is resized.
+    Snapshot(Snapshot),
+    Update(Update),
+    Bell,
+    /// The session ended, the connection is closed right after.
+    Exited,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct SessionInfo {
+    pub id: usize,
+    pub title: String,
+    /// Amount of clients attached to it.
+    pub attached: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Whole terminal of a session.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Snapshot {
+    pub session: usize,
+    pub title: String,
+    pub columns: usize,
+    pub rows: usize,
+    /// Scrollback of the primary screen, oldest line first.
+    pub history: Vec<Vec<SquareSnapshot>>,
+    /// Lines of the active screen, without their trailing empty squares.
+    pub screen: Vec<Vec<SquareSnapshot>>,
+    pub cursor: Cursor,
+    /// Bits of the terminal modes.
+    pub mode: u32,
+}
+
+/// Changes of a terminal since the last update.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Update {
+    /// Lines scrolled into the history of the primary screen, oldest
+    /// first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<Vec<SquareSnapshot>>,
+    /// Lines of history left, less than before when it was cleared.
+    pub history_size: usize,
+    /// Damaged lines of the active screen.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<LineUpdate>,
+    pub cursor: Cursor,
+    pub mode: u32,
+    /// New title, if it changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Squares from `left` to `right`, empty past the given ones.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LineUpdate {
+    pub line: usize,
+    pub left: usize,
+    pub right: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub squares: Vec<SquareSnapshot>,
+}
+
+/// Squares of `row` from `left` to `right`, without the trailing empty
+/// ones.
+fn squares(row: &Row<Square>, left: usize, right: usize) -> Vec<SquareSnapshot> {
+    let squares = &row[Column(left)..Column(right + 1)];
+    let length = squares
+        .iter()
+        .rposition(|square| !square.is_empty())
+        .map_or(0, |position| position + 1);
+    squares[..length].iter().map(SquareSnapshot::from).collect()
+}
+
+fn cursor<U: EventListener>(terminal: &Crosswords<U>) -> Cursor {
+    let pos = terminal.grid.cursor.pos;
+    Cursor {
+        line: pos.row.0.max(0) as usize,
+        column: pos.col.0,
+    }
+}
+
+/// Everything a client needs to mirror `terminal`.
+pub fn snapshot<U: EventListener>(
+    session: usize,
+    title: &str,
+    terminal: &Crosswords<U>,
+) -> Snapshot {
+    let primary = terminal.primary_grid();
+    let columns = terminal.grid.columns();
+    let history = (1..=primary.history_size())
+        .rev()
+        .map(|line| squares(&primary[Line(-(line as i32))], 0, columns - 1))
+        .collect();
+    let screen = (0..terminal.grid.screen_lines())
+        .map(|line| squares(&terminal.grid[Line(line as i32)], 0, columns - 1))
+        .collect();
+
+    Snapshot {
+        session,
+        title: title.to_owned(),
+        columns,
+        rows: terminal.grid.screen_lines(),
+        history,
+        screen,
+        cursor: cursor(terminal),
+        mode: terminal.mode().bits(),
+    }
+}
+
+/// Changes of `terminal` since the last update, `scrolled` holds the
+/// lines scrolled into history by then (see `Grid::scrolled_lines`). The
+/// damage of the terminal is reset.
+pub fn update<U: EventListener>(
+    terminal: &mut Crosswords<U>,
+    scrolled: &mut usize,
+) -> Update {
+    let primary = terminal.primary_grid();
+    let columns = primary.columns();
+    let added = primary
+        .scrolled_lines()
+        .wrapping_sub(*scrolled)
+        .min(primary.history_size());
+    *scrolled = primary.scrolled_lines();
+    let history = (1..=added)
+        .rev()
+        .map(|line| squares(&primary[Line(-(line as i32))], 0, columns - 1))
+        .collect();
+    let history_size = primary.history_size();
+
+    let screen_lines = terminal.grid.screen_lines();
+    let damaged: Vec<(usize, usize, usize)> = match terminal.damage() {
+        TermDamage::Full => (0..screen_lines)
+            .map(|line| (line, 0, columns - 1))
+            .collect(),
+        TermDamage::Partial(lines) => lines
+            .filter(|bounds| bounds.line < screen_lines)
+            .map(|bounds| (bounds.line, bounds.left, bounds.right.min(columns - 1)))
+            .collect(),
+    };
+    let lines = damaged
+        .into_iter()
+        .map(|(line, left, right)| LineUpdate {
+            line,
+            left,
+            right,
+            squares: squares(&terminal.grid[Line(line as i32)], left, right),
+        })
+        .collect();
+    terminal.reset_damage();
+
+    Update {
+        history,
+        history_size,
+        lines,
+        cursor: cursor(terminal),
+        mode: terminal.mode().bits(),
+        title: None,
+    }
+}
+
+/// Write `squares` into `row` from `left` to `right`, the squares past
+/// the given ones are emptied.
+fn write_row(
+    row: &mut Row<Square>,
+    left: usize,
+    right: usize,
+    squares: &[SquareSnapshot],
+) {
+    let right = right.min(row.len().saturating_sub(1));
+    for column in left..=right {
+        row[Column(column)] = squares
+            .get(column - left)
+            .map(Square::from)
+            .unwrap_or_default();
+    }
+}
+
+/// Push `rows` into the history of `grid`, oldest first.
+fn push_history(grid: &mut Grid<Square>, rows: &[Vec<SquareSnapshot>]) {
+    let screen_lines = grid.screen_lines();
+    let columns = grid.columns();
+    for chunk in rows.chunks(screen_lines) {
+        grid.scroll_up(&(Line(0)..Line(screen_lines as i32)), chunk.len());
+        for (index, row) in chunk.iter().enumerate() {
+            let line = Line(index as i32 - chunk.len() as i32);
+            write_row(&mut grid[line], 0, columns - 1, row);
+        }
+    }
+}
+
+fn set_cursor<U: EventListener>(terminal: &mut Crosswords<U>, cursor: Cursor) {
+    let line = cursor.line.min(terminal.grid.screen_lines() - 1);
+    let column = cursor.column.min(terminal.grid.columns() - 1);
+    terminal.grid.cursor.pos = Pos::new(Line(line as i32), Column(column));
+}
+
+/// Make `terminal` a mirror of the one `snapshot` was taken of.
+pub fn apply_snapshot<U: EventListener>(
+    terminal: &mut Crosswords<U>,
+    snapshot: &Snapshot,
+) {
+    terminal.resize::<(usize, usize)>(snapshot.columns, snapshot.rows);
+    terminal.mirror_mode(Mode::from_bits_truncate(snapshot.mode));
+
+    let primary = terminal.primary_grid_mut();
+    primary.clear_history();
+    push_history(primary, &snapshot.history);
+
+    let columns = terminal.grid.columns();
+    for line in 0..terminal.grid.screen_lines() {
+        let squares = snapshot.screen.get(line).map_or(&[][..], Vec::as_slice);
+        write_row(
+            &mut terminal.grid[Line(line as i32)],
+            0,
+            columns - 1,
+            squares,
+        );
+    }
+    set_cursor(terminal, snapshot.cursor);
+    terminal.set_title(Some(snapshot.title.to_owned()));
+    terminal.mark_fully_damaged();
+}
+
+/// Bring the mirror `terminal` up to date.
+pub fn apply_update<U: EventListener>(terminal: &mut Crosswords<U>, update: &Update) {
+    let primary = terminal.primary_grid_mut();
+    push_history(primary, &update.history);
+    primary.truncate_history(update.history_size);
+
+    terminal.mirror_mode(Mode::from_bits_truncate(update.mode));
+    let screen_lines = terminal.grid.screen_lines();
+    for line in update.lines.iter().filter(|line| line.line < screen_lines) {
+        write_row(
+            &mut terminal.grid[Line(line.line as i32)],
+            line.left,
+            line.right,
+            &line.squares,
+        );
+    }
+    set_cursor(terminal, update.cursor);
+    if let Some(title) = &update.title {
+        terminal.set_title(Some(title.to_owned()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::VoidListener;
+    use crate::performer::handler::ParserProcessor;
+
+    fn feed(terminal: &mut Crosswords<VoidListener>, bytes: &[u8]) {
+        let mut parser = ParserProcessor::new();
+        for byte in bytes {
+            parser.advance(terminal, *byte);
+        }
+    }
+
+    pub(super) fn text<U: EventListener>(terminal: &Crosswords<U>) -> Vec<String> {
+        let grid = &terminal.grid;
+        let top = -(grid.history_size() as i32);
+        (top..grid.screen_lines() as i32)
+            .map(|line| {
+                grid[Line(line)][..]
+                    .iter()
+                    .map(|square| square.c)
+                    .collect::<String>()
+                    .trim_end()
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_update_mirrors_terminal() {
+        let mut terminal = Crosswords::new(10, 4, VoidListener);
+        let mut scrolled = 0;
+        let mut mirror = Crosswords::new(10, 4, VoidListener);
+        apply_snapshot(&mut mirror, &snapshot(1, "sh", &terminal));
+        // The snapshot covers the damage so far.
+        update(&mut terminal, &mut scrolled);
+
+        feed(&mut terminal, b"$ ls\r\n");
+        let changes = update(&mut terminal, &mut scrolled);
+        assert!(changes.history.is_empty());
+        assert!(changes.lines.iter().all(|line| line.line <= 1));
+        apply_update(&mut mirror, &changes);
+        assert_eq!(text(&mirror), text(&terminal));
+
+        // Lines scrolled out of the screen are sent once.
+        feed(&mut terminal, b"a\r\nb\r\nc\r\nd\r\ne\r\n\x1b[31mred");
+        let changes = update(&mut terminal, &mut scrolled);
+        assert_eq!(changes.history.len(), 3);
+        apply_update(&mut mirror, &changes);
+        assert_eq!(text(&mirror), text(&terminal));
+        assert_eq!(mirror.grid.cursor.pos, terminal.grid.cursor.pos);
+        assert_eq!(
+            mirror.grid[Line(3)][Column(0)].fg,
+            terminal.grid[Line(3)][Column(0)].fg
+        );
+
+        assert!(update(&mut terminal, &mut scrolled).history.is_empty());
+
+        // Clearing the history and the screen.
+        feed(&mut terminal, b"\x1b[3J\x1b[2J\x1b[H");
+        apply_update(&mut mirror, &update(&mut terminal, &mut scrolled));
+        assert_eq!(text(&mirror), text(&terminal));
+    }
+
+    #[test]
+    fn test_update_alternate_screen() {
+        let mut terminal = Crosswords::new(10, 3, VoidListener);
+        let mut scrolled = 0;
+        let mut mirror = Crosswords::new(10, 3, VoidListener);
+        feed(&mut terminal, b"1\r\n2\r\n3\r\n4\r\n$ ");
+        apply_snapshot(&mut mirror, &snapshot(1, "sh", &terminal));
+        update(&mut terminal, &mut scrolled);
+        assert_eq!(text(&mirror), text(&terminal));
+
+        feed(&mut terminal, b"\x1b[?1049h\x1b[Hvim");
+        apply_update(&mut mirror, &update(&mut terminal, &mut scrolled));
+        assert!(mirror.mode().contains(Mode::ALT_SCREEN));
+        assert_eq!(text(&mirror), text(&terminal));
+
+        feed(&mut terminal, b"\x1b[?1049l");
+        apply_update(&mut mirror, &update(&mut terminal, &mut scrolled));
+        assert!(!mirror.mode().contains(Mode::ALT_SCREEN));
+        assert_eq!(text(&mirror), vec!["1", "2", "3", "4", "$"]);
+    }
+
+    #[test]
+    fn test_snapshot_format() {
+        let mut terminal = Crosswords::new(10, 2, VoidListener);
+        feed(&mut terminal, b"one\r\ntwo\r\nthree");
+
+        let snapshot = snapshot(3, "sh", &terminal);
+        assert_eq!(snapshot.history.len(), 1);
+        let message = serde_json::to_string(&Message::Snapshot(snapshot)).unwrap();
+        assert!(message.starts_with(r#"{"kind":"snapshot","session":3,"title":"sh""#));
+        assert!(message.contains(r#""history":[[{"c":"o"},{"c":"n"},{"c":"e"}]]"#));
+
+        let request: Request = serde_json::from_str(
+            r#"{"command":"attach","session":3,"size":{"columns":80,"rows":24,"width":0,"height":0}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request::Attach {
+                session: 3,
+                size: Size {
+                    columns: 80,
+                    rows: 24,
+                    width: 0,
+                    height: 0,
+                },
+            }
+        );
+    }
+}
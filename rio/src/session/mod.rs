@@ -0,0 +1,629 @@
+// Session persistence.
+//
+// The tabs of each window, with the panes of each tab and their scrollback,
+// cursor, title and working directory, are serialized into a versioned JSON
+// document stored in `<config dir>/sessions/session.json`. On launch (when
+// `[session] restore = true`) every window, its tabs and their splits are
+// re-created from that document, with the saved scrollback written above a
+// fresh shell.
+
+use crate::crosswords::grid::row::Row;
+use crate::crosswords::grid::{Dimensions, Grid, GridSquare};
+use crate::crosswords::pos::Column;
+use crate::crosswords::square::{Flags, Hyperlink, Square};
+use crate::crosswords::Crosswords;
+use crate::event::EventListener;
+use crate::screen::panes::SplitDirection;
+use colors::{AnsiColor, NamedColor};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Bumped whenever the on-disk format changes in an incompatible way.
+pub const SESSION_VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Io(err) => write!(f, "unable to access session file: {err}"),
+            SessionError::Parse(err) => write!(f, "unable to parse session file: {err}"),
+            SessionError::UnsupportedVersion(version) => write!(
+                f,
+                "session file version {version} is not supported (expected {SESSION_VERSION})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+impl From<io::Error> for SessionError {
+    fn from(err: io::Error) -> Self {
+        SessionError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SessionError {
+    fn from(err: serde_json::Error) -> Self {
+        SessionError::Parse(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub version: u32,
+    /// Windows in the order they were opened.
+    pub windows: Vec<WindowSnapshot>,
+}
+
+impl SessionSnapshot {
+    pub fn new(windows: Vec<WindowSnapshot>) -> Self {
+        SessionSnapshot {
+            version: SESSION_VERSION,
+            windows,
+        }
+    }
+
+    pub fn from_json(content: &str) -> Result<Self, SessionError> {
+        let snapshot: SessionSnapshot = serde_json::from_str(content)?;
+        if snapshot.version != SESSION_VERSION {
+            return Err(SessionError::UnsupportedVersion(snapshot.version));
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn to_json(&self) -> Result<String, SessionError> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowSnapshot {
+    pub current: usize,
+    pub tabs: Vec<TabSnapshot>,
+}
+
+impl WindowSnapshot {
+    pub fn new(current: usize, tabs: Vec<TabSnapshot>) -> Self {
+        WindowSnapshot { current, tabs }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TabSnapshot {
+    pub panes: PaneSnapshot,
+    /// Position of the focused pane, panes being counted in the order they
+    /// are drawn.
+    #[serde(default)]
+    pub current: usize,
+}
+
+impl TabSnapshot {
+    /// Tab made of a single pane.
+    #[cfg(test)]
+    pub fn single(context: ContextSnapshot) -> Self {
+        TabSnapshot {
+            panes: PaneSnapshot::Context(context),
+            current: 0,
+        }
+    }
+
+    /// Every pane, in the order they are drawn.
+    pub fn contexts(&self) -> Vec<&ContextSnapshot> {
+        let mut contexts = vec![];
+        self.panes.collect(&mut contexts);
+        contexts
+    }
+
+    /// The focused pane.
+    pub fn focused(&self) -> Option<&ContextSnapshot> {
+        let contexts = self.contexts();
+        contexts
+            .get(self.current)
+            .or_else(|| contexts.first())
+            .copied()
+    }
+}
+
+/// Splits of a tab, see [`crate::screen::panes::PaneTree`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PaneSnapshot {
+    Context(ContextSnapshot),
+    Split {
+        direction: SplitDirection,
+        ratio: f32,
+        first: Box<PaneSnapshot>,
+        second: Box<PaneSnapshot>,
+    },
+}
+
+impl PaneSnapshot {
+    fn collect<'a>(&'a self, contexts: &mut Vec<&'a ContextSnapshot>) {
+        match self {
+            PaneSnapshot::Context(context) => contexts.push(context),
+            PaneSnapshot::Split { first, second, .. } => {
+                first.collect(contexts);
+                second.collect(contexts);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextSnapshot {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
+    /// Rows of the primary screen from the oldest scrollback line down to
+    /// the last written line, or the cursor line when it is below.
+    pub rows: Vec<Vec<SquareSnapshot>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<CursorSnapshot>,
+}
+
+/// Line of the cursor, its row counts from the first saved row. A fresh
+/// shell starts below the restored rows, so the column is not kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorSnapshot {
+    pub row: usize,
+}
+
+impl ContextSnapshot {
+    pub fn new<T: EventListener>(
+        terminal: &Crosswords<T>,
+        working_directory: Option<String>,
+    ) -> Self {
+        let (rows, cursor) = rows_from_grid(terminal.primary_grid());
+        ContextSnapshot {
+            title: terminal.title().map(String::from),
+            working_directory,
+            rows,
+            cursor,
+        }
+    }
+
+    /// Write the saved rows and title into `terminal`, see
+    /// [`Crosswords::restore_history`].
+    pub fn restore<T: EventListener>(&self, terminal: &mut Crosswords<T>) {
+        let cursor_row = self.cursor.map(|cursor| cursor.row);
+        terminal.restore_history(self.to_rows(), cursor_row);
+        terminal.restore_title(self.title.clone());
+    }
+
+    /// Rebuild grid rows out of the snapshot.
+    pub fn to_rows(&self) -> Vec<Row<Square>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let squares: Vec<Square> = row.iter().map(Square::from).collect();
+                let occ = squares.len();
+                Row::from_vec(squares, occ)
+            })
+            .collect()
+    }
+}
+
+fn rows_from_grid(
+    grid: &Grid<Square>,
+) -> (Vec<Vec<SquareSnapshot>>, Option<CursorSnapshot>) {
+    let mut last = grid.cursor.pos.row;
+    let mut line = grid.bottommost_line();
+    while line > last {
+        if grid[line][..].iter().any(|square| !square.is_empty()) {
+            last = line;
+            break;
+        }
+        line -= 1;
+    }
+
+    let mut rows: Vec<Vec<SquareSnapshot>> = Vec::new();
+    let mut line = grid.topmost_line();
+    while line <= last {
+        let row = &grid[line];
+        let length = row[..]
+            .iter()
+            .rposition(|square| !square.is_empty())
+            .map_or(0, |position| position + 1);
+        rows.push(
+            row[Column(0)..Column(length)]
+                .iter()
+                .map(SquareSnapshot::from)
+                .collect(),
+        );
+        line += 1;
+    }
+
+    // Leading empty rows carry no information.
+    let cursor_row = (grid.cursor.pos.row - grid.topmost_line()).0 as usize;
+    let first = rows
+        .iter()
+        .position(|row| !row.is_empty())
+        .unwrap_or(rows.len())
+        .min(cursor_row);
+    rows.drain(..first);
+    if rows.iter().all(|row| row.is_empty()) {
+        return (vec![], None);
+    }
+
+    let cursor = CursorSnapshot {
+        row: cursor_row - first,
+    };
+    (rows, Some(cursor))
+}
+
+fn default_fg() -> AnsiColor {
+    AnsiColor::Named(NamedColor::Foreground)
+}
+
+fn default_bg() -> AnsiColor {
+    AnsiColor::Named(NamedColor::Background)
+}
+
+fn is_default_fg(color: &AnsiColor) -> bool {
+    *color == default_fg()
+}
+
+fn is_default_bg(color: &AnsiColor) -> bool {
+    *color == default_bg()
+}
+
+fn is_zero(flags: &u16) -> bool {
+    *flags == 0
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HyperlinkSnapshot {
+    pub id: String,
+    pub uri: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SquareSnapshot {
+    pub c: char,
+    #[serde(default = "default_fg", skip_serializing_if = "is_default_fg")]
+    pub fg: AnsiColor,
+    #[serde(default = "default_bg", skip_serializing_if = "is_default_bg")]
+    pub bg: AnsiColor,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub flags: u16,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub zerowidth: Vec<char>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underline_color: Option<AnsiColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hyperlink: Option<HyperlinkSnapshot>,
+}
+
+impl From<&Square> for SquareSnapshot {
+    fn from(square: &Square) -> Self {
+        SquareSnapshot {
+            c: square.c,
+            fg: square.fg,
+            bg: square.bg,
+            flags: square.flags.bits(),
+            zerowidth: square
+                .zerowidth()
+                .map(|chars| chars.to_vec())
+                .unwrap_or_default(),
+            underline_color: square.underline_color(),
+            hyperlink: square.hyperlink().map(|hyperlink| HyperlinkSnapshot {
+                id: hyperlink.id().to_string(),
+                uri: hyperlink.uri().to_string(),
+            }),
+        }
+    }
+}
+
+impl From<&SquareSnapshot> for Square {
+    fn from(snapshot: &SquareSnapshot) -> Self {
+        let mut square = Square {
+            c: snapshot.c,
+            fg: snapshot.fg,
+            bg: snapshot.bg,
+            flags: Flags::from_bits_truncate(snapshot.flags),
+            ..Square::default()
+        };
+        for character in &snapshot.zerowidth {
+            square.push_zerowidth(*character);
+        }
+        if snapshot.underline_color.is_some() {
+            square.set_underline_color(snapshot.underline_color);
+        }
+        if let Some(hyperlink) = &snapshot.hyperlink {
+            square.set_hyperlink(Some(Hyperlink::new(
+                Some(&hyperlink.id),
+                hyperlink.uri.clone(),
+            )));
+        }
+        square
+    }
+}
+
+/// Read the session stored at `path`.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<SessionSnapshot, SessionError> {
+    let content = fs::read_to_string(path)?;
+    SessionSnapshot::from_json(&content)
+}
+
+/// Write the session into `path`, the file is replaced atomically.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    snapshot: &SessionSnapshot,
+) -> Result<(), SessionError> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temporary = path.with_extension("json.tmp");
+    fs::write(&temporary, snapshot.to_json()?)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Remove the session stored at `path`, if any.
+pub fn clear<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref();
+    if path.exists() {
+        if let Err(err) = fs::remove_file(path) {
+            log::warn!("unable to remove session file: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::crosswords::pos::{Line, Pos};
+    use crate::event::VoidListener;
+    use crate::performer::handler::Handler;
+
+    fn write_str(cw: &mut Crosswords<VoidListener>, text: &str) {
+        for c in text.chars() {
+            cw.input(c);
+        }
+    }
+
+    #[test]
+    fn test_square_roundtrip() {
+        let mut square = Square {
+            c: 'a',
+            fg: AnsiColor::Indexed(3),
+            bg: AnsiColor::Spec(colors::ColorRgb { r: 1, g: 2, b: 3 }),
+            flags: Flags::BOLD | Flags::UNDERCURL,
+            ..Square::default()
+        };
+        square.push_zerowidth('\u{301}');
+        square.set_underline_color(Some(AnsiColor::Named(NamedColor::Red)));
+        square.set_hyperlink(Some(Hyperlink::new(
+            Some("link"),
+            String::from("https://raphamorim.io/rio"),
+        )));
+
+        let snapshot = SquareSnapshot::from(&square);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored =
+            Square::from(&serde_json::from_str::<SquareSnapshot>(&json).unwrap());
+        assert_eq!(restored, square);
+    }
+
+    #[test]
+    fn test_default_square_is_compact() {
+        let snapshot = SquareSnapshot::from(&Square::default());
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(json, "{\"c\":\" \"}");
+        assert_eq!(Square::from(&snapshot), Square::default());
+    }
+
+    #[test]
+    fn test_context_roundtrip() {
+        let mut cw = Crosswords::new(10, 3, VoidListener {});
+        write_str(&mut cw, "hello");
+        cw.carriage_return();
+        cw.linefeed();
+        write_str(&mut cw, "日本");
+        cw.carriage_return();
+        cw.linefeed();
+        write_str(&mut cw, "abc");
+        cw.carriage_return();
+        cw.linefeed();
+        write_str(&mut cw, "$ ");
+        cw.set_title(Some(String::from("vim")));
+
+        let snapshot = ContextSnapshot::new(&cw, Some(String::from("/tmp")));
+        assert_eq!(snapshot.title.as_deref(), Some("vim"));
+        assert_eq!(snapshot.working_directory.as_deref(), Some("/tmp"));
+        assert_eq!(snapshot.rows.len(), 4);
+        assert_eq!(snapshot.cursor, Some(CursorSnapshot { row: 3 }));
+        // Wide chars are stored along with their spacers.
+        assert_eq!(snapshot.rows[1].len(), 4);
+
+        let session = SessionSnapshot::new(vec![WindowSnapshot::new(
+            0,
+            vec![TabSnapshot::single(snapshot)],
+        )]);
+        let json = session.to_json().unwrap();
+        let restored = SessionSnapshot::from_json(&json).unwrap();
+        assert_eq!(restored, session);
+
+        let mut new_cw = Crosswords::new(10, 3, VoidListener {});
+        restored.windows[0].tabs[0].contexts()[0].restore(&mut new_cw);
+        assert_eq!(new_cw.title(), Some("vim"));
+        let grid = &new_cw.grid;
+        let text: Vec<String> = (grid.topmost_line().0..=grid.bottommost_line().0)
+            .map(|line| {
+                grid[Line(line)][..]
+                    .iter()
+                    .filter(|square| !square.flags.contains(Flags::WIDE_CHAR_SPACER))
+                    .map(|square| square.c)
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(text, vec!["hello", "日本", "abc", "$", "──────────", ""]);
+        assert_eq!(new_cw.grid.cursor.pos, Pos::new(Line(2), Column(0)));
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let mut cw = Crosswords::new(10, 5, VoidListener {});
+        write_str(&mut cw, "$ ls");
+        cw.carriage_return();
+        cw.linefeed();
+        write_str(&mut cw, "a  b");
+        cw.goto(Line(3), Column(6));
+
+        let snapshot = ContextSnapshot::new(&cw, None);
+        assert_eq!(snapshot.cursor, Some(CursorSnapshot { row: 3 }));
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.ends_with(",\"cursor\":{\"row\":3}}"));
+        let restored: ContextSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, snapshot);
+
+        let mut new_cw = Crosswords::new(10, 6, VoidListener {});
+        restored.restore(&mut new_cw);
+        // The cursor line, then the separator, the fresh shell starts below.
+        assert!(new_cw.grid[Line(3)][..]
+            .iter()
+            .all(|square| square.c == ' '));
+        assert_eq!(new_cw.grid[Line(4)][Column(0)].c, '─');
+        assert_eq!(new_cw.grid.cursor.pos, Pos::new(Line(5), Column(0)));
+    }
+
+    #[test]
+    fn test_rows_below_cursor() {
+        let mut cw = Crosswords::new(10, 4, VoidListener {});
+        write_str(&mut cw, "$ vim");
+        cw.goto(Line(2), Column(0));
+        write_str(&mut cw, "~");
+        cw.goto(Line(1), Column(3));
+
+        let snapshot = ContextSnapshot::new(&cw, None);
+        assert_eq!(snapshot.rows.len(), 3);
+        assert_eq!(snapshot.cursor, Some(CursorSnapshot { row: 1 }));
+
+        // Nothing written but the cursor moved down, its line is kept.
+        let mut cw = Crosswords::new(10, 4, VoidListener {});
+        write_str(&mut cw, "a");
+        cw.goto(Line(2), Column(1));
+        let snapshot = ContextSnapshot::new(&cw, None);
+        assert_eq!(snapshot.rows.len(), 3);
+        assert_eq!(snapshot.cursor, Some(CursorSnapshot { row: 2 }));
+
+        let mut restored = Crosswords::new(10, 4, VoidListener {});
+        snapshot.restore(&mut restored);
+        // Three rows and the separator, the first one went into history.
+        assert_eq!(restored.history_size(), 1);
+        assert_eq!(restored.grid.cursor.pos.row, Line(3));
+    }
+
+    #[test]
+    fn test_reject_unknown_version() {
+        let json = "{\"version\":999,\"windows\":[]}";
+        assert!(matches!(
+            SessionSnapshot::from_json(json),
+            Err(SessionError::UnsupportedVersion(999))
+        ));
+    }
+
+    #[test]
+    fn test_windows() {
+        let context = |title: &str| {
+            TabSnapshot::single(ContextSnapshot {
+                title: Some(title.to_string()),
+                working_directory: None,
+                rows: vec![],
+                cursor: None,
+            })
+        };
+        let session = SessionSnapshot::new(vec![
+            WindowSnapshot::new(1, vec![context("a"), context("b")]),
+            WindowSnapshot::new(0, vec![context("c")]),
+        ]);
+        let restored = SessionSnapshot::from_json(&session.to_json().unwrap()).unwrap();
+        assert_eq!(restored, session);
+        let titles: Vec<Vec<_>> = restored
+            .windows
+            .iter()
+            .map(|window| {
+                window
+                    .tabs
+                    .iter()
+                    .map(|tab| tab.focused().unwrap().title.as_deref().unwrap())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(titles, vec![vec!["a", "b"], vec!["c"]]);
+        assert_eq!(restored.windows[0].current, 1);
+    }
+
+    #[test]
+    fn test_splits_roundtrip() {
+        let context = |title: &str| ContextSnapshot {
+            title: Some(title.to_string()),
+            working_directory: None,
+            rows: vec![],
+            cursor: None,
+        };
+        let tab = TabSnapshot {
+            panes: PaneSnapshot::Split {
+                direction: SplitDirection::Right,
+                ratio: 0.25,
+                first: Box::new(PaneSnapshot::Context(context("a"))),
+                second: Box::new(PaneSnapshot::Split {
+                    direction: SplitDirection::Down,
+                    ratio: 0.5,
+                    first: Box::new(PaneSnapshot::Context(context("b"))),
+                    second: Box::new(PaneSnapshot::Context(context("c"))),
+                }),
+            },
+            current: 2,
+        };
+        let session = SessionSnapshot::new(vec![WindowSnapshot::new(0, vec![tab])]);
+        let restored = SessionSnapshot::from_json(&session.to_json().unwrap()).unwrap();
+        assert_eq!(restored, session);
+
+        let tab = &restored.windows[0].tabs[0];
+        let titles: Vec<_> = tab
+            .contexts()
+            .iter()
+            .map(|context| context.title.as_deref().unwrap())
+            .collect();
+        assert_eq!(titles, vec!["a", "b", "c"]);
+        assert_eq!(tab.focused().unwrap().title.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir()
+            .join(format!("rio-session-test-{}", std::process::id()))
+            .join("session.json");
+        let session = SessionSnapshot::new(vec![WindowSnapshot::new(
+            0,
+            vec![TabSnapshot::single(ContextSnapshot {
+                title: None,
+                working_directory: None,
+                rows: vec![vec![SquareSnapshot::from(&Square::default())]],
+                cursor: Some(CursorSnapshot { row: 0 }),
+            })],
+        )]);
+        save(&path, &session).unwrap();
+        assert_eq!(load(&path).unwrap(), session);
+        clear(&path);
+        assert!(!path.exists());
+        let _ = fs::remove_dir(path.parent().unwrap());
+    }
+}
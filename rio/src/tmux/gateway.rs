@@ -0,0 +1,623 @@
+use super::{Layout, Notification};
+use crate::crosswords::grid::Dimensions;
+use crate::crosswords::Crosswords;
+use crate::event::sync::FairMutex;
+use crate::event::{EventListener, Msg, RioEvent};
+use crate::performer::handler::ParserProcessor;
+use corcovado::channel;
+use corcovado::{Events, Poll, PollOpt, Ready, Token};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Keys sent by one `send-keys`, a paste is split into several.
+const KEYS_PER_COMMAND: usize = 256;
+
+const LIST_WINDOWS: &str =
+    "list-windows -F '#{window_id} #{window_layout} #{window_name}'";
+
+static NEXT_GATEWAY: AtomicUsize = AtomicUsize::new(1);
+
+/// What changed in the tmux session, for the tabs showing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Panes of a window and how they share it, the window is new unless a
+    /// tab shows it already.
+    Layout {
+        window: usize,
+        layout: Layout,
+    },
+    Renamed {
+        window: usize,
+        name: String,
+    },
+    Closed {
+        window: usize,
+    },
+    /// Every window is gone, control mode ended or another session is
+    /// shown.
+    Cleared,
+}
+
+/// Command waiting for its reply.
+#[derive(Debug)]
+enum Command {
+    ListWindows,
+    /// Content of a pane, scrollback included.
+    Capture(usize),
+    Cursor(usize),
+    Other,
+}
+
+/// Feeds the terminal of a context with output of its pane, after giving
+/// it the columns and rows of the pane when known.
+type Feeder = Box<dyn FnMut(&[u8], Option<(usize, usize)>) + Send>;
+
+#[derive(Default)]
+struct PaneState {
+    /// Feeds the terminal of the context showing the pane.
+    feeder: Option<Feeder>,
+    /// Columns and rows tmux gives the pane, from the last layout.
+    size: Option<(usize, usize)>,
+    /// Output received before a context showed the pane.
+    pending: Vec<u8>,
+    /// Whether `capture-pane` was sent, the output received until its reply
+    /// is part of it.
+    capturing: bool,
+    /// Whether the context showing it was closed, its output is dropped.
+    hidden: bool,
+}
+
+impl PaneState {
+    fn output(&mut self, bytes: &[u8]) {
+        if self.capturing || self.hidden {
+            return;
+        }
+
+        match &mut self.feeder {
+            Some(feeder) => feeder(bytes, self.size),
+            None => self.pending.extend_from_slice(bytes),
+        }
+    }
+
+    /// Bring the terminal showing the pane back to its size in tmux.
+    fn fit(&mut self) {
+        if let Some(feeder) = &mut self.feeder {
+            feeder(&[], self.size);
+        }
+    }
+}
+
+struct Inner {
+    /// Channel of the terminal running tmux.
+    writer: channel::Sender<Msg>,
+    panes: HashMap<usize, PaneState>,
+    /// Commands written to tmux, in order.
+    commands: VecDeque<Command>,
+    session: Option<usize>,
+    /// Whether the content of the panes was captured once.
+    captured: bool,
+    exited: bool,
+}
+
+impl Inner {
+    fn command(&mut self, line: &str, command: Command) {
+        if self.exited {
+            return;
+        }
+
+        let mut line = line.to_owned();
+        line.push('\n');
+        if self
+            .writer
+            .send(Msg::Command(line.into_bytes().into()))
+            .is_ok()
+        {
+            self.commands.push_back(command);
+        }
+    }
+
+    /// Ask for the content of `pane`, its output is dropped until then.
+    fn capture(&mut self, pane: usize) {
+        let state = self.panes.entry(pane).or_default();
+        state.pending.clear();
+        state.capturing = true;
+        self.command(
+            &format!("capture-pane -p -e -S - -t %{pane}"),
+            Command::Capture(pane),
+        );
+        self.command(
+            &format!("display-message -p -t %{pane} '#{{cursor_x}} #{{cursor_y}}'"),
+            Command::Cursor(pane),
+        );
+    }
+
+    /// Follow the size of the panes of `layout`.
+    fn layout(&mut self, layout: &Layout) {
+        for (pane, columns, rows) in layout.sizes() {
+            let state = self.panes.entry(pane).or_default();
+            state.size = Some((columns, rows));
+            state.fit();
+        }
+    }
+
+    fn reply(&mut self, lines: Vec<Vec<u8>>, error: bool) -> Vec<Change> {
+        let command = match self.commands.pop_front() {
+            Some(command) => command,
+            None => return vec![],
+        };
+        if error {
+            log::warn!(
+                "tmux {command:?} failed: {}",
+                String::from_utf8_lossy(&lines.concat())
+            );
+            if let Command::Capture(pane) = command {
+                self.panes.entry(pane).or_default().capturing = false;
+            }
+            return vec![];
+        }
+
+        match command {
+            Command::ListWindows => {
+                let mut changes = vec![];
+                for line in lines {
+                    let line = String::from_utf8_lossy(&line);
+                    let mut words = line.splitn(3, ' ');
+                    let window = words.next().and_then(|window| super::id(window, '@'));
+                    let layout = words.next().and_then(Layout::parse);
+                    let (Some(window), Some(layout)) = (window, layout) else {
+                        log::warn!("unexpected tmux window: {line}");
+                        continue;
+                    };
+
+                    for pane in layout.panes() {
+                        if !self.captured || !self.panes.contains_key(&pane) {
+                            self.capture(pane);
+                        }
+                    }
+                    self.layout(&layout);
+                    changes.push(Change::Layout { window, layout });
+                    changes.push(Change::Renamed {
+                        window,
+                        name: words.next().unwrap_or_default().to_owned(),
+                    });
+                }
+                self.captured = true;
+                changes
+            }
+            Command::Capture(pane) => {
+                let state = self.panes.entry(pane).or_default();
+                state.capturing = false;
+                state.output(&lines.join(&b"\r\n"[..]));
+                vec![]
+            }
+            Command::Cursor(pane) => {
+                let line = String::from_utf8_lossy(lines.first().map_or(&[][..], |l| l));
+                let mut position =
+                    line.split(' ').filter_map(|n| n.parse::<usize>().ok());
+                if let (Some(x), Some(y)) = (position.next(), position.next()) {
+                    let cursor = format!("\x1b[{};{}H", y + 1, x + 1);
+                    if let Some(state) = self.panes.get_mut(&pane) {
+                        state.output(cursor.as_bytes());
+                    }
+                }
+                vec![]
+            }
+            Command::Other => vec![],
+        }
+    }
+}
+
+/// The tmux client running in control mode in a terminal, shared by the
+/// contexts showing its panes.
+#[derive(Clone)]
+pub struct Gateway {
+    id: usize,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl PartialEq for Gateway {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Gateway {}
+
+impl std::fmt::Debug for Gateway {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Gateway({})", self.id)
+    }
+}
+
+impl Gateway {
+    /// Gateway writing its commands to `writer`, the channel of the
+    /// terminal running tmux.
+    pub fn new(writer: channel::Sender<Msg>) -> Gateway {
+        Gateway {
+            id: NEXT_GATEWAY.fetch_add(1, Ordering::Relaxed),
+            inner: Arc::new(Mutex::new(Inner {
+                writer,
+                panes: HashMap::new(),
+                commands: VecDeque::new(),
+                session: None,
+                captured: false,
+                exited: false,
+            })),
+        }
+    }
+
+    /// Give tmux the size of the terminal and ask for its windows, once
+    /// control mode started.
+    pub fn start(&self, columns: usize, rows: usize) {
+        self.resize(columns, rows);
+        self.inner
+            .lock()
+            .unwrap()
+            .command(LIST_WINDOWS, Command::ListWindows);
+    }
+
+    /// Windows follow the size of the terminal running tmux.
+    pub fn resize(&self, columns: usize, rows: usize) {
+        self.inner.lock().unwrap().command(
+            &format!("refresh-client -C {columns}x{rows}"),
+            Command::Other,
+        );
+    }
+
+    /// Handle a notification of control mode, returns what the tabs
+    /// showing the session have to follow.
+    pub fn handle(&self, notification: Notification) -> Vec<Change> {
+        let mut inner = self.inner.lock().unwrap();
+        match notification {
+            Notification::Reply {
+                lines,
+                error,
+                client,
+            } => {
+                // Replies to the command given on the tmux command line
+                // are not waited for.
+                if client {
+                    return inner.reply(lines, error);
+                }
+            }
+            Notification::Output { pane, bytes } => {
+                inner.panes.entry(pane).or_default().output(&bytes);
+            }
+            Notification::LayoutChange { window, layout } => {
+                inner.layout(&layout);
+                return vec![Change::Layout { window, layout }];
+            }
+            Notification::WindowAdd { .. } => {
+                inner.command(LIST_WINDOWS, Command::ListWindows);
+            }
+            Notification::WindowClose { window } => {
+                return vec![Change::Closed { window }];
+            }
+            Notification::WindowRenamed { window, name } => {
+                return vec![Change::Renamed { window, name }];
+            }
+            Notification::SessionChanged { session } => {
+                let previous = inner.session.replace(session);
+                if previous.is_some() && previous != Some(session) {
+                    inner.panes.clear();
+                    inner.captured = false;
+                    inner.command(LIST_WINDOWS, Command::ListWindows);
+                    return vec![Change::Cleared];
+                }
+            }
+            Notification::Exit | Notification::End if !inner.exited => {
+                inner.exited = true;
+                inner.panes.clear();
+                return vec![Change::Cleared];
+            }
+            Notification::Exit | Notification::End => {}
+        }
+        vec![]
+    }
+
+    /// Type `bytes` in `pane`.
+    pub fn send_keys(&self, pane: usize, bytes: &[u8]) {
+        let mut inner = self.inner.lock().unwrap();
+        for keys in bytes.chunks(KEYS_PER_COMMAND) {
+            let mut command = format!("send-keys -t %{pane} -H");
+            for key in keys {
+                let _ = write!(command, " {key:02x}");
+            }
+            inner.command(&command, Command::Other);
+        }
+    }
+
+    /// Feed the output of `pane` to `feeder`, starting with what was
+    /// received until now.
+    fn show(&self, pane: usize, mut feeder: Feeder) {
+        let mut inner = self.inner.lock().unwrap();
+        let state = inner.panes.entry(pane).or_default();
+        let hidden = std::mem::take(&mut state.hidden);
+        feeder(&std::mem::take(&mut state.pending), state.size);
+        state.feeder = Some(feeder);
+        // Its output was dropped while no context showed it.
+        if hidden {
+            inner.capture(pane);
+        }
+    }
+
+    /// Give the terminal showing `pane` its size in tmux again, after the
+    /// context resized it.
+    fn fit(&self, pane: usize) {
+        if let Some(state) = self.inner.lock().unwrap().panes.get_mut(&pane) {
+            state.fit();
+        }
+    }
+
+    fn hide(&self, pane: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(state) = inner.panes.get_mut(&pane) {
+            state.feeder = None;
+            state.hidden = true;
+        }
+    }
+}
+
+/// A pane shown in a context, see `Backend::Tmux`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pane {
+    pub gateway: Gateway,
+    pub window: usize,
+    pub id: usize,
+}
+
+impl Pane {
+    /// Feed `terminal` with the output of the pane, returns the channel to
+    /// write to it, as `Machine::channel` does.
+    ///
+    /// `terminal` keeps the columns and rows tmux gives the pane, which
+    /// follow the terminal running tmux, resizing the context puts them
+    /// back.
+    pub fn attach<U>(
+        &self,
+        terminal: Arc<FairMutex<Crosswords<U>>>,
+        event_proxy: U,
+    ) -> io::Result<channel::Sender<Msg>>
+    where
+        U: EventListener + Send + 'static,
+    {
+        let (sender, receiver) = channel::channel::<Msg>();
+        let poll = Poll::new()?;
+        poll.register(&receiver, Token(0), Ready::readable(), PollOpt::level())?;
+
+        let mut parser = ParserProcessor::new();
+        self.gateway.show(
+            self.id,
+            Box::new(move |bytes, size| {
+                let mut terminal = terminal.lock();
+                let resized = match size {
+                    Some((columns, rows))
+                        if (columns, rows)
+                            != (
+                                terminal.grid.columns(),
+                                terminal.grid.screen_lines(),
+                            ) =>
+                    {
+                        terminal.resize::<(usize, usize)>(columns, rows);
+                        true
+                    }
+                    _ => false,
+                };
+                if bytes.is_empty() && !resized {
+                    return;
+                }
+                for byte in bytes {
+                    parser.advance(&mut *terminal, *byte);
+                }
+                drop(terminal);
+                event_proxy.send_event(RioEvent::Wakeup);
+            }),
+        );
+
+        let pane = self.clone();
+        std::thread::Builder::new()
+            .name(format!("tmux pane %{}", self.id))
+            .spawn(move || {
+                let mut events = Events::with_capacity(16);
+                loop {
+                    if let Err(err) = poll.poll(&mut events, None) {
+                        if err.kind() == io::ErrorKind::Interrupted {
+                            continue;
+                        }
+                        log::error!("unable to poll the tmux pane channel: {err}");
+                        break;
+                    }
+                    while let Ok(msg) = receiver.try_recv() {
+                        match msg {
+                            Msg::Input(bytes) => pane.gateway.send_keys(pane.id, &bytes),
+                            Msg::Resize(_) => pane.gateway.fit(pane.id),
+                            Msg::Command(_) => {}
+                            Msg::Shutdown => {
+                                pane.gateway.hide(pane.id);
+                                return;
+                            }
+                        }
+                    }
+                }
+            })?;
+
+        Ok(sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::TRANSCRIPT;
+    use super::super::{Parser, START};
+    use super::*;
+    use crate::crosswords::grid::Dimensions;
+    use crate::crosswords::pos::Line;
+    use crate::event::VoidListener;
+
+    fn text(terminal: &Crosswords<VoidListener>) -> Vec<String> {
+        let grid = &terminal.grid;
+        let top = -(grid.history_size() as i32);
+        (top..grid.screen_lines() as i32)
+            .map(|line| {
+                grid[Line(line)][..]
+                    .iter()
+                    .map(|square| square.c)
+                    .collect::<String>()
+                    .trim_end()
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transcript() {
+        let (writer, commands) = channel::channel();
+        let gateway = Gateway::new(writer);
+        gateway.start(20, 5);
+
+        let start = TRANSCRIPT
+            .windows(START.len())
+            .position(|window| window == START)
+            .unwrap();
+        let mut parser = Parser::default();
+        let mut changes = vec![];
+        let mut terminals: HashMap<usize, Arc<FairMutex<Crosswords<VoidListener>>>> =
+            HashMap::new();
+        let mut channels = vec![];
+        for byte in &TRANSCRIPT[start + START.len()..] {
+            let Some(notification) = parser.advance(*byte) else {
+                continue;
+            };
+            if let Notification::WindowClose { .. } = notification {
+                gateway.send_keys(0, b"ls\r");
+                // The context resized it, as on `Msg::Resize`.
+                terminals[&1].lock().resize::<(usize, usize)>(20, 5);
+                gateway.fit(1);
+            }
+            for change in gateway.handle(notification) {
+                // Every pane is shown, as the tabs would.
+                if let Change::Layout { window, layout } = &change {
+                    for id in layout.panes() {
+                        if terminals.contains_key(&id) {
+                            continue;
+                        }
+                        let terminal = Arc::new(FairMutex::new(Crosswords::new(
+                            20,
+                            5,
+                            VoidListener,
+                        )));
+                        let pane = Pane {
+                            gateway: gateway.clone(),
+                            window: *window,
+                            id,
+                        };
+                        channels
+                            .push(pane.attach(terminal.clone(), VoidListener).unwrap());
+                        terminals.insert(id, terminal);
+                    }
+                }
+                changes.push(change);
+            }
+        }
+
+        assert_eq!(
+            text(&terminals[&0].lock()),
+            vec![
+                "old 1",
+                "old 2",
+                "$ ls",
+                "Cargo.toml  src",
+                "$ cargo build",
+                "",
+                ""
+            ]
+        );
+        let pane = terminals[&2].lock();
+        assert_eq!(text(&pane)[0], "vim");
+        assert!(pane.grid[Line(0)][..][0]
+            .flags
+            .contains(crate::crosswords::square::Flags::BOLD));
+        drop(pane);
+        // Received before the layout showing it.
+        assert_eq!(text(&terminals[&4].lock())[0], "$");
+
+        // Sized as in tmux, even once the context resized it.
+        let size = |id: usize| {
+            let terminal = terminals[&id].lock();
+            (terminal.grid.columns(), terminal.grid.screen_lines())
+        };
+        assert_eq!(size(2), (9, 2));
+        assert_eq!(size(1), (10, 5));
+
+        assert_eq!(
+            &changes[..4],
+            &[
+                Change::Layout {
+                    window: 0,
+                    layout: Layout::parse("b25f,20x5,0,0,0").unwrap(),
+                },
+                Change::Renamed {
+                    window: 0,
+                    name: String::from("zsh"),
+                },
+                Change::Layout {
+                    window: 1,
+                    layout: Layout::parse("c3e2,20x5,0,0{10x5,0,0,1,9x5,11,0,2}")
+                        .unwrap(),
+                },
+                Change::Renamed {
+                    window: 1,
+                    name: String::from("vim"),
+                },
+            ]
+        );
+        assert!(changes.contains(&Change::Renamed {
+            window: 0,
+            name: String::from("cargo build"),
+        }));
+        assert_eq!(
+            &changes[changes.len() - 3..],
+            &[
+                Change::Layout {
+                    window: 1,
+                    layout: Layout::parse(
+                        "e4f5,20x5,0,0{10x5,0,0,1,9x5,11,0[9x2,11,0,2,9x2,11,3,4]}"
+                    )
+                    .unwrap(),
+                },
+                Change::Closed { window: 2 },
+                Change::Cleared,
+            ]
+        );
+
+        let mut written = vec![];
+        while let Ok(Msg::Command(command)) = commands.try_recv() {
+            written.push(String::from_utf8(command.to_vec()).unwrap());
+        }
+        let capture = |pane: usize| {
+            vec![
+                format!("capture-pane -p -e -S - -t %{pane}\n"),
+                format!("display-message -p -t %{pane} '#{{cursor_x}} #{{cursor_y}}'\n"),
+            ]
+        };
+        let mut expected = vec![
+            String::from("refresh-client -C 20x5\n"),
+            format!("{LIST_WINDOWS}\n"),
+        ];
+        expected.extend((0..3).flat_map(capture));
+        expected.push(format!("{LIST_WINDOWS}\n"));
+        expected.extend(capture(3));
+        expected.push(String::from("send-keys -t %0 -H 6c 73 0d\n"));
+        assert_eq!(written, expected);
+
+        // Control mode ended.
+        gateway.send_keys(0, b"ls");
+        assert!(commands.try_recv().is_err());
+        for channel in channels {
+            channel.send(Msg::Shutdown).unwrap();
+        }
+    }
+}
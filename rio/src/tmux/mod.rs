@@ -0,0 +1,512 @@
+// tmux control mode.
+//
+// `tmux -CC` writes `DCS 1000 p` and then, instead of drawing its windows,
+// speaks a line protocol: notifications such as `%output %1 ...` or
+// `%layout-change @1 ...`, and the replies to the commands written to it,
+// between `%begin` and `%end` (or `%error`). It ends with `%exit` followed
+// by a string terminator.
+//
+// The terminal running tmux becomes a gateway: its output is parsed here
+// instead of being drawn, every tmux window gets a tab and every pane a
+// context with its own `Crosswords` fed by `%output`, so scrollback and
+// selection are Rio's own. What is typed in a pane goes back through
+// `send-keys`.
+
+mod gateway;
+
+pub use gateway::{Change, Gateway, Pane};
+
+/// Start of control mode, `DCS 1000 p`.
+const START: &[u8] = b"\x1bP1000p";
+
+/// Finds the start of control mode in the output of a terminal.
+#[derive(Debug, Default)]
+pub struct Detector {
+    matched: usize,
+}
+
+impl Detector {
+    /// Whether `byte` completes `DCS 1000 p`.
+    #[inline]
+    pub fn advance(&mut self, byte: u8) -> bool {
+        if byte == START[self.matched] {
+            self.matched += 1;
+        } else {
+            self.matched = usize::from(byte == START[0]);
+        }
+
+        if self.matched == START.len() {
+            self.matched = 0;
+            return true;
+        }
+        false
+    }
+}
+
+/// How a window is shared between its panes, as in `%layout-change`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    Pane {
+        id: usize,
+        columns: usize,
+        rows: usize,
+    },
+    /// Children side by side.
+    Horizontal(Vec<Layout>),
+    /// Children one above the other.
+    Vertical(Vec<Layout>),
+}
+
+impl Layout {
+    /// Parse a layout such as `5e1c,80x24,0,0{40x24,0,0,1,39x24,41,0,2}`.
+    pub fn parse(layout: &str) -> Option<Layout> {
+        // Starts with a checksum.
+        let (_, mut input) = layout.split_once(',')?;
+        let layout = Layout::node(&mut input)?;
+        input.is_empty().then_some(layout)
+    }
+
+    /// `COLUMNSxROWS,X,Y` followed by `,ID`, `{CHILDREN}` or `[CHILDREN]`.
+    fn node(input: &mut &str) -> Option<Layout> {
+        let columns = number(input)?;
+        *input = input.strip_prefix('x')?;
+        let rows = number(input)?;
+        for _ in 0..2 {
+            *input = input.strip_prefix(',')?;
+            number(input)?;
+        }
+
+        let (close, horizontal) = match input.chars().next()? {
+            ',' => {
+                *input = &input[1..];
+                let id = number(input)?;
+                return Some(Layout::Pane { id, columns, rows });
+            }
+            '{' => ('}', true),
+            '[' => (']', false),
+            _ => return None,
+        };
+        *input = &input[1..];
+
+        let mut children = vec![Layout::node(input)?];
+        loop {
+            match input.chars().next()? {
+                ',' => {
+                    *input = &input[1..];
+                    children.push(Layout::node(input)?);
+                }
+                c if c == close => {
+                    *input = &input[1..];
+                    break;
+                }
+                _ => return None,
+            }
+        }
+
+        Some(if horizontal {
+            Layout::Horizontal(children)
+        } else {
+            Layout::Vertical(children)
+        })
+    }
+
+    /// Panes, from left to right and top to bottom.
+    pub fn panes(&self) -> Vec<usize> {
+        match self {
+            Layout::Pane { id, .. } => vec![*id],
+            Layout::Horizontal(children) | Layout::Vertical(children) => {
+                children.iter().flat_map(Layout::panes).collect()
+            }
+        }
+    }
+
+    /// Panes along with their columns and rows, in the order of `panes`.
+    pub fn sizes(&self) -> Vec<(usize, usize, usize)> {
+        match self {
+            Layout::Pane { id, columns, rows } => vec![(*id, *columns, *rows)],
+            Layout::Horizontal(children) | Layout::Vertical(children) => {
+                children.iter().flat_map(Layout::sizes).collect()
+            }
+        }
+    }
+
+    /// How the panes are split from the first one, in order: the pane to
+    /// split, the new pane and whether it goes on the right rather than
+    /// below.
+    pub fn splits(&self) -> Vec<(usize, usize, bool)> {
+        let (children, horizontal) = match self {
+            Layout::Pane { .. } => return vec![],
+            Layout::Horizontal(children) => (children, true),
+            Layout::Vertical(children) => (children, false),
+        };
+        // Siblings first, the children then split their own area.
+        let mut splits: Vec<(usize, usize, bool)> = children
+            .windows(2)
+            .map(|pair| (pair[0].first_pane(), pair[1].first_pane(), horizontal))
+            .collect();
+        splits.extend(children.iter().flat_map(Layout::splits));
+        splits
+    }
+
+    /// Pane at the top left.
+    pub fn first_pane(&self) -> usize {
+        match self {
+            Layout::Pane { id, .. } => *id,
+            Layout::Horizontal(children) | Layout::Vertical(children) => {
+                children[0].first_pane()
+            }
+        }
+    }
+}
+
+/// Leading digits of `input`, which are consumed.
+fn number(input: &mut &str) -> Option<usize> {
+    let end = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let number = input[..end].parse().ok()?;
+    *input = &input[end..];
+    Some(number)
+}
+
+/// Identifier of a pane (`%1`), a window (`@1`) or a session (`$1`).
+fn id(word: &str, sigil: char) -> Option<usize> {
+    word.strip_prefix(sigil)?.parse().ok()
+}
+
+/// Bytes of `%output`, where tmux escapes control characters and
+/// backslashes as `\ooo`.
+fn unescape(bytes: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let octal = bytes.get(index + 1..index + 4).filter(|digits| {
+            bytes[index] == b'\\'
+                && digits.iter().all(|digit| (b'0'..=b'7').contains(digit))
+        });
+        match octal {
+            Some(digits) => {
+                let value = digits
+                    .iter()
+                    .fold(0u16, |value, digit| value * 8 + u16::from(digit - b'0'));
+                unescaped.push(value as u8);
+                index += 4;
+            }
+            None => {
+                unescaped.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    unescaped
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    /// Lines written by a command, `client` tells whether it was written by
+    /// Rio rather than given on the tmux command line.
+    Reply {
+        lines: Vec<Vec<u8>>,
+        error: bool,
+        client: bool,
+    },
+    Output {
+        pane: usize,
+        bytes: Vec<u8>,
+    },
+    LayoutChange {
+        window: usize,
+        layout: Layout,
+    },
+    WindowAdd {
+        window: usize,
+    },
+    WindowClose {
+        window: usize,
+    },
+    WindowRenamed {
+        window: usize,
+        name: String,
+    },
+    SessionChanged {
+        session: usize,
+    },
+    Exit,
+    /// Control mode ended, what follows is drawn again.
+    End,
+}
+
+/// Reply being received, between `%begin` and `%end`.
+#[derive(Debug)]
+struct Reply {
+    /// Time, number and flags of the command, repeated by `%end`.
+    arguments: String,
+    client: bool,
+    lines: Vec<Vec<u8>>,
+}
+
+/// Splits control mode into notifications.
+#[derive(Debug, Default)]
+pub struct Parser {
+    line: Vec<u8>,
+    reply: Option<Reply>,
+}
+
+impl Parser {
+    /// Process a byte of control mode, returns the notification it
+    /// completes.
+    pub fn advance(&mut self, byte: u8) -> Option<Notification> {
+        if byte != b'\n' {
+            self.line.push(byte);
+            // The string terminator ending control mode is not followed
+            // by a new line.
+            if self.line == b"\x1b\\" {
+                self.line.clear();
+                return Some(Notification::End);
+            }
+            return None;
+        }
+
+        let mut line = std::mem::take(&mut self.line);
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        self.line(line)
+    }
+
+    fn line(&mut self, line: Vec<u8>) -> Option<Notification> {
+        if let Some(reply) = &mut self.reply {
+            let text = String::from_utf8_lossy(&line);
+            let (error, arguments) = match text.split_once(' ') {
+                Some(("%end", arguments)) => (false, arguments),
+                Some(("%error", arguments)) => (true, arguments),
+                _ => {
+                    reply.lines.push(line);
+                    return None;
+                }
+            };
+            // Lines of the reply may start with `%end` too.
+            if arguments != reply.arguments {
+                reply.lines.push(line);
+                return None;
+            }
+
+            let reply = self.reply.take()?;
+            return Some(Notification::Reply {
+                lines: reply.lines,
+                error,
+                client: reply.client,
+            });
+        }
+
+        if let Some(output) = line.strip_prefix(b"%output ") {
+            let split = output.iter().position(|byte| *byte == b' ')?;
+            let pane = id(std::str::from_utf8(&output[..split]).ok()?, '%')?;
+            return Some(Notification::Output {
+                pane,
+                bytes: unescape(&output[split + 1..]),
+            });
+        }
+
+        let text = String::from_utf8_lossy(&line);
+        let mut words = text.splitn(3, ' ');
+        let notification = match (words.next()?, words.next(), words.next()) {
+            ("%begin", Some(time), Some(rest)) => {
+                let mut words = rest.split(' ');
+                words.next()?;
+                self.reply = Some(Reply {
+                    arguments: format!("{time} {rest}"),
+                    client: words.next() == Some("1"),
+                    lines: vec![],
+                });
+                return None;
+            }
+            ("%layout-change", Some(window), Some(rest)) => Notification::LayoutChange {
+                window: id(window, '@')?,
+                layout: Layout::parse(rest.split(' ').next()?)?,
+            },
+            ("%window-add", Some(window), _) => Notification::WindowAdd {
+                window: id(window, '@')?,
+            },
+            ("%window-close" | "%unlinked-window-close", Some(window), _) => {
+                Notification::WindowClose {
+                    window: id(window, '@')?,
+                }
+            }
+            ("%window-renamed", Some(window), name) => Notification::WindowRenamed {
+                window: id(window, '@')?,
+                name: name.unwrap_or_default().to_owned(),
+            },
+            ("%session-changed", Some(session), _) => Notification::SessionChanged {
+                session: id(session, '$')?,
+            },
+            ("%exit", ..) => Notification::Exit,
+            _ => {
+                log::debug!("ignored tmux notification: {text}");
+                return None;
+            }
+        };
+        Some(notification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Synthetic control mode session, written after the output of
+    /// `tmux -CC` with the replies to what `Gateway` asks once control mode
+    /// starts. Edge cases of the protocol have tests of their own.
+    pub(super) const TRANSCRIPT: &[u8] = include_bytes!("transcript");
+
+    fn parse(bytes: &[u8]) -> Vec<Notification> {
+        let mut parser = Parser::default();
+        bytes
+            .iter()
+            .filter_map(|byte| parser.advance(*byte))
+            .collect()
+    }
+
+    #[test]
+    fn test_detector() {
+        let mut detector = Detector::default();
+        let found: Vec<usize> = b"ls\x1bP1\x1bP1000p%exit"
+            .iter()
+            .enumerate()
+            .filter(|(_, byte)| detector.advance(**byte))
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(found, vec![11]);
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(
+            Layout::parse("b25f,80x24,0,0,0"),
+            Some(Layout::Pane {
+                id: 0,
+                columns: 80,
+                rows: 24
+            })
+        );
+
+        let layout = Layout::parse(
+            "d3a1,80x24,0,0{40x24,0,0,1,39x24,41,0[39x12,41,0,2,39x11,41,13,3]}",
+        )
+        .unwrap();
+        assert_eq!(layout.panes(), vec![1, 2, 3]);
+        assert_eq!(layout.sizes(), vec![(1, 40, 24), (2, 39, 12), (3, 39, 11)]);
+        assert_eq!(layout.first_pane(), 1);
+        assert_eq!(layout.splits(), vec![(1, 2, true), (2, 3, false)]);
+        match layout {
+            Layout::Horizontal(children) => {
+                assert!(matches!(children[1], Layout::Vertical(_)));
+            }
+            layout => panic!("unexpected layout: {layout:?}"),
+        }
+
+        assert_eq!(Layout::parse("b25f,80x24,0,0"), None);
+        assert_eq!(Layout::parse("b25f,80x24,0,0{40x24,0,0,1"), None);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(b"a\\033[1mb\\015\\012\\134"), b"a\x1b[1mb\r\n\\");
+        assert_eq!(unescape(b"\\09\\"), b"\\09\\");
+    }
+
+    #[test]
+    fn test_transcript() {
+        let start = TRANSCRIPT
+            .windows(START.len())
+            .position(|window| window == START)
+            .unwrap();
+        let notifications = parse(&TRANSCRIPT[start + START.len()..]);
+
+        // Given on the command line, not written by Rio.
+        assert_eq!(
+            notifications[0],
+            Notification::Reply {
+                lines: vec![],
+                error: false,
+                client: false,
+            }
+        );
+        assert_eq!(
+            notifications[1],
+            Notification::SessionChanged { session: 0 }
+        );
+        assert!(notifications.contains(&Notification::Output {
+            pane: 2,
+            bytes: b"\x1b[1mvim\x1b[m\r\n".to_vec(),
+        }));
+        assert!(notifications.contains(&Notification::WindowRenamed {
+            window: 0,
+            name: String::from("cargo build"),
+        }));
+        assert!(notifications.contains(&Notification::WindowClose { window: 2 }));
+
+        let layouts: Vec<Vec<usize>> = notifications
+            .iter()
+            .filter_map(|notification| match notification {
+                Notification::LayoutChange { layout, .. } => Some(layout.panes()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(layouts, vec![vec![1, 2, 4]]);
+
+        let replies = notifications
+            .iter()
+            .filter(|notification| {
+                matches!(notification, Notification::Reply { client: true, .. })
+            })
+            .count();
+        assert_eq!(replies, 12);
+
+        assert_eq!(
+            &notifications[notifications.len() - 2..],
+            &[Notification::Exit, Notification::End]
+        );
+    }
+
+    #[test]
+    fn test_reply_lines_like_end() {
+        let notifications =
+            parse(b"%begin 1 5 1\n%end 1 2 1\n%error 1 5 0\nls\r\n%end 1 5 1\n");
+        assert_eq!(
+            notifications,
+            vec![Notification::Reply {
+                lines: vec![
+                    b"%end 1 2 1".to_vec(),
+                    b"%error 1 5 0".to_vec(),
+                    b"ls".to_vec()
+                ],
+                error: false,
+                client: true,
+            }]
+        );
+
+        let notifications = parse(b"%begin 1 6 1\nno such pane\n%error 1 6 1\n");
+        assert_eq!(
+            notifications,
+            vec![Notification::Reply {
+                lines: vec![b"no such pane".to_vec()],
+                error: true,
+                client: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_end_without_new_line() {
+        // The string terminator is directly followed by what the shell
+        // writes next.
+        let mut parser = Parser::default();
+        let notifications: Vec<Notification> = b"%exit\n\x1b\\"
+            .iter()
+            .filter_map(|byte| parser.advance(*byte))
+            .collect();
+        assert_eq!(notifications, vec![Notification::Exit, Notification::End]);
+        assert!(parser.line.is_empty());
+    }
+}
@@ -1 +1,3 @@
+pub mod open;
+pub mod spawn;
 pub mod watch;
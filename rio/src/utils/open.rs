@@ -0,0 +1,104 @@
+#[cfg(not(windows))]
+use crate::utils::spawn::spawn_daemon;
+
+/// Schemes of the links Rio opens, others could start any program that
+/// registered one.
+const ALLOWED_SCHEMES: [&str; 4] = ["http", "https", "mailto", "file"];
+
+/// Open `target` (usually a URL) with the default application of the
+/// platform, without waiting for it.
+///
+/// Links can be set by the program running in the terminal, only those
+/// with a scheme of `ALLOWED_SCHEMES` are opened.
+pub fn open(target: &str) {
+    if !is_allowed(target) {
+        log::warn!("refused to open {target}, its scheme is not allowed");
+        return;
+    }
+
+    if let Err(err) = run(target) {
+        log::warn!("unable to open {target}: {err}");
+    }
+}
+
+/// Whether the scheme of `target` is one of `ALLOWED_SCHEMES`.
+fn is_allowed(target: &str) -> bool {
+    target.split_once(':').map_or(false, |(scheme, _)| {
+        ALLOWED_SCHEMES
+            .iter()
+            .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+    })
+}
+
+/// Program opening `target` and its arguments, `target` is given as a
+/// single argument and never goes through a shell.
+#[cfg(not(windows))]
+fn command(target: &str) -> (&'static str, Vec<String>) {
+    #[cfg(target_os = "macos")]
+    let program = "open";
+    #[cfg(not(target_os = "macos"))]
+    let program = "xdg-open";
+
+    (program, vec![target.to_string()])
+}
+
+#[cfg(not(windows))]
+fn run(target: &str) -> std::io::Result<()> {
+    let (program, args) = command(target);
+    let envs: [(&str, &str); 0] = [];
+    spawn_daemon(program, &args, None, envs)
+}
+
+/// `cmd /c start` would run what follows `&`, `|` or `^` in `target`, the
+/// shell opens it instead.
+#[cfg(windows)]
+fn run(target: &str) -> std::io::Result<()> {
+    use crate::panic::win32_string;
+    use windows_sys::Win32::UI::Shell::ShellExecuteW;
+    use windows_sys::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+    let operation = win32_string("open");
+    let file = win32_string(target);
+    let result = unsafe {
+        ShellExecuteW(
+            0isize,
+            operation.as_ptr(),
+            file.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values up to 32 are errors.
+    if result <= 32 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("ShellExecuteW failed with {result}"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_allowed() {
+        assert!(is_allowed("https://example.com/?a=1&b=2"));
+        assert!(is_allowed("HTTP://example.com"));
+        assert!(is_allowed("mailto:someone@example.com"));
+        assert!(is_allowed("file:///tmp/rio.log"));
+        assert!(!is_allowed("ms-settings:network"));
+        assert!(!is_allowed("javascript:alert(1)"));
+        assert!(!is_allowed("example.com&calc"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_command() {
+        let target = "https://example.com/?a=1&b=2|c^d";
+        let (_, args) = command(target);
+        assert_eq!(args, vec![target.to_string()]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::ffi::OsStr;
+use std::io;
+use std::process::{Command, Stdio};
+
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+#[cfg(windows)]
+use windows_sys::Win32::System::Threading::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+/// Start `program` detached from Rio, so it keeps running after Rio exits.
+/// It starts at `working_directory` when given, with `envs` added to the
+/// environment inherited from Rio.
+pub fn spawn_daemon<I, K, V>(
+    program: &str,
+    args: &[String],
+    working_directory: Option<&str>,
+    envs: I,
+) -> io::Result<()>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .envs(envs)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    if let Some(directory) = working_directory {
+        command.current_dir(directory);
+    }
+
+    #[cfg(unix)]
+    unsafe {
+        // Leave Rio's session so closing Rio does not hang up the program.
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    #[cfg(windows)]
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+
+    let mut child = command.spawn()?;
+
+    // Reap the child once it exits, to not leave a zombie behind.
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+/// Variables set in the environment of every program started in a
+/// terminal, on top of the ones inherited from Rio.
+pub fn terminal_environment(config: &config::Config) -> Vec<(String, String)> {
+    let mut environment = vec![];
+
+    #[cfg(unix)]
+    {
+        let terminfo = if teletypewriter::terminfo_exists("rio") {
+            "rio"
+        } else {
+            "xterm-256color"
+        };
+        environment.push((String::from("TERM"), String::from(terminfo)));
+    }
+    environment.push((String::from("COLORTERM"), String::from("truecolor")));
+
+    // Set env vars from config.
+    for env_config in config.env_vars.iter() {
+        let env_vec: Vec<&str> = env_config.split('=').collect();
+        if env_vec.len() == 2 {
+            environment.push((env_vec[0].to_string(), env_vec[1].to_string()));
+        }
+    }
+
+    environment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_environment() {
+        let config = config::Config {
+            env_vars: vec![String::from("EDITOR=vim"), String::from("INVALID")],
+            ..config::Config::default()
+        };
+
+        let environment = terminal_environment(&config);
+        assert!(
+            environment.contains(&(String::from("COLORTERM"), String::from("truecolor")))
+        );
+        assert!(environment.contains(&(String::from("EDITOR"), String::from("vim"))));
+        assert!(!environment.iter().any(|(key, _)| key == "INVALID"));
+    }
+}
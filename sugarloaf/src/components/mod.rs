@@ -1,2 +1,3 @@
 pub mod rect;
+pub mod tabs;
 pub mod text;
@@ -0,0 +1,330 @@
+// Tab bar, drawn as one line of sugars above or below the grid.
+//
+// Every tab gets the same width, it shows the tab index, its title and a
+// close button followed by a gap:
+//
+//  1 vim        ×   2 ~/rio      ×
+//
+// When the tabs do not fit, the first and last columns show an arrow for
+// the tabs hidden on that side and the bar scrolls so the active tab stays
+// visible:
+//
+// ‹ 3 vim        ×   4 ~/rio      × ›
+//
+// The bar keeps where each tab was drawn so a column can be mapped back to
+// the tab (or close button) under it.
+
+use crate::core::{Sugar, SugarStack};
+
+const MAX_TAB_WIDTH: usize = 24;
+const MIN_TAB_WIDTH: usize = 5;
+const CLOSE_BUTTON: char = '×';
+const ELLIPSIS: char = '…';
+const SCROLL_LEFT: char = '‹';
+const SCROLL_RIGHT: char = '›';
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TabBarPosition {
+    #[default]
+    Top,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TabBarColors {
+    /// Color behind and between the tabs.
+    pub bar: [f32; 4],
+    pub background: [f32; 4],
+    pub foreground: [f32; 4],
+    pub active_background: [f32; 4],
+    pub active_foreground: [f32; 4],
+}
+
+/// Columns taken by the tab at `index`, from `start` up to (not including)
+/// `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TabSpan {
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl TabSpan {
+    /// Column of the close button.
+    #[inline]
+    pub fn close(&self) -> usize {
+        self.end - 2
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabBarHit {
+    Tab(usize),
+    Close(usize),
+    /// Arrow standing for the hidden tab at the given index.
+    Scroll(usize),
+}
+
+#[derive(Debug, Default)]
+pub struct TabBar {
+    spans: Vec<TabSpan>,
+    /// First visible tab, when not all of them fit.
+    first: usize,
+    /// Hidden tabs on the left and on the right.
+    hidden: (bool, bool),
+    columns: usize,
+}
+
+impl TabBar {
+    /// Where the visible tabs out of `tabs` go in a line of `columns`
+    /// columns. When they do not fit the bar scrolls, as little as possible,
+    /// so the tab at `active` is shown.
+    pub fn layout(&mut self, tabs: usize, active: usize, columns: usize) -> &[TabSpan] {
+        self.columns = columns;
+        self.hidden = (false, false);
+        self.spans.clear();
+        if tabs == 0 || columns < MIN_TAB_WIDTH {
+            self.first = 0;
+            return &self.spans;
+        }
+
+        let mut width = (columns / tabs).clamp(MIN_TAB_WIDTH, MAX_TAB_WIDTH);
+        let mut offset = 0;
+        let mut visible = columns / width;
+        if visible < tabs {
+            // Keep a column on each side for the arrows.
+            let available = columns.saturating_sub(2);
+            if available < MIN_TAB_WIDTH {
+                self.first = 0;
+                return &self.spans;
+            }
+            width = width.min(available);
+            visible = available / width;
+            offset = 1;
+        }
+
+        let active = active.min(tabs - 1);
+        let last_first = tabs.saturating_sub(visible);
+        if active < self.first {
+            self.first = active;
+        } else if active >= self.first + visible {
+            self.first = active + 1 - visible;
+        }
+        self.first = self.first.min(last_first);
+        if visible < tabs {
+            self.hidden = (self.first > 0, self.first < last_first);
+        }
+
+        self.spans = (self.first..self.first + visible.min(tabs))
+            .enumerate()
+            .map(|(position, index)| TabSpan {
+                index,
+                start: offset + position * width,
+                end: offset + (position + 1) * width,
+            })
+            .collect();
+        &self.spans
+    }
+
+    /// Line of `columns` sugars drawing `titles`, with the tab at `active`
+    /// highlighted.
+    pub fn build(
+        &mut self,
+        titles: &[&str],
+        active: usize,
+        columns: usize,
+        colors: &TabBarColors,
+    ) -> SugarStack {
+        self.layout(titles.len(), active, columns);
+
+        let sugar = |content: char, foreground_color, background_color| Sugar {
+            content,
+            foreground_color,
+            background_color,
+            style: None,
+            decoration: None,
+        };
+
+        let mut stack: SugarStack = Vec::with_capacity(columns);
+        if self.spans.first().map_or(false, |span| span.start > 0) {
+            let arrow = if self.hidden.0 { SCROLL_LEFT } else { ' ' };
+            stack.push(sugar(arrow, colors.foreground, colors.bar));
+        }
+        for span in self.spans.iter() {
+            let (foreground, background) = if span.index == active {
+                (colors.active_foreground, colors.active_background)
+            } else {
+                (colors.foreground, colors.background)
+            };
+
+            let label = format!(" {} {}", span.index + 1, titles[span.index]);
+            for c in fit(&label, span.close() - span.start).chars() {
+                stack.push(sugar(c, foreground, background));
+            }
+            stack.push(sugar(CLOSE_BUTTON, foreground, background));
+            stack.push(sugar(' ', colors.foreground, colors.bar));
+        }
+
+        while stack.len() < columns {
+            stack.push(sugar(' ', colors.foreground, colors.bar));
+        }
+        if self.hidden.1 {
+            stack[columns - 1] = sugar(SCROLL_RIGHT, colors.foreground, colors.bar);
+        }
+        stack
+    }
+
+    /// Tab, tab close button or scroll arrow drawn at `column`.
+    pub fn hit(&self, column: usize) -> Option<TabBarHit> {
+        if column == 0 && self.hidden.0 {
+            return Some(TabBarHit::Scroll(self.first - 1));
+        }
+        if column + 1 == self.columns && self.hidden.1 {
+            return Some(TabBarHit::Scroll(self.first + self.spans.len()));
+        }
+
+        let span = self
+            .spans
+            .iter()
+            .find(|span| column >= span.start && column < span.end - 1)?;
+        if column == span.close() {
+            Some(TabBarHit::Close(span.index))
+        } else {
+            Some(TabBarHit::Tab(span.index))
+        }
+    }
+}
+
+/// `text` cut or padded to exactly `width` characters.
+fn fit(text: &str, width: usize) -> String {
+    let count = text.chars().count();
+    if count <= width {
+        return format!("{text:width$}");
+    }
+
+    let mut fitted: String = text.chars().take(width.saturating_sub(1)).collect();
+    fitted.push(ELLIPSIS);
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLORS: TabBarColors = TabBarColors {
+        bar: [0.5, 0.5, 0.5, 1.0],
+        background: [0.0, 0.0, 0.0, 1.0],
+        foreground: [1.0, 1.0, 1.0, 1.0],
+        active_background: [1.0, 1.0, 0.0, 1.0],
+        active_foreground: [0.0, 0.0, 0.0, 1.0],
+    };
+
+    fn content(stack: &SugarStack) -> String {
+        stack.iter().map(|sugar| sugar.content).collect()
+    }
+
+    #[test]
+    fn test_layout() {
+        let mut bar = TabBar::default();
+        assert!(bar.layout(0, 0, 80).is_empty());
+        assert_eq!(
+            bar.layout(2, 0, 80),
+            vec![
+                TabSpan {
+                    index: 0,
+                    start: 0,
+                    end: 24
+                },
+                TabSpan {
+                    index: 1,
+                    start: 24,
+                    end: 48
+                }
+            ]
+        );
+        assert_eq!(
+            bar.layout(4, 0, 40),
+            vec![
+                TabSpan {
+                    index: 0,
+                    start: 0,
+                    end: 10
+                },
+                TabSpan {
+                    index: 1,
+                    start: 10,
+                    end: 20
+                },
+                TabSpan {
+                    index: 2,
+                    start: 20,
+                    end: 30
+                },
+                TabSpan {
+                    index: 3,
+                    start: 30,
+                    end: 40
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layout_scrolls_to_active() {
+        let mut bar = TabBar::default();
+        // Two tabs of 5 columns fit between the arrows
+        let indexes = |bar: &mut TabBar, active| -> Vec<usize> {
+            bar.layout(5, active, 12)
+                .iter()
+                .map(|span| span.index)
+                .collect()
+        };
+        assert_eq!(indexes(&mut bar, 0), vec![0, 1]);
+        assert_eq!(bar.hidden, (false, true));
+        assert_eq!(indexes(&mut bar, 3), vec![2, 3]);
+        assert_eq!(bar.hidden, (true, true));
+        // Scrolls no further than needed
+        assert_eq!(indexes(&mut bar, 2), vec![2, 3]);
+        assert_eq!(indexes(&mut bar, 4), vec![3, 4]);
+        assert_eq!(bar.hidden, (true, false));
+        assert_eq!(bar.spans[0].start, 1);
+    }
+    #[test]
+    fn test_build() {
+        let mut bar = TabBar::default();
+        let stack = bar.build(&["vim", "a very long title"], 1, 20, &COLORS);
+        assert_eq!(stack.len(), 20);
+        assert_eq!(content(&stack), " 1 vim  ×  2 a ve…× ");
+        assert_eq!(stack[0].background_color, COLORS.background);
+        assert_eq!(stack[10].background_color, COLORS.active_background);
+        assert_eq!(stack[9].background_color, COLORS.bar);
+        assert_eq!(stack[19].background_color, COLORS.bar);
+    }
+
+    #[test]
+    fn test_hit() {
+        let mut bar = TabBar::default();
+        bar.build(&["vim", "htop"], 0, 20, &COLORS);
+        assert_eq!(bar.hit(0), Some(TabBarHit::Tab(0)));
+        assert_eq!(bar.hit(8), Some(TabBarHit::Close(0)));
+        assert_eq!(bar.hit(9), None);
+        assert_eq!(bar.hit(10), Some(TabBarHit::Tab(1)));
+        assert_eq!(bar.hit(18), Some(TabBarHit::Close(1)));
+        assert_eq!(bar.hit(25), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut bar = TabBar::default();
+        let titles = ["a", "b", "c", "d", "e"];
+        let stack = bar.build(&titles, 2, 12, &COLORS);
+        assert_eq!(stack.len(), 12);
+        assert_eq!(content(&stack), "‹ 2…×  3…× ›");
+        assert_eq!(stack[6].background_color, COLORS.active_background);
+        assert_eq!(bar.hit(0), Some(TabBarHit::Scroll(0)));
+        assert_eq!(bar.hit(1), Some(TabBarHit::Tab(1)));
+        assert_eq!(bar.hit(4), Some(TabBarHit::Close(1)));
+        assert_eq!(bar.hit(6), Some(TabBarHit::Tab(2)));
+        assert_eq!(bar.hit(11), Some(TabBarHit::Scroll(3)));
+    }
+}
@@ -283,7 +283,7 @@ impl<F: Font + Sync, H: BuildHasher> GlyphBrush<(), F, H> {
     /// Panics if the provided `target` has a texture format that does not match
     /// the `render_format` provided on creation of the `GlyphBrush`.
     #[inline]
-    pub fn _draw_queued_with_transform_and_scissoring(
+    pub fn draw_queued_with_transform_and_scissoring(
         &mut self,
         device: &wgpu::Device,
         staging_belt: &mut wgpu::util::StagingBelt,
@@ -22,6 +22,16 @@ pub struct SugarDecoration {
 }
 
 pub type SugarStack = Vec<Sugar>;
+
+/// Part of the screen, in cells, the next stacks are drawn into. Text going
+/// past it is clipped.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SugarViewport {
+    pub column: usize,
+    pub line: usize,
+    pub columns: usize,
+    pub lines: usize,
+}
 pub type SugarPile = Vec<SugarStack>;
 
 #[derive(Copy, Default, Debug, Clone)]
@@ -21,6 +21,8 @@ pub struct SugarloafLayout {
     pub style: SugarloafStyle,
     pub background_color: wgpu::Color,
     pub min_cols_lines: (usize, usize),
+    /// Lines taken out of the grid, like the tab bar.
+    pub reserved_lines: usize,
 }
 
 #[inline]
@@ -98,6 +100,7 @@ impl SugarloafLayout {
             },
             background_color: wgpu::Color::BLACK,
             min_cols_lines,
+            reserved_lines: 0,
         };
 
         update_styles(&mut layout);
@@ -153,10 +156,39 @@ impl SugarloafLayout {
             self.min_cols_lines,
         );
         self.columns = columns;
-        self.lines = lines;
+        self.lines = std::cmp::max(
+            lines.saturating_sub(self.reserved_lines),
+            self.min_cols_lines.1,
+        );
         self
     }
 
+    /// Take `reserved_lines` lines out of the grid, returns true when it
+    /// changed the amount of lines.
+    pub fn set_reserved_lines(&mut self, reserved_lines: usize) -> bool {
+        if self.reserved_lines == reserved_lines {
+            return false;
+        }
+
+        self.reserved_lines = reserved_lines;
+        let (_, lines) = compute(
+            self.width,
+            self.height,
+            self.scale_factor,
+            self.font_size,
+            self.font_bound,
+            self.padding,
+            self.min_cols_lines,
+        );
+        let lines = std::cmp::max(
+            lines.saturating_sub(self.reserved_lines),
+            self.min_cols_lines.1,
+        );
+        let changed = self.lines != lines;
+        self.lines = lines;
+        changed
+    }
+
     pub fn update_columns_lines_per_font_bound(&mut self, font_bound: f32) {
         self.font_bound = font_bound / self.scale_factor;
 
@@ -1,7 +1,7 @@
 use crate::components::rect::{Rect, RectBrush};
 use crate::components::text;
 use crate::context::Context;
-use crate::core::SugarStack;
+use crate::core::{SugarStack, SugarViewport};
 use crate::font::Font;
 use crate::layout::SugarloafLayout;
 use glyph_brush::ab_glyph::{self, Font as GFont, FontArc};
@@ -81,6 +81,9 @@ pub struct Sugarloaf {
     acc_line_y: f32,
     font_bounds: FontBounds,
     font_name: String,
+    viewport: Option<SugarViewport>,
+    /// Text of each viewport, drawn clipped to it after the rest.
+    viewport_sections: Vec<(SugarViewport, Vec<OwnedSection>)>,
 }
 
 const FONT_ID_REGULAR: usize = 0;
@@ -123,6 +126,8 @@ impl Sugarloaf {
             acc_line_y: 0.0,
             font_bounds: FontBounds::default(),
             layout,
+            viewport: None,
+            viewport_sections: vec![],
         })
     }
 
@@ -196,6 +201,52 @@ impl Sugarloaf {
         self
     }
 
+    /// Draw the next stacks from the first line of `viewport`, `None` goes
+    /// back to the whole screen. The viewport is reset on every render.
+    pub fn set_viewport(&mut self, viewport: Option<SugarViewport>) {
+        self.reset_state();
+        self.viewport = viewport;
+        if let Some(viewport) = viewport {
+            self.viewport_sections.push((viewport, vec![]));
+        }
+    }
+
+    /// Position of the first line, the top left of the viewport when
+    /// there is one.
+    #[inline]
+    fn origin(&self, viewport: Option<&SugarViewport>) -> (f32, f32) {
+        let (x, y) = self.layout.style.screen_position;
+        match viewport {
+            Some(viewport) => (
+                x + viewport.column as f32 * self.font_bounds.default.0,
+                y + viewport.line as f32 * self.font_bounds.default.1,
+            ),
+            None => (x, y),
+        }
+    }
+
+    /// Part of the surface covered by `viewport`, if any.
+    fn viewport_region(&self, viewport: &SugarViewport) -> Option<text::Region> {
+        let (x, y) = self.origin(Some(viewport));
+        let width = self.ctx.size.width;
+        let height = self.ctx.size.height;
+        let x = (x.max(0.) as u32).min(width);
+        let y = ((y - self.font_bounds.default.1).max(0.) as u32).min(height);
+        let region = text::Region {
+            x,
+            y,
+            width: ((viewport.columns as f32 * self.font_bounds.default.0).ceil() as u32)
+                .min(width - x),
+            height: ((viewport.lines as f32 * self.font_bounds.default.1).ceil() as u32)
+                .min(height - y),
+        };
+
+        if region.width == 0 || region.height == 0 {
+            return None;
+        }
+        Some(region)
+    }
+
     #[inline]
     pub fn stack(&mut self, stack: SugarStack) {
         let mut text: Vec<OwnedText> = vec![];
@@ -209,6 +260,7 @@ impl Sugarloaf {
         let emojis: &FontArc = &fonts[2];
         let unicode: &FontArc = &fonts[3];
         let glyph_zero = ab_glyph::GlyphId(0);
+        let (origin_x, origin_y) = self.origin(self.viewport.as_ref());
 
         for sugar in stack.iter() {
             let mut add_pos_x = self.font_bounds.default.0;
@@ -241,9 +293,8 @@ impl Sugarloaf {
             }
 
             if self.acc_line_y == 0.0 {
-                self.acc_line_y = (self.layout.style.screen_position.1
-                    - self.font_bounds.default.1)
-                    / self.ctx.scale;
+                self.acc_line_y =
+                    (origin_y - self.font_bounds.default.1) / self.ctx.scale;
             }
 
             text.push(
@@ -254,10 +305,7 @@ impl Sugarloaf {
             );
 
             self.rects.push(Rect {
-                position: [
-                    (self.layout.style.screen_position.0 / self.ctx.scale) + x,
-                    self.acc_line_y,
-                ],
+                position: [(origin_x / self.ctx.scale) + x, self.acc_line_y],
                 color: sugar.background_color,
                 size: [
                     add_pos_x * mod_size,
@@ -268,7 +316,7 @@ impl Sugarloaf {
             if let Some(decoration) = &sugar.decoration {
                 self.rects.push(Rect {
                     position: [
-                        (self.layout.style.screen_position.0 / self.ctx.scale)
+                        (origin_x / self.ctx.scale)
                             + x
                             + ((add_pos_x * decoration.position.0) / self.ctx.scale),
                         self.acc_line_y
@@ -287,11 +335,8 @@ impl Sugarloaf {
             x += add_pos_x / self.ctx.scale;
         }
 
-        let section = &OwnedSection {
-            screen_position: (
-                self.layout.style.screen_position.0,
-                self.layout.style.screen_position.1 + self.acc_line,
-            ),
+        let section = OwnedSection {
+            screen_position: (origin_x, origin_y + self.acc_line),
             bounds: self.layout.style.bounds,
             text,
             layout: glyph_brush::Layout::default_single_line()
@@ -299,13 +344,36 @@ impl Sugarloaf {
                 .h_align(glyph_brush::HorizontalAlign::Left),
         };
 
-        self.text_brush.queue(section);
+        match self.viewport_sections.last_mut() {
+            Some((_, sections)) if self.viewport.is_some() => sections.push(section),
+            _ => self.text_brush.queue(&section),
+        }
 
-        self.acc_line_y =
-            (self.layout.style.screen_position.1 + self.acc_line) / self.ctx.scale;
+        self.acc_line_y = (origin_y + self.acc_line) / self.ctx.scale;
         self.acc_line += self.font_bounds.default.1;
     }
 
+    /// Width and height of a cell in physical pixels, the advance and line
+    /// height `stack` lays text out with.
+    #[inline]
+    pub fn cell_size(&self) -> (f32, f32) {
+        match self.font_bounds.default {
+            (width, height) if width > 0. && height > 0. => (width, height),
+            // Bounds are only known once `calculate_bounds` ran.
+            _ => {
+                let text_scale = self.layout.style.text_scale;
+                (text_scale / 2., text_scale)
+            }
+        }
+    }
+
+    /// Top left corner of the first line in physical pixels.
+    #[inline]
+    pub fn grid_origin(&self) -> (f32, f32) {
+        let (x, y) = self.layout.style.screen_position;
+        (x, y - self.cell_size().1)
+    }
+
     pub fn get_context(&self) -> &Context {
         &self.ctx
     }
@@ -526,6 +594,29 @@ impl Sugarloaf {
                     (self.ctx.size.width, self.ctx.size.height),
                 );
 
+                self.viewport = None;
+                for (viewport, sections) in std::mem::take(&mut self.viewport_sections) {
+                    let region = match self.viewport_region(&viewport) {
+                        Some(region) => region,
+                        None => continue,
+                    };
+
+                    for section in sections.iter() {
+                        self.text_brush.queue(section);
+                    }
+                    let _ = self.text_brush.draw_queued_with_transform_and_scissoring(
+                        &self.ctx.device,
+                        &mut self.ctx.staging_belt,
+                        &mut encoder,
+                        view,
+                        orthographic_projection(
+                            self.ctx.size.width,
+                            self.ctx.size.height,
+                        ),
+                        region,
+                    );
+                }
+
                 self.ctx.staging_belt.finish();
                 self.ctx.queue.submit(Some(encoder.finish()));
                 frame.present();
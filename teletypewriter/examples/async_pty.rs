@@ -0,0 +1,34 @@
+// cargo run -p teletypewriter --features tokio --example async_pty
+
+#[cfg(unix)]
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    use teletypewriter::{PtyBuilder, WinsizeBuilder};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // `read` stops at the first line, written by the example.
+    let mut pty = PtyBuilder::new("sh")
+        .args(["-c", "read line; echo \"$line from $(stty size)\""])
+        .spawn_async()?;
+    let exited = pty.exited();
+
+    pty.resize(WinsizeBuilder {
+        rows: 30,
+        cols: 100,
+        width: 0,
+        height: 0,
+    })
+    .await?;
+    pty.write_all(b"hello\n").await?;
+
+    // Prints the echoed input, then "hello from 30 100".
+    let mut output = String::new();
+    pty.read_to_string(&mut output).await?;
+    print!("{output}");
+
+    println!("sh {}", exited.await?);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn main() {}
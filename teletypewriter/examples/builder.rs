@@ -0,0 +1,38 @@
+#[cfg(unix)]
+fn main() -> std::io::Result<()> {
+    use std::io::{ErrorKind, Read};
+    use teletypewriter::{PtyBuilder, TermiosPreset};
+
+    // Runs `sh -c` as a login shell in /tmp, with a variable of its own.
+    let mut process = PtyBuilder::new("sh")
+        .args(["-c", "echo $0 $PWD $GREETING"])
+        .login(true)
+        .current_dir("/tmp")
+        .env("GREETING", "hello")
+        .env_remove("DESKTOP_STARTUP_ID")
+        .size(80, 25)
+        .pixel_size(640, 400)
+        .termios(TermiosPreset::Utf8)
+        .spawn()?;
+
+    // Prints "-sh /tmp hello", the variable was not set in this process.
+    assert!(std::env::var("GREETING").is_err());
+    let mut buf = [0u8; 1024];
+    loop {
+        match process.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => print!("{}", String::from_utf8_lossy(&buf[..len])),
+            // The pty is non blocking.
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            // The child exited.
+            Err(_) => break,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn main() {}
@@ -0,0 +1,219 @@
+// Pseudoterminal driven by a tokio runtime instead of a corcovado `Poll`.
+//
+// The main side is read and written through `AsyncFd`, and a task reaps the
+// child on SIGCHLD, so its exit can be awaited from anywhere.
+
+use crate::{ExitStatus, Pty, WinsizeBuilder};
+use std::future::Future;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Main side of the pseudoterminal, the [`Pty`] owns and closes it.
+struct MainFd(RawFd);
+
+impl AsRawFd for MainFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// A [`Pty`] implementing [`AsyncRead`] and [`AsyncWrite`].
+///
+/// ```no_run
+/// # async fn run() -> std::io::Result<()> {
+/// use teletypewriter::PtyBuilder;
+/// use tokio::io::AsyncReadExt;
+///
+/// let mut pty = PtyBuilder::new("sh").args(["-c", "echo hello"]).spawn_async()?;
+/// let mut output = String::new();
+/// pty.read_to_string(&mut output).await?;
+/// let status = pty.wait().await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Reading returns end of file once the child side of the pseudoterminal
+/// is closed, usually when the child exits. Dropping it terminates the
+/// child, see [`Pty`].
+pub struct AsyncPty {
+    // Deregistered before the `Pty` closes the descriptor.
+    fd: AsyncFd<MainFd>,
+    pty: Pty,
+    exit: watch::Receiver<Option<ExitStatus>>,
+    reaper: JoinHandle<()>,
+}
+
+/// Resolves once the child exited, see [`AsyncPty::exited`].
+pub struct ChildExit(Pin<Box<dyn Future<Output = io::Result<ExitStatus>> + Send>>);
+
+impl Future for ChildExit {
+    type Output = io::Result<ExitStatus>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
+}
+
+impl AsyncPty {
+    /// Drive `pty` with the current tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// When called outside of a tokio runtime.
+    pub fn new(pty: Pty) -> io::Result<AsyncPty> {
+        let fd = AsyncFd::new(MainFd(*pty.child))?;
+        let (sender, exit) = watch::channel(None);
+
+        // Created before the first look at the child, a SIGCHLD sent in
+        // between is not missed.
+        let mut sigchld = signal(SignalKind::child())?;
+        let child = pty.child.clone();
+        let reaper = tokio::spawn(async move {
+            loop {
+                match child.waitpid() {
+                    Ok(Some(status)) => {
+                        let _ = sender.send(Some(status));
+                        return;
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        log::error!("unable to wait for process {}: {err}", child.pid());
+                        return;
+                    }
+                }
+
+                if sigchld.recv().await.is_none() {
+                    return;
+                }
+            }
+        });
+
+        Ok(AsyncPty {
+            fd,
+            pty,
+            exit,
+            reaper,
+        })
+    }
+
+    #[inline]
+    pub fn pid(&self) -> libc::pid_t {
+        self.pty.child.pid()
+    }
+
+    pub async fn resize(&self, winsize: WinsizeBuilder) -> io::Result<()> {
+        self.pty.child.set_winsize(winsize)
+    }
+
+    /// Wait for the child to exit.
+    pub async fn wait(&self) -> io::Result<ExitStatus> {
+        self.exited().await
+    }
+
+    /// Future resolving once the child exited, it does not borrow the
+    /// pseudoterminal so it can be awaited while reading from it.
+    pub fn exited(&self) -> ChildExit {
+        let mut exit = self.exit.clone();
+        ChildExit(Box::pin(async move {
+            loop {
+                if let Some(status) = *exit.borrow_and_update() {
+                    return Ok(status);
+                }
+
+                if exit.changed().await.is_err() {
+                    return (*exit.borrow()).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::Other,
+                            "unable to wait for the child process",
+                        )
+                    });
+                }
+            }
+        }))
+    }
+}
+
+impl Drop for AsyncPty {
+    fn drop(&mut self) {
+        self.reaper.abort();
+        if self.exit.borrow().is_some() {
+            self.pty.set_exited();
+        }
+    }
+}
+
+impl AsyncRead for AsyncPty {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_read_ready(cx))?;
+            let unfilled = buf.initialize_unfilled();
+            match guard.try_io(|fd| read(fd.as_raw_fd(), unfilled)) {
+                Ok(Ok(len)) => {
+                    buf.advance(len);
+                    return Poll::Ready(Ok(()));
+                }
+                // On Linux, reads fail with EIO once the child side is
+                // closed.
+                Ok(Err(err)) if err.raw_os_error() == Some(libc::EIO) => {
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) => return Poll::Ready(Err(err)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for AsyncPty {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.fd.poll_write_ready(cx))?;
+            match guard.try_io(|fd| write(fd.as_raw_fd(), buf)) {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// The main side of a pseudoterminal can't be half closed, the child
+    /// gets a hang up once it is dropped.
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn read(fd: RawFd, buf: &mut [u8]) -> io::Result<usize> {
+    match unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } {
+        -1 => Err(io::Error::last_os_error()),
+        len => Ok(len as usize),
+    }
+}
+
+fn write(fd: RawFd, buf: &[u8]) -> io::Result<usize> {
+    match unsafe { libc::write(fd, buf.as_ptr() as *const libc::c_void, buf.len()) } {
+        -1 => Err(io::Error::last_os_error()),
+        len => Ok(len as usize),
+    }
+}
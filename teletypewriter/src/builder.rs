@@ -0,0 +1,194 @@
+use crate::{Pty, WinsizeBuilder};
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Terminal settings the pseudoterminal starts with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TermiosPreset {
+    /// Canonical mode with echo, signals and UTF-8 input, what shells expect.
+    #[default]
+    Utf8,
+    /// Same as [`TermiosPreset::Utf8`] without UTF-8 input handling.
+    Ascii,
+    /// Input is passed through byte by byte, without echo nor signals.
+    Raw,
+}
+
+/// Describes the program started in a new pseudoterminal, the same way
+/// [`std::process::Command`] does for regular processes.
+///
+/// ```no_run
+/// use teletypewriter::PtyBuilder;
+///
+/// let pty = PtyBuilder::new("zsh")
+///     .login(true)
+///     .current_dir("/tmp")
+///     .env("TERM", "xterm-256color")
+///     .env_remove("DESKTOP_STARTUP_ID")
+///     .size(80, 25)
+///     .spawn();
+/// ```
+#[derive(Debug, Clone)]
+pub struct PtyBuilder {
+    pub(crate) program: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) working_directory: Option<PathBuf>,
+    /// Variables changed in the inherited environment, `None` for the
+    /// removed ones.
+    pub(crate) envs: BTreeMap<OsString, Option<OsString>>,
+    pub(crate) env_clear: bool,
+    pub(crate) login: bool,
+    pub(crate) winsize: WinsizeBuilder,
+    pub(crate) termios: TermiosPreset,
+}
+
+impl PtyBuilder {
+    pub fn new<S: Into<String>>(program: S) -> Self {
+        PtyBuilder {
+            program: program.into(),
+            args: vec![],
+            working_directory: None,
+            envs: BTreeMap::new(),
+            env_clear: false,
+            login: false,
+            winsize: WinsizeBuilder {
+                cols: 80,
+                rows: 24,
+                width: 0,
+                height: 0,
+            },
+            termios: TermiosPreset::default(),
+        }
+    }
+
+    pub fn arg<S: Into<String>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Directory the program starts at, it is ignored when it does not
+    /// exist.
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.working_directory = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> &mut Self {
+        self.envs.insert(
+            key.as_ref().to_os_string(),
+            Some(value.as_ref().to_os_string()),
+        );
+        self
+    }
+
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        for (key, value) in vars {
+            self.env(key, value);
+        }
+        self
+    }
+
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        self.envs.insert(key.as_ref().to_os_string(), None);
+        self
+    }
+
+    /// Start from an empty environment instead of the one of this process.
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.envs.clear();
+        self.env_clear = true;
+        self
+    }
+
+    /// Run the program as a login shell, its `argv[0]` is then its name
+    /// prefixed with `-`. Ignored on Windows.
+    pub fn login(&mut self, login: bool) -> &mut Self {
+        self.login = login;
+        self
+    }
+
+    pub fn size(&mut self, columns: u16, rows: u16) -> &mut Self {
+        self.winsize.cols = columns;
+        self.winsize.rows = rows;
+        self
+    }
+
+    pub fn pixel_size(&mut self, width: u16, height: u16) -> &mut Self {
+        self.winsize.width = width;
+        self.winsize.height = height;
+        self
+    }
+
+    /// Ignored on Windows.
+    pub fn termios(&mut self, termios: TermiosPreset) -> &mut Self {
+        self.termios = termios;
+        self
+    }
+
+    /// Name the program sees itself run as.
+    pub(crate) fn arg0(&self) -> String {
+        if !self.login {
+            return self.program.to_owned();
+        }
+
+        let name = Path::new(&self.program)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.program.to_owned());
+        format!("-{name}")
+    }
+
+    /// Environment the program starts with.
+    pub(crate) fn environment(&self) -> Vec<(OsString, OsString)> {
+        let mut environment: BTreeMap<OsString, OsString> = if self.env_clear {
+            BTreeMap::new()
+        } else {
+            std::env::vars_os().collect()
+        };
+
+        for (key, value) in self.envs.iter() {
+            match value {
+                Some(value) => environment.insert(key.to_owned(), value.to_owned()),
+                None => environment.remove(key),
+            };
+        }
+
+        environment.into_iter().collect()
+    }
+
+    /// Start the program in a new pseudoterminal.
+    pub fn spawn(&self) -> io::Result<Pty> {
+        #[cfg(not(windows))]
+        return crate::unix::spawn(self);
+
+        #[cfg(windows)]
+        return crate::windows::spawn(self);
+    }
+
+    /// Start the program in a new pseudoterminal driven by the current
+    /// tokio runtime.
+    #[cfg(all(unix, feature = "tokio"))]
+    pub fn spawn_async(&self) -> io::Result<crate::AsyncPty> {
+        crate::AsyncPty::new(self.spawn()?)
+    }
+}
@@ -1,5 +1,8 @@
 extern crate libc;
 
+mod builder;
+pub use builder::{PtyBuilder, TermiosPreset};
+
 #[cfg(not(windows))]
 mod unix;
 #[cfg(not(windows))]
@@ -10,6 +13,11 @@ pub mod windows;
 #[cfg(windows)]
 pub use self::windows::*;
 
+#[cfg(all(unix, feature = "tokio"))]
+mod async_pty;
+#[cfg(all(unix, feature = "tokio"))]
+pub use async_pty::{AsyncPty, ChildExit};
+
 use std::io;
 
 #[repr(C)]
@@ -45,10 +53,36 @@ pub trait ProcessReadWrite {
     fn deregister(&mut self, _: &corcovado::Poll) -> io::Result<()>;
 }
 
+/// How the child terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The child exited on its own with this code.
+    Code(i32),
+    /// The child was killed by this signal, Unix only.
+    Signal(i32),
+}
+
+impl ExitStatus {
+    /// Whether the child exited with code 0.
+    #[inline]
+    pub fn success(&self) -> bool {
+        *self == ExitStatus::Code(0)
+    }
+}
+
+impl std::fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExitStatus::Code(code) => write!(f, "exited with code {code}"),
+            ExitStatus::Signal(signal) => write!(f, "killed by signal {signal}"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum ChildEvent {
-    /// Indicates the child has exited.
-    Exited,
+    /// Indicates the child has exited, along with how when it is known.
+    Exited(Option<ExitStatus>),
 }
 
 pub trait EventedPty: ProcessReadWrite {
@@ -83,3 +117,49 @@ impl WinsizeBuilder {
         }
     }
 }
+
+/// Parity bit of the characters sent over a serial line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+}
+
+impl std::str::FromStr for Parity {
+    type Err = String;
+
+    fn from_str(parity: &str) -> Result<Parity, String> {
+        match parity.to_lowercase().as_str() {
+            "none" => Ok(Parity::None),
+            "odd" => Ok(Parity::Odd),
+            "even" => Ok(Parity::Even),
+            _ => Err(format!(
+                "unknown parity {parity}, expected none, odd or even"
+            )),
+        }
+    }
+}
+
+/// Line settings of a serial device, see `Stream::serial`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialSettings {
+    pub baud_rate: u32,
+    pub parity: Parity,
+    /// From 5 to 8.
+    pub data_bits: u8,
+    /// 1 or 2.
+    pub stop_bits: u8,
+}
+
+impl Default for SerialSettings {
+    fn default() -> SerialSettings {
+        SerialSettings {
+            baud_rate: 115_200,
+            parity: Parity::None,
+            data_bits: 8,
+            stop_bits: 1,
+        }
+    }
+}
@@ -1,23 +1,32 @@
 #![cfg(unix)]
 
 mod signals;
+mod stream;
+
+pub use stream::{Stream, StreamIo, StreamPeer};
 
 extern crate libc;
 
-use crate::{ChildEvent, EventedPty, ProcessReadWrite, Winsize, WinsizeBuilder};
+use crate::{
+    ChildEvent, EventedPty, ExitStatus, ProcessReadWrite, PtyBuilder, TermiosPreset,
+    Winsize, WinsizeBuilder,
+};
 use corcovado::unix::EventedFd;
 use signal_hook::consts as sigconsts;
 use signals::Signals;
-use std::ffi::{CStr, CString};
+use std::ffi::{CStr, CString, OsStr};
 use std::fs::File;
 use std::io;
 use std::ops::Deref;
 use std::os::fd::AsRawFd;
 use std::os::fd::FromRawFd;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 use std::process::Command;
 use std::ptr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[cfg(all(target_os = "linux", not(target_env = "musl")))]
 const TIOCSWINSZ: libc::c_ulong = 0x5414;
@@ -46,31 +55,9 @@ extern "C" {
     fn ptsname(fd: *mut libc::c_int) -> *mut libc::c_char;
 }
 
-#[cfg(target_os = "macos")]
-fn default_shell_command(shell: &str) {
-    let command_shell_string = CString::new(shell).unwrap();
-    let command_pointer = command_shell_string.as_ptr();
-    let args = CString::new("--login").unwrap();
-    let args_pointer = args.as_ptr();
-    unsafe {
-        libc::execvp(command_pointer, vec![args_pointer].as_ptr());
-    }
-}
-
-#[cfg(not(target_os = "macos"))]
-fn default_shell_command(shell: &str) {
-    let command_shell_string = CString::new(shell).unwrap();
-    let command_pointer = command_shell_string.as_ptr();
-    // let home = std::env::var("HOME").unwrap();
-    // let args = CString::new(home).unwrap();
-    // let args_pointer = args.as_ptr() as *const i8;
-    unsafe {
-        libc::execvp(
-            command_pointer,
-            vec![command_pointer, std::ptr::null()].as_ptr(),
-        );
-    }
-}
+/// Time a child is given to exit after SIGHUP, and then after SIGTERM,
+/// before it gets killed once its [`Pty`] is dropped.
+pub const TERMINATE_TIMEOUT: Duration = Duration::from_millis(500);
 
 pub struct Pty {
     pub child: Child,
@@ -78,6 +65,25 @@ pub struct Pty {
     token: corcovado::Token,
     signals_token: corcovado::Token,
     signals: Signals,
+    /// Whether the child exited and was reaped already.
+    exited: bool,
+}
+
+impl Pty {
+    /// The child was reaped by someone else, it must not be signaled
+    /// anymore.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn set_exited(&mut self) {
+        self.exited = true;
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        if !self.exited {
+            self.child.terminate(TERMINATE_TIMEOUT);
+        }
+    }
 }
 
 impl Deref for Pty {
@@ -337,15 +343,61 @@ pub fn create_termp(utf8: bool) -> libc::termios {
 /// It returns two [`Pty`] along with respective process name [`String`] and process id (`libc::pid_`)
 ///
 pub fn create_pty(shell: &str, columns: u16, rows: u16) -> Pty {
-    let mut main = 0;
-    let winsize = Winsize {
-        ws_row: rows as libc::c_ushort,
-        ws_col: columns as libc::c_ushort,
-        ws_width: 0 as libc::c_ushort,
-        ws_height: 0 as libc::c_ushort,
-    };
-    let term = create_termp(true);
+    create_pty_with_working_directory(shell, None, columns, rows)
+}
+
+/// Same as [`create_pty`] but the child process starts at `working_directory`,
+/// if one is given and it exists. On macOS the shell runs as a login shell.
+pub fn create_pty_with_working_directory(
+    shell: &str,
+    working_directory: Option<&str>,
+    columns: u16,
+    rows: u16,
+) -> Pty {
+    let mut builder = PtyBuilder::new(shell);
+    builder.login(cfg!(target_os = "macos")).size(columns, rows);
+    if let Some(dir) = working_directory {
+        builder.current_dir(dir);
+    }
+
+    builder.spawn().expect("Fork failed.")
+}
+
+/// Start the program described by `builder`, see [`PtyBuilder::spawn`].
+pub(crate) fn spawn(builder: &PtyBuilder) -> io::Result<Pty> {
+    // Everything the child needs is allocated before forking.
+    let environment = builder.environment();
+    let search_path = environment
+        .iter()
+        .find(|(key, _)| key == "PATH")
+        .map(|(_, value)| value.to_owned());
+    let program = find_executable(&builder.program, search_path.as_deref());
+    let program = CString::new(program.into_os_string().into_vec())?;
+
+    let argv = std::iter::once(builder.arg0())
+        .chain(builder.args.iter().cloned())
+        .map(CString::new)
+        .collect::<Result<Vec<CString>, _>>()?;
+    let envp = environment
+        .into_iter()
+        .map(|(mut key, value)| {
+            key.push("=");
+            key.push(value);
+            CString::new(key.into_vec())
+        })
+        .collect::<Result<Vec<CString>, _>>()?;
+    let argv_pointers = null_terminated(&argv);
+    let envp_pointers = null_terminated(&envp);
+
+    let working_directory = builder
+        .working_directory
+        .as_ref()
+        .and_then(|dir| CString::new(dir.as_os_str().as_bytes()).ok());
+    let error_message = format!("failed to execute {}\n", builder.program);
+    let winsize = builder.winsize.build();
+    let term = termios_preset(builder.termios);
 
+    let mut main = 0;
     match unsafe {
         forkpty(
             &mut main as *mut _,
@@ -354,10 +406,23 @@ pub fn create_pty(shell: &str, columns: u16, rows: u16) -> Pty {
             &winsize as *const _,
         )
     } {
-        0 => {
-            default_shell_command(shell);
-            unreachable!();
-        }
+        0 => unsafe {
+            if let Some(dir) = &working_directory {
+                libc::chdir(dir.as_ptr());
+            }
+            libc::execve(
+                program.as_ptr(),
+                argv_pointers.as_ptr(),
+                envp_pointers.as_ptr(),
+            );
+            // Only reached when the program could not be executed.
+            libc::write(
+                libc::STDERR_FILENO,
+                error_message.as_ptr() as *const _,
+                error_message.len(),
+            );
+            libc::_exit(127);
+        },
         id if id > 0 => {
             let ptsname: String = tty_ptsname(main).unwrap_or_else(|_| "".to_string());
             let child = Child {
@@ -370,17 +435,57 @@ pub fn create_pty(shell: &str, columns: u16, rows: u16) -> Pty {
                 set_nonblocking(main);
             }
 
-            let signals = Signals::new([sigconsts::SIGCHLD])
-                .expect("error preparing signal handling");
-            Pty {
+            let signals = Signals::new([sigconsts::SIGCHLD])?;
+            Ok(Pty {
                 child,
                 signals,
                 file: unsafe { File::from_raw_fd(main) },
                 token: corcovado::Token(0),
                 signals_token: corcovado::Token(0),
+                exited: false,
+            })
+        }
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn null_terminated(strings: &[CString]) -> Vec<*const libc::c_char> {
+    strings
+        .iter()
+        .map(|string| string.as_ptr())
+        .chain(std::iter::once(ptr::null()))
+        .collect()
+}
+
+/// Path of `program`, looked up in `search_path` like `execvp` does when it
+/// has no slash. Falls back to `program` itself when it is not found.
+fn find_executable(program: &str, search_path: Option<&OsStr>) -> PathBuf {
+    if program.contains('/') {
+        return PathBuf::from(program);
+    }
+
+    let search_path = search_path.unwrap_or_else(|| OsStr::new("/usr/bin:/bin"));
+    std::env::split_paths(search_path)
+        .map(|dir| dir.join(program))
+        .find(|path| {
+            path.metadata().map_or(false, |metadata| {
+                metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+            })
+        })
+        .unwrap_or_else(|| PathBuf::from(program))
+}
+
+fn termios_preset(preset: TermiosPreset) -> libc::termios {
+    match preset {
+        TermiosPreset::Utf8 => create_termp(true),
+        TermiosPreset::Ascii => create_termp(false),
+        TermiosPreset::Raw => {
+            let mut term = create_termp(false);
+            unsafe {
+                libc::cfmakeraw(&mut term);
             }
+            term
         }
-        _ => panic!("Fork failed."),
     }
 }
 
@@ -430,22 +535,59 @@ impl Child {
         }
     }
 
-    /// Return the child’s exit status if it has already exited. If the child is still running, return Ok(None).
+    /// Return the child’s exit status if it has already exited, reaping it. If the child is still running, return Ok(None).
     /// https://linux.die.net/man/2/waitpid
-    pub fn waitpid(&self) -> Result<Option<i32>, String> {
+    pub fn waitpid(&self) -> Result<Option<ExitStatus>, String> {
         let mut status = 0 as libc::c_int;
-        // If WNOHANG was specified in options and there were no children in a waitable state, then waitid() returns 0 immediately and the state of the siginfo_t structure pointed to by infop is unspecified. To distinguish this case from that where a child was in a waitable state, zero out the si_pid field before the call and check for a nonzero value in this field after the call returns.
+        // With WNOHANG, waitpid returns 0 while the child is still running.
         let res =
             unsafe { waitpid(*self.pid, &mut status as *mut libc::c_int, libc::WNOHANG) };
         if res <= -1 {
-            return Err(String::from("error"));
+            return Err(io::Error::last_os_error().to_string());
         }
 
-        if res == 0 && status == 0 {
+        if res == 0 {
             return Ok(None);
         }
 
-        Ok(Some(status))
+        Ok(Some(exit_status(status)))
+    }
+
+    /// Ask the child to terminate with SIGHUP, then SIGTERM and at last
+    /// SIGKILL, each one sent when the child is still running `timeout`
+    /// after the previous one, and reap it. It returns right away, the
+    /// child is waited for on its own thread.
+    pub fn terminate(&self, timeout: Duration) {
+        let pid = *self.pid;
+        unsafe {
+            libc::kill(pid, libc::SIGHUP);
+        }
+
+        let spawned = std::thread::Builder::new()
+            .name(format!("reaper {pid}"))
+            .spawn(move || {
+                for signal in [libc::SIGTERM, libc::SIGKILL] {
+                    if wait_timeout(pid, timeout) {
+                        return;
+                    }
+                    unsafe {
+                        libc::kill(pid, signal);
+                    }
+                }
+
+                let mut status = 0 as libc::c_int;
+                unsafe {
+                    waitpid(pid, &mut status as *mut libc::c_int, 0);
+                }
+            });
+        if let Err(err) = spawned {
+            log::error!("unable to wait for process {pid}: {err}");
+        }
+    }
+
+    /// Process id of the spawned program.
+    pub fn pid(&self) -> libc::pid_t {
+        *self.pid
     }
 
     pub fn close(&self) {
@@ -462,29 +604,132 @@ impl Deref for Child {
     }
 }
 
-impl Drop for Child {
-    fn drop(&mut self) {
-        unsafe {
-            libc::kill(*self.pid, libc::SIGHUP);
+/// Decode a status filled by `waitpid`.
+fn exit_status(status: libc::c_int) -> ExitStatus {
+    if libc::WIFSIGNALED(status) {
+        ExitStatus::Signal(libc::WTERMSIG(status))
+    } else {
+        ExitStatus::Code(libc::WEXITSTATUS(status))
+    }
+}
+
+/// Wait up to `timeout` for the child `pid` to exit, reaping it. Returns
+/// false when it is still running.
+fn wait_timeout(pid: libc::pid_t, timeout: Duration) -> bool {
+    let start = Instant::now();
+    loop {
+        let mut status = 0 as libc::c_int;
+        match unsafe { waitpid(pid, &mut status as *mut libc::c_int, libc::WNOHANG) } {
+            // Still running.
+            0 => {}
+            // Reaped, or it is not a child anymore.
+            _ => return true,
+        }
+
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Whether the terminal behind the main side `fd` reads a password: input
+/// is line edited but not echoed. Programs in raw mode (editors, full screen
+/// applications) disable echo along with line editing, their input isn't a
+/// password. Returns true when it can't be told.
+pub fn reads_password(fd: libc::c_int) -> bool {
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    match unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } {
+        0 => {
+            let lflag = unsafe { termios.assume_init() }.c_lflag;
+            lflag & libc::ECHO == 0 && lflag & libc::ICANON != 0
         }
+        _ => true,
     }
 }
 
+/// Process group in the foreground of the terminal behind the main side
+/// `fd`, the shell or the program it runs.
+pub fn foreground_process_group(fd: libc::c_int) -> Option<libc::pid_t> {
+    match unsafe { libc::tcgetpgrp(fd) } {
+        pgrp if pgrp > 0 => Some(pgrp),
+        _ => None,
+    }
+}
+
+/// Name of the program run by the process `pid`, empty when it can't be
+/// told.
 pub fn command_per_pid(pid: libc::pid_t) -> String {
+    #[cfg(target_os = "linux")]
+    if let Ok(name) = std::fs::read_to_string(format!("/proc/{pid}/comm")) {
+        return name.trim_end().to_string();
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut name = [0u8; 256];
+        let len = unsafe {
+            libc::proc_name(
+                pid,
+                name.as_mut_ptr() as *mut libc::c_void,
+                name.len() as u32,
+            )
+        };
+        if len > 0 {
+            return String::from_utf8_lossy(&name[..len as usize]).to_string();
+        }
+    }
+
     let current_process_name = Command::new("ps")
         .arg("-p")
         .arg(format!("{pid:}"))
         .arg("-o")
         .arg("comm=")
         .output()
-        .expect("failed to execute process")
-        .stdout;
+        .map(|output| output.stdout)
+        .unwrap_or_default();
 
-    std::str::from_utf8(&current_process_name)
-        .unwrap_or("")
+    String::from_utf8_lossy(&current_process_name)
+        .trim_end()
         .to_string()
 }
 
+/// Current working directory of the process `pid`.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+pub fn working_directory_per_pid(pid: libc::pid_t) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/{pid}/cwd")).ok()
+}
+
+/// Current working directory of the process `pid`.
+#[cfg(target_os = "macos")]
+pub fn working_directory_per_pid(pid: libc::pid_t) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut info: libc::proc_vnodepathinfo = unsafe { std::mem::zeroed() };
+    let size = std::mem::size_of::<libc::proc_vnodepathinfo>() as libc::c_int;
+    let res = unsafe {
+        libc::proc_pidinfo(
+            pid,
+            libc::PROC_PIDVNODEPATHINFO,
+            0,
+            &mut info as *mut _ as *mut libc::c_void,
+            size,
+        )
+    };
+    if res != size {
+        return None;
+    }
+
+    let path = unsafe { CStr::from_ptr(info.pvi_cdir.vip_path.as_ptr() as *const _) };
+    Some(PathBuf::from(std::ffi::OsStr::from_bytes(path.to_bytes())))
+}
+
+/// Current working directory of the process `pid`.
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "macos")))]
+pub fn working_directory_per_pid(_pid: libc::pid_t) -> Option<PathBuf> {
+    None
+}
+
 impl EventedPty for Pty {
     #[inline]
     fn next_child_event(&mut self) -> Option<ChildEvent> {
@@ -494,12 +739,15 @@ impl EventedPty for Pty {
             }
 
             match self.child.waitpid() {
-                Err(_e) => {
-                    // std::process::exit(1);
+                Err(err) => {
+                    log::error!("unable to wait for process {}: {err}", self.child.pid());
                     None
                 }
                 Ok(None) => None,
-                Ok(Some(..)) => Some(ChildEvent::Exited),
+                Ok(Some(status)) => {
+                    self.exited = true;
+                    Some(ChildEvent::Exited(Some(status)))
+                }
             }
         })
     }
@@ -527,3 +775,139 @@ pub fn tty_ptsname(fd: libc::c_int) -> Result<String, String> {
 
     Ok(str_buf)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait(pty: &mut Pty) -> ExitStatus {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Some(status) = pty.child.waitpid().unwrap() {
+                pty.exited = true;
+                return status;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("process {} did not exit", pty.child.pid());
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let mut pty = PtyBuilder::new("sh")
+            .args(["-c", "exit 3"])
+            .spawn()
+            .unwrap();
+        let status = wait(&mut pty);
+        assert_eq!(status, ExitStatus::Code(3));
+        assert!(!status.success());
+        assert_eq!(status.to_string(), "exited with code 3");
+
+        let mut pty = PtyBuilder::new("sh")
+            .args(["-c", "kill -9 $$"])
+            .spawn()
+            .unwrap();
+        let status = wait(&mut pty);
+        assert_eq!(status, ExitStatus::Signal(libc::SIGKILL));
+        assert_eq!(status.to_string(), "killed by signal 9");
+    }
+
+    #[test]
+    fn test_foreground_process_group() {
+        let mut pty = PtyBuilder::new("sh")
+            .args(["-c", "exec sleep 5"])
+            .spawn()
+            .unwrap();
+        let pid = pty.child.pid();
+
+        // Once the shell execs sleep, sleep leads the session and its group.
+        let start = Instant::now();
+        while command_per_pid(pid) != "sleep" {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "{pid} is not sleep"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(foreground_process_group(*pty.child), Some(pid));
+
+        pty.child.terminate(Duration::from_millis(100));
+        pty.exited = true;
+    }
+
+    /// Polls `reads_password` until it returns `expected`.
+    fn wait_reads_password(pty: &Pty, expected: bool) {
+        let start = Instant::now();
+        while reads_password(*pty.child) != expected {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "reads_password never became {expected}"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_reads_password() {
+        // Canonical mode with echo.
+        let mut pty = PtyBuilder::new("sh")
+            .args(["-c", "exec sleep 5"])
+            .spawn()
+            .unwrap();
+        wait_reads_password(&pty, false);
+        pty.child.terminate(Duration::from_millis(100));
+        pty.exited = true;
+
+        // A password prompt only turns echo off.
+        let mut pty = PtyBuilder::new("sh")
+            .args(["-c", "stty -echo; exec sleep 5"])
+            .spawn()
+            .unwrap();
+        wait_reads_password(&pty, true);
+        pty.child.terminate(Duration::from_millis(100));
+        pty.exited = true;
+
+        // Raw mode turns off echo and line editing.
+        let mut pty = PtyBuilder::new("sh")
+            .args(["-c", "stty raw -echo; exec sleep 5"])
+            .spawn()
+            .unwrap();
+        let start = Instant::now();
+        loop {
+            let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+            assert_eq!(
+                unsafe { libc::tcgetattr(*pty.child, termios.as_mut_ptr()) },
+                0
+            );
+            if unsafe { termios.assume_init() }.c_lflag & libc::ECHO == 0 {
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5), "echo stays on");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!reads_password(*pty.child));
+        pty.child.terminate(Duration::from_millis(100));
+        pty.exited = true;
+    }
+
+    #[test]
+    fn test_terminate() {
+        // Ignores SIGHUP and SIGTERM, only SIGKILL stops it.
+        let mut pty = PtyBuilder::new("sh")
+            .args(["-c", "trap '' HUP TERM; while true; do sleep 0.05; done"])
+            .spawn()
+            .unwrap();
+        let pid = pty.child.pid();
+        // Give the shell time to set up its traps.
+        std::thread::sleep(Duration::from_millis(200));
+        pty.child.terminate(Duration::from_millis(100));
+
+        let start = Instant::now();
+        while unsafe { libc::kill(pid, 0) } == 0 {
+            assert!(start.elapsed() < Duration::from_secs(5), "{pid} still runs");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // Reaped by the terminate thread, dropping it must not signal again.
+        pty.exited = true;
+    }
+}
@@ -0,0 +1,525 @@
+// Terminals driven by something else than a program in a pseudoterminal: a
+// Unix socket, a serial device, a recording or an in-process stream. They
+// are read and written like a `Pty`, and tell their end through the child
+// event token.
+
+use crate::{
+    ChildEvent, EventedPty, Parity, ProcessReadWrite, SerialSettings, WinsizeBuilder,
+};
+use corcovado::unix::EventedFd;
+use corcovado::{Registration, SetReadiness};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::mpsc;
+
+/// Other end of an in-process [`Stream`], see [`Stream::pair`].
+pub struct StreamPeer {
+    /// Receives what is typed in the terminal, and writes what it shows.
+    pub stream: UnixStream,
+    /// Sizes the terminal is resized to.
+    pub resizes: mpsc::Receiver<WinsizeBuilder>,
+}
+
+/// Reads and writes the stream, and tells the end of it once a read
+/// returns end of file.
+pub struct StreamIo {
+    file: File,
+    end: SetReadiness,
+}
+
+impl StreamIo {
+    fn end(&self) {
+        let _ = self.end.set_readiness(corcovado::Ready::readable());
+    }
+}
+
+impl Read for StreamIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.file.read(buf) {
+            Ok(0) if !buf.is_empty() => {
+                self.end();
+                Ok(0)
+            }
+            // Serial devices fail with EIO once they are gone.
+            Err(err) if err.raw_os_error() == Some(libc::EIO) => {
+                self.end();
+                Err(err)
+            }
+            result => result,
+        }
+    }
+}
+
+impl Write for StreamIo {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// How a [`Stream`] tells its size.
+enum Resize {
+    /// It has no size, or it is told in-band by the terminal once the
+    /// other end sets the DEC private mode 2048.
+    Ignore,
+    /// Sent to the [`StreamPeer`].
+    Peer(mpsc::Sender<WinsizeBuilder>),
+}
+
+/// A terminal connected to something else than a local program.
+///
+/// How it is resized and when it ends depends on what it is connected to:
+///
+/// | Constructor            | Resize                      | End                        |
+/// |------------------------|-----------------------------|----------------------------|
+/// | [`Stream::connect`]    | in-band, with mode 2048     | the server closes          |
+/// | [`Stream::serial`]     | ignored, a line has no size | the device goes away       |
+/// | [`Stream::replay`]     | ignored                     | never, it is kept to read  |
+/// | [`Stream::pair`]       | sent to the [`StreamPeer`]  | the peer stream is dropped |
+///
+/// Its end is reported as [`ChildEvent::Exited`] without a status.
+pub struct Stream {
+    io: StreamIo,
+    end: Registration,
+    /// Whether the end was reported already.
+    ended: bool,
+    resize: Resize,
+    token: corcovado::Token,
+    end_token: corcovado::Token,
+}
+
+impl Stream {
+    fn new(file: File, resize: Resize) -> Stream {
+        let (end, set_end) = Registration::new2();
+        Stream {
+            io: StreamIo { file, end: set_end },
+            end,
+            ended: false,
+            resize,
+            token: corcovado::Token(0),
+            end_token: corcovado::Token(0),
+        }
+    }
+
+    fn from_unix_stream(stream: UnixStream, resize: Resize) -> io::Result<Stream> {
+        stream.set_nonblocking(true)?;
+        let file = unsafe { File::from_raw_fd(stream.into_raw_fd()) };
+        Ok(Stream::new(file, resize))
+    }
+
+    /// Connect to the Unix socket at `path`. The server is not told the
+    /// size of the terminal, unless it sets the DEC private mode 2048 to
+    /// have the terminal write it in the stream.
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<Stream> {
+        Stream::from_unix_stream(UnixStream::connect(path)?, Resize::Ignore)
+    }
+
+    /// Open the serial device at `path`, e.g. `/dev/ttyUSB0`.
+    pub fn serial<P: AsRef<Path>>(
+        path: P,
+        settings: SerialSettings,
+    ) -> io::Result<Stream> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(path)?;
+        configure_serial(file.as_raw_fd(), settings)?;
+        Ok(Stream::new(file, Resize::Ignore))
+    }
+
+    /// Show the bytes recorded in the file at `path`, e.g. by `script`.
+    /// What is typed is discarded.
+    pub fn replay<P: AsRef<Path>>(path: P) -> io::Result<Stream> {
+        let recording = std::fs::read(path)?;
+        let (stream, mut peer) = UnixStream::pair()?;
+        std::thread::Builder::new()
+            .name(String::from("replay"))
+            .spawn(move || {
+                if peer.write_all(&recording).is_err() {
+                    return;
+                }
+                // Keep the stream open, and drained, until it is dropped.
+                let _ = io::copy(&mut peer, &mut io::sink());
+            })?;
+        Stream::from_unix_stream(stream, Resize::Ignore)
+    }
+
+    /// A stream fed by this process through the returned [`StreamPeer`].
+    pub fn pair() -> io::Result<(Stream, StreamPeer)> {
+        let (stream, peer) = UnixStream::pair()?;
+        let (sender, resizes) = mpsc::channel();
+        let stream = Stream::from_unix_stream(stream, Resize::Peer(sender))?;
+        Ok((
+            stream,
+            StreamPeer {
+                stream: peer,
+                resizes,
+            },
+        ))
+    }
+}
+
+fn configure_serial(fd: libc::c_int, settings: SerialSettings) -> io::Result<()> {
+    let speed = baud_rate(settings.baud_rate)?;
+    let mut term = std::mem::MaybeUninit::<libc::termios>::uninit();
+    if unsafe { libc::tcgetattr(fd, term.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut term = unsafe { term.assume_init() };
+
+    unsafe { libc::cfmakeraw(&mut term) };
+    term.c_cflag |= libc::CLOCAL | libc::CREAD;
+    term.c_cflag &= !libc::CSIZE;
+    term.c_cflag |= match settings.data_bits {
+        5 => libc::CS5,
+        6 => libc::CS6,
+        7 => libc::CS7,
+        8 => libc::CS8,
+        bits => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported data bits {bits}"),
+            ))
+        }
+    };
+    match settings.parity {
+        Parity::None => {
+            term.c_cflag &= !(libc::PARENB | libc::PARODD);
+            term.c_iflag &= !libc::INPCK;
+        }
+        Parity::Odd => {
+            term.c_cflag |= libc::PARENB | libc::PARODD;
+            term.c_iflag |= libc::INPCK;
+        }
+        Parity::Even => {
+            term.c_cflag |= libc::PARENB;
+            term.c_cflag &= !libc::PARODD;
+            term.c_iflag |= libc::INPCK;
+        }
+    }
+    match settings.stop_bits {
+        1 => term.c_cflag &= !libc::CSTOPB,
+        2 => term.c_cflag |= libc::CSTOPB,
+        bits => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported stop bits {bits}"),
+            ))
+        }
+    }
+
+    unsafe {
+        libc::cfsetispeed(&mut term, speed);
+        libc::cfsetospeed(&mut term, speed);
+    }
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &term) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Speeds are the rates themselves on BSDs.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn baud_rate(rate: u32) -> io::Result<libc::speed_t> {
+    Ok(rate as libc::speed_t)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "freebsd")))]
+fn baud_rate(rate: u32) -> io::Result<libc::speed_t> {
+    Ok(match rate {
+        50 => libc::B50,
+        75 => libc::B75,
+        110 => libc::B110,
+        134 => libc::B134,
+        150 => libc::B150,
+        200 => libc::B200,
+        300 => libc::B300,
+        600 => libc::B600,
+        1200 => libc::B1200,
+        1800 => libc::B1800,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        500000 => libc::B500000,
+        576000 => libc::B576000,
+        921600 => libc::B921600,
+        1000000 => libc::B1000000,
+        1152000 => libc::B1152000,
+        1500000 => libc::B1500000,
+        2000000 => libc::B2000000,
+        2500000 => libc::B2500000,
+        3000000 => libc::B3000000,
+        3500000 => libc::B3500000,
+        4000000 => libc::B4000000,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported baud rate {rate}"),
+            ))
+        }
+    })
+}
+
+impl ProcessReadWrite for Stream {
+    type Reader = StreamIo;
+    type Writer = StreamIo;
+
+    #[inline]
+    fn reader(&mut self) -> &mut StreamIo {
+        &mut self.io
+    }
+
+    #[inline]
+    fn read_token(&self) -> corcovado::Token {
+        self.token
+    }
+
+    #[inline]
+    fn writer(&mut self) -> &mut StreamIo {
+        &mut self.io
+    }
+
+    #[inline]
+    fn write_token(&self) -> corcovado::Token {
+        self.token
+    }
+
+    fn set_winsize(&mut self, winsize: WinsizeBuilder) -> io::Result<()> {
+        match &self.resize {
+            Resize::Ignore => Ok(()),
+            Resize::Peer(resizes) => {
+                // The peer may not care about sizes anymore.
+                let _ = resizes.send(winsize);
+                Ok(())
+            }
+        }
+    }
+
+    fn register(
+        &mut self,
+        poll: &corcovado::Poll,
+        token: &mut dyn Iterator<Item = corcovado::Token>,
+        interest: corcovado::Ready,
+        poll_opts: corcovado::PollOpt,
+    ) -> io::Result<()> {
+        self.token = token.next().unwrap();
+        poll.register(
+            &EventedFd(&self.io.file.as_raw_fd()),
+            self.token,
+            interest,
+            poll_opts,
+        )?;
+
+        self.end_token = token.next().unwrap();
+        poll.register(
+            &self.end,
+            self.end_token,
+            corcovado::Ready::readable(),
+            corcovado::PollOpt::level(),
+        )
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &corcovado::Poll,
+        interest: corcovado::Ready,
+        poll_opts: corcovado::PollOpt,
+    ) -> io::Result<()> {
+        poll.reregister(
+            &EventedFd(&self.io.file.as_raw_fd()),
+            self.token,
+            interest,
+            poll_opts,
+        )?;
+
+        poll.reregister(
+            &self.end,
+            self.end_token,
+            corcovado::Ready::readable(),
+            corcovado::PollOpt::level(),
+        )
+    }
+
+    fn deregister(&mut self, poll: &corcovado::Poll) -> io::Result<()> {
+        poll.deregister(&EventedFd(&self.io.file.as_raw_fd()))?;
+        poll.deregister(&self.end)
+    }
+}
+
+impl EventedPty for Stream {
+    #[inline]
+    fn child_event_token(&self) -> corcovado::Token {
+        self.end_token
+    }
+
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        if self.ended || !self.io.end.readiness().is_readable() {
+            return None;
+        }
+
+        self.ended = true;
+        Some(ChildEvent::Exited(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// Read from `stream` until `expected` was read or its end.
+    fn read_until(stream: &mut Stream, expected: &[u8]) -> Vec<u8> {
+        let start = Instant::now();
+        let mut output = vec![];
+        let mut buf = [0u8; 1024];
+        while !output.ends_with(expected) {
+            assert!(start.elapsed() < Duration::from_secs(5), "read {output:?}");
+            match stream.reader().read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => output.extend_from_slice(&buf[..len]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => panic!("{err}"),
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn test_pair() {
+        let (mut stream, mut peer) = Stream::pair().unwrap();
+        peer.stream.write_all(b"hello").unwrap();
+        assert_eq!(read_until(&mut stream, b"hello"), b"hello");
+
+        stream.writer().write_all(b"ls\r").unwrap();
+        let mut input = [0u8; 3];
+        peer.stream.read_exact(&mut input).unwrap();
+        assert_eq!(&input, b"ls\r");
+
+        stream
+            .set_winsize(WinsizeBuilder {
+                rows: 24,
+                cols: 80,
+                width: 0,
+                height: 0,
+            })
+            .unwrap();
+        assert_eq!(peer.resizes.try_recv().unwrap().cols, 80);
+
+        assert_eq!(stream.next_child_event(), None);
+        drop(peer);
+        assert!(read_until(&mut stream, b"never").is_empty());
+        assert_eq!(stream.next_child_event(), Some(ChildEvent::Exited(None)));
+        assert_eq!(stream.next_child_event(), None);
+    }
+
+    #[test]
+    fn test_connect() {
+        let path = std::env::temp_dir()
+            .join(format!("teletypewriter-socket-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+
+        let mut stream = Stream::connect(&path).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        server.write_all(b"$ ").unwrap();
+        assert_eq!(read_until(&mut stream, b"$ "), b"$ ");
+
+        drop(server);
+        assert!(read_until(&mut stream, b"never").is_empty());
+        assert_eq!(stream.next_child_event(), Some(ChildEvent::Exited(None)));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_replay() {
+        let path = std::env::temp_dir()
+            .join(format!("teletypewriter-replay-{}", std::process::id()));
+        std::fs::write(&path, b"\x1b[1mrecorded\x1b[0m").unwrap();
+
+        let mut stream = Stream::replay(&path).unwrap();
+        assert_eq!(
+            read_until(&mut stream, b"\x1b[0m"),
+            b"\x1b[1mrecorded\x1b[0m"
+        );
+        // Typing does nothing, and the recording stays.
+        stream.writer().write_all(b"q").unwrap();
+        assert_eq!(stream.next_child_event(), None);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_serial() {
+        // A pseudoterminal stands for the device.
+        let mut main = 0;
+        let mut device = 0;
+        let res = unsafe {
+            libc::openpty(
+                &mut main,
+                &mut device,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        assert_eq!(res, 0);
+        let device_path = crate::tty_ptsname(main).unwrap();
+
+        let settings = SerialSettings {
+            baud_rate: 9600,
+            parity: Parity::Even,
+            data_bits: 7,
+            stop_bits: 2,
+        };
+        let mut stream = Stream::serial(&device_path, settings).unwrap();
+
+        let mut term = std::mem::MaybeUninit::<libc::termios>::uninit();
+        assert_eq!(unsafe { libc::tcgetattr(device, term.as_mut_ptr()) }, 0);
+        let term = unsafe { term.assume_init() };
+        // Pseudoterminals always have 8 bits without parity, unlike
+        // serial lines, but keep the rest.
+        assert_eq!(term.c_lflag & (libc::ICANON | libc::ECHO), 0);
+        assert_ne!(term.c_iflag & libc::INPCK, 0);
+        assert_ne!(term.c_cflag & libc::CSTOPB, 0);
+        assert_eq!(
+            unsafe { libc::cfgetospeed(&term) },
+            baud_rate(9600).unwrap()
+        );
+
+        let mut main = unsafe { File::from_raw_fd(main) };
+        main.write_all(b"login: ").unwrap();
+        assert_eq!(read_until(&mut stream, b"login: "), b"login: ");
+
+        assert!(
+            Stream::serial(
+                &device_path,
+                SerialSettings {
+                    baud_rate: 12345,
+                    ..settings
+                }
+            )
+            .is_err()
+                || cfg!(any(target_os = "macos", target_os = "freebsd"))
+        );
+        unsafe { libc::close(device) };
+    }
+}
@@ -5,11 +5,17 @@ use std::sync::atomic::{AtomicPtr, Ordering};
 
 use windows_sys::Win32::Foundation::{BOOLEAN, HANDLE};
 use windows_sys::Win32::System::Threading::{
-    RegisterWaitForSingleObject, UnregisterWait, INFINITE, WT_EXECUTEINWAITTHREAD,
-    WT_EXECUTEONLYONCE,
+    GetExitCodeProcess, RegisterWaitForSingleObject, UnregisterWait, INFINITE,
+    WT_EXECUTEINWAITTHREAD, WT_EXECUTEONLYONCE,
 };
 
-use crate::ChildEvent;
+use crate::{ChildEvent, ExitStatus};
+
+/// Given to the callback, the child handle is needed to read its exit code.
+struct ExitContext {
+    event_tx: Sender<ChildEvent>,
+    child_handle: HANDLE,
+}
 
 /// WinAPI callback to run when child process exits.
 extern "system" fn child_exit_callback(ctx: *mut c_void, timed_out: BOOLEAN) {
@@ -17,8 +23,14 @@ extern "system" fn child_exit_callback(ctx: *mut c_void, timed_out: BOOLEAN) {
         return;
     }
 
-    let event_tx: Box<_> = unsafe { Box::from_raw(ctx as *mut Sender<ChildEvent>) };
-    let _ = event_tx.send(ChildEvent::Exited);
+    let context: Box<_> = unsafe { Box::from_raw(ctx as *mut ExitContext) };
+    let mut exit_code = 0;
+    let status = match unsafe { GetExitCodeProcess(context.child_handle, &mut exit_code) }
+    {
+        0 => None,
+        _ => Some(ExitStatus::Code(exit_code as i32)),
+    };
+    let _ = context.event_tx.send(ChildEvent::Exited(status));
 }
 
 pub struct ChildExitWatcher {
@@ -31,7 +43,10 @@ impl ChildExitWatcher {
         let (event_tx, event_rx) = channel::<ChildEvent>();
 
         let mut wait_handle: HANDLE = 0;
-        let sender_ref = Box::new(event_tx);
+        let sender_ref = Box::new(ExitContext {
+            event_tx,
+            child_handle,
+        });
 
         let success = unsafe {
             RegisterWaitForSingleObject(
@@ -103,9 +118,9 @@ mod tests {
         poll.poll(&mut events, Some(WAIT_TIMEOUT)).unwrap();
         assert_eq!(events.iter().next().unwrap().token(), child_events_token);
         // Verify that at least one `ChildEvent::Exited` was received.
-        assert_eq!(
+        assert!(matches!(
             child_exit_watcher.event_rx().try_recv(),
-            Ok(ChildEvent::Exited)
-        );
+            Ok(ChildEvent::Exited(_))
+        ));
     }
 }
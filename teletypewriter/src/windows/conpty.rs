@@ -2,6 +2,7 @@ use crate::Winsize;
 use log::*;
 use std::io::Error;
 use std::os::windows::io::IntoRawHandle;
+use std::path::Path;
 use std::{mem, ptr};
 
 use crate::windows::pipes::{EventedAnonRead, EventedAnonWrite};
@@ -16,13 +17,13 @@ use windows_sys::{s, w};
 
 use windows_sys::Win32::System::Threading::{
     CreateProcessW, InitializeProcThreadAttributeList, UpdateProcThreadAttribute,
-    EXTENDED_STARTUPINFO_PRESENT, PROCESS_INFORMATION,
+    CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT, PROCESS_INFORMATION,
     PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE, STARTF_USESTDHANDLES, STARTUPINFOEXW,
     STARTUPINFOW,
 };
 
 use crate::windows::child::ChildExitWatcher;
-use crate::windows::{cmdline, win32_string, Pty};
+use crate::windows::{win32_string, Pty};
 
 /// Load the pseudoconsole API from conpty.dll if possible, otherwise use the
 /// standard Windows API.
@@ -103,7 +104,12 @@ impl Drop for Conpty {
 // The ConPTY handle can be sent between threads.
 unsafe impl Send for Conpty {}
 
-pub fn new(shell: &str, columns: u16, rows: u16) -> Option<Pty> {
+pub fn new(
+    command_line: &str,
+    working_directory: Option<&Path>,
+    environment: Option<&[u16]>,
+    winsize: Winsize,
+) -> Option<Pty> {
     let api = ConptyApi::new();
     let mut pty_handle: HPCON = 0;
 
@@ -114,13 +120,6 @@ pub fn new(shell: &str, columns: u16, rows: u16) -> Option<Pty> {
     let (conout, conout_pty_handle) = miow::pipe::anonymous(0).unwrap();
     let (conin_pty_handle, conin) = miow::pipe::anonymous(0).unwrap();
 
-    let winsize = Winsize {
-        ws_row: rows as libc::c_ushort,
-        ws_col: columns as libc::c_ushort,
-        ws_width: 0 as libc::c_ushort,
-        ws_height: 0 as libc::c_ushort,
-    };
-
     // Create the Pseudo Console, using the pipes.
     let result = unsafe {
         (api.create)(
@@ -208,9 +207,8 @@ pub fn new(shell: &str, columns: u16, rows: u16) -> Option<Pty> {
         }
     }
 
-    let cmdline = win32_string(&cmdline(shell));
-    // let cwd = win32_string;
-    // let cwd = config.working_directory.as_ref().map(win32_string);
+    let cmdline = win32_string(command_line);
+    let cwd = working_directory.map(win32_string);
 
     let mut proc_info: PROCESS_INFORMATION = unsafe { mem::zeroed() };
     unsafe {
@@ -220,10 +218,9 @@ pub fn new(shell: &str, columns: u16, rows: u16) -> Option<Pty> {
             ptr::null_mut(),
             ptr::null_mut(),
             false as i32,
-            EXTENDED_STARTUPINFO_PRESENT,
-            ptr::null_mut(),
-            ptr::null(),
-            // cwd.as_ref().map_or_else(ptr::null, |s| s.as_ptr()),
+            EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT,
+            environment.map_or_else(ptr::null, |block| block.as_ptr()) as *const _,
+            cwd.as_ref().map_or_else(ptr::null, |s| s.as_ptr()),
             &mut startup_info_ex.StartupInfo as *mut STARTUPINFOW,
             &mut proc_info as *mut PROCESS_INFORMATION,
         ) > 0;
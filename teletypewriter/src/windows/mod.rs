@@ -3,14 +3,16 @@ mod conpty;
 mod pipes;
 mod spsc;
 
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::io::{self};
 use std::iter::once;
 use std::os::windows::ffi::OsStrExt;
 use std::sync::mpsc::TryRecvError;
 
 use crate::windows::child::ChildExitWatcher;
-use crate::{ChildEvent, EventedPty, ProcessReadWrite, Winsize, WinsizeBuilder};
+use crate::{
+    ChildEvent, EventedPty, ProcessReadWrite, PtyBuilder, Winsize, WinsizeBuilder,
+};
 
 use conpty::Conpty as Backend;
 use pipes::{EventedAnonRead as ReadPipe, EventedAnonWrite as WritePipe};
@@ -30,9 +32,93 @@ pub struct Pty {
 // Creates conpty instead of pty
 // Windows Pseudo Console (ConPTY)
 pub fn create_pty(shell: &str, columns: u16, rows: u16) -> Pty {
-    conpty::new(shell, columns, rows)
-        .ok_or_else(|| panic!("failed to spawn conpty"))
-        .unwrap()
+    create_pty_with_working_directory(shell, None, columns, rows)
+}
+
+/// Same as [`create_pty`] but the child process starts at `working_directory`.
+pub fn create_pty_with_working_directory(
+    shell: &str,
+    working_directory: Option<&str>,
+    columns: u16,
+    rows: u16,
+) -> Pty {
+    let mut builder = PtyBuilder::new(shell);
+    builder.size(columns, rows);
+    if let Some(dir) = working_directory {
+        builder.current_dir(dir);
+    }
+
+    builder.spawn().expect("failed to spawn conpty")
+}
+
+/// Start the program described by `builder`, see [`PtyBuilder::spawn`].
+pub(crate) fn spawn(builder: &PtyBuilder) -> io::Result<Pty> {
+    let command_line = if builder.args.is_empty() {
+        cmdline(&builder.program)
+    } else {
+        once(builder.program.as_str())
+            .chain(builder.args.iter().map(String::as_str))
+            .map(quote_argument)
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+
+    // The environment is only given when it differs from the inherited one.
+    let environment = (builder.env_clear || !builder.envs.is_empty())
+        .then(|| environment_block(builder.environment()));
+
+    conpty::new(
+        &command_line,
+        builder.working_directory.as_deref(),
+        environment.as_deref(),
+        builder.winsize.build(),
+    )
+    .ok_or_else(io::Error::last_os_error)
+}
+
+/// Environment in the format `CreateProcessW` expects, `KEY=VALUE` pairs
+/// sorted by key and separated by nulls, ending with an extra null.
+fn environment_block(mut environment: Vec<(OsString, OsString)>) -> Vec<u16> {
+    environment.sort_by_key(|(key, _)| key.to_string_lossy().to_uppercase());
+
+    let mut block = vec![];
+    for (key, value) in environment {
+        block.extend(key.encode_wide());
+        block.push('=' as u16);
+        block.extend(value.encode_wide());
+        block.push(0);
+    }
+    block.push(0);
+    block
+}
+
+/// Quote `argument` the way `CommandLineToArgvW` splits it back.
+fn quote_argument(argument: &str) -> String {
+    if !argument.is_empty() && !argument.contains([' ', '\t', '"']) {
+        return argument.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in argument.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
 }
 
 impl Pty {
@@ -207,7 +293,7 @@ impl EventedPty for Pty {
         match self.child_watcher.event_rx().try_recv() {
             Ok(ev) => Some(ev),
             Err(TryRecvError::Empty) => None,
-            Err(TryRecvError::Disconnected) => Some(ChildEvent::Exited),
+            Err(TryRecvError::Disconnected) => Some(ChildEvent::Exited(None)),
         }
     }
 }
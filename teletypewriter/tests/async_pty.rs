@@ -0,0 +1,70 @@
+#![cfg(unix)]
+
+use std::time::Duration;
+use teletypewriter::{ExitStatus, PtyBuilder, WinsizeBuilder};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn test_read_output() {
+    let mut pty = PtyBuilder::new("/bin/sh")
+        .args(["-c", "echo hello; exit 3"])
+        .spawn_async()
+        .unwrap();
+
+    let mut output = String::new();
+    timeout(TIMEOUT, pty.read_to_string(&mut output))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(output, "hello\r\n");
+
+    let status = timeout(TIMEOUT, pty.wait()).await.unwrap().unwrap();
+    assert_eq!(status, ExitStatus::Code(3));
+}
+
+#[tokio::test]
+async fn test_write_and_resize() {
+    let mut pty = PtyBuilder::new("/bin/sh")
+        .args(["-c", "stty -echo; read line; echo \"$line $(stty size)\""])
+        .spawn_async()
+        .unwrap();
+    let exited = pty.exited();
+
+    pty.resize(WinsizeBuilder {
+        rows: 30,
+        cols: 100,
+        width: 0,
+        height: 0,
+    })
+    .await
+    .unwrap();
+    pty.write_all(b"hello\n").await.unwrap();
+
+    let mut output = String::new();
+    timeout(TIMEOUT, pty.read_to_string(&mut output))
+        .await
+        .unwrap()
+        .unwrap();
+    // The input may be echoed before `stty -echo` runs.
+    assert!(output.ends_with("hello 30 100\r\n"), "{output:?}");
+
+    let status = timeout(TIMEOUT, exited).await.unwrap().unwrap();
+    assert!(status.success());
+}
+
+#[tokio::test]
+async fn test_exit_future() {
+    let pty = PtyBuilder::new("/bin/sh")
+        .args(["-c", "kill -9 $$"])
+        .spawn_async()
+        .unwrap();
+    let exited = pty.exited();
+
+    let status = timeout(TIMEOUT, exited).await.unwrap().unwrap();
+    assert_eq!(status, ExitStatus::Signal(9));
+    // Still known once the child was reaped.
+    assert_eq!(pty.wait().await.unwrap(), ExitStatus::Signal(9));
+}